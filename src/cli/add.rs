@@ -1,10 +1,148 @@
 use clap::Args;
 
+use crate::index::ignore::Ignore;
+use crate::index::pathspec::Pathspec;
+use crate::index::{worktree, Index, IndexEntry};
+use crate::repo::Repo;
+
+/// Add file contents to the index.
+///
+/// Every matching file in the working tree is hashed into a blob and staged,
+/// and tracked files that no longer exist are removed from the index. Paths
+/// may be files, directories or globs. Ignored files are skipped unless `-f`
+/// is given.
+///
+/// # Example
+/// ```bash
+/// $ git add src/*.rs
+/// $ git add -n .
+/// add 'hello.txt'
+/// ```
 #[derive(Args, Debug)]
 pub struct Add {
-  pub name: Option<String>,
+  /// Files to add content from (directories and globs such as `src/*.rs` are
+  /// allowed).
+  pub pathspec: Vec<String>,
+
+  /// Stage new, modified and deleted files in the whole working tree (or in the
+  /// given paths).
+  #[clap(short = 'A', long, conflicts_with = "update")]
+  pub all: bool,
+
+  /// Only stage modified and deleted files that are already tracked.
+  #[clap(short, long)]
+  pub update: bool,
+
+  /// Don't actually add the files, just show what would be staged.
+  #[clap(short = 'n', long)]
+  pub dry_run: bool,
+
+  /// Show every file that is added or removed.
+  #[clap(short, long)]
+  pub verbose: bool,
+
+  /// Allow adding files that are otherwise ignored.
+  #[clap(short, long)]
+  pub force: bool,
 }
 
-pub fn cmd_add() -> Result<(), String> {
-  todo!("cmd_add");
+/// Stages the matching files of the working tree.
+///
+/// Tracked files are updated first (in index order) and then, unless `-u` is
+/// given, untracked files are added.
+pub fn cmd_add(opts: &Add) -> Result<(), String> {
+  if opts.pathspec.is_empty() && !opts.all && !opts.update {
+    println!("Nothing specified, nothing added.");
+    println!("hint: Maybe you wanted to say 'git add .'?");
+    return Ok(());
+  }
+  let repo: Repo = Repo::default();
+  let pathspec = Pathspec::new(&repo, &opts.pathspec)?;
+  let mut index = Index::read(&repo)?;
+  let mut ignore = Ignore::new(&repo);
+  let files = match opts.update {
+    true => Vec::new(),
+    false if opts.force => worktree::files(&repo, None)?,
+    false => worktree::files(&repo, Some(&mut ignore))?,
+  };
+
+  let mut tracked: Vec<String> = Vec::new();
+  for entry in &index.entries {
+    if tracked.last() != Some(&entry.path) {
+      tracked.push(entry.path.clone());
+    }
+  }
+  let candidates = tracked.iter().chain(&files).map(|path| path.as_str());
+  let unmatched = pathspec.unmatched(candidates);
+
+  // pathspecs that only name ignored files are refused, but don't stop the
+  // other files from being added
+  let mut ignored = Vec::new();
+  if !unmatched.is_empty() {
+    let unmatched: Vec<String> = unmatched.into_iter().map(String::from).collect();
+    let unmatched = Pathspec::new(&repo, &unmatched)?;
+    for path in worktree::files(&repo, None)? {
+      if unmatched.matches(&path) && ignore.is_ignored(&path, false) {
+        ignored.push(path);
+      }
+    }
+    let candidates = ignored.iter().map(|path| path.as_str());
+    if let Some(unmatched) = unmatched.unmatched(candidates).first() {
+      return Err(format!("pathspec '{}' did not match any files", unmatched));
+    }
+  }
+
+  let report = opts.verbose || opts.dry_run;
+  for path in tracked.iter().filter(|path| pathspec.matches(path)) {
+    let previous = index.entry(path).cloned();
+    match worktree::entry(&repo, path, previous.as_ref(), !opts.dry_run)? {
+      None => {
+        index.remove(path);
+        if report {
+          println!("remove '{}'", path);
+        }
+      }
+      Some(entry) => match previous {
+        Some(previous) if previous.hash == entry.hash && previous.mode == entry.mode => {
+          // unchanged, but the stat data may be newer
+          let current = index.entry_mut(path).unwrap();
+          *current = IndexEntry {
+            assume_valid: current.assume_valid,
+            skip_worktree: current.skip_worktree,
+            ..entry
+          };
+        }
+        _ => {
+          index.add(entry);
+          if report {
+            println!("add '{}'", path);
+          }
+        }
+      },
+    }
+  }
+
+  for path in files.iter().filter(|path| pathspec.matches(path)) {
+    if tracked.binary_search(path).is_ok() {
+      continue;
+    }
+    if let Some(entry) = worktree::entry(&repo, path, None, !opts.dry_run)? {
+      index.add(entry);
+      if report {
+        println!("add '{}'", path);
+      }
+    }
+  }
+
+  if !opts.dry_run {
+    index.write(&repo)?;
+  }
+  match ignored.is_empty() {
+    true => Ok(()),
+    false => Err(format!(
+      "The following paths are ignored by one of your .gitignore files:\n{}\n\
+       hint: Use -f if you really want to add them.",
+      ignored.join("\n")
+    )),
+  }
 }
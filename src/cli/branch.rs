@@ -0,0 +1,611 @@
+use std::path::Path;
+
+use clap::Args;
+use colored::Colorize;
+
+use crate::cli::status::detached_description;
+use crate::index::pathspec::wildmatch;
+use crate::object::commit::Commit;
+use crate::object::identity::Identity;
+use crate::object::refs::short_name;
+use crate::object::serializable::Unbox;
+use crate::object::{
+  abbreviate, find_ref, merge_base, pretty, read, refs, revision, DEFAULT_ABBREV,
+};
+use crate::repo::Repo;
+
+/// List, create, or delete branches.
+///
+/// Without arguments, the local branches are listed and the current one is
+/// marked with a `*`. With a name, a new branch is created at the given start
+/// point (HEAD by default). Branches can also be deleted (`-d`), renamed
+/// (`-m`) or set up to track an upstream branch (`-u`).
+///
+/// # Example
+/// ```bash
+/// $ git branch -vv
+///   feature 635c073 [main: behind 1] add feature
+/// * main    6ebf049 [origin/main: ahead 1] fix typo
+/// ```
+#[derive(Args, Debug)]
+pub struct Branch {
+  /// The branch to create, delete or rename, followed by the start point of
+  /// a new branch or the new name of a renamed one. When listing, the
+  /// patterns that the branches must match.
+  pub names: Vec<String>,
+
+  /// Show the hash and subject of each branch and how far it is from its
+  /// upstream. Twice to show the name of the upstream too.
+  #[clap(short, long, parse(from_occurrences))]
+  pub verbose: u64,
+
+  /// List both the local and the remote-tracking branches.
+  #[clap(short, long)]
+  pub all: bool,
+
+  /// List (or delete, with `-d`) the remote-tracking branches.
+  #[clap(short, long)]
+  pub remotes: bool,
+
+  /// List the branches, only those that match one of the patterns if any.
+  #[clap(short, long)]
+  pub list: bool,
+
+  /// Print the name of the current branch.
+  #[clap(long)]
+  pub show_current: bool,
+
+  /// Delete a branch. It must be merged into its upstream, or into HEAD if it
+  /// has none.
+  #[clap(short, long)]
+  pub delete: bool,
+
+  /// Delete a branch, even if it isn't merged.
+  #[clap(short = 'D')]
+  pub force_delete: bool,
+
+  /// Rename a branch, along with its reflog and config.
+  #[clap(short = 'm', long = "move")]
+  pub rename: bool,
+
+  /// Rename a branch, even if the new name is taken.
+  #[clap(short = 'M')]
+  pub force_rename: bool,
+
+  /// Reset the branch to the start point if it exists already.
+  #[clap(short, long)]
+  pub force: bool,
+
+  /// Make the start point the upstream of the new branch.
+  #[clap(short, long, conflicts_with = "no-track")]
+  pub track: bool,
+
+  /// Don't set up an upstream for the new branch, even if
+  /// `branch.autoSetupMerge` says so.
+  #[clap(long)]
+  pub no_track: bool,
+
+  /// Set the upstream of a branch (the current one by default).
+  #[clap(short = 'u', long, value_name = "upstream")]
+  pub set_upstream_to: Option<String>,
+
+  /// Remove the upstream of a branch (the current one by default).
+  #[clap(long)]
+  pub unset_upstream: bool,
+}
+
+pub fn cmd_branch(opts: &Branch) -> Result<(), String> {
+  let mut repo: Repo = Repo::default();
+  let names: Vec<&str> = opts.names.iter().map(String::as_str).collect();
+
+  if opts.show_current {
+    if let Some(branch) = refs::current_branch(&repo) {
+      println!("{}", short_name(&branch));
+    }
+    return Ok(());
+  }
+  if opts.delete || opts.force_delete {
+    return delete_branches(&mut repo, &names, opts.force_delete, opts.remotes);
+  }
+  if opts.rename || opts.force_rename {
+    return match names[..] {
+      [] => Err("branch name required".to_string()),
+      [new] => rename_branch(&mut repo, None, new, opts.force_rename),
+      [old, new] => rename_branch(&mut repo, Some(old), new, opts.force_rename),
+      _ => Err("too many arguments for a rename operation".to_string()),
+    };
+  }
+  if let Some(upstream) = &opts.set_upstream_to {
+    let branch = match names[..] {
+      [] => None,
+      [branch] => Some(branch),
+      _ => return Err("too many arguments to set new upstream".to_string()),
+    };
+    return set_upstream_to(&mut repo, branch, upstream);
+  }
+  if opts.unset_upstream {
+    let branch = match names[..] {
+      [] => None,
+      [branch] => Some(branch),
+      _ => return Err("too many arguments to unset upstream".to_string()),
+    };
+    return unset_upstream(&mut repo, branch);
+  }
+  if opts.list || names.is_empty() {
+    return list_branches(&repo, opts, &names);
+  }
+  if opts.all || opts.remotes {
+    return Err(
+      "The -a, and -r, options to 'git branch' do not take a branch name.\n\
+       Did you mean to use: -a|-r --list <pattern>?"
+        .to_string(),
+    );
+  }
+  match names[..] {
+    [name] => create_branch(&mut repo, name, "HEAD", opts.force, track(opts)),
+    [name, start] => create_branch(&mut repo, name, start, opts.force, track(opts)),
+    _ => Err("too many arguments".to_string()),
+  }
+}
+
+/// Whether a new branch should track its start point: `None` leaves it to
+/// `branch.autoSetupMerge`.
+fn track(opts: &Branch) -> Option<bool> {
+  match (opts.track, opts.no_track) {
+    (true, _) => Some(true),
+    (_, true) => Some(false),
+    _ => None,
+  }
+}
+
+/// A line of the list of branches.
+struct Entry {
+  /// The name of the branch as it is listed, or the description of a detached
+  /// HEAD.
+  name: String,
+
+  /// The full name of the branch, `None` for a detached HEAD.
+  refname: Option<String>,
+
+  /// The commit the branch points at.
+  hash: String,
+
+  /// The short name of the ref that a symbolic ref (eg. `origin/HEAD`) points
+  /// at.
+  target: Option<String>,
+
+  /// Whether the branch is checked out.
+  current: bool,
+
+  /// Whether the branch is a remote-tracking branch.
+  remote: bool,
+}
+
+/// Lists the branches that match any of the patterns (or all of them if
+/// there are none), the current one first if HEAD is detached.
+fn list_branches(repo: &Repo, opts: &Branch, patterns: &[&str]) -> Result<(), String> {
+  let matches = |name: &str| {
+    patterns.is_empty()
+      || patterns
+        .iter()
+        .any(|pattern| wildmatch(pattern.as_bytes(), name.as_bytes(), false))
+  };
+  let current = refs::current_branch(repo);
+  let all = refs::collect(repo, None);
+  let mut entries = Vec::new();
+
+  if !opts.remotes || opts.all {
+    if let (None, Ok(head)) = (&current, refs::resolve(repo, Path::new("HEAD"))) {
+      if matches("HEAD") {
+        let name = match detached_description(repo, Some(&head))? {
+          Some(description) => format!("({})", description),
+          None => "(no branch)".to_string(),
+        };
+        entries.push(Entry {
+          name,
+          refname: None,
+          hash: head,
+          target: None,
+          current: true,
+          remote: false,
+        });
+      }
+    }
+    for (refname, hash) in &all {
+      match refname.strip_prefix("refs/heads/") {
+        Some(name) if matches(name) => entries.push(Entry {
+          name: name.to_string(),
+          refname: Some(refname.clone()),
+          hash: hash.clone(),
+          target: refs::read_symbolic(repo, refname).map(|target| short_name(&target).to_string()),
+          current: current.as_ref() == Some(refname),
+          remote: false,
+        }),
+        _ => (),
+      }
+    }
+  }
+  if opts.remotes || opts.all {
+    for (refname, hash) in &all {
+      match refname.strip_prefix("refs/remotes/") {
+        Some(name) if matches(name) => entries.push(Entry {
+          name: match opts.all {
+            true => format!("remotes/{}", name),
+            false => name.to_string(),
+          },
+          refname: Some(refname.clone()),
+          hash: hash.clone(),
+          target: refs::read_symbolic(repo, refname).map(|target| short_name(&target).to_string()),
+          current: false,
+          remote: true,
+        }),
+        _ => (),
+      }
+    }
+  }
+
+  let width = entries.iter().map(|entry| entry.name.chars().count()).max();
+  for entry in &entries {
+    let name = match opts.verbose {
+      0 => entry.name.clone(),
+      _ => format!("{:<1$}", entry.name, width.unwrap_or(0)),
+    };
+    let name = match (entry.current, entry.remote) {
+      (true, _) => name.green().to_string(),
+      (_, true) => name.red().to_string(),
+      _ => name,
+    };
+    let marker = if entry.current { "* " } else { "  " };
+    let details = match (&entry.target, opts.verbose) {
+      (Some(target), _) => format!(" -> {}", target),
+      (None, 0) => String::new(),
+      (None, verbose) => {
+        let object = read(repo.clone(), &entry.hash, Some("commit"))?;
+        let subject = pretty::title(object.unbox::<Commit>()?.message());
+        let tracking = match (&entry.refname, entry.remote) {
+          (Some(refname), false) => tracking(repo, refname, &entry.hash, verbose > 1)?,
+          _ => String::new(),
+        };
+        let hash = abbreviate(repo, &entry.hash, DEFAULT_ABBREV)?;
+        format!(" {} {}{}", hash, tracking, subject)
+      }
+    };
+    println!("{}{}{}", marker, name, details);
+  }
+  Ok(())
+}
+
+/// Describes how a branch compares to its upstream, eg. `[ahead 1, behind 2] `
+/// or `[origin/main: gone] ` when the upstream doesn't exist anymore. With
+/// `named`, the upstream is named even if the branch is up to date with it.
+fn tracking(repo: &Repo, refname: &str, hash: &str, named: bool) -> Result<String, String> {
+  let upstream = match revision::upstream(repo, refname) {
+    Ok(upstream) => upstream,
+    Err(_) => return Ok(String::new()),
+  };
+  let counts = match refs::resolve(repo, Path::new(&upstream)) {
+    Ok(upstream) => match revision::ahead_behind(repo, hash, &upstream)? {
+      (0, 0) => None,
+      (ahead, 0) => Some(format!("ahead {}", ahead)),
+      (0, behind) => Some(format!("behind {}", behind)),
+      (ahead, behind) => Some(format!("ahead {}, behind {}", ahead, behind)),
+    },
+    Err(_) => Some("gone".to_string()),
+  };
+  Ok(match (named, counts) {
+    (true, Some(counts)) => format!("[{}: {}] ", short_name(&upstream), counts),
+    (true, None) => format!("[{}] ", short_name(&upstream)),
+    (false, Some(counts)) => format!("[{}] ", counts),
+    (false, None) => String::new(),
+  })
+}
+
+/// Creates a branch at the given start point, or resets it there with
+/// `force`, and sets up its upstream.
+///
+/// With `track`, the start point becomes the upstream (and must be a branch);
+/// without it, it is up to `branch.autoSetupMerge`, which by default only
+/// tracks remote-tracking branches.
+pub(crate) fn create_branch(
+  repo: &mut Repo,
+  name: &str,
+  start: &str,
+  force: bool,
+  track: Option<bool>,
+) -> Result<(), String> {
+  check_name(name)?;
+  let refname = format!("refs/heads/{}", name);
+  let exists = refs::resolve(repo, Path::new(&refname)).is_ok();
+  if exists && !force {
+    return Err(format!("a branch named '{}' already exists", name));
+  }
+  if exists && refs::current_branch(repo).as_ref() == Some(&refname) {
+    return Err(format!(
+      "cannot force update the branch '{}' checked out at '{}'",
+      name,
+      repo.work_tree.display()
+    ));
+  }
+  let hash = revision::resolve_commit(repo, start)
+    .map_err(|_| format!("not a valid object name: '{}'", start))?;
+
+  let upstream = find_ref(repo, start).and_then(|start| upstream_config(repo, &start));
+  let track = match upstream {
+    _ if track == Some(false) => None,
+    None if track == Some(true) => {
+      return Err(format!(
+        "cannot set up tracking information; starting point '{}' is not a branch",
+        start
+      ))
+    }
+    Some(upstream) if track == Some(true) => Some(upstream),
+    Some(upstream) => {
+      match repo
+        .config_value("branch", None, "autosetupmerge")
+        .as_deref()
+      {
+        Some("false") => None,
+        Some("always") => Some(upstream),
+        _ if upstream.0 == "." => None,
+        _ => Some(upstream),
+      }
+    }
+    None => None,
+  };
+
+  let message = match exists {
+    true => format!("branch: Reset to {}", start),
+    false => format!("branch: Created from {}", start),
+  };
+  let identity = Identity::reflog(repo)?.to_string();
+  refs::update(repo, &refname, &hash, &identity, &message)?;
+  match track {
+    Some((remote, merge)) => set_upstream(repo, name, &remote, &merge),
+    None => Ok(()),
+  }
+}
+
+/// Deletes branches (or remote-tracking branches, with `remotes`).
+///
+/// Unless `force` is set, a branch is only deleted if it is merged into its
+/// upstream, or into HEAD if it has none. The branches that can't be deleted
+/// are reported and skipped, and make the command fail once the others are
+/// deleted.
+fn delete_branches(
+  repo: &mut Repo,
+  names: &[&str],
+  force: bool,
+  remotes: bool,
+) -> Result<(), String> {
+  if names.is_empty() {
+    return Err("branch name required".to_string());
+  }
+  let (prefix, kind) = match remotes {
+    true => ("refs/remotes/", "remote-tracking branch"),
+    false => ("refs/heads/", "branch"),
+  };
+  let head = revision::resolve_commit(repo, "HEAD").ok();
+  let current = refs::current_branch(repo);
+
+  let mut failed = Vec::new();
+  for name in names {
+    let refname = format!("{}{}", prefix, name);
+    if !remotes && current.as_ref() == Some(&refname) {
+      println!(
+        "error: Cannot delete branch '{}' checked out at '{}'",
+        name,
+        repo.work_tree.display()
+      );
+      failed.push(*name);
+      continue;
+    }
+    let hash = match refs::resolve(repo, Path::new(&refname)) {
+      Ok(hash) => hash,
+      Err(_) => {
+        println!("error: {} '{}' not found.", kind, name);
+        failed.push(*name);
+        continue;
+      }
+    };
+    if !remotes && !force && !is_merged(repo, &refname, &hash, head.as_deref())? {
+      println!("error: The branch '{}' is not fully merged.", name);
+      println!(
+        "If you are sure you want to delete it, run 'git branch -D {}'.",
+        name
+      );
+      failed.push(*name);
+      continue;
+    }
+
+    refs::delete(repo, &refname)?;
+    if !remotes {
+      repo.rename_config_section("branch", Some(name), None)?;
+    }
+    let hash = abbreviate(repo, &hash, DEFAULT_ABBREV)?;
+    println!("Deleted {} {} (was {}).", kind, name, hash);
+  }
+  match failed.is_empty() {
+    true => Ok(()),
+    false => Err(format!("could not delete {}", failed.join(", "))),
+  }
+}
+
+/// Whether a branch is merged into its upstream, or into HEAD if it has none.
+fn is_merged(repo: &Repo, refname: &str, hash: &str, head: Option<&str>) -> Result<bool, String> {
+  let merged_into = |target: Option<&str>| match target {
+    Some(target) => merge_base::is_ancestor(repo, hash, target),
+    None => Ok::<bool, String>(false),
+  };
+  let upstream = revision::upstream(repo, refname)
+    .ok()
+    .and_then(|upstream| Some((refs::resolve(repo, Path::new(&upstream)).ok()?, upstream)));
+  let merged = match &upstream {
+    Some((upstream_hash, upstream)) => {
+      let merged = merged_into(Some(upstream_hash))?;
+      if merged && head.is_some() && !merged_into(head)? {
+        println!(
+          "warning: deleting branch '{}' that has been merged to",
+          short_name(refname)
+        );
+        println!("         '{}', but not yet merged to HEAD.", upstream);
+      }
+      merged
+    }
+    None => merged_into(head)?,
+  };
+  Ok(merged)
+}
+
+/// Renames a branch (the current one if `old` is `None`), along with its
+/// reflog and its config section.
+fn rename_branch(repo: &mut Repo, old: Option<&str>, new: &str, force: bool) -> Result<(), String> {
+  let current = refs::current_branch(repo);
+  let old = match (old, &current) {
+    (Some(old), _) => old.to_string(),
+    (None, Some(current)) => short_name(current).to_string(),
+    (None, None) => return Err("cannot rename the current branch while not on any.".to_string()),
+  };
+  let (old_ref, new_ref) = (format!("refs/heads/{}", old), format!("refs/heads/{}", new));
+
+  if refs::resolve(repo, Path::new(&old_ref)).is_err() {
+    // the current branch has no commits yet, so there is only HEAD to move
+    if current.as_ref() == Some(&old_ref) {
+      check_name(new)?;
+      return refs::update_symbolic(repo, "HEAD", &new_ref);
+    }
+    return Err(format!("No branch named '{}'.", old));
+  }
+  check_name(new)?;
+  if refs::resolve(repo, Path::new(&new_ref)).is_ok() {
+    if !force {
+      return Err(format!("a branch named '{}' already exists", new));
+    }
+    if new_ref == old_ref {
+      return Ok(());
+    }
+  }
+
+  let message = format!("Branch: renamed {} to {}", old_ref, new_ref);
+  let identity = Identity::reflog(repo)?.to_string();
+  refs::rename(repo, &old_ref, &new_ref, &identity, &message)?;
+  repo.rename_config_section("branch", Some(&old), Some(new))
+}
+
+/// Sets the upstream of a branch (the current one if `branch` is `None`).
+fn set_upstream_to(repo: &mut Repo, branch: Option<&str>, upstream: &str) -> Result<(), String> {
+  let name = match branch {
+    Some(branch) => branch.to_string(),
+    None => match refs::current_branch(repo) {
+      Some(current) => short_name(&current).to_string(),
+      None => {
+        return Err(format!(
+          "could not set upstream of HEAD to {} when it does not point to any branch.",
+          upstream
+        ))
+      }
+    },
+  };
+  if refs::resolve(repo, Path::new(&format!("refs/heads/{}", name))).is_err() {
+    return Err(format!("branch '{}' does not exist", name));
+  }
+  let refname = match find_ref(repo, upstream) {
+    Some(refname) => refname,
+    None => {
+      return Err(format!(
+        "the requested upstream branch '{}' does not exist",
+        upstream
+      ))
+    }
+  };
+  match upstream_config(repo, &refname) {
+    Some((remote, merge)) => set_upstream(repo, &name, &remote, &merge),
+    None => Err(format!(
+      "cannot set up tracking information; starting point '{}' is not a branch",
+      upstream
+    )),
+  }
+}
+
+/// Removes the upstream of a branch (the current one if `branch` is `None`).
+fn unset_upstream(repo: &mut Repo, branch: Option<&str>) -> Result<(), String> {
+  let name = match branch {
+    Some(branch) => branch.to_string(),
+    None => match refs::current_branch(repo) {
+      Some(current) => short_name(&current).to_string(),
+      None => {
+        return Err(
+          "could not unset upstream of HEAD when it does not point to any branch.".to_string(),
+        )
+      }
+    },
+  };
+  if repo.config_value("branch", Some(&name), "merge").is_none() {
+    return Err(format!("Branch '{}' has no upstream information", name));
+  }
+  repo.set_config("branch", Some(&name), "remote", None)?;
+  repo.set_config("branch", Some(&name), "merge", None)
+}
+
+/// Returns the `branch.<name>.remote` and `.merge` config that makes a branch
+/// track the given ref, or `None` if it isn't a branch.
+///
+/// A local branch is tracked through the `.` remote. A remote-tracking branch
+/// is mapped back to the remote's branch by the remote's fetch refspec (eg.
+/// `+refs/heads/*:refs/remotes/origin/*`).
+fn upstream_config(repo: &Repo, refname: &str) -> Option<(String, String)> {
+  if refname.starts_with("refs/heads/") {
+    return Some((".".to_string(), refname.to_string()));
+  }
+  repo
+    .config_subsections("remote")
+    .into_iter()
+    .find_map(|remote| {
+      let refspec = repo.config_value("remote", Some(&remote), "fetch")?;
+      let (src, dst) = refspec.trim_start_matches('+').split_once(':')?;
+      let merge = match (src.strip_suffix('*'), dst.strip_suffix('*')) {
+        (Some(src), Some(dst)) => format!("{}{}", src, refname.strip_prefix(dst)?),
+        (None, None) if dst == refname => src.to_string(),
+        _ => return None,
+      };
+      Some((remote, merge))
+    })
+}
+
+/// Writes the upstream config of a branch.
+fn set_upstream(repo: &mut Repo, name: &str, remote: &str, merge: &str) -> Result<(), String> {
+  let upstream = match remote {
+    "." => short_name(merge).to_string(),
+    remote => format!("{}/{}", remote, short_name(merge)),
+  };
+  if remote == "." && merge.strip_prefix("refs/heads/") == Some(name) {
+    println!("warning: not setting branch '{}' as its own upstream", name);
+    return Ok(());
+  }
+  repo.set_config("branch", Some(name), "remote", Some(remote))?;
+  repo.set_config("branch", Some(name), "merge", Some(merge))?;
+  println!("branch '{}' set up to track '{}'.", name, upstream);
+  Ok(())
+}
+
+/// Checks that a branch name is a valid ref name (see `git check-ref-format`).
+pub(crate) fn check_name(name: &str) -> Result<(), String> {
+  let valid = !name.is_empty()
+    && name != "HEAD"
+    && name != "@"
+    && !name.starts_with('-')
+    && !name.ends_with('/')
+    && !name.ends_with('.')
+    && !name.contains("..")
+    && !name.contains("//")
+    && !name.contains("@{")
+    && !name
+      .chars()
+      .any(|c| c.is_ascii_control() || " ~^:?*[\\".contains(c))
+    && name
+      .split('/')
+      .all(|part| !part.starts_with('.') && !part.ends_with(".lock"));
+  match valid {
+    true => Ok(()),
+    false => Err(format!("'{}' is not a valid branch name", name)),
+  }
+}
@@ -2,7 +2,10 @@ use std::path::PathBuf;
 
 use clap::Args;
 
-use crate::{object::read, repo::Repo};
+use crate::{
+  object::{find_object, read},
+  repo::Repo,
+};
 
 #[derive(Args, Debug)]
 pub struct CatFile {
@@ -25,7 +28,8 @@ pub struct CatFile {
 /// ```
 pub fn cmd_cat_file(opts: &CatFile) -> Result<(), String> {
   if let Some(repo) = Repo::find_repo(&PathBuf::from("."), true)? {
-    let gob = read(repo, &opts.object, Some(&opts.typename))?;
+    let hash = find_object(repo.clone(), &opts.object, Some(&opts.typename), true)?;
+    let gob = read(repo, &hash, Some(&opts.typename))?;
     print!("{}", String::from_utf8_lossy(gob.serialize()));
     Ok(())
   } else {
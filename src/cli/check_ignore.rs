@@ -0,0 +1,71 @@
+use clap::Args;
+
+use crate::index::ignore::Ignore;
+use crate::index::pathspec::{self, quote};
+use crate::index::Index;
+use crate::repo::Repo;
+
+/// Debug gitignore / exclude files.
+///
+/// Prints each of the given paths that is ignored. With `-v`, the pattern that
+/// decided it is printed too, along with the file and line it came from (this
+/// includes `!` patterns that re-include a path).
+///
+/// # Example
+/// ```bash
+/// $ git check-ignore -v target/debug main.rs
+/// .gitignore:1:/target    target/debug
+/// ```
+#[derive(Args, Debug)]
+pub struct CheckIgnore {
+  /// The paths to check.
+  pub paths: Vec<String>,
+
+  /// Show the matching pattern for each path.
+  #[clap(short, long)]
+  pub verbose: bool,
+
+  /// Show paths that don't match any pattern too (only with `-v`).
+  #[clap(short, long, requires = "verbose")]
+  pub non_matching: bool,
+
+  /// Don't look in the index (tracked files are never ignored otherwise).
+  #[clap(long)]
+  pub no_index: bool,
+}
+
+pub fn cmd_check_ignore(opts: &CheckIgnore) -> Result<(), String> {
+  if opts.paths.is_empty() {
+    return Err("no path specified".to_string());
+  }
+  let repo: Repo = Repo::default();
+  let prefix = pathspec::prefix(&repo)?;
+  let index = match opts.no_index {
+    true => Index::new(),
+    false => Index::read(&repo)?,
+  };
+  let mut ignore = Ignore::new(&repo);
+
+  for arg in &opts.paths {
+    let path = pathspec::resolve(&repo, &prefix, arg)?;
+    let tracked = index.entries.iter().any(|entry| entry.path == path);
+    let is_dir = arg.ends_with('/') || repo.work_tree.join(&path).is_dir();
+    let found = match tracked {
+      true => None,
+      false => ignore.find(&path, is_dir),
+    };
+    match found {
+      Some(found) if opts.verbose => println!(
+        "{}:{}:{}\t{}",
+        found.source,
+        found.pattern.line,
+        found.pattern.text,
+        quote(arg, false)
+      ),
+      Some(found) if found.is_ignored() => println!("{}", quote(arg, false)),
+      None if opts.non_matching => println!("::\t{}", quote(arg, false)),
+      _ => (),
+    }
+  }
+  Ok(())
+}
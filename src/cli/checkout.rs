@@ -1,71 +1,490 @@
-use clap::Args;
-use std::fs::write;
+use std::collections::BTreeMap;
 use std::path::Path;
 
-use crate::{
-  object::{blob::Blob, commit::Commit, read, serializable::Unbox, tree::Tree},
-  repo::Repo,
-};
+use clap::Args;
+
+use crate::cli::branch::{check_name, create_branch};
+use crate::cli::status::tracking_info;
+use crate::index::checkout::{self, switch_trees, write_file, Files};
+use crate::index::pathspec::Pathspec;
+use crate::index::worktree::{self, FileState};
+use crate::index::Index;
+use crate::object::commit::Commit;
+use crate::object::identity::Identity;
+use crate::object::refs::short_name;
+use crate::object::serializable::Unbox;
+use crate::object::walk::{Options, Walk};
+use crate::object::{abbreviate, find_object, pretty, read, refs, revision, tree, DEFAULT_ABBREV};
+use crate::repo::Repo;
 
+/// The most lost commits listed when leaving a detached HEAD; any more than
+/// one beyond that are only counted.
+const ORPHAN_CUTOFF: usize = 4;
+
+/// Switch branches or restore working tree files.
+///
+/// With a branch, HEAD is pointed at it and the index and the working tree
+/// are updated to match it. Only the files that differ between the two
+/// commits are touched, so local changes to other files are carried over;
+/// if a local change would be lost, nothing is done unless `-f` is given.
+/// With any other commit, HEAD is detached at it.
+///
+/// With paths, the files are restored from the index, or from the given
+/// commit (in which case the index is updated too), and HEAD doesn't move.
+///
+/// # Example
+/// ```bash
+/// $ git checkout -b topic
+/// Switched to a new branch 'topic'
+/// $ git checkout main -- README.md
+/// ```
 #[derive(Args, Debug)]
 pub struct Checkout {
-  /// The commit or tree to checkout.
-  pub object: String,
+  /// The branch or commit to switch to (`-` for the previous one), or the
+  /// commit to restore the paths from followed by the paths.
+  pub args: Vec<String>,
+
+  /// Create a new branch at the start point (HEAD by default) and switch to
+  /// it.
+  #[clap(short = 'b', value_name = "new-branch")]
+  pub new_branch: Option<String>,
+
+  /// Detach HEAD at the commit, even if it is a branch.
+  #[clap(long)]
+  pub detach: bool,
+
+  /// Throw away local changes: the index and the working tree are reset to
+  /// the commit that is checked out. When restoring paths, unmerged paths
+  /// are skipped.
+  #[clap(short, long)]
+  pub force: bool,
+
+  /// Don't print anything but errors.
+  #[clap(short, long)]
+  pub quiet: bool,
+
+  /// Make the start point the upstream of the new branch.
+  #[clap(short, long, conflicts_with = "no-track")]
+  pub track: bool,
 
-  /// The EMPTY directory to checkout on.
-  pub path: String,
+  /// Don't set up an upstream for the new branch.
+  #[clap(long)]
+  pub no_track: bool,
+
+  /// The paths to restore.
+  #[clap(last = true)]
+  pub paths: Vec<String>,
 }
 
 pub fn cmd_checkout(opts: &Checkout) -> Result<(), String> {
-  let repo: Repo = Repo::default();
+  let mut repo: Repo = Repo::default();
+  let (target, paths) = split_args(&repo, opts)?;
+  let target = match target {
+    Some("-") => Some(revision::previous_checkout(&repo, 1)?),
+    target => target.map(str::to_string),
+  };
+
+  if !paths.is_empty() {
+    if let Some(branch) = &opts.new_branch {
+      return Err(format!(
+        "Cannot update paths and switch to branch '{}' at the same time.",
+        branch
+      ));
+    }
+    if opts.detach {
+      return Err("git checkout: --detach does not take a path argument".to_string());
+    }
+    // like git, the paths are only counted when they aren't given after `--`
+    let count = !opts.quiet && opts.paths.is_empty();
+    return restore_paths(&repo, target.as_deref(), &paths, opts.force, count);
+  }
+  switch_branch(&mut repo, target.as_deref(), opts)
+}
 
-  // Parse the commit into a commit object.
-  let mut object = read(repo.clone(), &opts.object, None)?;
+/// Splits the arguments into the branch or commit and the paths.
+///
+/// Without `--`, the first argument is the branch or commit if it is one (or
+/// a branch that can be created from a remote-tracking branch), and all of
+/// the arguments are paths otherwise.
+fn split_args<'a>(
+  repo: &Repo,
+  opts: &'a Checkout,
+) -> Result<(Option<&'a str>, Vec<String>), String> {
+  let args: Vec<&str> = opts.args.iter().map(String::as_str).collect();
+  if !opts.paths.is_empty() {
+    return match args[..] {
+      [] => Ok((None, opts.paths.clone())),
+      [target] => Ok((Some(target), opts.paths.clone())),
+      _ => Err(format!(
+        "only one reference expected, {} given.",
+        args.len()
+      )),
+    };
+  }
+  let first = match args.first() {
+    Some(&first) => first,
+    None => return Ok((None, Vec::new())),
+  };
+  let is_target = first == "-"
+    || (opts.new_branch.is_some() && args.len() == 1)
+    || revision::resolve(repo, first).is_ok()
+    || (args.len() == 1 && remote_branch(repo, first).is_some());
+  let paths = args[is_target as usize..].iter().map(|arg| arg.to_string());
+  Ok((is_target.then_some(first), paths.collect()))
+}
 
-  // Parse the commit object into a tree.
-  if object.format().eq("commit") {
-    let commit = object.unbox::<Commit>()?;
-    let tree_hash = commit.map.get("tree").unwrap();
-    object = read(repo.clone(), tree_hash, Some("tree"))?;
+/// Finds the remote-tracking branch that `git checkout <name>` creates a
+/// branch from when there is no branch of that name: `<remote>/<name>`, if
+/// exactly one remote has it.
+fn remote_branch(repo: &Repo, name: &str) -> Option<String> {
+  if refs::resolve(repo, Path::new(&format!("refs/heads/{}", name))).is_ok() {
+    return None;
   }
+  let mut found = repo
+    .config_subsections("remote")
+    .into_iter()
+    .filter(|remote| {
+      let refname = format!("refs/remotes/{}/{}", remote, name);
+      refs::resolve(repo, Path::new(&refname)).is_ok()
+    });
+  match (found.next(), found.next()) {
+    (Some(remote), None) => Some(format!("{}/{}", remote, name)),
+    _ => None,
+  }
+}
 
-  let tree = object.unbox::<Tree>()?;
+/// Moves HEAD to a branch or a commit, taking the index and the working tree
+/// along.
+fn switch_branch(repo: &mut Repo, target: Option<&str>, opts: &Checkout) -> Result<(), String> {
+  let old_branch = refs::current_branch(repo);
+  let old = refs::resolve(repo, Path::new("HEAD")).ok();
 
-  // Verify the path is an empty directory. If it's not a directory, fail with
-  // an Err. if it's not empty, fail with an Err. If some of the paths don't
-  // exist yet, create them.
-  let path = Path::new(&opts.path);
-  // create the directory entries along the path if they do not exist
-  if let Err(msg) = std::fs::create_dir_all(path) {
-    return Err(format!("failed to create path {} ({})", &opts.path, msg));
+  // the branch HEAD ends up on (`None` if it is detached), the commit it
+  // points at, and the start point of a branch that has to be created
+  let mut start = None;
+  let (branch, new) = match (&opts.new_branch, target) {
+    (Some(name), target) => {
+      check_name(name)?;
+      if refs::resolve(repo, Path::new(&format!("refs/heads/{}", name))).is_ok() {
+        return Err(format!("a branch named '{}' already exists", name));
+      }
+      let new = match target {
+        Some(target) => Some(revision::resolve_commit(repo, target).map_err(|_| {
+          format!(
+            "'{}' is not a commit and a branch '{}' cannot be created from it",
+            target, name
+          )
+        })?),
+        None => old.clone(),
+      };
+      start = Some(target.unwrap_or("HEAD").to_string());
+      (Some(format!("refs/heads/{}", name)), new)
+    }
+    // checking out HEAD itself leaves HEAD alone
+    (None, None | Some("HEAD")) if !opts.detach => (old_branch.clone(), old.clone()),
+    (None, None) => (None, old.clone()),
+    (None, Some(target)) => {
+      let refname = format!("refs/heads/{}", target);
+      match refs::resolve(repo, Path::new(&refname)) {
+        Ok(hash) if !opts.detach => (Some(refname), Some(hash)),
+        _ => match revision::resolve_commit(repo, target) {
+          Ok(hash) => (None, Some(hash)),
+          Err(msg) => match remote_branch(repo, target).filter(|_| !opts.detach) {
+            Some(remote) => {
+              let hash = revision::resolve_commit(repo, &remote)?;
+              start = Some(remote);
+              (Some(refname), Some(hash))
+            }
+            None => return Err(msg),
+          },
+        },
+      }
+    }
+  };
+  let stay = opts.new_branch.is_none() && !opts.detach && matches!(target, None | Some("HEAD"));
+  // look up the identity for the reflog before anything is changed
+  let identity = match stay {
+    true => String::new(),
+    false => Identity::reflog(repo)?.to_string(),
+  };
+
+  let mut index = Index::read(repo)?;
+  let (old_files, new_files) = (files(repo, old.as_deref())?, files(repo, new.as_deref())?);
+  switch_trees(
+    repo, &mut index, &old_files, &new_files, opts.force, "checkout",
+  )?;
+  index.write(repo)?;
+
+  if !opts.quiet && !opts.force {
+    print_local_changes(repo, &index, &new_files)?;
   }
-  if !path.is_dir() {
-    return Err(format!("{} is not a directory", opts.path));
+  if let (None, Some(old), false) = (&old_branch, &old, opts.quiet) {
+    if new.as_ref() != Some(old) {
+      print_orphans(repo, old, new.as_deref())?;
+    }
   }
-  if path.read_dir().unwrap().next().is_some() {
-    return Err(format!("{} is not empty", opts.path));
+
+  if let (Some(start), Some(name)) = (&start, &branch) {
+    let track = match (opts.track, opts.no_track) {
+      (true, _) => Some(true),
+      (_, true) => Some(false),
+      _ => None,
+    };
+    if new.is_some() {
+      create_branch(repo, short_name(name), start, false, track)?;
+    }
+  }
+  if !stay {
+    let from = match (&old_branch, &old) {
+      (Some(branch), _) => short_name(branch).to_string(),
+      (None, Some(old)) => old.clone(),
+      (None, None) => "HEAD".to_string(),
+    };
+    let to = match (&opts.new_branch, target) {
+      (Some(name), _) => name.as_str(),
+      (None, Some(target)) => target,
+      (None, None) => "HEAD",
+    };
+    let message = format!("checkout: moving from {} to {}", from, to);
+    match &new {
+      Some(new) => refs::move_head(repo, branch.as_deref(), new, &identity, &message)?,
+      // a new branch on an unborn HEAD stays unborn
+      None => refs::update_symbolic(repo, "HEAD", branch.as_deref().unwrap_or("HEAD"))?,
+    }
+  }
+  if opts.quiet {
+    return Ok(());
   }
 
-  tree_checkout(&repo, tree, path)?;
+  match &branch {
+    None if !stay => {
+      let advice = repo
+        .config_value("advice", None, "detachedHead")
+        .or_else(|| repo.config_value("advice", None, "detachedhead"));
+      if old_branch.is_some() && !opts.detach && advice.as_deref() != Some("false") {
+        print_detach_advice(target.unwrap_or("HEAD"));
+      }
+      println!(
+        "HEAD is now at {}",
+        describe(repo, new.as_deref().unwrap_or_default())?
+      );
+    }
+    None => (),
+    Some(_) if stay => (),
+    Some(name) if old_branch.as_ref() == Some(name) => {
+      println!("Already on '{}'", short_name(name))
+    }
+    Some(name) if start.is_some() => println!("Switched to a new branch '{}'", short_name(name)),
+    Some(name) => println!("Switched to branch '{}'", short_name(name)),
+  }
+  // the upstream of a branch that was just created isn't reported
+  if let (Some(name), None) = (&branch, &start) {
+    if let Some(info) = tracking_info(repo, name)? {
+      println!("{}", info);
+    }
+  }
   Ok(())
 }
 
-fn tree_checkout(repo: &Repo, tree: &Tree, path: &Path) -> Result<(), String> {
-  for item in tree.entries() {
-    let obj = read(repo.clone(), &item.hash, None)?;
-    let dest = path.join(item.path.as_str());
+/// Restores files from the index, or from a commit or tree (`source`) into
+/// both the index and the working tree.
+///
+/// Files that the source doesn't have are left alone. Unmerged paths can only
+/// be restored from a commit, and are skipped with `force`. With `count`, the
+/// number of files that had to be written is reported.
+fn restore_paths(
+  repo: &Repo,
+  source: Option<&str>,
+  paths: &[String],
+  force: bool,
+  count: bool,
+) -> Result<(), String> {
+  let pathspec = Pathspec::new(repo, paths)?;
+  let mut index = Index::read(repo)?;
+  let (source, from) = match source {
+    Some(source) => {
+      let hash = revision::resolve(repo, source)?;
+      let tree = find_object(repo.clone(), &hash, Some("tree"), true)?;
+      let from = abbreviate(repo, &tree, DEFAULT_ABBREV)?;
+      (Some(tree::flatten(repo, &tree)?), from)
+    }
+    None => (None, "the index".to_string()),
+  };
 
-    if obj.format().eq("tree") {
-      if let Err(msg) = std::fs::create_dir_all(&dest) {
-        return Err(format!("failed to create path {:?} ({})", &dest, msg));
+  let tracked = index.entries.iter().map(|entry| entry.path.as_str());
+  let known = source
+    .iter()
+    .flat_map(|files| files.keys().map(String::as_str));
+  let unmatched = pathspec.unmatched(tracked.chain(known));
+  if !unmatched.is_empty() {
+    for path in unmatched {
+      println!(
+        "error: pathspec '{}' did not match any file(s) known to git",
+        path
+      );
+    }
+    return Ok(());
+  }
+
+  // the mode and hash that each path is restored to
+  let mut restore: Files = BTreeMap::new();
+  match &source {
+    Some(files) => restore.extend(
+      files
+        .iter()
+        .filter(|(path, _)| pathspec.matches(path))
+        .map(|(path, file)| (path.clone(), file.clone())),
+    ),
+    None => {
+      let mut unmerged = Vec::new();
+      for entry in index
+        .entries
+        .iter()
+        .filter(|entry| pathspec.matches(&entry.path))
+      {
+        match entry.stage {
+          0 => {
+            restore.insert(entry.path.clone(), (entry.mode, entry.hash.clone()));
+          }
+          _ if unmerged.last() != Some(&entry.path) => unmerged.push(entry.path.clone()),
+          _ => (),
+        }
       }
-      let tree = obj.unbox::<Tree>()?;
-      tree_checkout(repo, tree, &dest)?;
-    } else if obj.format().eq("blob") {
-      if let Err(msg) = write(&dest, &obj.unbox::<Blob>()?.data()) {
-        return Err(format!("failed to write file {:?} ({})", &dest, msg));
+      let level = if force { "warning" } else { "error" };
+      for path in &unmerged {
+        println!("{}: path '{}' is unmerged", level, path);
+      }
+      if !unmerged.is_empty() && !force {
+        return Ok(());
       }
     }
   }
+
+  let mut written = 0;
+  for (path, (mode, hash)) in restore {
+    if let Some(entry) = index.entry(&path) {
+      let unchanged = matches!(
+        worktree::compare(repo, &index, entry)?,
+        FileState::Unchanged(_)
+      );
+      if unchanged && entry.mode == mode && entry.hash == hash {
+        continue;
+      }
+    }
+    index.add(write_file(repo, &path, mode, &hash)?);
+    written += 1;
+  }
+  index.write(repo)?;
+  if count {
+    let paths = if written == 1 { "path" } else { "paths" };
+    println!("Updated {} {} from {}", written, paths, from);
+  }
   Ok(())
 }
+
+/// Reads the files of a commit (none for an unborn branch).
+fn files(repo: &Repo, commit: Option<&str>) -> Result<Files, String> {
+  match commit {
+    Some(hash) => checkout::files(repo, hash),
+    None => Ok(BTreeMap::new()),
+  }
+}
+
+/// Lists the local changes that were carried over, compared to the commit
+/// that was checked out, eg. `M\tREADME.md`.
+fn print_local_changes(repo: &Repo, index: &Index, head: &Files) -> Result<(), String> {
+  let mut paths: Vec<&str> = head.keys().map(String::as_str).collect();
+  paths.extend(index.entries.iter().map(|entry| entry.path.as_str()));
+  paths.sort_unstable();
+  paths.dedup();
+  for path in paths {
+    let code = match (head.get(path), index.entry(path)) {
+      (_, Some(entry)) => match worktree::compare(repo, index, entry)? {
+        FileState::Deleted => 'D',
+        _ if head.get(path).is_none() => 'A',
+        FileState::Modified(_) => 'M',
+        FileState::Unchanged(_) if head.get(path) != Some(&(entry.mode, entry.hash.clone())) => 'M',
+        FileState::Unchanged(_) => continue,
+      },
+      (Some(_), None) if index.entries.iter().any(|entry| entry.path == path) => 'U',
+      (Some(_), None) => 'D',
+      (None, None) => 'U',
+    };
+    println!("{}\t{}", code, path);
+  }
+  Ok(())
+}
+
+/// Warns about the commits that are no longer reachable from any ref after
+/// leaving a detached HEAD at `old`, or just says where HEAD was.
+fn print_orphans(repo: &Repo, old: &str, new: Option<&str>) -> Result<(), String> {
+  let mut walk = Walk::new(repo, Options::default());
+  walk.push(old);
+  for hash in refs::collect(repo, None)
+    .values()
+    .map(String::as_str)
+    .chain(new)
+  {
+    if let Ok(commit) = revision::resolve_commit(repo, hash) {
+      walk.hide(&commit);
+    }
+  }
+  let lost = walk.run()?;
+  if lost.is_empty() {
+    println!("Previous HEAD position was {}", describe(repo, old)?);
+    return Ok(());
+  }
+
+  let (count, pronoun) = match lost.len() {
+    1 => ("1 commit".to_string(), "it"),
+    n => (format!("{} commits", n), "them"),
+  };
+  println!(
+    "Warning: you are leaving {} behind, not connected to\nany of your branches:\n",
+    count
+  );
+  let shown = match lost.len() > ORPHAN_CUTOFF + 1 {
+    true => ORPHAN_CUTOFF,
+    false => lost.len(),
+  };
+  for hash in &lost[..shown] {
+    println!("  {}", describe(repo, hash)?);
+  }
+  if shown < lost.len() {
+    println!(" ... and {} more.", lost.len() - shown);
+  }
+  println!(
+    "\nIf you want to keep {} by creating a new branch, this may be a good time\nto do so with:\n",
+    pronoun
+  );
+  println!(
+    " git branch <new-branch-name> {}\n",
+    abbreviate(repo, old, DEFAULT_ABBREV)?
+  );
+  Ok(())
+}
+
+/// Explains what a detached HEAD is, when switching to one from a branch.
+fn print_detach_advice(name: &str) {
+  println!("Note: switching to '{}'.\n", name);
+  println!("You are in 'detached HEAD' state. You can look around, make experimental");
+  println!("changes and commit them, and you can discard any commits you make in this");
+  println!("state without impacting any branches by switching back to a branch.\n");
+  println!("If you want to create a new branch to retain commits you create, you may");
+  println!("do so (now or later) by using -c with the switch command. Example:\n");
+  println!("  git switch -c <new-branch-name>\n");
+  println!("Or undo this operation with:\n");
+  println!("  git switch -\n");
+  println!("Turn off this advice by setting config variable advice.detachedHead to false\n");
+}
+
+/// Describes a commit by its abbreviated hash and subject.
+fn describe(repo: &Repo, hash: &str) -> Result<String, String> {
+  let object = read(repo.clone(), hash, Some("commit"))?;
+  let commit = object.unbox::<Commit>()?;
+  Ok(format!(
+    "{} {}",
+    abbreviate(repo, hash, DEFAULT_ABBREV)?,
+    pretty::title(commit.message())
+  ))
+}
@@ -0,0 +1,84 @@
+use std::process;
+
+use clap::Args;
+
+use crate::index::sequencer::{self, Outcome, PickOptions};
+use crate::repo::Repo;
+
+/// Apply the changes introduced by some existing commits.
+///
+/// Each commit's changes are applied on top of HEAD with a three-way merge,
+/// and committed with the commit's author and message. Ranges (eg.
+/// `main..topic`) pick their commits oldest first. A merge commit needs
+/// `--mainline` to say which parent its changes are taken against.
+///
+/// When a commit doesn't apply cleanly, the cherry-pick stops with the
+/// conflicts in the index and the working tree; once they are resolved (and
+/// added), `git cherry-pick --continue` commits the result and carries on,
+/// `git cherry-pick --skip` leaves the commit out and `git cherry-pick
+/// --abort` goes back to where the cherry-pick started. The commits still to
+/// come are recorded in `.git/sequencer`.
+///
+/// # Example
+/// ```bash
+/// $ git cherry-pick topic
+/// [main 5e2a0c1] fix the typo
+/// ```
+#[derive(Args, Debug)]
+pub struct CherryPick {
+  /// The commits to pick.
+  #[clap(required_unless_present_any = &["proceed", "skip", "abort"])]
+  pub commits: Vec<String>,
+
+  /// Add a line saying which commit was picked to the message.
+  #[clap(short = 'x')]
+  pub record_origin: bool,
+
+  /// Apply the changes to the index and the working tree without committing.
+  #[clap(short = 'n', long)]
+  pub no_commit: bool,
+
+  /// The parent (counting from 1) of a merge commit to take its changes
+  /// against.
+  #[clap(short, long)]
+  pub mainline: Option<usize>,
+
+  /// Carry on once the conflicts that stopped the cherry-pick are resolved.
+  #[clap(
+    long = "continue",
+    conflicts_with_all = &["commits", "no-commit", "mainline", "skip", "abort"]
+  )]
+  pub proceed: bool,
+
+  /// Leave out the commit that stopped the cherry-pick, and carry on.
+  #[clap(long, conflicts_with_all = &["commits", "no-commit", "mainline", "abort"])]
+  pub skip: bool,
+
+  /// Give up on the cherry-pick and go back to where it started.
+  #[clap(long, conflicts_with_all = &["commits", "no-commit", "mainline"])]
+  pub abort: bool,
+}
+
+pub fn cmd_cherry_pick(opts: &CherryPick) -> Result<(), String> {
+  let repo = Repo::default();
+  let outcome = if opts.proceed {
+    sequencer::continue_picks(&repo, false)?
+  } else if opts.skip {
+    sequencer::skip_pick(&repo, false)?
+  } else if opts.abort {
+    return sequencer::abort_picks(&repo, false);
+  } else {
+    let options = PickOptions {
+      revert: false,
+      mainline: opts.mainline,
+      record_origin: opts.record_origin,
+      no_commit: opts.no_commit,
+    };
+    sequencer::pick(&repo, &options, &opts.commits)?
+  };
+  // like git, stopping for the user to step in is a failure
+  if outcome == Outcome::Stopped {
+    process::exit(1);
+  }
+  Ok(())
+}
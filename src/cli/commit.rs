@@ -1,10 +1,205 @@
+use std::io::Read;
+use std::path::Path;
+use std::{fs, io};
+
 use clap::Args;
 
-#[derive(Args, Debug)]
+use crate::index::Index;
+use crate::object::commit::{Commit as CommitObject, CommitBuilder};
+use crate::object::identity::Identity;
+use crate::object::serializable::Unbox;
+use crate::object::{self, abbreviate, refs, DEFAULT_ABBREV};
+use crate::repo::editor::{cleanup, edit, EDITOR_HELP};
+use crate::repo::{state, Repo};
+
+/// The reminder added to the message template of a merge commit.
+const MERGE_HELP: &str = "#\n\
+# It looks like you may be committing a merge.\n\
+# If this is not correct, please run\n\
+#\tgit update-ref -d MERGE_HEAD\n\
+# and try again.\n\n";
+
+/// Record changes to the repository.
+///
+/// Writes the index out as a tree, creates a commit object pointing at it and
+/// moves the current branch (or a detached HEAD) to the new commit.
+///
+/// During a merge (while `MERGE_HEAD` exists), the commit concludes it: the
+/// merged commit becomes the second parent, and the message starts out as
+/// the one the merge prepared. A cherry-pick that stopped for conflicts (with
+/// `CHERRY_PICK_HEAD`) is concluded the same way, keeping the author of the
+/// picked commit.
+///
+/// # Example
+/// ```bash
+/// $ git commit -m "update readme"
+/// [main 3c1b2e9] update readme
+/// ```
+#[derive(Args, Debug, Default)]
 pub struct Commit {
-  pub name: Option<String>,
+  /// Use the given message (several `-m` become separate paragraphs).
+  #[clap(short, long, multiple_occurrences = true, conflicts_with = "file")]
+  pub message: Vec<String>,
+
+  /// Take the message from the given file (`-` reads standard input).
+  #[clap(short = 'F', long)]
+  pub file: Option<String>,
+
+  /// Replace the tip of the current branch with a new commit.
+  #[clap(long)]
+  pub amend: bool,
+
+  /// Reuse the message of the amended commit without launching an editor.
+  #[clap(long)]
+  pub no_edit: bool,
+
+  /// Allow recording a commit with the same tree as its parent.
+  #[clap(long)]
+  pub allow_empty: bool,
+
+  /// Don't print a summary of the new commit.
+  #[clap(short, long)]
+  pub quiet: bool,
+}
+
+pub fn cmd_commit(opts: &Commit) -> Result<(), String> {
+  let repo: Repo = Repo::default();
+  let index = Index::read(&repo)?;
+  if index.has_conflicts() {
+    return Err("Committing is not possible because you have unmerged files.".to_string());
+  }
+
+  let merge_head = state::merge_head(&repo);
+  if opts.amend && merge_head.is_some() {
+    return Err("You are in the middle of a merge -- cannot amend.".to_string());
+  }
+
+  // an amended commit is replaced by a commit with the same parents
+  let head = refs::resolve(&repo, Path::new("HEAD")).ok();
+  let amended = match (opts.amend, &head) {
+    (true, None) => return Err("You have nothing to amend.".to_string()),
+    (true, Some(head)) => Some(object::read(repo.clone(), head, Some("commit"))?),
+    (false, _) => None,
+  };
+  let amended = match &amended {
+    Some(object) => Some(object.unbox::<CommitObject>()?),
+    None => None,
+  };
+  let mut parents = match amended {
+    Some(commit) => commit.parents(),
+    None => head.into_iter().collect(),
+  };
+  parents.extend(merge_head.clone());
+
+  let tree = index.write_tree(&repo)?;
+  if !opts.allow_empty && merge_head.is_none() {
+    let unchanged = match parents.first() {
+      Some(parent) => {
+        let object = object::read(repo.clone(), parent, Some("commit"))?;
+        object.unbox::<CommitObject>()?.tree() == tree
+      }
+      None => index.entries.is_empty(),
+    };
+    if unchanged && opts.amend {
+      return Err(
+        "You asked to amend the most recent commit, but doing so would make it empty \
+         (use --allow-empty to record it anyway)."
+          .to_string(),
+      );
+    } else if unchanged {
+      return Err("nothing to commit (use --allow-empty to record an empty commit)".to_string());
+    }
+  }
+
+  let picked = match state::pick_head(&repo, false) {
+    Some(hash) if !opts.amend => Some(object::read(repo.clone(), &hash, Some("commit"))?),
+    _ => None,
+  };
+  let picked = match &picked {
+    Some(object) => Some(object.unbox::<CommitObject>()?),
+    None => None,
+  };
+  let merge_msg = state::merge_msg(&repo);
+
+  let message = match (&opts.file, amended) {
+    _ if !opts.message.is_empty() => cleanup(&opts.message.join("\n\n"), false),
+    (Some(file), _) => cleanup(&read_message_file(file)?, false),
+    (None, Some(commit)) if opts.no_edit => commit.message().to_string(),
+    (None, Some(commit)) => {
+      let template = format!("{}{}", commit.message(), EDITOR_HELP);
+      cleanup(&edit(&repo, "COMMIT_EDITMSG", &template)?, true)
+    }
+    (None, None) if merge_head.is_some() => {
+      let merge_msg = merge_msg.unwrap_or_default();
+      let template = format!("{}{}{}", merge_msg, MERGE_HELP, EDITOR_HELP);
+      cleanup(&edit(&repo, "COMMIT_EDITMSG", &template)?, true)
+    }
+    // a cherry-pick or revert left the message it prepared
+    (None, None) if merge_msg.is_some() => {
+      let template = format!("{}{}", merge_msg.unwrap_or_default(), EDITOR_HELP);
+      cleanup(&edit(&repo, "COMMIT_EDITMSG", &template)?, true)
+    }
+    (None, None) => cleanup(&edit(&repo, "COMMIT_EDITMSG", EDITOR_HELP)?, true),
+  };
+  if message.is_empty() {
+    return Err("Aborting commit due to empty commit message.".to_string());
+  }
+
+  let committer = Identity::committer(&repo)?.to_string();
+  let author = match amended.or(picked) {
+    Some(commit) => commit.author().to_string(),
+    None => Identity::author(&repo)?.to_string(),
+  };
+  let mut builder = CommitBuilder::new(&tree)
+    .author(&author)
+    .committer(&committer)
+    .message(&message);
+  for parent in &parents {
+    builder = builder.parent(parent);
+  }
+  let hash = object::write(&builder.build(repo.clone()), false)?;
+
+  let subject = message.lines().next().unwrap_or("");
+  let reason = match (opts.amend, parents.len()) {
+    (true, _) => "commit (amend)",
+    (false, 0) => "commit (initial)",
+    (false, 1) if picked.is_some() => "commit (cherry-pick)",
+    (false, 1) => "commit",
+    (false, _) => "commit (merge)",
+  };
+  refs::update_head(
+    &repo,
+    &hash,
+    &committer,
+    &format!("{}: {}", reason, subject),
+  )?;
+  state::clear_merge(&repo);
+  state::clear_pick(&repo);
+
+  let branch = match refs::current_branch(&repo) {
+    Some(branch) => branch.trim_start_matches("refs/heads/").to_string(),
+    None => "detached HEAD".to_string(),
+  };
+  let root = match parents.is_empty() {
+    true => " (root-commit)",
+    false => "",
+  };
+  let short = abbreviate(&repo, &hash, DEFAULT_ABBREV)?;
+  if !opts.quiet {
+    println!("[{}{} {}] {}", branch, root, short, subject);
+  }
+  Ok(())
 }
 
-pub fn cmd_commit() -> Result<(), String> {
-  todo!("cmd_commit");
+/// Reads a commit message from a file, or from standard input for `-`.
+fn read_message_file(file: &str) -> Result<String, String> {
+  let mut message = String::new();
+  let result = match file {
+    "-" => io::stdin().read_to_string(&mut message).map(|_| ()),
+    _ => fs::read_to_string(file).map(|data| message = data),
+  };
+  match result {
+    Ok(()) => Ok(message),
+    Err(msg) => Err(format!("could not read log file '{}' ({})", file, msg)),
+  }
 }
@@ -0,0 +1,645 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::slice;
+
+use clap::Args;
+
+use crate::crypto::sha_1;
+use crate::diff::rename::{self, parse_score};
+use crate::diff::stat::{self, Counts, FileStat};
+use crate::diff::tree::{diff_files, diff_trees, FileChange, Side, Status};
+use crate::diff::unified::Options;
+use crate::diff::{patch, Algorithm};
+use crate::index::pathspec::{quote, Pathspec};
+use crate::index::worktree::{self, FileState};
+use crate::index::{Index, IndexEntry};
+use crate::object::mode::Mode;
+use crate::object::refs::{self, NULL_HASH};
+use crate::object::revision::{self, Revision};
+use crate::object::{abbreviate, find_object, merge_base, read_raw, tree, DEFAULT_ABBREV};
+use crate::repo::Repo;
+
+/// Show changes between commits, commit and working tree, etc.
+///
+/// With no commits, shows the changes in the working tree that aren't staged
+/// yet (the working tree vs the index), and with `--cached` the changes that
+/// are (the index vs HEAD, or the given commit). With one commit, the working
+/// tree is compared with that commit. Two commits (`A B` or `A..B`) are
+/// compared with each other, and `A...B` shows the changes on `B` since it
+/// forked from `A` (ie. from their merge base to `B`).
+///
+/// Arguments that aren't commits (or anything after `--`) limit the diff to
+/// the given paths. Renames are detected unless `diff.renames` is turned off.
+/// Combined diffs of merge conflicts aren't supported: conflicted paths are
+/// only listed as unmerged.
+///
+/// With `--no-index`, compares two files on the filesystem, which don't have
+/// to be in a repository.
+///
+/// # Example
+/// ```bash
+/// $ git diff --stat main...topic
+///  src/main.rs | 4 +++-
+///  1 file changed, 3 insertions(+), 1 deletion(-)
+/// ```
+#[derive(Args, Debug)]
+pub struct Diff {
+  /// The commits to compare, followed by the paths to limit the diff to (or
+  /// the two files to compare, with `--no-index`).
+  pub args: Vec<String>,
+
+  /// Paths to limit the diff to.
+  #[clap(last = true)]
+  pub paths: Vec<String>,
+
+  /// Compare two paths on the filesystem.
+  #[clap(long)]
+  pub no_index: bool,
+
+  /// Compare the index with HEAD (or the given commit) instead of the
+  /// working tree with the index.
+  #[clap(long, alias = "staged")]
+  pub cached: bool,
+
+  /// Show this many lines of context around each change.
+  #[clap(short = 'U', long = "unified", default_value_t = 3)]
+  pub context: usize,
+
+  /// Use the patience diff algorithm.
+  #[clap(long)]
+  pub patience: bool,
+
+  /// Use the histogram diff algorithm.
+  #[clap(long)]
+  pub histogram: bool,
+
+  /// Spend extra time to make sure the smallest possible diff is produced.
+  #[clap(long)]
+  pub minimal: bool,
+
+  /// Choose a diff algorithm (myers, minimal, patience or histogram).
+  #[clap(long, value_name = "ALGORITHM")]
+  pub diff_algorithm: Option<Algorithm>,
+
+  /// Show how many lines of each file changed, with a graph.
+  #[clap(long)]
+  pub stat: bool,
+
+  /// Show how many lines were added and deleted in each file, in a format
+  /// meant for scripts.
+  #[clap(long)]
+  pub numstat: bool,
+
+  /// Show only the names of changed files.
+  #[clap(long)]
+  pub name_only: bool,
+
+  /// Show only the names and status of changed files.
+  #[clap(long)]
+  pub name_status: bool,
+
+  /// Show the modes, hashes and status of changed files.
+  #[clap(long)]
+  pub raw: bool,
+
+  #[clap(flatten)]
+  pub renames: RenameArgs,
+
+  /// Color the output: `always`, `never` or `auto` (only when writing to a
+  /// terminal, the default).
+  #[clap(
+    long,
+    value_name = "WHEN",
+    min_values = 0,
+    require_equals = true,
+    default_missing_value = "always",
+    possible_values = ["always", "never", "auto"]
+  )]
+  pub color: Option<String>,
+
+  /// Turn off colors.
+  #[clap(long)]
+  pub no_color: bool,
+}
+
+/// What one side of a diff is read from.
+enum Snapshot {
+  /// A tree, or nothing at all (eg. for HEAD on an unborn branch).
+  Tree(Option<String>),
+  Index,
+  WorkTree,
+}
+
+/// A line (or a file) of the output.
+enum Entry {
+  Change(FileChange),
+
+  /// A path with merge conflicts, with its mode and hash in the old tree
+  /// and its mode in the working tree (if those are being compared).
+  Unmerged {
+    path: String,
+    old: Option<(Mode, String)>,
+    new: Option<Mode>,
+  },
+}
+
+impl Entry {
+  fn path(&self) -> &str {
+    match self {
+      Entry::Change(change) => change.path(),
+      Entry::Unmerged { path, .. } => path,
+    }
+  }
+}
+
+pub fn cmd_diff(opts: &Diff) -> Result<(), String> {
+  match opts.color.as_deref() {
+    _ if opts.no_color => colored::control::set_override(false),
+    Some("always") => colored::control::set_override(true),
+    Some("never") => colored::control::set_override(false),
+    _ => (),
+  }
+  if opts.no_index {
+    return diff_no_index(opts);
+  }
+
+  let repo: Repo = Repo::default();
+  let (revisions, paths) = split_args(&repo, opts)?;
+  let pathspec = Pathspec::new(&repo, &paths)?;
+  let (old, new) = snapshots(&repo, opts, &revisions)?;
+  let mut index = Index::read(&repo)?;
+
+  let mut refreshed = Vec::new();
+  let mut dirty = BTreeSet::new();
+  let mut unmerged = Vec::new();
+  let mut changes = match (&old, &new) {
+    (Snapshot::Tree(old), Snapshot::Tree(new)) => {
+      let mut changes = diff_trees(&repo, old.as_deref(), new.as_deref(), true)?;
+      changes.retain(|change| pathspec.matches(change.path()));
+      changes
+    }
+    _ => {
+      let mut old_files = files(&repo, &index, &old, &pathspec, &mut refreshed, &mut dirty)?;
+      let mut new_files = files(&repo, &index, &new, &pathspec, &mut refreshed, &mut dirty)?;
+      let conflicted: BTreeSet<&str> = index
+        .entries
+        .iter()
+        .filter(|entry| entry.stage > 0 && pathspec.matches(&entry.path))
+        .map(|entry| entry.path.as_str())
+        .collect();
+      for path in conflicted {
+        let worktree_mode = match new {
+          Snapshot::WorkTree => worktree::entry(&repo, path, None, false)?,
+          _ => None,
+        };
+        unmerged.push(Entry::Unmerged {
+          path: path.to_string(),
+          old: old_files.remove(path),
+          new: worktree_mode.as_ref().map(|entry| entry.mode),
+        });
+        // the working tree is compared with our side of the conflict
+        let ours = index
+          .entries
+          .iter()
+          .find(|e| e.path == path && e.stage == 2);
+        if let (Snapshot::Index, Some(ours), Some(file)) = (&old, ours, worktree_mode) {
+          old_files.insert(ours.path.clone(), (ours.mode, ours.hash.clone()));
+          dirty.insert(file.path.clone());
+          new_files.insert(file.path, (file.mode, file.hash));
+        }
+      }
+      diff_files(&old_files, &new_files)
+    }
+  };
+
+  // save the stat data of files that had to be rehashed, like `status` does
+  if !refreshed.is_empty() {
+    for fresh in &refreshed {
+      index.entry_mut(&fresh.path).unwrap().refresh(fresh);
+    }
+    let _ = index.write(&repo);
+  }
+
+  // `--raw` only shows the hashes of files in the working tree that match
+  // the index, or that were hashed to look for renames anyway
+  let worktree = matches!(new, Snapshot::WorkTree);
+  let mut hashed = false;
+  let renames = rename_options(&repo, &opts.renames)?;
+  if let Some(options) = renames {
+    let is_source = |change: &FileChange| match change.status {
+      Status::Deleted => true,
+      Status::Added => false,
+      _ => options.copies,
+    };
+    hashed =
+      changes.iter().any(|change| change.status == Status::Added) && changes.iter().any(is_source);
+    changes = rename::detect(changes, &options, |side| contents(&repo, side, worktree))?;
+  }
+  let unhashed: BTreeSet<String> = changes
+    .iter()
+    .filter(|change| {
+      let destination = matches!(
+        change.status,
+        Status::Added | Status::Renamed(_) | Status::Copied(_)
+      );
+      dirty.contains(change.path()) && !(hashed && destination)
+    })
+    .map(|change| change.path().to_string())
+    .collect();
+
+  // conflicts come before any other change to the same path
+  let mut entries: Vec<Entry> = Vec::new();
+  let mut unmerged = unmerged.into_iter().peekable();
+  for change in changes {
+    while let Some(next) = unmerged.next_if(|entry| entry.path() <= change.path()) {
+      entries.push(next);
+    }
+    entries.push(Entry::Change(change));
+  }
+  entries.extend(unmerged);
+
+  show(
+    opts,
+    &entries,
+    &unhashed,
+    &|hash| abbreviate(&repo, hash, DEFAULT_ABBREV).unwrap_or_else(|_| hash[..7].to_string()),
+    &mut |side| contents(&repo, side, worktree),
+  )
+}
+
+/// Compares two files outside of any repository.
+fn diff_no_index(opts: &Diff) -> Result<(), String> {
+  let [old, new] = &opts.args[..] else {
+    return Err("usage: git diff --no-index <path> <path>".to_string());
+  };
+  let side = |path: &str| -> Result<Side, String> {
+    let (mode, data) = read(Path::new(path))?;
+    Ok(Side {
+      path: path.to_string(),
+      mode,
+      hash: blob_hash(&data),
+    })
+  };
+  let entries: Vec<Entry> = FileChange::between(Some(side(old)?), Some(side(new)?))
+    .map(Entry::Change)
+    .into_iter()
+    .collect();
+  show(
+    opts,
+    &entries,
+    &BTreeSet::new(),
+    &|hash| hash[..DEFAULT_ABBREV].to_string(),
+    &mut |side| read(Path::new(&side.path)).map(|(_, data)| data),
+  )
+}
+
+/// Prints the entries of a diff in the format the options ask for.
+///
+/// `unhashed` holds the paths whose new side `--raw` shows without a hash.
+/// `abbrev` shortens hashes and `load` reads the contents of one side of a
+/// change.
+fn show(
+  opts: &Diff,
+  entries: &[Entry],
+  unhashed: &BTreeSet<String>,
+  abbrev: &dyn Fn(&str) -> String,
+  load: &mut dyn FnMut(&Side) -> Result<Vec<u8>, String>,
+) -> Result<(), String> {
+  let mut out = io::stdout().lock();
+  let color = colored::control::SHOULD_COLORIZE.should_colorize();
+  let options = Options {
+    algorithm: algorithm(opts),
+    context: opts.context,
+  };
+  let mut load_both = |change: &FileChange| -> Result<(Vec<u8>, Vec<u8>), String> {
+    let old = change.old.as_ref().map_or(Ok(Vec::new()), &mut *load)?;
+    let new = change.new.as_ref().map_or(Ok(Vec::new()), &mut *load)?;
+    Ok((old, new))
+  };
+
+  if opts.name_only || opts.name_status || opts.raw {
+    for entry in entries {
+      let line = match entry {
+        _ if opts.name_only => quote(entry.path(), false),
+        Entry::Change(change) if opts.name_status => {
+          format!("{}\t{}", change.status, change.paths())
+        }
+        Entry::Unmerged { path, .. } if opts.name_status => format!("U\t{}", quote(path, false)),
+        Entry::Change(change) => {
+          let side = |side: &Option<Side>, unhashed: bool| match side {
+            Some(side) if unhashed => (side.mode.to_string(), abbrev(NULL_HASH)),
+            Some(side) => (side.mode.to_string(), abbrev(&side.hash)),
+            None => ("000000".to_string(), abbrev(NULL_HASH)),
+          };
+          let (old_mode, old_hash) = side(&change.old, false);
+          let (new_mode, new_hash) = side(&change.new, unhashed.contains(change.path()));
+          format!(
+            ":{} {} {} {} {}\t{}",
+            old_mode,
+            new_mode,
+            old_hash,
+            new_hash,
+            change.status,
+            change.paths()
+          )
+        }
+        Entry::Unmerged { path, old, new } => {
+          let mode = |mode: Option<Mode>| mode.map_or("000000".to_string(), |m| m.to_string());
+          format!(
+            ":{} {} {} {} U\t{}",
+            mode(old.as_ref().map(|(mode, _)| *mode)),
+            mode(*new),
+            abbrev(old.as_ref().map_or(NULL_HASH, |(_, hash)| hash)),
+            abbrev(NULL_HASH),
+            quote(path, false)
+          )
+        }
+      };
+      writeln!(out, "{}", line).map_err(|msg| msg.to_string())?;
+    }
+  } else if opts.stat || opts.numstat {
+    let mut stats = Vec::new();
+    for entry in entries {
+      stats.push(match entry {
+        Entry::Change(change) => {
+          let (old, new) = load_both(change)?;
+          let old_path = change.old.as_ref().map_or(change.path(), |side| &side.path);
+          FileStat {
+            name: stat::name(old_path, change.path()),
+            counts: Counts::between(&old, &new, options.algorithm),
+          }
+        }
+        Entry::Unmerged { path, .. } => FileStat {
+          name: quote(path, false),
+          counts: Counts::Unmerged,
+        },
+      });
+    }
+    let mut result = Ok(());
+    if opts.numstat {
+      result = stat::write_numstat(&mut out, &stats);
+    }
+    if opts.stat {
+      result = result.and_then(|_| stat::write_stat(&mut out, &stats, stat_width(), color));
+    }
+    result.map_err(|msg| msg.to_string())?;
+  } else {
+    for entry in entries {
+      let result = match entry {
+        Entry::Change(change) => {
+          let (old, new) = load_both(change)?;
+          patch::write(&mut out, change, (&old, &new), abbrev, &options, color)
+        }
+        Entry::Unmerged { path, .. } => writeln!(out, "* Unmerged path {}", path),
+      };
+      result.map_err(|msg| msg.to_string())?;
+    }
+  }
+  Ok(())
+}
+
+/// Splits the arguments into the revisions to compare and the paths to limit
+/// the diff to.
+///
+/// Arguments are revisions up to the first one that isn't one, and paths
+/// from there on. Paths must exist unless they come after `--`, so that a
+/// mistyped revision isn't silently taken for a path.
+fn split_args(repo: &Repo, opts: &Diff) -> Result<(Vec<Revision>, Vec<String>), String> {
+  let mut revisions = Vec::new();
+  let mut paths = Vec::new();
+  for arg in &opts.args {
+    if paths.is_empty() {
+      if let Ok(revision) = revision::parse(repo, arg) {
+        revisions.push(revision);
+        continue;
+      }
+    }
+    if fs::symlink_metadata(arg).is_err() {
+      return Err(format!(
+        "ambiguous argument '{}': unknown revision or path not in the working tree.",
+        arg
+      ));
+    }
+    paths.push(arg.clone());
+  }
+  paths.extend(opts.paths.iter().cloned());
+  Ok((revisions, paths))
+}
+
+/// Works out what is compared with what from the revisions and `--cached`.
+fn snapshots(
+  repo: &Repo,
+  opts: &Diff,
+  revisions: &[Revision],
+) -> Result<(Snapshot, Snapshot), String> {
+  let tree = |hash: &str| -> Result<Snapshot, String> {
+    let tree = find_object(repo.clone(), hash, Some("tree"), true)?;
+    Ok(Snapshot::Tree(Some(tree)))
+  };
+  Ok(match revisions {
+    [] if opts.cached => match refs::resolve(repo, Path::new("HEAD")) {
+      Ok(head) => (tree(&head)?, Snapshot::Index),
+      Err(_) => (Snapshot::Tree(None), Snapshot::Index),
+    },
+    [] => (Snapshot::Index, Snapshot::WorkTree),
+    [Revision::Single(commit)] if opts.cached => (tree(commit)?, Snapshot::Index),
+    [Revision::Single(commit)] => (tree(commit)?, Snapshot::WorkTree),
+    _ if opts.cached => return Err("--cached compares the index with one commit".to_string()),
+    [Revision::Range(from, to)]
+    | [Revision::Single(from), Revision::Single(to)]
+    | [Revision::Exclude(from), Revision::Single(to)]
+    | [Revision::Single(to), Revision::Exclude(from)] => (tree(from)?, tree(to)?),
+    [Revision::SymmetricDifference(left, right)] => {
+      match merge_base::merge_bases(repo, left, slice::from_ref(right))?.first() {
+        Some(base) => (tree(base)?, tree(right)?),
+        None => return Err(format!("{} and {} have no merge base", left, right)),
+      }
+    }
+    _ => return Err("usage: git diff [<commit> [<commit>]] [--] [<path>...]".to_string()),
+  })
+}
+
+/// Lists the files of a snapshot that match the pathspec, mapped to their
+/// modes and hashes. Paths with merge conflicts are left out of the index
+/// and the working tree.
+///
+/// Files in the working tree whose stat data matches the index aren't read
+/// at all. Fresh entries for files that had to be rehashed but turned out to
+/// be unchanged are added to `refreshed`, and files that don't match the
+/// index to `dirty`.
+fn files(
+  repo: &Repo,
+  index: &Index,
+  snapshot: &Snapshot,
+  pathspec: &Pathspec,
+  refreshed: &mut Vec<IndexEntry>,
+  dirty: &mut BTreeSet<String>,
+) -> Result<BTreeMap<String, (Mode, String)>, String> {
+  let mut files = BTreeMap::new();
+  let entries = index
+    .entries
+    .iter()
+    .filter(|entry| entry.stage == 0 && pathspec.matches(&entry.path));
+  match snapshot {
+    Snapshot::Tree(None) => (),
+    Snapshot::Tree(Some(hash)) => {
+      files = tree::flatten(repo, hash)?;
+      files.retain(|path, _| pathspec.matches(path));
+    }
+    Snapshot::Index => {
+      // an intent-to-add entry is only a placeholder
+      for entry in entries.filter(|entry| !entry.intent_to_add) {
+        files.insert(entry.path.clone(), (entry.mode, entry.hash.clone()));
+      }
+    }
+    Snapshot::WorkTree => {
+      for entry in entries {
+        let file = match worktree::compare(repo, index, entry)? {
+          FileState::Deleted => continue,
+          _ if entry.intent_to_add => {
+            dirty.insert(entry.path.clone());
+            let file = worktree::entry(repo, &entry.path, Some(entry), false)?;
+            file.map_or((entry.mode, entry.hash.clone()), |file| {
+              (file.mode, file.hash)
+            })
+          }
+          FileState::Modified(new) => {
+            dirty.insert(entry.path.clone());
+            (new.mode, new.hash)
+          }
+          FileState::Unchanged(fresh) => {
+            refreshed.extend(fresh);
+            (entry.mode, entry.hash.clone())
+          }
+        };
+        files.insert(entry.path.clone(), file);
+      }
+    }
+  }
+  Ok(files)
+}
+
+/// The rename detection options shared by the commands that show diffs.
+#[derive(Args, Debug)]
+pub struct RenameArgs {
+  /// Detect renames, optionally with a similarity threshold.
+  #[clap(
+    short = 'M',
+    long,
+    value_name = "N",
+    min_values = 0,
+    require_equals = true
+  )]
+  pub find_renames: Option<Option<String>>,
+
+  /// Detect copies as well as renames, optionally with a threshold.
+  #[clap(
+    short = 'C',
+    long,
+    value_name = "N",
+    min_values = 0,
+    require_equals = true
+  )]
+  pub find_copies: Option<Option<String>>,
+
+  /// Turn off rename detection.
+  #[clap(long)]
+  pub no_renames: bool,
+}
+
+/// How renames are detected: as `-M`, `-C` and `--no-renames` say, or else as
+/// `diff.renames` says (renames but not copies, by default).
+pub(crate) fn rename_options(
+  repo: &Repo,
+  args: &RenameArgs,
+) -> Result<Option<rename::Options>, String> {
+  let RenameArgs {
+    find_renames,
+    find_copies,
+    no_renames,
+  } = args;
+  let copies = match (find_renames, find_copies) {
+    _ if *no_renames => return Ok(None),
+    (_, Some(_)) => true,
+    (Some(_), None) => false,
+    (None, None) => match repo.config_value("diff", None, "renames").as_deref() {
+      Some("copies") | Some("copy") => true,
+      Some("false") | Some("no") | Some("off") | Some("0") => return Ok(None),
+      _ => false,
+    },
+  };
+  let mut options = rename::Options {
+    copies,
+    ..Default::default()
+  };
+  if let Some(Some(score)) = find_copies.clone().or(find_renames.clone()) {
+    options.minimum_score = parse_score(&score)?;
+  }
+  Ok(Some(options))
+}
+
+/// Reads the contents of one side of a change.
+///
+/// Files that changed in the working tree aren't in the object database, so
+/// they are read from the working tree instead when `worktree` is set. A
+/// submodule is shown as the commit it is at.
+fn contents(repo: &Repo, side: &Side, worktree: bool) -> Result<Vec<u8>, String> {
+  if side.mode == Mode::Gitlink {
+    return Ok(format!("Subproject commit {}\n", side.hash).into_bytes());
+  }
+  match read_raw(repo, &side.hash)? {
+    Some((_, data)) => Ok(data),
+    None if worktree => read(&repo.work_tree.join(&side.path)).map(|(_, data)| data),
+    None => Err(format!("object not found {}", side.hash)),
+  }
+}
+
+/// The width `--stat` fits its lines in: `$COLUMNS`, or else 80 columns.
+pub(crate) fn stat_width() -> usize {
+  env::var("COLUMNS")
+    .ok()
+    .and_then(|columns| columns.parse().ok())
+    .filter(|&columns| columns > 0)
+    .unwrap_or(80)
+}
+
+/// Picks the algorithm from the flags (the last one given on the command
+/// line isn't known to clap, so explicit names win over shorthands).
+fn algorithm(opts: &Diff) -> Algorithm {
+  match opts.diff_algorithm {
+    Some(algorithm) => algorithm,
+    None if opts.histogram => Algorithm::Histogram,
+    None if opts.patience => Algorithm::Patience,
+    None if opts.minimal => Algorithm::Minimal,
+    None => Algorithm::Myers,
+  }
+}
+
+/// Reads a file the way it would be stored in a blob: a symbolic link holds
+/// its target.
+fn read(path: &Path) -> Result<(Mode, Vec<u8>), String> {
+  let unreadable = |msg: io::Error| format!("could not read '{}': {}", path.display(), msg);
+  let metadata = fs::symlink_metadata(path).map_err(unreadable)?;
+  if metadata.file_type().is_symlink() {
+    let target = fs::read_link(path).map_err(unreadable)?;
+    Ok((Mode::Symbolic, target.as_os_str().as_bytes().to_vec()))
+  } else {
+    let mode = match metadata.mode() & 0o100 != 0 {
+      true => Mode::Executable,
+      false => Mode::Normal,
+    };
+    Ok((mode, fs::read(path).map_err(unreadable)?))
+  }
+}
+
+/// Hashes data as a blob, without writing it anywhere.
+fn blob_hash(data: &[u8]) -> String {
+  let mut object = format!("blob {}\0", data.len()).into_bytes();
+  object.extend_from_slice(data);
+  sha_1(&object)
+}
@@ -0,0 +1,90 @@
+use clap::Args;
+
+use crate::cli::diff::{rename_options, RenameArgs};
+use crate::diff::rename;
+use crate::diff::tree::{diff_trees, read_blob, Side};
+use crate::index::pathspec::quote;
+use crate::object::find_object;
+use crate::object::refs::NULL_HASH;
+use crate::repo::Repo;
+
+/// Compares the content and mode of blobs found via two tree objects.
+///
+/// Prints one line per changed path in git's "raw" format: the old and new
+/// modes and hashes, a status letter and the path. Renames and copies (with
+/// `-M` and `-C`) show the similarity of the two files and both paths.
+///
+/// The similarity threshold must be attached with an `=`, as in `-M=90%`.
+///
+/// # Example
+/// ```bash
+/// $ git diff-tree -r -M HEAD~1 HEAD
+/// :100644 100644 3b18e51... 8c7e5a6... R087    hello.txt    greeting.txt
+/// ```
+#[derive(Args, Debug)]
+pub struct DiffTree {
+  /// The old tree (or commit).
+  pub old: String,
+
+  /// The new tree (or commit).
+  pub new: String,
+
+  /// Recurse into subtrees.
+  #[clap(short)]
+  pub recursive: bool,
+
+  #[clap(flatten)]
+  pub renames: RenameArgs,
+
+  /// Show only the names of changed files.
+  #[clap(long)]
+  pub name_only: bool,
+
+  /// Show only the names and status of changed files.
+  #[clap(long)]
+  pub name_status: bool,
+}
+
+pub fn cmd_diff_tree(opts: &DiffTree) -> Result<(), String> {
+  let repo: Repo = Repo::default();
+  let old = find_object(repo.clone(), &opts.old, Some("tree"), true)?;
+  let new = find_object(repo.clone(), &opts.new, Some("tree"), true)?;
+  let mut changes = diff_trees(&repo, Some(&old), Some(&new), opts.recursive)?;
+
+  // unlike `diff`, renames are only detected when asked for
+  let renames = &opts.renames;
+  if renames.find_renames.is_some() || renames.find_copies.is_some() {
+    if let Some(options) = rename_options(&repo, &opts.renames)? {
+      changes = rename::detect(changes, &options, |side| read_blob(&repo, &side.hash))?;
+    }
+  }
+
+  for change in &changes {
+    if opts.name_only {
+      println!("{}", quote(change.path(), false));
+    } else if opts.name_status {
+      println!("{}\t{}", change.status, change.paths());
+    } else {
+      let (old_mode, old_hash) = mode_and_hash(&change.old);
+      let (new_mode, new_hash) = mode_and_hash(&change.new);
+      println!(
+        ":{} {} {} {} {}\t{}",
+        old_mode,
+        new_mode,
+        old_hash,
+        new_hash,
+        change.status,
+        change.paths()
+      );
+    }
+  }
+  Ok(())
+}
+
+/// The mode and hash of one side of a change, or zeros if it is missing.
+fn mode_and_hash(side: &Option<Side>) -> (String, &str) {
+  match side {
+    Some(side) => (side.mode.to_string(), &side.hash),
+    None => ("000000".to_string(), NULL_HASH),
+  }
+}
@@ -1,71 +1,551 @@
 use std::collections::HashSet;
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::slice;
 
 use clap::Args;
-use colored::Colorize;
-use indexmap::IndexMap;
+use regex::RegexBuilder;
 
 use crate::{
-  object::{commit::Commit, find_object, read, serializable::Unbox},
+  cli::diff::{rename_options, stat_width, RenameArgs},
+  diff::{
+    pickaxe::Pickaxe,
+    rename,
+    stat::{self, Counts, FileStat},
+    tree::{diff_trees, read_blob, FileChange, Side},
+    Algorithm,
+  },
+  index::pathspec::Pathspec,
+  object::{
+    commit::Commit,
+    date::{self, Style},
+    graph::Graph,
+    mode::Mode,
+    pretty::{self, Decorations, Format},
+    read,
+    revision::{self, Revision},
+    serializable::Unbox,
+    walk::{Grep, Options, Order, Walk},
+  },
   repo::Repo,
 };
 
 /// Display history of a given commit.
 ///
-/// Starting at the given commit (or HEAD by default) this command will show
-/// the history of changes for a particular commit.
+/// Starting at the given commits (or HEAD by default) this command will show
+/// the commits that are reachable from them, newest first. Commits reachable
+/// from a commit given as `^<commit>` are left out, so `A..B` (which is
+/// `^A B`) shows the commits on B that are not on A.
+///
+/// Arguments that aren't commits (or anything after `--`) limit the history
+/// to the commits that change those paths. The commits can also be filtered
+/// by their author, committer, message and date, and by the text their
+/// changes add or remove (`-S` and `-G`).
+///
+/// Each commit is shown in the format given with `--pretty` or `--format`,
+/// and with `--graph` the history is drawn next to the commits. `--stat` and
+/// `--name-status` also show the files each commit changed (merges show none,
+/// unless only their first parent is followed).
 ///
 /// # Example
 /// ```bash
-/// $ git log
+/// $ git log --topo-order -n 5 main..topic
+/// $ git log --graph --oneline --decorate
+/// $ git log --format="%h %an %ad %s" --date=short
+/// $ git log --author=Justin --since=2.weeks.ago -- src/repo
+/// $ git log --stat -M -- src/diff
 /// ```
 #[derive(Args, Debug)]
 pub struct Log {
-  /// The commit to start at.
-  #[clap(default_value_t = String::from("HEAD"))]
-  pub commit: String,
+  /// The commits to start at, followed by the paths to limit the history to.
+  pub revisions: Vec<String>,
+
+  /// Paths to limit the history to.
+  #[clap(last = true)]
+  pub paths: Vec<String>,
+
+  /// Show no parents before all of their children, in commit date order.
+  #[clap(long, conflicts_with = "topo-order")]
+  pub date_order: bool,
+
+  /// Show no parents before all of their children, and avoid mixing up
+  /// commits from different lines of history.
+  #[clap(long)]
+  pub topo_order: bool,
+
+  /// Show the commits in reverse order.
+  #[clap(long)]
+  pub reverse: bool,
+
+  /// Follow only the first parent of merge commits.
+  #[clap(long)]
+  pub first_parent: bool,
+
+  /// Limit the number of commits shown.
+  #[clap(short = 'n', long, value_name = "number")]
+  pub max_count: Option<usize>,
+
+  /// Skip this many commits before showing any.
+  #[clap(long, value_name = "number", default_value_t = 0)]
+  pub skip: usize,
+
+  /// Show commits more recent than a date (eg. `2005-04-07` or
+  /// `2.weeks.ago`).
+  #[clap(long, alias = "after", value_name = "date")]
+  pub since: Option<String>,
+
+  /// Show commits older than a date.
+  #[clap(long, alias = "before", value_name = "date")]
+  pub until: Option<String>,
+
+  /// Show only the commits whose author (`Name <email>`) matches the pattern
+  /// (or any of them, if given several times).
+  #[clap(long, value_name = "pattern", multiple_occurrences = true)]
+  pub author: Vec<String>,
+
+  /// Show only the commits whose committer matches the pattern.
+  #[clap(long, value_name = "pattern", multiple_occurrences = true)]
+  pub committer: Vec<String>,
+
+  /// Show only the commits with a message line that matches the pattern (or
+  /// any of them, if given several times).
+  #[clap(long, value_name = "pattern", multiple_occurrences = true)]
+  pub grep: Vec<String>,
+
+  /// Show only the commits whose message matches all the `--grep` patterns.
+  #[clap(long)]
+  pub all_match: bool,
+
+  /// Show only the commits whose message doesn't match the `--grep`
+  /// patterns.
+  #[clap(long)]
+  pub invert_grep: bool,
+
+  /// Match the patterns regardless of case.
+  #[clap(short = 'i', long)]
+  pub regexp_ignore_case: bool,
+
+  /// Take the patterns as fixed strings rather than regular expressions.
+  #[clap(short = 'F', long)]
+  pub fixed_strings: bool,
+
+  /// Show only merge commits.
+  #[clap(long, conflicts_with = "no-merges")]
+  pub merges: bool,
+
+  /// Don't show merge commits.
+  #[clap(long)]
+  pub no_merges: bool,
+
+  /// Show only the commits that change the number of times a string occurs
+  /// in a file (ie. that add or remove it).
+  #[clap(short = 'S', value_name = "string", conflicts_with = "g")]
+  pub s: Option<String>,
+
+  /// Show only the commits that add or remove a line matching the pattern.
+  #[clap(short = 'G', value_name = "pattern")]
+  pub g: Option<String>,
+
+  /// Take the string of `-S` as a regular expression.
+  #[clap(long)]
+  pub pickaxe_regex: bool,
+
+  /// Show how many lines of each file the commits changed, with a graph.
+  #[clap(long)]
+  pub stat: bool,
+
+  /// Show the names and status of the files the commits changed.
+  #[clap(long, conflicts_with = "stat")]
+  pub name_status: bool,
+
+  #[clap(flatten)]
+  pub renames: RenameArgs,
+
+  /// Show each commit on a single line (`--pretty=oneline --abbrev-commit`).
+  #[clap(long)]
+  pub oneline: bool,
+
+  /// The format to show commits in: `oneline`, `short`, `medium` (the
+  /// default), `full`, `fuller`, `raw`, `format:<string>` or
+  /// `tformat:<string>`.
+  #[clap(
+    long,
+    value_name = "format",
+    min_values = 0,
+    require_equals = true,
+    default_missing_value = "medium"
+  )]
+  pub pretty: Option<String>,
+
+  /// The same as `--pretty`.
+  #[clap(long, value_name = "format")]
+  pub format: Option<String>,
+
+  /// Show abbreviated commit hashes.
+  #[clap(long)]
+  pub abbrev_commit: bool,
+
+  /// The format of dates: `default`, `local`, `iso`, `iso-strict`, `rfc`,
+  /// `short`, `raw`, `unix` or `relative`.
+  #[clap(long, value_name = "format")]
+  pub date: Option<String>,
+
+  /// Show the names of the refs pointing at each commit: `short` (the
+  /// default), `full`, `no`, or `auto` (only when writing to a terminal).
+  #[clap(
+    long,
+    value_name = "style",
+    min_values = 0,
+    require_equals = true,
+    default_missing_value = "short",
+    possible_values = ["short", "full", "auto", "no"]
+  )]
+  pub decorate: Option<String>,
+
+  /// Don't show the names of refs.
+  #[clap(long)]
+  pub no_decorate: bool,
+
+  /// Draw the history as a graph next to the commits.
+  #[clap(long)]
+  pub graph: bool,
 }
 
 pub fn cmd_log(opts: &Log) -> Result<(), String> {
   let repo: Repo = Repo::default();
-  let mut seen: HashSet<String> = HashSet::default();
-  print_commit(
-    repo.clone(),
-    find_object(repo, &opts.commit, None, false).to_string(),
-    &mut seen,
-  )?;
+
+  // the graph needs every parent before its children
+  let order = match (opts.topo_order, opts.date_order) {
+    (true, _) => Order::Topo,
+    (_, true) => Order::Date,
+    _ if opts.graph => Order::Topo,
+    _ => Order::Walk,
+  };
+  if opts.graph && opts.reverse {
+    return Err(String::from(
+      "options '--reverse' and '--graph' cannot be used together",
+    ));
+  }
+  // commits that don't match the pickaxe are only left out once the walk
+  // has found them, so they don't count towards `--max-count`
+  let pickaxe = pickaxe(opts)?;
+  let limit_shown = pickaxe.is_some() && !opts.reverse;
+  let mut walk = Walk::new(
+    &repo,
+    Options {
+      order,
+      reverse: opts.reverse,
+      first_parent: opts.first_parent,
+      skip: opts.skip,
+      max_count: if limit_shown { None } else { opts.max_count },
+      since: opts.since.as_deref().map(date::approxidate).transpose()?,
+      until: opts.until.as_deref().map(date::approxidate).transpose()?,
+      min_parents: if opts.merges { 2 } else { 0 },
+      max_parents: opts.no_merges.then_some(1),
+      rewrite_parents: opts.graph,
+    },
+  );
+  let (mut revisions, paths) = split_args(&repo, opts)?;
+  if revisions.is_empty() {
+    revisions.push(revision::parse(&repo, "HEAD")?);
+  }
+  for revision in revisions {
+    walk.add(revision)?;
+  }
+  let pathspec = Pathspec::new(&repo, &paths)?;
+  walk.set_pathspec(pathspec.clone());
+  walk.set_grep(grep(opts)?);
+
+  let (format, terminator) = match (&opts.format, &opts.pretty) {
+    (Some(format), _) | (None, Some(format)) => Format::parse(format)?,
+    (None, None) if opts.oneline => (Format::Oneline, true),
+    (None, None) => (Format::Medium, false),
+  };
+  let date = match &opts.date {
+    Some(date) => Style::parse(date)?,
+    None => Style::Default,
+  };
+  let decorate = match opts.decorate.clone() {
+    _ if opts.no_decorate => String::from("no"),
+    Some(decorate) => decorate,
+    None => repo
+      .config_value("log", None, "decorate")
+      .map_or(String::from("auto"), |value| match value.as_str() {
+        "true" | "yes" | "on" | "1" => String::from("short"),
+        "false" | "off" | "0" => String::from("no"),
+        _ => value,
+      }),
+  };
+  let decorations = Decorations::load(&repo, decorate == "full")?;
+  // an empty format shows nothing at all, not even a newline
+  let empty = format == Format::User(String::new());
+  let options = pretty::Options {
+    format,
+    abbrev_commit: opts.abbrev_commit || opts.oneline,
+    date,
+    decorate: match decorate.as_str() {
+      "short" | "full" => true,
+      "auto" => std::io::stdout().is_terminal(),
+      _ => false,
+    },
+  };
+
+  let renames = rename_options(&repo, &opts.renames)?;
+  let show_changes = opts.stat || opts.name_status;
+  let mut graph = opts.graph.then(Graph::default);
+  let mut done = HashSet::new();
+  let mut shown = 0;
+  let mut missing_newline = false;
+  for hash in walk.run()? {
+    if limit_shown && opts.max_count.is_some_and(|max| shown >= max) {
+      break;
+    }
+    let object = read(repo.clone(), &hash, Some("commit"))?;
+    let commit = object.unbox::<Commit>()?;
+
+    // the graph goes through the commits the pickaxe leaves out, and shows
+    // where they were with a line of dots
+    let mut out = String::new();
+    if let Some(graph) = &mut graph {
+      let mut parents = walk.parents(&hash).to_vec();
+      if opts.first_parent {
+        parents.truncate(1);
+      }
+      // a parent can come first when `--until` leaves out the commits
+      // between them, and then it doesn't get a column of its own
+      let mut interesting = Vec::new();
+      for parent in parents {
+        if !done.contains(&parent) && walk.is_interesting(&parent)? {
+          interesting.push(parent);
+        }
+      }
+      graph.update(&hash, interesting);
+    }
+    done.insert(hash.clone());
+    let mut changes = match pickaxe.is_some() || show_changes {
+      true => changes(
+        &repo,
+        commit,
+        walk.parents(&hash),
+        opts.first_parent,
+        &pathspec,
+        &renames,
+      )?,
+      false => Vec::new(),
+    };
+    if let Some(pickaxe) = &pickaxe {
+      if !pickaxe.matches(&changes, |side| contents(&repo, side))? {
+        continue;
+      }
+      // only the files the pickaxe found are shown
+      let mut found = Vec::new();
+      for change in changes {
+        if pickaxe.matches(slice::from_ref(&change), |side| contents(&repo, side))? {
+          found.push(change);
+        }
+      }
+      changes = found;
+    }
+    let entry = pretty::format(
+      &repo,
+      &hash,
+      commit,
+      walk.parents(&hash),
+      &options,
+      &decorations,
+    )?;
+
+    // entries are separated by a newline, drawn with the graph if the
+    // previous entry ended with a newline of its own
+    if shown > 0 && !terminator {
+      if let (Some(graph), false) = (&mut graph, missing_newline) {
+        graph.show_padding(&mut out);
+      }
+      out.push('\n');
+    }
+    shown += 1;
+    missing_newline = !entry.ends_with('\n');
+    match &mut graph {
+      Some(graph) => {
+        graph.show_commit(&mut out);
+        graph.show_commit_msg(&mut out, &entry);
+      }
+      None => out.push_str(&entry),
+    }
+    if terminator && !empty {
+      if let (Some(graph), false) = (&mut graph, missing_newline) {
+        graph.show_padding(&mut out);
+      }
+      out.push('\n');
+    }
+
+    // the changes follow the message after a blank line (or straight after
+    // the line of `--oneline`), with the graph drawn next to each line
+    let width = stat_width().saturating_sub(graph.as_ref().map_or(0, |graph| graph.width()));
+    let diff = match show_changes {
+      true => format_changes(&repo, &changes, opts, width)?,
+      false => String::new(),
+    };
+    if !diff.is_empty() {
+      let mut lines: Vec<&str> = diff.lines().collect();
+      if !empty && options.format != Format::Oneline {
+        match out.ends_with('\n') {
+          true => lines.insert(0, ""),
+          false => out.push('\n'),
+        }
+      }
+      for line in lines {
+        if let Some(graph) = &mut graph {
+          graph.show_padding(&mut out);
+        }
+        out.push_str(line);
+        out.push('\n');
+      }
+    }
+    print!("{}", out);
+  }
   Ok(())
 }
 
-/// Walks through the commit tree and prints out each commit.
-fn print_commit(repo: Repo, hash: String, seen: &mut HashSet<String>) -> Result<(), String> {
-  if seen.contains(&hash) {
-    // already added commit, nothing to do!
-    return Ok(());
+/// Splits the arguments into the revisions to walk and the paths to limit
+/// the history to, the way `diff` does: paths must exist unless they come
+/// after `--`.
+fn split_args(repo: &Repo, opts: &Log) -> Result<(Vec<Revision>, Vec<String>), String> {
+  let mut revisions = Vec::new();
+  let mut paths = Vec::new();
+  for arg in &opts.revisions {
+    if paths.is_empty() {
+      if let Ok(revision) = revision::parse(repo, arg) {
+        revisions.push(revision);
+        continue;
+      }
+    }
+    if fs::symlink_metadata(arg).is_err() {
+      return Err(format!(
+        "ambiguous argument '{}': unknown revision or path not in the working tree.",
+        arg
+      ));
+    }
+    paths.push(arg.clone());
   }
-  seen.insert(hash.to_owned());
+  paths.extend(opts.paths.iter().cloned());
+  Ok((revisions, paths))
+}
 
-  let commit_object = read(repo.clone(), &hash, Some("commit"))?;
-  assert!(commit_object.format().eq("commit"));
-  let commit: &Commit = commit_object.unbox::<Commit>()?;
+/// Compiles the `--author`, `--committer` and `--grep` patterns.
+fn grep(opts: &Log) -> Result<Grep, String> {
+  let compile = |patterns: &[String]| -> Result<Vec<regex::Regex>, String> {
+    patterns
+      .iter()
+      .map(|pattern| {
+        let source = match opts.fixed_strings {
+          true => regex::escape(pattern),
+          false => pattern.clone(),
+        };
+        RegexBuilder::new(&source)
+          .case_insensitive(opts.regexp_ignore_case)
+          .build()
+          .map_err(|msg| format!("invalid regex {} ({})", pattern, msg))
+      })
+      .collect()
+  };
+  Ok(Grep {
+    authors: compile(&opts.author)?,
+    committers: compile(&opts.committer)?,
+    messages: compile(&opts.grep)?,
+    all_match: opts.all_match,
+    invert: opts.invert_grep,
+  })
+}
+
+/// Compiles the `-S` or `-G` search, if there is one.
+fn pickaxe(opts: &Log) -> Result<Option<Pickaxe>, String> {
+  let (pattern, literal) = match (&opts.s, &opts.g) {
+    (Some(string), _) => (string, !opts.pickaxe_regex),
+    (None, Some(pattern)) => (pattern, false),
+    (None, None) => return Ok(None),
+  };
+  let source = match literal {
+    true => regex::escape(pattern),
+    false => pattern.clone(),
+  };
+  let regex = regex::bytes::RegexBuilder::new(&source)
+    .case_insensitive(opts.regexp_ignore_case)
+    .build()
+    .map_err(|msg| format!("invalid regex {} ({})", pattern, msg))?;
+  Ok(Some(match opts.s {
+    Some(_) => Pickaxe::Count(regex),
+    None => Pickaxe::Lines(regex),
+  }))
+}
 
-  let map: &IndexMap<String, String> = &commit.map;
-  if !map.contains_key("parent") {
-    // base case - the initial commit has no parent
-    return Ok(());
+/// The changes a commit makes to the paths the pathspec matches, compared
+/// with its parent as the walk sees it (or with nothing, for a root commit).
+/// Merges have no changes, unless only their first parent is followed.
+fn changes(
+  repo: &Repo,
+  commit: &Commit,
+  parents: &[String],
+  first_parent: bool,
+  pathspec: &Pathspec,
+  renames: &Option<rename::Options>,
+) -> Result<Vec<FileChange>, String> {
+  let parent_tree = match parents {
+    [] => None,
+    [parent, ..] if parents.len() == 1 || first_parent => {
+      let object = read(repo.clone(), parent, Some("commit"))?;
+      Some(object.unbox::<Commit>()?.tree().to_string())
+    }
+    _ => return Ok(Vec::new()),
+  };
+  let mut changes = diff_trees(repo, parent_tree.as_deref(), Some(commit.tree()), true)?;
+  changes.retain(|change| pathspec.matches(change.path()));
+  match renames {
+    Some(options) => rename::detect(changes, options, |side| read_blob(repo, &side.hash)),
+    None => Ok(changes),
   }
+}
 
-  let parents: Vec<&str> = map.get("parent").unwrap().split(' ').collect();
-  for parent in parents {
-    for key in map.keys() {
-      match key.as_str() {
-        "tree" => println!("commit {}", map.get("tree").unwrap().yellow()),
-        "author" => println!("Author: {}", map.get("author").unwrap()),
-        "" => println!("\n    {}", map.get("").unwrap()),
-        _ => continue,
+/// Shows the changes of a commit as `--stat` or `--name-status` asks, with a
+/// `--stat` graph that fits in `width` columns.
+fn format_changes(
+  repo: &Repo,
+  changes: &[FileChange],
+  opts: &Log,
+  width: usize,
+) -> Result<String, String> {
+  let mut out = Vec::new();
+  let result = match opts.name_status {
+    true => changes
+      .iter()
+      .try_for_each(|change| writeln!(out, "{}\t{}", change.status, change.paths())),
+    false => {
+      let mut stats = Vec::new();
+      for change in changes {
+        let load = |side: &Option<Side>| {
+          side
+            .as_ref()
+            .map_or(Ok(Vec::new()), |side| contents(repo, side))
+        };
+        let old_path = change.old.as_ref().map_or(change.path(), |side| &side.path);
+        stats.push(FileStat {
+          name: stat::name(old_path, change.path()),
+          counts: Counts::between(&load(&change.old)?, &load(&change.new)?, Algorithm::Myers),
+        });
       }
+      let color = std::io::stdout().is_terminal();
+      stat::write_stat(&mut out, &stats, width, color)
     }
-    print_commit(repo.clone(), parent.to_string(), seen)?; // do without clone?
-  }
+  };
+  result.map_err(|msg| msg.to_string())?;
+  String::from_utf8(out).map_err(|msg| msg.to_string())
+}
 
-  Ok(())
+/// Reads the contents of one side of a change. A submodule is shown as the
+/// commit it is at.
+fn contents(repo: &Repo, side: &Side) -> Result<Vec<u8>, String> {
+  match side.mode {
+    Mode::Gitlink => Ok(format!("Subproject commit {}\n", side.hash).into_bytes()),
+    _ => read_blob(repo, &side.hash),
+  }
 }
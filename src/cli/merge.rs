@@ -1,10 +1,314 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+
 use clap::Args;
 
+use crate::cli::commit::{cmd_commit, Commit as CommitOpts};
+use crate::cli::diff::stat_width;
+use crate::diff::rename;
+use crate::diff::stat::{self, Counts, FileStat};
+use crate::diff::tree::{diff_files, read_blob};
+use crate::diff::Algorithm;
+use crate::index::checkout::{files, remove_file, switch_trees, write_file, Files};
+use crate::index::merge::{checkout_merge, merge_commits, Options};
+use crate::index::Index;
+use crate::object::commit::CommitBuilder;
+use crate::object::identity::Identity;
+use crate::object::merge_base::is_ancestor;
+use crate::object::refs::{self, short_name};
+use crate::object::{self, abbreviate, find_ref, revision, DEFAULT_ABBREV};
+use crate::repo::editor::cleanup;
+use crate::repo::{state, Repo};
+
+/// Join two or more development histories together.
+///
+/// Brings the changes of another commit into the current branch. If the
+/// current branch is an ancestor of it, the branch is simply moved forward
+/// (unless `--no-ff` is given). Otherwise the changes both sides made since
+/// their merge base are merged path by path, and recorded in a new commit
+/// with both as parents.
+///
+/// Changes that can't be merged are left as conflicts: the working tree gets
+/// the file with conflict markers, and the index has the base, our and their
+/// version of the path (in stages 1 to 3). Once they are resolved (and
+/// added), `git merge --continue` (or `git commit`) records the merge, and
+/// `git merge --abort` gives up on it.
+///
+/// # Example
+/// ```bash
+/// $ git merge topic
+/// Auto-merging README.md
+/// Merge made by the 'ort' strategy.
+///  README.md | 2 +-
+///  1 file changed, 1 insertion(+), 1 deletion(-)
+/// ```
 #[derive(Args, Debug)]
 pub struct Merge {
-  pub name: Option<String>,
+  /// The commit to merge (the upstream of the current branch by default).
+  pub commit: Option<String>,
+
+  /// Fast-forward when possible (the default).
+  #[clap(long, conflicts_with_all = &["no-ff", "ff-only"])]
+  pub ff: bool,
+
+  /// Create a merge commit even when a fast-forward is possible.
+  #[clap(long, conflicts_with = "ff-only")]
+  pub no_ff: bool,
+
+  /// Refuse to merge unless it is a fast-forward.
+  #[clap(long)]
+  pub ff_only: bool,
+
+  /// Use the given message for the merge commit (several `-m` become separate
+  /// paragraphs).
+  #[clap(short, long, multiple_occurrences = true)]
+  pub message: Vec<String>,
+
+  /// Give up on the merge in progress and go back to the state before it.
+  #[clap(long, conflicts_with_all = &["commit", "proceed"])]
+  pub abort: bool,
+
+  /// Record the merge in progress, once its conflicts are resolved.
+  #[clap(long = "continue", conflicts_with = "commit")]
+  pub proceed: bool,
+}
+
+pub fn cmd_merge(opts: &Merge) -> Result<(), String> {
+  let repo: Repo = Repo::default();
+  let merging = state::merge_head(&repo).is_some();
+  if opts.abort {
+    return match merging {
+      true => abort(&repo),
+      false => Err("There is no merge to abort (MERGE_HEAD missing).".to_string()),
+    };
+  }
+  // like in git, concluding the merge is a plain `git commit`
+  if opts.proceed {
+    return match merging {
+      true => cmd_commit(&CommitOpts::default()),
+      false => Err("There is no merge in progress (MERGE_HEAD missing).".to_string()),
+    };
+  }
+  let mut index = Index::read(&repo)?;
+  if index.has_conflicts() {
+    println!("error: Merging is not possible because you have unmerged files.");
+    println!("hint: Fix them up in the work tree, and then use 'git add/rm <file>'");
+    println!("hint: as appropriate to mark resolution and make a commit.");
+    return Err("Exiting because of an unresolved conflict.".to_string());
+  }
+  if merging {
+    return Err(
+      "You have not concluded your merge (MERGE_HEAD exists).\n\
+       Please, commit your changes before you merge."
+        .to_string(),
+    );
+  }
+
+  let name = match &opts.commit {
+    Some(name) => name.clone(),
+    None => match revision::upstream(&repo, "HEAD") {
+      Ok(refname) => short_name(&refname).to_string(),
+      Err(_) => return Err("No remote for the current branch.".to_string()),
+    },
+  };
+  let theirs = match revision::resolve_commit(&repo, &name) {
+    Ok(hash) => hash,
+    Err(_) => return Err(format!("{} - not something we can merge", name)),
+  };
+  let head = refs::resolve(&repo, Path::new("HEAD"))?;
+  // look up the identity for the reflog before anything is changed
+  let reflog_identity = Identity::reflog(&repo)?.to_string();
+  write_orig_head(&repo, &head)?;
+
+  if is_ancestor(&repo, &theirs, &head)? {
+    println!("Already up to date.");
+    return Ok(());
+  }
+  let (head_files, their_files) = (files(&repo, &head)?, files(&repo, &theirs)?);
+  if is_ancestor(&repo, &head, &theirs)? && !opts.no_ff {
+    println!(
+      "Updating {}..{}",
+      abbreviate(&repo, &head, DEFAULT_ABBREV)?,
+      abbreviate(&repo, &theirs, DEFAULT_ABBREV)?
+    );
+    switch_trees(&repo, &mut index, &head_files, &their_files, false, "merge")?;
+    println!("Fast-forward");
+    index.write(&repo)?;
+    let reason = format!("merge {}: Fast-forward", name);
+    refs::update_head(&repo, &theirs, &reflog_identity, &reason)?;
+    return print_diffstat(&repo, &head_files, &their_files);
+  }
+  if opts.ff_only {
+    return Err("Not possible to fast-forward, aborting.".to_string());
+  }
+  let identity = Identity::committer(&repo)?.to_string();
+
+  // the merge is made from the trees, so the index can't have changes of
+  // its own
+  let staged: BTreeSet<&str> = index
+    .entries
+    .iter()
+    .filter(|entry| head_files.get(&entry.path) != Some(&(entry.mode, entry.hash.clone())))
+    .map(|entry| entry.path.as_str())
+    .chain(
+      head_files
+        .keys()
+        .filter(|path| index.entry(path).is_none())
+        .map(|path| path.as_str()),
+    )
+    .collect();
+  if !staged.is_empty() {
+    println!("error: Your local changes to the following files would be overwritten by merge:");
+    println!("  {}", staged.into_iter().collect::<Vec<_>>().join(" "));
+    println!("Merge with strategy ort failed.");
+    process::exit(2);
+  }
+
+  let merge = merge_commits(&repo, &head, &theirs, &Options::new(&repo, "HEAD", &name)?)?;
+  if let Err(msg) = checkout_merge(&repo, &mut index, &head_files, &merge) {
+    println!("error: {}", msg);
+    println!("Merge with strategy ort failed.");
+    process::exit(2);
+  }
+  index.write(&repo)?;
+  for msg in &merge.messages {
+    println!("{}", msg);
+  }
+
+  let message = match opts.message.is_empty() {
+    true => format!("{}\n", default_message(&repo, &name)),
+    false => cleanup(&opts.message.join("\n\n"), false),
+  };
+  if !merge.is_clean() {
+    let mut merge_msg = message;
+    merge_msg.push_str("\n# Conflicts:\n");
+    for path in merge.conflicts.keys() {
+      merge_msg.push_str(&format!("#\t{}\n", path));
+    }
+    let mode = if opts.no_ff { "no-ff" } else { "" };
+    state::write_merge(&repo, &theirs, &merge_msg, mode)?;
+    println!("Automatic merge failed; fix conflicts and then commit the result.");
+    process::exit(1);
+  }
+
+  let tree = index.write_tree(&repo)?;
+  let author = Identity::author(&repo)?.to_string();
+  let commit = CommitBuilder::new(&tree)
+    .parent(&head)
+    .parent(&theirs)
+    .author(&author)
+    .committer(&identity)
+    .message(&message)
+    .build(repo.clone());
+  let hash = object::write(&commit, false)?;
+  let strategy = "Merge made by the 'ort' strategy.";
+  refs::update_head(
+    &repo,
+    &hash,
+    &identity,
+    &format!("merge {}: {}", name, strategy),
+  )?;
+  println!("{}", strategy);
+  print_diffstat(&repo, &head_files, &merge.files)
+}
+
+/// Gives up on the merge in progress: the paths the merge changed (or that
+/// were changed since) are reset to HEAD, in the index and the working tree.
+/// Local changes to any other path are kept.
+fn abort(repo: &Repo) -> Result<(), String> {
+  let head = refs::resolve(repo, Path::new("HEAD"))?;
+  let head_files = files(repo, &head)?;
+  let identity = Identity::reflog(repo)?.to_string();
+  let mut index = Index::read(repo)?;
+  let mut paths: BTreeSet<String> = index
+    .entries
+    .iter()
+    .filter(|entry| {
+      entry.stage > 0 || head_files.get(&entry.path) != Some(&(entry.mode, entry.hash.clone()))
+    })
+    .map(|entry| entry.path.clone())
+    .collect();
+  paths.extend(
+    head_files
+      .keys()
+      .filter(|path| index.entry(path).is_none())
+      .cloned(),
+  );
+  for path in paths {
+    match head_files.get(&path) {
+      Some((mode, hash)) => index.add(write_file(repo, &path, *mode, hash)?),
+      None => {
+        index.remove(&path);
+        remove_file(repo, &path)?;
+      }
+    }
+  }
+  index.write(repo)?;
+  refs::update_head(repo, &head, &identity, "reset: moving to HEAD")?;
+  state::clear_merge(repo);
+  Ok(())
+}
+
+/// The message of a merge commit, which says what was merged, and into what
+/// unless it is the main branch, eg. `Merge branch 'topic' into release`.
+fn default_message(repo: &Repo, name: &str) -> String {
+  let mut name = name;
+  while let Some(stripped) = name.strip_suffix("~0").or_else(|| name.strip_suffix("^0")) {
+    name = stripped;
+  }
+  let mut message = match find_ref(repo, name) {
+    Some(refname) if refname.starts_with("refs/heads/") => {
+      format!("Merge branch '{}'", short_name(&refname))
+    }
+    Some(refname) if refname.starts_with("refs/tags/") => {
+      format!("Merge tag '{}'", short_name(&refname))
+    }
+    Some(refname) if refname.starts_with("refs/remotes/") => {
+      format!("Merge remote-tracking branch '{}'", short_name(&refname))
+    }
+    _ => match revision::resolve_commit(repo, name) {
+      Ok(hash) => format!("Merge commit '{}'", hash),
+      Err(_) => format!("Merge commit '{}'", name),
+    },
+  };
+  match refs::current_branch(repo) {
+    Some(branch) if ["refs/heads/main", "refs/heads/master"].contains(&branch.as_str()) => (),
+    Some(branch) => message.push_str(&format!(" into {}", short_name(&branch))),
+    None => message.push_str(" into HEAD"),
+  }
+  message
+}
+
+/// Prints what the merge changed, like `diff --stat --summary` would.
+fn print_diffstat(repo: &Repo, old: &Files, new: &Files) -> Result<(), String> {
+  let changes = rename::detect(diff_files(old, new), &rename::Options::default(), |side| {
+    read_blob(repo, &side.hash)
+  })?;
+  let mut stats = Vec::new();
+  for change in &changes {
+    let load = |side: &Option<crate::diff::tree::Side>| match side {
+      Some(side) => read_blob(repo, &side.hash),
+      None => Ok(Vec::new()),
+    };
+    let old_path = change.old.as_ref().map_or(change.path(), |side| &side.path);
+    stats.push(FileStat {
+      name: stat::name(old_path, change.path()),
+      counts: Counts::between(&load(&change.old)?, &load(&change.new)?, Algorithm::Myers),
+    });
+  }
+  let mut out = io::stdout();
+  stat::write_stat(&mut out, &stats, stat_width(), false)
+    .and_then(|_| stat::write_summary(&mut out, &changes))
+    .map_err(|msg| msg.to_string())
 }
 
-pub fn cmd_merge() -> Result<(), String> {
-  todo!("cmd_merge");
+/// Saves the commit HEAD was at before the merge (`ORIG_HEAD`).
+fn write_orig_head(repo: &Repo, head: &str) -> Result<(), String> {
+  match fs::write(repo.git_dir.join("ORIG_HEAD"), format!("{}\n", head)) {
+    Ok(()) => Ok(()),
+    Err(msg) => Err(format!("unable to write ORIG_HEAD ({})", msg)),
+  }
 }
@@ -0,0 +1,76 @@
+use std::process;
+
+use clap::Args;
+
+use crate::object::merge_base::{is_ancestor, merge_bases, octopus_merge_bases};
+use crate::object::revision;
+use crate::repo::Repo;
+
+/// Find as good common ancestors as possible for a merge.
+///
+/// Prints the best common ancestor of the first commit and the others (as if
+/// the others were merged together first), or all of them with `--all`. When
+/// there is none, nothing is printed and the exit status is 1, so that it can
+/// be used in scripts.
+///
+/// # Example
+/// ```bash
+/// $ git merge-base main topic
+/// 6ebf049bd1ce2e1a8a2c2c5a1ab5f5a5ac66d2c1
+/// $ git merge-base --is-ancestor main topic && echo "topic has main"
+/// topic has main
+/// ```
+#[derive(Args, Debug)]
+pub struct MergeBase {
+  /// The commits to find the common ancestors of.
+  pub commits: Vec<String>,
+
+  /// Print all of the best common ancestors, not just one.
+  #[clap(short, long)]
+  pub all: bool,
+
+  /// Find the best common ancestors of all of the commits, like an octopus
+  /// merge of them would.
+  #[clap(long, conflicts_with = "is-ancestor")]
+  pub octopus: bool,
+
+  /// Exit with status 0 if the first commit is an ancestor of the second
+  /// one, and 1 otherwise.
+  #[clap(long)]
+  pub is_ancestor: bool,
+}
+
+pub fn cmd_merge_base(opts: &MergeBase) -> Result<(), String> {
+  let repo: Repo = Repo::default();
+  let mut commits = Vec::new();
+  for name in &opts.commits {
+    match revision::resolve_commit(&repo, name) {
+      Ok(hash) => commits.push(hash),
+      Err(_) => return Err(format!("Not a valid object name {}", name)),
+    }
+  }
+
+  if opts.is_ancestor {
+    return match &commits[..] {
+      [ancestor, descendant] => match is_ancestor(&repo, ancestor, descendant)? {
+        true => Ok(()),
+        false => process::exit(1),
+      },
+      _ => Err("usage: git merge-base --is-ancestor <commit> <commit>".to_string()),
+    };
+  }
+  let bases = match &commits[..] {
+    [] => return Err("usage: git merge-base [-a | --all] <commit> <commit>...".to_string()),
+    commits if opts.octopus => octopus_merge_bases(&repo, commits)?,
+    [_] => return Err("usage: git merge-base [-a | --all] <commit> <commit>...".to_string()),
+    [one, others @ ..] => merge_bases(&repo, one, others)?,
+  };
+
+  if bases.is_empty() {
+    process::exit(1);
+  }
+  for base in bases.iter().take(if opts.all { bases.len() } else { 1 }) {
+    println!("{}", base);
+  }
+  Ok(())
+}
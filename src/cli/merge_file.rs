@@ -0,0 +1,132 @@
+use std::io::{self, Write};
+use std::{fs, process};
+
+use clap::Args;
+
+use crate::diff::is_binary;
+use crate::diff::merge::{merge, Favor, Level, Options, Style, DEFAULT_MARKER_SIZE};
+
+/// Run a three-way file merge.
+///
+/// Merges the changes that led from `base` to `other` into `current`, and
+/// writes the result back to `current` (or prints it with `-p`). Lines both
+/// files changed are a conflict, which is written out between conflict markers
+/// named after the files (or the `-L` labels):
+/// ```text
+/// <<<<<<< current
+/// our lines
+/// =======
+/// their lines
+/// >>>>>>> other
+/// ```
+/// The exit status is the number of conflicts (at most 127), so that it is 0
+/// for a clean merge.
+///
+/// # Example
+/// ```bash
+/// $ git merge-file -p --diff3 ours.txt base.txt theirs.txt
+/// ```
+#[derive(Args, Debug)]
+pub struct MergeFile {
+  /// The file to merge into (our side).
+  pub current: String,
+
+  /// The common ancestor of both files.
+  pub base: String,
+
+  /// The file to merge the changes of (their side).
+  pub other: String,
+
+  /// Print the result instead of writing it to the current file.
+  #[clap(short = 'p', long)]
+  pub stdout: bool,
+
+  /// Don't warn about conflicts.
+  #[clap(short, long)]
+  pub quiet: bool,
+
+  /// Show the base version of conflicts too.
+  #[clap(long, conflicts_with = "zdiff3")]
+  pub diff3: bool,
+
+  /// Like --diff3, but move the lines both sides start and end with out of
+  /// conflicts.
+  #[clap(long)]
+  pub zdiff3: bool,
+
+  /// Resolve conflicts with our side.
+  #[clap(long, conflicts_with_all = &["theirs", "union"])]
+  pub ours: bool,
+
+  /// Resolve conflicts with their side.
+  #[clap(long, conflicts_with = "union")]
+  pub theirs: bool,
+
+  /// Resolve conflicts with both sides, ours first.
+  #[clap(long)]
+  pub union: bool,
+
+  /// The length of the conflict markers.
+  #[clap(long, default_value_t = DEFAULT_MARKER_SIZE)]
+  pub marker_size: usize,
+
+  /// The labels of the current, base and other file (the file names by
+  /// default).
+  #[clap(
+    short = 'L',
+    value_name = "name",
+    number_of_values = 1,
+    multiple_occurrences = true
+  )]
+  pub labels: Vec<String>,
+}
+
+pub fn cmd_merge_file(opts: &MergeFile) -> Result<(), String> {
+  if opts.labels.len() > 3 {
+    return Err("too many labels on the command line".to_string());
+  }
+  let names = [&opts.current, &opts.base, &opts.other];
+  let mut contents = Vec::new();
+  for name in names {
+    let data = fs::read(name).map_err(|_| format!("Could not stat {}", name))?;
+    if is_binary(&data) {
+      return Err(format!("Cannot merge binary files: {}", name));
+    }
+    contents.push(data);
+  }
+  let label = |i: usize| Some(opts.labels.get(i).unwrap_or(names[i]).clone());
+
+  let options = Options {
+    level: Level::ZealousAlnum,
+    style: match (opts.diff3, opts.zdiff3) {
+      (true, _) => Style::Diff3,
+      (_, true) => Style::ZealousDiff3,
+      _ => Style::Merge,
+    },
+    favor: match (opts.ours, opts.theirs, opts.union) {
+      (true, _, _) => Some(Favor::Ours),
+      (_, true, _) => Some(Favor::Theirs),
+      (_, _, true) => Some(Favor::Union),
+      _ => None,
+    },
+    marker_size: opts.marker_size,
+    ours_label: label(0),
+    base_label: label(1),
+    theirs_label: label(2),
+    ..Options::default()
+  };
+  let merged = merge(&contents[1], &contents[0], &contents[2], &options);
+
+  if opts.stdout {
+    io::stdout()
+      .write_all(&merged.data)
+      .map_err(|e| e.to_string())?;
+  } else {
+    fs::write(&opts.current, &merged.data)
+      .map_err(|_| format!("Could not open {} for writing", opts.current))?;
+  }
+  if merged.conflicts > 0 {
+    process::exit(merged.conflicts.min(127) as i32);
+  }
+  Ok(())
+}
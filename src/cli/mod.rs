@@ -1,31 +1,49 @@
 pub(crate) mod add;
+pub(crate) mod branch;
 pub(crate) mod cat_file;
+pub(crate) mod check_ignore;
 pub(crate) mod checkout;
+pub(crate) mod cherry_pick;
 pub(crate) mod commit;
+pub(crate) mod diff;
+pub(crate) mod diff_tree;
 pub(crate) mod hash_object;
 pub(crate) mod init;
 pub(crate) mod log;
 pub(crate) mod merge;
+pub(crate) mod merge_base;
+pub(crate) mod merge_file;
 pub(crate) mod rebase;
 pub(crate) mod rev_parse;
+pub(crate) mod revert;
 pub(crate) mod rm;
 pub(crate) mod show_ref;
 pub(crate) mod show_tree;
+pub(crate) mod status;
 pub(crate) mod tag;
 
 use add::Add;
+use branch::Branch;
 use cat_file::CatFile;
+use check_ignore::CheckIgnore;
 use checkout::Checkout;
+use cherry_pick::CherryPick;
 use clap::{Parser, Subcommand};
 use commit::Commit;
+use diff::Diff;
+use diff_tree::DiffTree;
 use hash_object::HashObject;
 use init::Init;
 use log::Log;
 use merge::Merge;
+use merge_base::MergeBase;
+use merge_file::MergeFile;
 use rebase::Rebase;
 use rev_parse::RevParse;
+use revert::Revert;
 use rm::Rm;
 use show_tree::ShowTree;
+use status::Status;
 use tag::Tag;
 
 use self::show_ref::ShowRef;
@@ -48,15 +66,30 @@ pub enum Command {
   /// Add file contents to the index.
   Add(Add),
 
+  /// List, create, or delete branches.
+  Branch(Branch),
+
   /// Provide content or type and size information for repository objects.
   CatFile(CatFile),
 
+  /// Debug gitignore / exclude files.
+  CheckIgnore(CheckIgnore),
+
   /// Switch branches or restore working tree files.
   Checkout(Checkout),
 
+  /// Apply the changes introduced by some existing commits.
+  CherryPick(CherryPick),
+
   /// Record changes to the repository.
   Commit(Commit),
 
+  /// Show changes between commits, commit and working tree, etc.
+  Diff(Diff),
+
+  /// Compares the content and mode of blobs found via two tree objects.
+  DiffTree(DiffTree),
+
   /// Compute object ID and optionally creates a blob from a file.
   HashObject(HashObject),
 
@@ -64,7 +97,7 @@ pub enum Command {
   Init(Init),
 
   /// Show commit logs.
-  Log(Log),
+  Log(Box<Log>),
 
   /// List the contents of a tree object.
   LsTree(ShowTree),
@@ -72,18 +105,30 @@ pub enum Command {
   /// Join two or more development histories together.
   Merge(Merge),
 
+  /// Find as good common ancestors as possible for a merge.
+  MergeBase(MergeBase),
+
+  /// Run a three-way file merge.
+  MergeFile(MergeFile),
+
   /// Reapply commits on top of another base tip.
   Rebase(Rebase),
 
   /// Pick out and massage parameters.
   RevParse(RevParse),
 
+  /// Revert some existing commits.
+  Revert(Revert),
+
   /// Remove files from the working tree and from the index.
   Rm(Rm),
 
   /// List references in a local repository.
   ShowRef(ShowRef),
 
+  /// Show the working tree status.
+  Status(Status),
+
   /// Create, list, delete or verify a tag object signed with GPG.
   Tag(Tag),
 }
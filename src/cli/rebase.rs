@@ -1,10 +1,804 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::slice;
+
 use clap::Args;
 
+use crate::diff::patch::patch_id;
+use crate::diff::tree::{diff_trees, read_blob};
+use crate::index::checkout::{files, switch_trees};
+use crate::index::sequencer::{self, Action, Applied, Instruction};
+use crate::index::status::Status;
+use crate::index::Index;
+use crate::object::commit::Commit;
+use crate::object::identity::Identity;
+use crate::object::merge_base::{is_ancestor, merge_bases};
+use crate::object::pretty::title;
+use crate::object::refs::{self, short_name};
+use crate::object::serializable::Unbox;
+use crate::object::walk::{Options, Order, Walk};
+use crate::object::{self, abbreviate, revision, DEFAULT_ABBREV};
+use crate::repo::editor::{cleanup, edit, editor, run_editor, EDITOR_HELP};
+use crate::repo::Repo;
+
+/// The help at the end of the todo list of an interactive rebase.
+const TODO_HELP: &str = "#
+# Commands:
+# p, pick <commit> = use commit
+# r, reword <commit> = use commit, but edit the commit message
+# e, edit <commit> = use commit, but stop for amending
+# s, squash <commit> = use commit, but meld into previous commit
+# f, fixup <commit> = like \"squash\" but keep only the previous
+#                    commit's log message
+# x, exec <command> = run command (the rest of the line) using shell
+# d, drop <commit> = remove commit
+#
+# These lines can be re-ordered; they are executed from top to bottom.
+#
+# If you remove a line here THAT COMMIT WILL BE LOST.
+#
+# However, if you remove everything, the rebase will be aborted.
+#
+";
+
+/// Reapply commits on top of another base tip.
+///
+/// The commits of the current branch that `upstream` doesn't have are
+/// replayed, one by one and oldest first, on top of `upstream` (or of the
+/// commit given with `--onto`), and the branch is moved to the result.
+/// Merge commits are left out, and so are commits that make the same change
+/// as one upstream already has.
+///
+/// Progress is recorded in `.git/rebase-merge`. When a commit doesn't apply
+/// cleanly, the rebase stops with the conflicts in the index and the working
+/// tree; once they are resolved (and added), `git rebase --continue` commits
+/// the result and carries on, `git rebase --skip` leaves the commit out and
+/// `git rebase --abort` goes back to where the rebase started.
+///
+/// With `--interactive`, the list of commits is opened in the sequence editor
+/// (`GIT_SEQUENCE_EDITOR`, `sequence.editor` or the commit message editor)
+/// first. Each line says what to do with a commit: `pick` it, `reword` its
+/// message, stop to `edit` it, meld it into the previous one with `squash`
+/// (or `fixup`, which drops its message) or `drop` it. `exec` lines run a
+/// shell command.
+///
+/// # Example
+/// ```bash
+/// $ git rebase main
+/// Successfully rebased and updated refs/heads/topic.
+/// ```
 #[derive(Args, Debug)]
 pub struct Rebase {
-  pub name: Option<String>,
+  /// The branch to compare against (the upstream of the current branch by
+  /// default).
+  pub upstream: Option<String>,
+
+  /// Replay the commits on top of this commit instead of `upstream`.
+  #[clap(long)]
+  pub onto: Option<String>,
+
+  /// Edit the list of commits to replay first.
+  #[clap(short, long)]
+  pub interactive: bool,
+
+  /// Carry on once the conflicts that stopped the rebase are resolved.
+  #[clap(
+    long = "continue",
+    conflicts_with_all = &["upstream", "onto", "interactive", "skip", "abort"]
+  )]
+  pub proceed: bool,
+
+  /// Leave out the commit that stopped the rebase, and carry on.
+  #[clap(long, conflicts_with_all = &["upstream", "onto", "interactive", "abort"])]
+  pub skip: bool,
+
+  /// Give up on the rebase and go back to where it started.
+  #[clap(long, conflicts_with_all = &["upstream", "onto", "interactive"])]
+  pub abort: bool,
+}
+
+/// A rebase in progress, as recorded in `.git/rebase-merge`.
+struct State {
+  dir: PathBuf,
+
+  /// The branch being rebased, or `detached HEAD`.
+  head_name: String,
+
+  /// The commit the commits are replayed onto.
+  onto: String,
+
+  /// The commit HEAD was at when the rebase started.
+  orig_head: String,
+}
+
+impl State {
+  fn read(repo: &Repo) -> Option<Self> {
+    let dir = repo.git_dir.join("rebase-merge");
+    let read = |file: &str| {
+      fs::read_to_string(dir.join(file))
+        .ok()
+        .map(|data| data.trim_end().to_string())
+    };
+    Some(Self {
+      head_name: read("head-name")?,
+      onto: read("onto")?,
+      orig_head: read("orig-head")?,
+      dir,
+    })
+  }
+
+  fn path(&self, file: &str) -> PathBuf {
+    self.dir.join(file)
+  }
+
+  /// The branch being rebased, or `None` if HEAD was detached.
+  fn branch(&self) -> Option<&str> {
+    Some(self.head_name.as_str()).filter(|name| name.starts_with("refs/"))
+  }
+}
+
+/// What happened to an instruction of the todo list.
+enum Step {
+  /// It is done, on to the next one.
+  Next,
+
+  /// The rebase stopped for the commit to be edited.
+  Stopped,
+
+  /// The commit stopped for its conflicts to be resolved.
+  Conflicts,
+
+  /// The commit couldn't be applied at all, and has to be tried again.
+  Failed,
+}
+
+pub fn cmd_rebase(opts: &Rebase) -> Result<(), String> {
+  let repo: Repo = Repo::default();
+  if opts.proceed || opts.skip || opts.abort {
+    let state = match State::read(&repo) {
+      Some(state) => state,
+      None => return Err("No rebase in progress?".to_string()),
+    };
+    return match (opts.abort, opts.skip) {
+      (true, _) => abort(&repo, &state),
+      (_, true) => skip(&repo, &state),
+      _ => proceed(&repo, &state),
+    };
+  }
+  let dir = repo.git_dir.join("rebase-merge");
+  if dir.exists() {
+    return Err(format!(
+      "It seems that there is already a rebase-merge directory, and\n\
+       I wonder if you are in the middle of another rebase.  If that is the\n\
+       case, please try\n\
+       \tgit rebase (--continue | --abort | --skip)\n\
+       If that is not the case, please\n\
+       \trm -fr \"{}\"\n\
+       and run me again.  I am stopping in case you still have something\n\
+       valuable there.\n",
+      dir.display()
+    ));
+  }
+
+  let upstream_name = match &opts.upstream {
+    Some(name) => name.clone(),
+    None => match revision::upstream(&repo, "HEAD") {
+      Ok(refname) => short_name(&refname).to_string(),
+      Err(_) => {
+        return Err(
+          "There is no tracking information for the current branch.\n\
+           Please specify which branch you want to rebase against."
+            .to_string(),
+        )
+      }
+    },
+  };
+  let upstream = match revision::resolve_commit(&repo, &upstream_name) {
+    Ok(hash) => hash,
+    Err(_) => return Err(format!("invalid upstream '{}'", upstream_name)),
+  };
+  let (onto_name, onto) = match &opts.onto {
+    Some(name) => match revision::resolve_commit(&repo, name) {
+      Ok(hash) => (name.clone(), hash),
+      Err(_) => return Err(format!("Does not point to a valid commit '{}'", name)),
+    },
+    None => (upstream_name, upstream.clone()),
+  };
+  let head = refs::resolve(&repo, Path::new("HEAD"))?;
+  let branch = refs::current_branch(&repo);
+  let identity = Identity::reflog(&repo)?.to_string();
+
+  let mut index = Index::read(&repo)?;
+  let status = Status::collect(&repo, &mut index)?;
+  let unstaged = status.has_unstaged_changes() || !status.conflicts.is_empty();
+  if unstaged || status.has_staged_changes() {
+    let mut msg = match unstaged {
+      true => "cannot rebase: You have unstaged changes.".to_string(),
+      false => "cannot rebase: Your index contains uncommitted changes.".to_string(),
+    };
+    if unstaged && status.has_staged_changes() {
+      msg.push_str("\nadditionally, your index contains uncommitted changes.");
+    }
+    msg.push_str("\nPlease commit or stash them.");
+    return Err(msg);
+  }
+
+  if !opts.interactive
+    && is_ancestor(&repo, &onto, &head)?
+    && merge_bases(&repo, &upstream, slice::from_ref(&head))? == [onto.clone()]
+  {
+    match &branch {
+      Some(branch) => println!("Current branch {} is up to date.", short_name(branch)),
+      None => println!("HEAD is up to date."),
+    }
+    return Ok(());
+  }
+
+  let mut todo = Vec::new();
+  let mut skipped = false;
+  let applied = upstream_patch_ids(&repo, &upstream, &head)?;
+  for hash in commits(&repo, &head, &upstream)? {
+    if !applied.is_empty() && applied.contains(&commit_patch_id(&repo, &hash)?) {
+      println!(
+        "warning: skipped previously applied commit {}",
+        abbreviate(&repo, &hash, DEFAULT_ABBREV)?
+      );
+      skipped = true;
+      continue;
+    }
+    todo.push(Instruction::new(&repo, Action::Pick, &hash)?);
+  }
+  let advice = repo
+    .config_value("advice", None, "skippedCherryPicks")
+    .or_else(|| repo.config_value("advice", None, "skippedcherrypicks"));
+  if skipped && advice.as_deref() != Some("false") {
+    println!("hint: use --reapply-cherry-picks to include skipped commits");
+    println!("hint: Disable this message with \"git config advice.skippedCherryPicks false\"");
+  }
+
+  if let Err(msg) = fs::create_dir_all(&dir) {
+    return Err(format!(
+      "could not create directory '{}' ({})",
+      dir.display(),
+      msg
+    ));
+  }
+  let head_name = branch
+    .clone()
+    .unwrap_or_else(|| "detached HEAD".to_string());
+  for (file, data) in [
+    ("head-name", head_name),
+    ("onto", onto.clone()),
+    ("orig-head", head.clone()),
+  ] {
+    sequencer::write(&dir.join(file), &format!("{}\n", data))?;
+  }
+  sequencer::write(&dir.join("interactive"), "")?;
+  if opts.interactive {
+    todo = match edit_todo(&repo, &dir, &todo, &upstream, &head, &onto) {
+      Ok(todo) => todo,
+      Err(msg) => {
+        let _ = fs::remove_dir_all(&dir);
+        return Err(msg);
+      }
+    };
+  }
+
+  sequencer::write(&repo.git_dir.join("ORIG_HEAD"), &format!("{}\n", head))?;
+
+  // picks that would recreate the commits they pick are left as they are,
+  // and the rebase starts from the last of them
+  let mut start = onto;
+  let mut done = Vec::new();
+  while let Some(first) = todo.first().filter(|first| first.action == Action::Pick) {
+    let object = object::read(repo.clone(), &first.argument, Some("commit"))?;
+    if object.unbox::<Commit>()?.parents() != [start.clone()] {
+      break;
+    }
+    start = first.argument.clone();
+    done.push(todo.remove(0));
+  }
+  sequencer::write_todo(&repo, &dir.join("git-rebase-todo"), &todo, false)?;
+  sequencer::write_todo(&repo, &dir.join("done"), &done, false)?;
+  sequencer::write(&dir.join("msgnum"), &format!("{}\n", done.len()))?;
+  sequencer::write(&dir.join("end"), &format!("{}\n", done.len() + todo.len()))?;
+
+  let (head_files, start_files) = (files(&repo, &head)?, files(&repo, &start)?);
+  if let Err(msg) = switch_trees(
+    &repo,
+    &mut index,
+    &head_files,
+    &start_files,
+    false,
+    "checkout",
+  ) {
+    let _ = fs::remove_dir_all(&dir);
+    return Err(msg);
+  }
+  index.write(&repo)?;
+  let message = format!("rebase (start): checkout {}", onto_name);
+  refs::move_head(&repo, None, &start, &identity, &message)?;
+  match State::read(&repo) {
+    Some(state) => run(&repo, &state),
+    None => Err("could not read the state of the rebase".to_string()),
+  }
+}
+
+/// The commits to replay: the ones `head` has and `upstream` doesn't, oldest
+/// first, without merges.
+fn commits(repo: &Repo, head: &str, upstream: &str) -> Result<Vec<String>, String> {
+  let options = Options {
+    order: Order::Topo,
+    reverse: true,
+    max_parents: Some(1),
+    ..Options::default()
+  };
+  let mut walk = Walk::new(repo, options);
+  walk.push(head);
+  walk.hide(upstream);
+  walk.run()
+}
+
+/// The patch IDs of the commits `upstream` has and `head` doesn't, to leave
+/// out the commits that were already applied upstream.
+fn upstream_patch_ids(repo: &Repo, upstream: &str, head: &str) -> Result<HashSet<String>, String> {
+  let options = Options {
+    max_parents: Some(1),
+    ..Options::default()
+  };
+  let mut walk = Walk::new(repo, options);
+  walk.push(upstream);
+  walk.hide(head);
+  let mut ids = HashSet::new();
+  for hash in walk.run()? {
+    ids.insert(commit_patch_id(repo, &hash)?);
+  }
+  Ok(ids)
+}
+
+/// The patch ID of the changes a commit made to its first parent.
+fn commit_patch_id(repo: &Repo, hash: &str) -> Result<String, String> {
+  let object = object::read(repo.clone(), hash, Some("commit"))?;
+  let commit = object.unbox::<Commit>()?;
+  let parent_tree = match commit.parents().first() {
+    Some(parent) => {
+      let object = object::read(repo.clone(), parent, Some("commit"))?;
+      let tree = object.unbox::<Commit>()?.tree().to_string();
+      Some(tree)
+    }
+    None => None,
+  };
+  let changes = diff_trees(repo, parent_tree.as_deref(), Some(commit.tree()), true)?;
+  patch_id(&changes, |side| read_blob(repo, &side.hash))
+}
+
+/// Lets the user edit the todo list in the sequence editor. Fails if the
+/// edited list can't be used.
+fn edit_todo(
+  repo: &Repo,
+  dir: &Path,
+  todo: &[Instruction],
+  upstream: &str,
+  head: &str,
+  onto: &str,
+) -> Result<Vec<Instruction>, String> {
+  let mut text = String::new();
+  for instruction in todo {
+    text.push_str(&format!("{}\n", instruction.line(repo, true)?));
+  }
+  let count = match todo.len() {
+    1 => "1 command".to_string(),
+    n => format!("{} commands", n),
+  };
+  text.push_str(&format!(
+    "\n# Rebase {}..{} onto {} ({})\n{}",
+    abbreviate(repo, upstream, DEFAULT_ABBREV)?,
+    abbreviate(repo, head, DEFAULT_ABBREV)?,
+    abbreviate(repo, onto, DEFAULT_ABBREV)?,
+    count,
+    TODO_HELP
+  ));
+  let path = dir.join("git-rebase-todo");
+  sequencer::write(&path, &text)?;
+  let editor = env::var("GIT_SEQUENCE_EDITOR")
+    .ok()
+    .or_else(|| repo.config_value("sequence", None, "editor"))
+    .unwrap_or_else(|| editor(repo));
+  run_editor(&editor, &path)?;
+
+  let text = fs::read_to_string(&path).unwrap_or_default();
+  let todo = sequencer::parse_todo(repo, &text).map_err(|errors| errors.join("\n"))?;
+  match todo.first().map(|first| first.action) {
+    None => Err("nothing to do".to_string()),
+    Some(action @ (Action::Squash | Action::Fixup)) => Err(format!(
+      "cannot '{}' without a previous commit",
+      action.name()
+    )),
+    Some(_) => Ok(todo),
+  }
+}
+
+/// Works through the todo list, until it is done or something stops it.
+fn run(repo: &Repo, state: &State) -> Result<(), String> {
+  let todo_path = state.path("git-rebase-todo");
+  let done_path = state.path("done");
+  loop {
+    let mut todo = sequencer::read_todo(repo, &todo_path)?;
+    if todo.is_empty() {
+      return finish(repo, state);
+    }
+    let instruction = todo.remove(0);
+    let mut done = sequencer::read_todo(repo, &done_path)?;
+    done.push(instruction.clone());
+    sequencer::write_todo(repo, &done_path, &done, false)?;
+    sequencer::write_todo(repo, &todo_path, &todo, false)?;
+    sequencer::write(&state.path("msgnum"), &format!("{}\n", done.len()))?;
+
+    // the subject on the line is whatever the user left there
+    let (short, subject) = match instruction.action {
+      Action::Exec => (String::new(), String::new()),
+      _ => {
+        let object = object::read(repo.clone(), &instruction.argument, Some("commit"))?;
+        let subject = title(object.unbox::<Commit>()?.message());
+        let short = abbreviate(repo, &instruction.argument, DEFAULT_ABBREV)?;
+        (short, subject)
+      }
+    };
+    match step(repo, state, &instruction, todo.first())? {
+      Step::Next => (),
+      Step::Stopped => {
+        println!("Stopped at {}...  {}", short, subject);
+        println!("You can amend the commit now, with\n\n  git commit --amend \n");
+        println!("Once you are satisfied with your changes, run\n\n  git rebase --continue");
+        return Ok(());
+      }
+      Step::Conflicts => {
+        println!("error: could not apply {}... {}", short, subject);
+        println!("hint: Resolve all conflicts manually, mark them as resolved with");
+        println!("hint: \"git add/rm <conflicted_files>\", then run \"git rebase --continue\".");
+        println!("hint: You can instead skip this commit: run \"git rebase --skip\".");
+        println!(
+          "hint: To abort and get back to the state before \"git rebase\", run \
+           \"git rebase --abort\"."
+        );
+        println!("Could not apply {}... {}", short, subject);
+        process::exit(1);
+      }
+      Step::Failed => {
+        done.pop();
+        todo.insert(0, instruction.clone());
+        sequencer::write_todo(repo, &done_path, &done, false)?;
+        sequencer::write_todo(repo, &todo_path, &todo, false)?;
+        sequencer::write(&state.path("msgnum"), &format!("{}\n", done.len()))?;
+        println!("hint: Could not execute the todo command");
+        println!("hint:");
+        println!("hint:     {}", instruction.line(repo, true)?);
+        println!("hint:");
+        println!("hint: It has been rescheduled; run \"git rebase --continue\" to try it again.");
+        process::exit(1);
+      }
+    }
+  }
+}
+
+/// Carries out an instruction of the todo list. `next` is the instruction
+/// that follows it, if there is one.
+fn step(
+  repo: &Repo,
+  state: &State,
+  instruction: &Instruction,
+  next: Option<&Instruction>,
+) -> Result<Step, String> {
+  let hash = &instruction.argument;
+  match instruction.action {
+    Action::Drop => return Ok(Step::Next),
+    Action::Exec => {
+      println!("Executing: {}", hash);
+      let status = Command::new("sh").arg("-c").arg(hash).status();
+      if !matches!(status, Ok(status) if status.success()) {
+        println!("warning: execution failed: {}", hash);
+        println!("You can fix the problem, and then run\n\n  git rebase --continue\n\n");
+        process::exit(1);
+      }
+      return Ok(Step::Next);
+    }
+    _ => (),
+  }
+
+  let head = refs::resolve(repo, Path::new("HEAD"))?;
+  let object = object::read(repo.clone(), hash, Some("commit"))?;
+  let commit = object.unbox::<Commit>()?;
+  let fast_forward =
+    matches!(instruction.action, Action::Pick | Action::Edit) && commit.parents() == [head.clone()];
+  if fast_forward {
+    let identity = Identity::reflog(repo)?.to_string();
+    let mut index = Index::read(repo)?;
+    let (head_files, new_files) = (files(repo, &head)?, files(repo, hash)?);
+    if let Err(msg) = switch_trees(repo, &mut index, &head_files, &new_files, false, "merge") {
+      println!("error: {}", msg);
+      return Ok(Step::Failed);
+    }
+    index.write(repo)?;
+    refs::update_head(repo, hash, &identity, "rebase: fast-forward")?;
+  } else {
+    let revert = instruction.action == Action::Revert;
+    let applied = sequencer::apply(repo, &files(repo, &head)?, hash, None, revert, true)?;
+    if matches!(instruction.action, Action::Squash | Action::Fixup)
+      && !matches!(applied, Applied::Failed)
+    {
+      add_to_chain(repo, state, instruction, commit.message())?;
+    }
+    match applied {
+      Applied::Failed => return Ok(Step::Failed),
+      Applied::Conflicts => {
+        sequencer::write(&state.path("stopped-sha"), &format!("{}\n", hash))?;
+        sequencer::write(&state.path("message"), commit.message())?;
+        return Ok(Step::Conflicts);
+      }
+      Applied::Clean(tree) => record(repo, state, instruction, &tree, next, false)?,
+    }
+  }
+
+  if instruction.action == Action::Edit {
+    let head = refs::resolve(repo, Path::new("HEAD"))?;
+    sequencer::write(&state.path("amend"), &format!("{}\n", head))?;
+    sequencer::write(&state.path("stopped-sha"), &format!("{}\n", hash))?;
+    sequencer::write(&state.path("message"), commit.message())?;
+    return Ok(Step::Stopped);
+  }
+  Ok(Step::Next)
+}
+
+/// Commits the tree a commit of the todo list was applied to, or with
+/// `squash` and `fixup`, melds it into HEAD.
+///
+/// `continuing` is set when the commit stopped for conflicts that have been
+/// resolved since, in which case the user gets to edit the message again.
+fn record(
+  repo: &Repo,
+  state: &State,
+  instruction: &Instruction,
+  tree: &str,
+  next: Option<&Instruction>,
+  continuing: bool,
+) -> Result<(), String> {
+  let object = object::read(repo.clone(), &instruction.argument, Some("commit"))?;
+  let commit = object.unbox::<Commit>()?;
+  let head = refs::resolve(repo, Path::new("HEAD"))?;
+  let head_object = object::read(repo.clone(), &head, Some("commit"))?;
+  let head_commit = head_object.unbox::<Commit>()?;
+
+  let (parents, author, message, edited) = match instruction.action {
+    Action::Squash | Action::Fixup => {
+      let template = fs::read_to_string(state.path("message-squash")).unwrap_or_default();
+      let last = !matches!(
+        next.map(|next| next.action),
+        Some(Action::Squash | Action::Fixup)
+      );
+      let fixups = fs::read_to_string(state.path("current-fixups")).unwrap_or_default();
+      let squashed = fixups.lines().any(|line| line.starts_with("squash "));
+      let edited = last && (squashed || continuing);
+      // the comments only go once the last commit has been melded in
+      let message = match (edited, last) {
+        (true, _) => edit_message(repo, &template)?,
+        (false, last) => cleanup(&template, last),
+      };
+      if last {
+        let _ = fs::remove_file(state.path("current-fixups"));
+        let _ = fs::remove_file(state.path("message-squash"));
+      }
+      let author = head_commit.author().to_string();
+      (head_commit.parents(), author, message, edited)
+    }
+    _ => {
+      if tree == head_commit.tree() {
+        if !continuing {
+          println!(
+            "dropping {} {} -- patch contents already upstream",
+            instruction.argument,
+            title(commit.message())
+          );
+        }
+        return Ok(());
+      }
+      // a revert is a new commit of the user's, not a copy of the commit
+      let (author, message) = match instruction.action {
+        Action::Revert => (
+          Identity::author(repo)?.to_string(),
+          sequencer::message(repo, &instruction.argument, true, None, false)?,
+        ),
+        _ => (commit.author().to_string(), commit.message().to_string()),
+      };
+      let edited = continuing || instruction.action == Action::Reword;
+      let message = match edited {
+        true => edit_message(repo, &message)?,
+        false => message,
+      };
+      (vec![head], author, message, edited)
+    }
+  };
+
+  let hash = sequencer::commit(repo, tree, &parents, &author, &message)?;
+  let subject = message.lines().next().unwrap_or("");
+  let action = match continuing {
+    true => "continue",
+    false => instruction.action.name(),
+  };
+  let identity = Identity::committer(repo)?.to_string();
+  let reason = format!("rebase ({}): {}", action, subject);
+  refs::update_head(repo, &hash, &identity, &reason)?;
+  if edited {
+    let short = abbreviate(repo, &hash, DEFAULT_ABBREV)?;
+    println!("[detached HEAD {}] {}", short, subject);
+  }
+  Ok(())
+}
+
+/// Lets the user edit the message of a commit, starting from `template`.
+fn edit_message(repo: &Repo, template: &str) -> Result<String, String> {
+  let template = format!("{}{}", template, EDITOR_HELP);
+  let message = cleanup(&edit(repo, "COMMIT_EDITMSG", &template)?, true);
+  match message.is_empty() {
+    true => Err("Aborting commit due to empty commit message.".to_string()),
+    false => Ok(message),
+  }
+}
+
+/// Adds the message of a commit that is squashed (or fixed up) into HEAD to
+/// the message of the squashed commit (`message-squash`), which starts with
+/// the message of HEAD. The message of a fixup is commented out.
+fn add_to_chain(
+  repo: &Repo,
+  state: &State,
+  instruction: &Instruction,
+  message: &str,
+) -> Result<(), String> {
+  let fixups_path = state.path("current-fixups");
+  let template_path = state.path("message-squash");
+  let mut fixups = fs::read_to_string(&fixups_path).unwrap_or_default();
+  let count = fixups.lines().count() + 2;
+  let mut template = match fixups.is_empty() {
+    true => {
+      let head = refs::resolve(repo, Path::new("HEAD"))?;
+      let object = object::read(repo.clone(), &head, Some("commit"))?;
+      let message = object.unbox::<Commit>()?.message().to_string();
+      format!("# This is the 1st commit message:\n\n{}", message)
+    }
+    false => {
+      let template = fs::read_to_string(&template_path).unwrap_or_default();
+      let (_, rest) = template.split_once('\n').unwrap_or_default();
+      rest.to_string()
+    }
+  };
+  template.insert_str(
+    0,
+    &format!("# This is a combination of {} commits.\n", count),
+  );
+  match instruction.action {
+    Action::Squash => {
+      template.push_str(&format!("\n# This is the commit message #{}:\n\n", count));
+      template.push_str(message);
+    }
+    _ => {
+      template.push_str(&format!(
+        "\n# The commit message #{} will be skipped:\n\n",
+        count
+      ));
+      for line in message.lines() {
+        match line.is_empty() {
+          true => template.push_str("#\n"),
+          false => template.push_str(&format!("# {}\n", line)),
+        }
+      }
+    }
+  }
+  fixups.push_str(&format!(
+    "{} {}\n",
+    instruction.action.name(),
+    instruction.argument
+  ));
+  sequencer::write(&template_path, &template)?;
+  sequencer::write(&fixups_path, &fixups)
+}
+
+/// Carries on with the rebase once the conflicts that stopped it are
+/// resolved, or once the commit it stopped at is edited.
+fn proceed(repo: &Repo, state: &State) -> Result<(), String> {
+  if sequencer::needs_merge(repo)? {
+    return Err(
+      "You must edit all merge conflicts and then\nmark them as resolved using git add".to_string(),
+    );
+  }
+  let tree = Index::read(repo)?.write_tree(repo)?;
+  let head = refs::resolve(repo, Path::new("HEAD"))?;
+  let head_object = object::read(repo.clone(), &head, Some("commit"))?;
+  let head_commit = head_object.unbox::<Commit>()?;
+
+  if state.path("amend").is_file() {
+    // the commit that was stopped at for editing gets the staged changes
+    if tree != head_commit.tree() {
+      let message = edit_message(repo, head_commit.message())?;
+      let author = head_commit.author();
+      let hash = sequencer::commit(repo, &tree, &head_commit.parents(), author, &message)?;
+      let identity = Identity::committer(repo)?.to_string();
+      let subject = title(&message);
+      let reason = format!("rebase (continue): {}", subject);
+      refs::update_head(repo, &hash, &identity, &reason)?;
+      let short = abbreviate(repo, &hash, DEFAULT_ABBREV)?;
+      println!("[detached HEAD {}] {}", short, subject);
+    }
+  } else if state.path("stopped-sha").is_file() {
+    let done = sequencer::read_todo(repo, &state.path("done"))?;
+    let todo = sequencer::read_todo(repo, &state.path("git-rebase-todo"))?;
+    if let Some(instruction) = done.last() {
+      record(repo, state, instruction, &tree, todo.first(), true)?;
+    }
+  }
+  for file in ["amend", "stopped-sha", "message"] {
+    let _ = fs::remove_file(state.path(file));
+  }
+  run(repo, state)
+}
+
+/// Leaves out the commit the rebase stopped at, throwing away the changes
+/// it made, and carries on.
+fn skip(repo: &Repo, state: &State) -> Result<(), String> {
+  let head = refs::resolve(repo, Path::new("HEAD"))?;
+  sequencer::reset(repo, &head, &head)?;
+  let todo = sequencer::read_todo(repo, &state.path("git-rebase-todo"))?;
+  if !matches!(
+    todo.first().map(|next| next.action),
+    Some(Action::Squash | Action::Fixup)
+  ) {
+    let _ = fs::remove_file(state.path("current-fixups"));
+    let _ = fs::remove_file(state.path("message-squash"));
+  }
+  for file in ["amend", "stopped-sha", "message"] {
+    let _ = fs::remove_file(state.path(file));
+  }
+  run(repo, state)
+}
+
+/// Gives up on the rebase: HEAD goes back to the branch (or commit) it was
+/// on, and the index and the working tree are reset to it.
+fn abort(repo: &Repo, state: &State) -> Result<(), String> {
+  let head = refs::resolve(repo, Path::new("HEAD"))?;
+  let identity = Identity::reflog(repo)?.to_string();
+  sequencer::reset(repo, &head, &state.orig_head)?;
+  let message = format!(
+    "rebase (abort): returning to {}",
+    state.branch().unwrap_or(&state.orig_head)
+  );
+  refs::move_head(repo, state.branch(), &state.orig_head, &identity, &message)?;
+  remove_state(state)
+}
+
+/// Moves the branch that was rebased to where HEAD ended up, and checks it
+/// out again.
+fn finish(repo: &Repo, state: &State) -> Result<(), String> {
+  let head = refs::resolve(repo, Path::new("HEAD"))?;
+  let identity = Identity::reflog(repo)?.to_string();
+  if let Some(branch) = state.branch() {
+    let message = format!("rebase (finish): {} onto {}", branch, state.onto);
+    refs::update(repo, branch, &head, &identity, &message)?;
+    let message = format!("rebase (finish): returning to {}", branch);
+    refs::move_head(repo, Some(branch), &head, &identity, &message)?;
+  }
+  remove_state(state)?;
+  println!("Successfully rebased and updated {}.", state.head_name);
+  Ok(())
 }
 
-pub fn cmd_rebase() -> Result<(), String> {
-  todo!("cmd_rebase");
+fn remove_state(state: &State) -> Result<(), String> {
+  match fs::remove_dir_all(&state.dir) {
+    Ok(()) => Ok(()),
+    Err(msg) => Err(format!(
+      "could not remove '{}' ({})",
+      state.dir.display(),
+      msg
+    )),
+  }
 }
@@ -1,10 +1,117 @@
+use std::env;
+use std::slice;
+
 use clap::Args;
 
+use crate::object::revision::{self, Revision};
+use crate::object::{abbreviate, merge_base, DEFAULT_ABBREV};
+use crate::repo::Repo;
+
+/// Pick out and massage parameters.
+///
+/// Resolves each revision expression into the object hashes it stands for. A
+/// range like `A..B` prints `B` and then `^A`, the commits to exclude.
+///
+/// # Example
+/// ```bash
+/// $ git rev-parse HEAD~2 main..feature
+/// 3b18e512dba79e4c8300dd08aeb37f8e728b8dad
+/// 9daeafb9864cf43055ae93beb0afd6c7d144bfa4
+/// ^817abab1dd32cdf6ca40f4d75242064479817141
+/// ```
 #[derive(Args, Debug)]
 pub struct RevParse {
-  pub name: Option<String>,
+  /// The revisions to parse.
+  pub revisions: Vec<String>,
+
+  /// Verify that exactly one revision is given and that it names an object.
+  #[clap(long)]
+  pub verify: bool,
+
+  /// Abbreviate hashes to a unique prefix of at least the given length.
+  #[clap(long, value_name = "LENGTH", min_values = 0, require_equals = true)]
+  pub short: Option<Option<usize>>,
+
+  /// Print the short name of the ref a revision refers to.
+  #[clap(long)]
+  pub abbrev_ref: bool,
+
+  /// Print the path to the git directory.
+  #[clap(long)]
+  pub git_dir: bool,
+
+  /// Print the absolute path to the top of the working tree.
+  #[clap(long)]
+  pub show_toplevel: bool,
+}
+
+pub fn cmd_rev_parse(opts: &RevParse) -> Result<(), String> {
+  let repo: Repo = Repo::default();
+
+  if opts.git_dir {
+    // like git, print a relative path when we are at the top of the work tree
+    let cwd = env::current_dir().and_then(|cwd| cwd.canonicalize());
+    match cwd {
+      Ok(cwd) if cwd == repo.work_tree => println!(".git"),
+      _ => println!("{}", repo.git_dir.display()),
+    }
+  }
+  if opts.show_toplevel {
+    println!("{}", repo.work_tree.display());
+  }
+
+  let short = |hash: &str| -> Result<String, String> {
+    match opts.short {
+      Some(len) => abbreviate(&repo, hash, len.unwrap_or(DEFAULT_ABBREV)),
+      None => Ok(hash.to_string()),
+    }
+  };
+
+  if opts.verify {
+    if opts.revisions.len() != 1 {
+      return Err("Needed a single revision".to_string());
+    }
+    return match revision::parse(&repo, &opts.revisions[0]) {
+      Ok(Revision::Single(hash)) => {
+        println!("{}", short(&hash)?);
+        Ok(())
+      }
+      _ => Err("Needed a single revision".to_string()),
+    };
+  }
+
+  for expr in &opts.revisions {
+    if opts.abbrev_ref {
+      match revision::ref_name(&repo, expr)? {
+        Some(refname) => println!("{}", shorten_ref(&refname)),
+        None if expr == "HEAD" || expr == "@" => println!("HEAD"),
+        None => println!("{}", short(&revision::resolve(&repo, expr)?)?),
+      }
+      continue;
+    }
+    match revision::parse(&repo, expr)? {
+      Revision::Single(hash) => println!("{}", short(&hash)?),
+      Revision::Exclude(hash) => println!("^{}", short(&hash)?),
+      Revision::Range(from, to) => {
+        println!("{}", short(&to)?);
+        println!("^{}", short(&from)?);
+      }
+      Revision::SymmetricDifference(left, right) => {
+        println!("{}", short(&right)?);
+        println!("{}", short(&left)?);
+        for base in merge_base::merge_bases(&repo, &left, slice::from_ref(&right))? {
+          println!("^{}", short(&base)?);
+        }
+      }
+    }
+  }
+  Ok(())
 }
 
-pub fn cmd_rev_parse() -> Result<(), String> {
-  todo!("cmd_rev_parse");
+/// Strips the well-known prefixes off of a full ref name.
+fn shorten_ref(refname: &str) -> &str {
+  ["refs/heads/", "refs/tags/", "refs/remotes/", "refs/"]
+    .iter()
+    .find_map(|prefix| refname.strip_prefix(prefix))
+    .unwrap_or(refname)
 }
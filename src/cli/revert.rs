@@ -0,0 +1,76 @@
+use std::process;
+
+use clap::Args;
+
+use crate::index::sequencer::{self, Outcome, PickOptions};
+use crate::repo::Repo;
+
+/// Revert some existing commits.
+///
+/// The reverse of each commit's changes is applied on top of HEAD with a
+/// three-way merge, and committed with a message that says which commit it
+/// reverts. Ranges revert their commits newest first. A merge commit needs
+/// `--mainline` to say which parent's side of history is kept.
+///
+/// Conflicts stop the revert the same way they stop `git cherry-pick`, to be
+/// resolved and carried on with `--continue`, left out with `--skip` or given
+/// up with `--abort`.
+///
+/// # Example
+/// ```bash
+/// $ git revert HEAD
+/// [main 8d1f4b7] Revert "fix the typo"
+/// ```
+#[derive(Args, Debug)]
+pub struct Revert {
+  /// The commits to revert.
+  #[clap(required_unless_present_any = &["proceed", "skip", "abort"])]
+  pub commits: Vec<String>,
+
+  /// Apply the reverted changes to the index and the working tree without
+  /// committing.
+  #[clap(short = 'n', long)]
+  pub no_commit: bool,
+
+  /// The parent (counting from 1) of a merge commit whose side is kept.
+  #[clap(short, long)]
+  pub mainline: Option<usize>,
+
+  /// Carry on once the conflicts that stopped the revert are resolved.
+  #[clap(
+    long = "continue",
+    conflicts_with_all = &["commits", "no-commit", "mainline", "skip", "abort"]
+  )]
+  pub proceed: bool,
+
+  /// Leave out the commit that stopped the revert, and carry on.
+  #[clap(long, conflicts_with_all = &["commits", "no-commit", "mainline", "abort"])]
+  pub skip: bool,
+
+  /// Give up on the revert and go back to where it started.
+  #[clap(long, conflicts_with_all = &["commits", "no-commit", "mainline"])]
+  pub abort: bool,
+}
+
+pub fn cmd_revert(opts: &Revert) -> Result<(), String> {
+  let repo = Repo::default();
+  let outcome = if opts.proceed {
+    sequencer::continue_picks(&repo, true)?
+  } else if opts.skip {
+    sequencer::skip_pick(&repo, true)?
+  } else if opts.abort {
+    return sequencer::abort_picks(&repo, true);
+  } else {
+    let options = PickOptions {
+      revert: true,
+      mainline: opts.mainline,
+      record_origin: false,
+      no_commit: opts.no_commit,
+    };
+    sequencer::pick(&repo, &options, &opts.commits)?
+  };
+  if outcome == Outcome::Stopped {
+    process::exit(1);
+  }
+  Ok(())
+}
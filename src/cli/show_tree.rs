@@ -2,7 +2,10 @@ use clap::Args;
 
 use crate::object::serializable::Unbox;
 use crate::object::tree::Tree;
-use crate::{object::read, repo::Repo};
+use crate::{
+  object::{find_object, read},
+  repo::Repo,
+};
 
 /// Print the contents of a tree object.
 #[derive(Args, Debug)]
@@ -13,7 +16,8 @@ pub struct ShowTree {
 
 pub fn cmd_show_tree(opts: &ShowTree) -> Result<(), String> {
   let repo: Repo = Repo::default();
-  let tree_object = read(repo.clone(), &opts.object, Some("tree"))?;
+  let hash = find_object(repo.clone(), &opts.object, Some("tree"), true)?;
+  let tree_object = read(repo.clone(), &hash, Some("tree"))?;
   assert!(tree_object.format().eq("tree"));
   let tree: &Tree = tree_object.unbox::<Tree>()?;
 
@@ -0,0 +1,425 @@
+use std::path::Path;
+
+use clap::Args;
+use colored::Colorize;
+
+use crate::index::pathspec::{self, quote, relative};
+use crate::index::status::{Change, Conflict, FileStatus, Status as WorkTreeStatus};
+use crate::index::Index;
+use crate::object::mode::Mode;
+use crate::object::refs::{self, NULL_HASH};
+use crate::object::{abbreviate, find_ref, revision, DEFAULT_ABBREV};
+use crate::repo::{state, Repo};
+
+/// Show the working tree status.
+///
+/// Lists the changes that are staged for the next commit (HEAD vs the index),
+/// the changes that aren't (the index vs the working tree) and the files that
+/// aren't tracked at all.
+///
+/// # Example
+/// ```bash
+/// $ git status --short --branch
+/// ## main
+/// M  src/main.rs
+///  M README.md
+/// ?? notes.txt
+/// ```
+#[derive(Args, Debug)]
+pub struct Status {
+  /// Give the output in the short format.
+  #[clap(short, long)]
+  pub short: bool,
+
+  /// Give the output in a stable, easy to parse format (`v1`, the default,
+  /// or `v2`).
+  #[clap(
+    long,
+    min_values = 0,
+    require_equals = true,
+    default_missing_value = "v1",
+    possible_values = ["v1", "v2"]
+  )]
+  pub porcelain: Option<String>,
+
+  /// Show the branch in the short and porcelain formats too.
+  #[clap(short, long)]
+  pub branch: bool,
+}
+
+pub fn cmd_status(opts: &Status) -> Result<(), String> {
+  let repo: Repo = Repo::default();
+  let mut index = Index::read(&repo)?;
+  let status = WorkTreeStatus::collect(&repo, &mut index)?;
+
+  // save the stat data of files that had to be rehashed; this is only an
+  // optimization, so it doesn't matter if someone else holds the lock
+  if status.refreshed {
+    let _ = index.write(&repo);
+  }
+
+  match opts.porcelain.as_deref() {
+    Some("v2") => print_porcelain_v2(&repo, &status, opts.branch),
+    Some(_) => print_short(&repo, &status, opts.branch, &[], false),
+    None if opts.short => {
+      let prefix = pathspec::prefix(&repo)?;
+      print_short(&repo, &status, opts.branch, &prefix, true)
+    }
+    None => print_long(&repo, &status),
+  }
+}
+
+/// Prints the `--short` (or `--porcelain=v1`, without colors and with paths
+/// relative to the root) format: a two letter code for the index and the
+/// working tree followed by the path.
+fn print_short(
+  repo: &Repo,
+  status: &WorkTreeStatus,
+  branch: bool,
+  prefix: &[String],
+  color: bool,
+) -> Result<(), String> {
+  let paint = |text: String, staged: bool| match (color, staged) {
+    (false, _) => text,
+    (true, true) => text.green().to_string(),
+    (true, false) => text.red().to_string(),
+  };
+  let path = |path: &str| quote(&relative(path, prefix), true);
+
+  if branch {
+    let name = match (refs::current_branch(repo), &status.head) {
+      (Some(name), Some(_)) => paint(branch_name(&name).to_string(), true),
+      (Some(name), None) => format!(
+        "No commits yet on {}",
+        paint(branch_name(&name).to_string(), true)
+      ),
+      (None, _) => paint("HEAD (no branch)".to_string(), false),
+    };
+    println!("## {}", name);
+  }
+
+  // conflicts and changes are printed together, sorted by path
+  let mut lines: Vec<(&str, String)> = Vec::new();
+  for conflict in &status.conflicts {
+    let code = paint(conflict.code().to_string(), false);
+    lines.push((&conflict.path, format!("{} {}", code, path(&conflict.path))));
+  }
+  for change in &status.changes {
+    let code = |change: Option<Change>| change.map_or(' ', |change| change.code()).to_string();
+    let line = format!(
+      "{}{} {}",
+      paint(code(change.staged), true),
+      paint(code(change.unstaged), false),
+      path(&change.path)
+    );
+    lines.push((&change.path, line));
+  }
+  lines.sort_by(|a, b| a.0.cmp(b.0));
+  for (_, line) in lines {
+    println!("{}", line);
+  }
+  for untracked in &status.untracked {
+    println!("{} {}", paint("??".to_string(), false), path(untracked));
+  }
+  Ok(())
+}
+
+/// Prints the `--porcelain=v2` format, which has the modes and hashes of every
+/// changed path in HEAD, the index and the working tree.
+fn print_porcelain_v2(repo: &Repo, status: &WorkTreeStatus, branch: bool) -> Result<(), String> {
+  if branch {
+    println!(
+      "# branch.oid {}",
+      status.head.as_deref().unwrap_or("(initial)")
+    );
+    match refs::current_branch(repo) {
+      Some(name) => println!("# branch.head {}", branch_name(&name)),
+      None => println!("# branch.head (detached)"),
+    }
+  }
+
+  let mode = |mode: Option<Mode>| mode.map_or("000000".to_string(), |mode| mode.to_string());
+  fn hash(file: &Option<(Mode, String)>) -> &str {
+    file.as_ref().map_or(NULL_HASH, |(_, hash)| hash)
+  }
+  let submodule = |modes: &[Option<Mode>]| match modes.contains(&Some(Mode::Gitlink)) {
+    true => "SC..",
+    false => "N...",
+  };
+
+  // unlike the short format, changed entries come before unmerged ones
+  for change in &status.changes {
+    let code = |change: Option<Change>| change.map_or('.', |change| change.code());
+    let head_mode = change.head.as_ref().map(|(mode, _)| *mode);
+    let index_mode = change.index.as_ref().map(|(mode, _)| *mode);
+    println!(
+      "1 {}{} {} {} {} {} {} {} {}",
+      code(change.staged),
+      code(change.unstaged),
+      submodule(&[head_mode, index_mode, change.worktree]),
+      mode(head_mode),
+      mode(index_mode),
+      mode(change.worktree),
+      hash(&change.head),
+      hash(&change.index),
+      quote(&change.path, false)
+    );
+  }
+  for conflict in &status.conflicts {
+    let [base, ours, theirs] = &conflict.stages;
+    let modes = [base, ours, theirs].map(|stage| stage.as_ref().map(|(mode, _)| *mode));
+    println!(
+      "u {} {} {} {} {} {} {} {} {} {}",
+      conflict.code(),
+      submodule(&modes),
+      mode(modes[0]),
+      mode(modes[1]),
+      mode(modes[2]),
+      mode(conflict.worktree),
+      hash(base),
+      hash(ours),
+      hash(theirs),
+      quote(&conflict.path, false)
+    );
+  }
+  for untracked in &status.untracked {
+    println!("? {}", quote(untracked, false));
+  }
+  Ok(())
+}
+
+/// Prints the long format, with a section for each kind of change and hints
+/// on what to do about them.
+fn print_long(repo: &Repo, status: &WorkTreeStatus) -> Result<(), String> {
+  let prefix = pathspec::prefix(repo)?;
+  let path = |path: &str| quote(&relative(path, &prefix), false);
+  let merging = state::merge_head(repo).is_some();
+
+  match refs::current_branch(repo) {
+    Some(name) => {
+      println!("On branch {}", branch_name(&name));
+      if let Some(info) = tracking_info(repo, &name)? {
+        println!("{}\n", info);
+      }
+    }
+    None => {
+      let description = detached_description(repo, status.head.as_deref())?;
+      let description = description.unwrap_or_else(|| "Not currently on any branch.".to_string());
+      println!("{}", description.red())
+    }
+  }
+  if merging && !status.conflicts.is_empty() {
+    println!("You have unmerged paths.");
+    println!("  (fix conflicts and run \"git commit\")");
+    println!("  (use \"git merge --abort\" to abort the merge)");
+    println!();
+  } else if merging {
+    println!("All conflicts fixed but you are still merging.");
+    println!("  (use \"git commit\" to conclude merge)");
+    println!();
+  }
+  if status.head.is_none() {
+    println!("\nNo commits yet\n");
+  }
+  let unstage_hint = || match status.head {
+    _ if merging => (),
+    Some(_) => println!("  (use \"git restore --staged <file>...\" to unstage)"),
+    None => println!("  (use \"git rm --cached <file>...\" to unstage)"),
+  };
+
+  let staged: Vec<&FileStatus> = status
+    .changes
+    .iter()
+    .filter(|c| c.staged.is_some())
+    .collect();
+  if !staged.is_empty() {
+    println!("Changes to be committed:");
+    unstage_hint();
+    for change in staged {
+      let line = format!(
+        "{}{}",
+        label(change.staged.unwrap(), 12),
+        path(&change.path)
+      );
+      println!("\t{}", line.green());
+    }
+    println!();
+  }
+
+  if !status.conflicts.is_empty() {
+    println!("Unmerged paths:");
+    unstage_hint();
+    println!("{}", resolution_hint(&status.conflicts));
+    for conflict in &status.conflicts {
+      let line = format!("{:<17}{}", conflict.description(), path(&conflict.path));
+      println!("\t{}", line.red());
+    }
+    println!();
+  }
+
+  let unstaged: Vec<&FileStatus> = status
+    .changes
+    .iter()
+    .filter(|c| c.unstaged.is_some())
+    .collect();
+  if !unstaged.is_empty() {
+    println!("Changes not staged for commit:");
+    match unstaged.iter().any(|c| c.unstaged == Some(Change::Deleted)) {
+      true => println!("  (use \"git add/rm <file>...\" to update what will be committed)"),
+      false => println!("  (use \"git add <file>...\" to update what will be committed)"),
+    }
+    println!("  (use \"git restore <file>...\" to discard changes in working directory)");
+    for change in &unstaged {
+      let line = format!(
+        "{}{}",
+        label(change.unstaged.unwrap(), 12),
+        path(&change.path)
+      );
+      println!("\t{}", line.red());
+    }
+    println!();
+  }
+
+  if !status.untracked.is_empty() {
+    println!("Untracked files:");
+    println!("  (use \"git add <file>...\" to include in what will be committed)");
+    for untracked in &status.untracked {
+      println!("\t{}", path(untracked).red());
+    }
+    println!();
+  }
+
+  if status.has_staged_changes() || !status.conflicts.is_empty() {
+    // there is something to commit (or to resolve first)
+  } else if status.has_unstaged_changes() {
+    println!("no changes added to commit (use \"git add\" and/or \"git commit -a\")");
+  } else if !status.untracked.is_empty() {
+    println!("nothing added to commit but untracked files present (use \"git add\" to track)");
+  } else if status.head.is_none() {
+    println!("nothing to commit (create/copy files and use \"git add\" to track)");
+  } else {
+    println!("nothing to commit, working tree clean");
+  }
+  Ok(())
+}
+
+/// Pads the long format description of a change to `width` columns.
+fn label(change: Change, width: usize) -> String {
+  let label = match change {
+    Change::Added => "new file:",
+    Change::Modified => "modified:",
+    Change::Deleted => "deleted:",
+    Change::TypeChanged => "typechange:",
+  };
+  format!("{:<width$}", label, width = width)
+}
+
+/// The hint on how to mark conflicts as resolved, which depends on whether
+/// resolving them means adding or removing files.
+fn resolution_hint(conflicts: &[Conflict]) -> &'static str {
+  let both_deleted = conflicts.iter().any(|c| c.code() == "DD");
+  let deleted_on_one_side = conflicts
+    .iter()
+    .any(|c| c.code() == "UD" || c.code() == "DU");
+  let other = conflicts
+    .iter()
+    .any(|c| !matches!(c.code(), "DD" | "UD" | "DU"));
+  match (both_deleted, deleted_on_one_side, other) {
+    (false, false, _) => "  (use \"git add <file>...\" to mark resolution)",
+    (true, false, false) => "  (use \"git rm <file>...\" to mark resolution)",
+    _ => "  (use \"git add/rm <file>...\" as appropriate to mark resolution)",
+  }
+}
+
+/// Describes a detached HEAD by what was checked out, eg. `HEAD detached at
+/// v1.0` (or `from`, if HEAD has moved since).
+///
+/// The checkout is found in the reflog of HEAD. It is named by the ref that
+/// was checked out if that ref still points at the same commit, and by the
+/// abbreviated hash otherwise. `None` if nothing was ever checked out.
+pub(crate) fn detached_description(
+  repo: &Repo,
+  head: Option<&str>,
+) -> Result<Option<String>, String> {
+  let reflog = refs::reflog(repo, "HEAD").unwrap_or_default();
+  let checkout = reflog.iter().rev().find_map(|entry| {
+    let moved = entry.message.strip_prefix("checkout: moving from ")?;
+    moved.rsplit_once(" to ").map(|(_, to)| (to, &entry.new))
+  });
+  let (target, hash) = match checkout {
+    Some(checkout) => checkout,
+    None => return Ok(None),
+  };
+  let refname = find_ref(repo, target)
+    .filter(|refname| revision::resolve_commit(repo, refname).as_ref() == Ok(hash));
+  let name = match &refname {
+    Some(refname) => refname
+      .strip_prefix("refs/tags/")
+      .or_else(|| refname.strip_prefix("refs/remotes/"))
+      .unwrap_or(refname)
+      .to_string(),
+    None => abbreviate(repo, hash, DEFAULT_ABBREV)?,
+  };
+  let position = match head == Some(hash.as_str()) {
+    true => "at",
+    false => "from",
+  };
+  Ok(Some(format!("HEAD detached {} {}", position, name)))
+}
+
+/// Describes how a branch compares to its upstream, eg. `Your branch is ahead
+/// of 'origin/main' by 1 commit.`, along with a hint on what to do about it.
+/// `None` if the branch has no upstream.
+pub(crate) fn tracking_info(repo: &Repo, branch: &str) -> Result<Option<String>, String> {
+  let upstream = match revision::upstream(repo, branch) {
+    Ok(upstream) => upstream,
+    Err(_) => return Ok(None),
+  };
+  let hash = match refs::resolve(repo, Path::new(branch)) {
+    Ok(hash) => hash,
+    Err(_) => return Ok(None),
+  };
+  let name = upstream
+    .strip_prefix("refs/remotes/")
+    .unwrap_or_else(|| branch_name(&upstream));
+  let upstream = match refs::resolve(repo, Path::new(&upstream)) {
+    Ok(upstream) => upstream,
+    Err(_) => {
+      return Ok(Some(format!(
+        "Your branch is based on '{}', but the upstream is gone.\n  \
+         (use \"git branch --unset-upstream\" to fixup)",
+        name
+      )))
+    }
+  };
+  let commits = |n: usize| if n == 1 { "commit" } else { "commits" };
+  let info = match revision::ahead_behind(repo, &hash, &upstream)? {
+    (0, 0) => format!("Your branch is up to date with '{}'.", name),
+    (ahead, 0) => format!(
+      "Your branch is ahead of '{}' by {} {}.\n  \
+       (use \"git push\" to publish your local commits)",
+      name,
+      ahead,
+      commits(ahead)
+    ),
+    (0, behind) => format!(
+      "Your branch is behind '{}' by {} {}, and can be fast-forwarded.\n  \
+       (use \"git pull\" to update your local branch)",
+      name,
+      behind,
+      commits(behind)
+    ),
+    (ahead, behind) => format!(
+      "Your branch and '{}' have diverged,\n\
+       and have {} and {} different commits each, respectively.\n  \
+       (use \"git pull\" to merge the remote branch into yours)",
+      name, ahead, behind
+    ),
+  };
+  Ok(Some(info))
+}
+
+/// Strips `refs/heads/` from the full name of a branch.
+fn branch_name(name: &str) -> &str {
+  name.strip_prefix("refs/heads/").unwrap_or(name)
+}
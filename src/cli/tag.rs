@@ -5,11 +5,10 @@ use clap::Args;
 use crate::{
   object::refs,
   object::{
-    self,
-    mail_map::{self, MailMap},
-    serializable::Serializable,
+    self, find_object, identity::Identity, mail_map::MailMap, serializable::Serializable,
     tag::Tag as TagObject,
   },
+  repo::editor::{cleanup, edit},
   repo::{repo_dir, Repo},
 };
 
@@ -26,18 +25,25 @@ pub struct Tag {
   /// Creates an annotated tag.
   #[clap(short, long)]
   pub annotated: bool,
+
+  /// Use the given tag message (implies `-a`).
+  #[clap(short, long)]
+  pub message: Option<String>,
 }
 
 pub fn cmd_tag(opts: &Tag) -> Result<(), String> {
   let repo: Repo = Repo::default();
-  if opts.name.is_none() {
-    list_all_tags(&repo);
-  } else if opts.annotated {
-    let tag_name = &opts.name.as_ref().unwrap();
-    let hash = create_annotated_tag(&repo, tag_name, &opts.object)?;
-    create_simple_tag(&repo, tag_name, &hash);
-  } else {
-    create_simple_tag(&repo, opts.name.as_ref().unwrap(), &opts.object);
+  match &opts.name {
+    None => list_all_tags(&repo),
+    Some(tag_name) if opts.annotated || opts.message.is_some() => {
+      let object = find_object(repo.clone(), &opts.object, None, false)?;
+      let hash = create_annotated_tag(&repo, tag_name, &object, opts.message.as_deref())?;
+      create_simple_tag(&repo, tag_name, &hash);
+    }
+    Some(tag_name) => {
+      let object = find_object(repo.clone(), &opts.object, None, false)?;
+      create_simple_tag(&repo, tag_name, &object);
+    }
   }
   Ok(())
 }
@@ -56,26 +62,46 @@ fn list_all_tags(repo: &Repo) {
 fn create_simple_tag(repo: &Repo, name: &String, object: &String) {
   let path = repo_dir(&repo.git_dir, &["refs", "tags"], true).unwrap();
   let mut file = File::create(path.join(name)).expect("create failed");
-  if object.eq("HEAD") {
-    let mut payload = refs::resolve(repo, &repo.git_dir.join("HEAD")).expect("resolve");
-    payload.push('\n');
-    file.write_all(payload.as_bytes()).expect("write failed");
-  } else {
-    let mut payload: String = String::from(object);
-    payload.push('\n');
-    file.write_all(payload.as_bytes()).expect("write failed");
-  };
+  let mut payload: String = String::from(object);
+  payload.push('\n');
+  file.write_all(payload.as_bytes()).expect("write failed");
 }
 
-fn create_annotated_tag(repo: &Repo, name: &String, object: &String) -> Result<String, String> {
-  // TODO: Create git objects with signature, name, email, and message (editor?)
+/// Writes a tag object for `object` and returns its hash.
+///
+/// Without a message, the user is asked for one in their editor.
+fn create_annotated_tag(
+  repo: &Repo,
+  name: &String,
+  object: &String,
+  message: Option<&str>,
+) -> Result<String, String> {
+  let kind = match object::read_raw(repo, object)? {
+    Some((kind, _)) => kind,
+    None => return Err(format!("object not found {}", object)),
+  };
+  let message = match message {
+    Some(message) => cleanup(message, false),
+    None => {
+      let template = format!(
+        "\n#\n# Write a message for tag:\n#   {}\n# Lines starting with '#' will be ignored.\n",
+        name
+      );
+      let message = cleanup(&edit(repo, "TAG_EDITMSG", &template)?, true);
+      if message.is_empty() {
+        return Err("no tag message?".to_string());
+      }
+      message
+    }
+  };
+
   let mut mail_map: MailMap = MailMap::new();
-  mail_map.map.insert("commit".to_owned(), object.to_owned());
-  mail_map.map.insert("tag".to_owned(), name.to_owned());
-  mail_map
-    .map
-    .insert("".to_owned(), "\n".to_owned());
-  let payload = mail_map::map_to_bytes(&mail_map.map);
-  let new_tag: Box<dyn Serializable> = Box::new(TagObject::new(repo.clone(), &payload));
+  mail_map.push("object", object);
+  mail_map.push("type", &kind);
+  mail_map.push("tag", name);
+  let tagger = Identity::committer(repo)?;
+  mail_map.push("tagger", &tagger.to_string());
+  mail_map.set_message(&message);
+  let new_tag: Box<dyn Serializable> = Box::new(TagObject::new(repo.clone(), mail_map.to_bytes()));
   object::write(&*new_tag, false)
 }
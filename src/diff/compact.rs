@@ -0,0 +1,391 @@
+//! Slides groups of changed lines to where they read best, as git's xdiff
+//! does after running any of the diff algorithms.
+//!
+//! A group of added (or removed) lines can often be moved up or down without
+//! changing what the diff means, when the lines just above it are the same as
+//! its last lines (or the lines just below the same as its first ones). A
+//! group is moved so that it lines up with a group of changes in the other
+//! file if it can, and otherwise to where the split lines up best with the
+//! indentation of the code around it (git's "indent heuristic").
+
+/// Indentation beyond this is treated as this much.
+const MAX_INDENT: i32 = 200;
+
+/// Blank lines beyond this many in a row are treated as this many.
+const MAX_BLANKS: i32 = 20;
+
+// the weights of the indent heuristic, as tuned by git
+const START_OF_FILE_PENALTY: i32 = 1;
+const END_OF_FILE_PENALTY: i32 = 21;
+const TOTAL_BLANK_WEIGHT: i32 = -30;
+const POST_BLANK_WEIGHT: i32 = 6;
+const RELATIVE_INDENT_PENALTY: i32 = -4;
+const RELATIVE_INDENT_WITH_BLANK_PENALTY: i32 = 10;
+const RELATIVE_OUTDENT_PENALTY: i32 = 24;
+const RELATIVE_OUTDENT_WITH_BLANK_PENALTY: i32 = 17;
+const RELATIVE_DEDENT_PENALTY: i32 = 23;
+const RELATIVE_DEDENT_WITH_BLANK_PENALTY: i32 = 17;
+const INDENT_WEIGHT: i32 = 60;
+
+/// How far the indent heuristic looks when sliding a group up.
+const MAX_SLIDING: usize = 100;
+
+/// Slides the groups of changed lines of one file, keeping them in step with
+/// the groups of the other file.
+///
+/// `changed` and `other_changed` are the flags of the two files. They must
+/// describe the same diff: both files have the same number of groups (some of
+/// them empty), separated by runs of equal lines. Without `indent_heuristic`,
+/// groups that don't line up with the other file are left as far down as they
+/// go.
+pub fn compact(
+  lines: &[&[u8]],
+  classes: &[usize],
+  changed: &mut [bool],
+  other_changed: &[bool],
+  indent_heuristic: bool,
+) {
+  let mut file = File::new(lines, classes, changed);
+  let other = Groups::new(other_changed);
+  let mut group = file.first_group();
+  let mut other_group = other.first();
+
+  loop {
+    if group.end != group.start {
+      // slide the group up and down as far as it goes, merging it with the
+      // groups it runs into
+      let mut earliest_end;
+      let mut end_matching_other;
+      loop {
+        let size = group.end - group.start;
+        end_matching_other = None;
+
+        while file.slide_up(&mut group) {
+          other_group = other.previous(&other_group).expect("groups out of sync");
+        }
+        earliest_end = group.end;
+        if other_group.end > other_group.start {
+          end_matching_other = Some(group.end);
+        }
+
+        while file.slide_down(&mut group) {
+          other_group = other.next(&other_group).expect("groups out of sync");
+          if other_group.end > other_group.start {
+            end_matching_other = Some(group.end);
+          }
+        }
+        if size == group.end - group.start {
+          break;
+        }
+      }
+
+      // the group is now as far down as it goes, so it can only move up
+      if group.end == earliest_end {
+        // it can't move at all
+      } else if end_matching_other.is_some() {
+        while other_group.end == other_group.start {
+          file.slide_up(&mut group);
+          other_group = other.previous(&other_group).expect("groups out of sync");
+        }
+      } else if indent_heuristic {
+        let size = group.end - group.start;
+        let lowest = earliest_end
+          .max((group.end - size).saturating_sub(1))
+          .max(group.end.saturating_sub(MAX_SLIDING));
+        let mut best: Option<(usize, Score)> = None;
+        for shift in lowest..=group.end {
+          let mut score = Score::default();
+          score.add(&file.measure(shift));
+          score.add(&file.measure(shift - size));
+          if best.as_ref().is_none_or(|(_, best)| score.cmp(best) <= 0) {
+            best = Some((shift, score));
+          }
+        }
+        let best_shift = best.map_or(group.end, |(shift, _)| shift);
+        while group.end > best_shift {
+          file.slide_up(&mut group);
+          other_group = other.previous(&other_group).expect("groups out of sync");
+        }
+      }
+    }
+
+    match file.groups().next(&group) {
+      Some(next) => group = next,
+      None => break,
+    }
+    other_group = other.next(&other_group).expect("groups out of sync");
+  }
+  file.finish(changed);
+}
+
+/// A run of changed lines, `start..end` (possibly empty, between two equal
+/// lines).
+#[derive(Clone, Copy)]
+struct Group {
+  start: usize,
+  end: usize,
+}
+
+/// The changed flags of a file, with a `false` sentinel at each end: index
+/// `i + 1` holds the flag of line `i`.
+struct Groups {
+  flags: Vec<bool>,
+}
+
+impl Groups {
+  fn new(changed: &[bool]) -> Self {
+    let mut flags = Vec::with_capacity(changed.len() + 2);
+    flags.push(false);
+    flags.extend_from_slice(changed);
+    flags.push(false);
+    Self { flags }
+  }
+
+  fn len(&self) -> usize {
+    self.flags.len() - 2
+  }
+
+  fn get(&self, line: usize) -> bool {
+    self.flags[line + 1]
+  }
+
+  fn set(&mut self, line: usize, changed: bool) {
+    self.flags[line + 1] = changed;
+  }
+
+  fn first(&self) -> Group {
+    let mut end = 0;
+    while self.get(end) {
+      end += 1;
+    }
+    Group { start: 0, end }
+  }
+
+  fn next(&self, group: &Group) -> Option<Group> {
+    if group.end == self.len() {
+      return None;
+    }
+    let start = group.end + 1;
+    let mut end = start;
+    while self.get(end) {
+      end += 1;
+    }
+    Some(Group { start, end })
+  }
+
+  fn previous(&self, group: &Group) -> Option<Group> {
+    if group.start == 0 {
+      return None;
+    }
+    let end = group.start - 1;
+    let mut start = end;
+    while start > 0 && self.get(start - 1) {
+      start -= 1;
+    }
+    Some(Group { start, end })
+  }
+}
+
+/// The file whose groups are being moved.
+struct File<'a> {
+  lines: &'a [&'a [u8]],
+  classes: &'a [usize],
+  groups: Groups,
+}
+
+impl<'a> File<'a> {
+  fn new(lines: &'a [&'a [u8]], classes: &'a [usize], changed: &[bool]) -> Self {
+    Self {
+      lines,
+      classes,
+      groups: Groups::new(changed),
+    }
+  }
+
+  fn groups(&self) -> &Groups {
+    &self.groups
+  }
+
+  fn first_group(&self) -> Group {
+    self.groups.first()
+  }
+
+  fn finish(self, changed: &mut [bool]) {
+    changed.copy_from_slice(&self.groups.flags[1..self.groups.flags.len() - 1]);
+  }
+
+  /// Moves a group down by one line if the line below it equals its first
+  /// line, merging it with the group below if they touch.
+  fn slide_down(&mut self, group: &mut Group) -> bool {
+    if group.end < self.groups.len() && self.classes[group.start] == self.classes[group.end] {
+      self.groups.set(group.start, false);
+      self.groups.set(group.end, true);
+      group.start += 1;
+      group.end += 1;
+      while self.groups.get(group.end) {
+        group.end += 1;
+      }
+      true
+    } else {
+      false
+    }
+  }
+
+  /// Moves a group up by one line if the line above it equals its last line,
+  /// merging it with the group above if they touch.
+  fn slide_up(&mut self, group: &mut Group) -> bool {
+    if group.start > 0 && self.classes[group.start - 1] == self.classes[group.end - 1] {
+      group.start -= 1;
+      group.end -= 1;
+      self.groups.set(group.start, true);
+      self.groups.set(group.end, false);
+      while group.start > 0 && self.groups.get(group.start - 1) {
+        group.start -= 1;
+      }
+      true
+    } else {
+      false
+    }
+  }
+
+  /// Describes the surroundings of a split just before line `split`.
+  fn measure(&self, split: usize) -> Measurement {
+    let (end_of_file, indent) = match self.lines.get(split) {
+      Some(line) => (false, indentation(line)),
+      None => (true, None),
+    };
+
+    let mut pre_blank = 0;
+    let mut pre_indent = None;
+    for line in self.lines[..split.min(self.lines.len())].iter().rev() {
+      pre_indent = indentation(line);
+      if pre_indent.is_some() {
+        break;
+      }
+      pre_blank += 1;
+      if pre_blank == MAX_BLANKS {
+        pre_indent = Some(0);
+        break;
+      }
+    }
+
+    let mut post_blank = 0;
+    let mut post_indent = None;
+    for line in self.lines.iter().skip(split + 1) {
+      post_indent = indentation(line);
+      if post_indent.is_some() {
+        break;
+      }
+      post_blank += 1;
+      if post_blank == MAX_BLANKS {
+        post_indent = Some(0);
+        break;
+      }
+    }
+
+    Measurement {
+      end_of_file,
+      indent,
+      pre_blank,
+      pre_indent,
+      post_blank,
+      post_indent,
+    }
+  }
+}
+
+/// The width of a line's indentation (with tabs stopping every 8 columns),
+/// or `None` if the line is blank.
+fn indentation(line: &[u8]) -> Option<i32> {
+  let mut width = 0;
+  for &byte in line {
+    match byte {
+      b' ' => width += 1,
+      b'\t' => width += 8 - width % 8,
+      b'\n' | b'\r' | 0x0b | 0x0c => (),
+      _ => return Some(width),
+    }
+    if width >= MAX_INDENT {
+      return Some(MAX_INDENT);
+    }
+  }
+  None
+}
+
+/// What is around a place where a group could start or end.
+struct Measurement {
+  /// Whether the split is at the end of the file.
+  end_of_file: bool,
+
+  /// The indentation of the line after the split.
+  indent: Option<i32>,
+
+  /// The number of blank lines before the split.
+  pre_blank: i32,
+
+  /// The indentation of the nearest non-blank line before the split.
+  pre_indent: Option<i32>,
+
+  /// The number of blank lines after the line after the split.
+  post_blank: i32,
+
+  /// The indentation of the nearest non-blank line after those.
+  post_indent: Option<i32>,
+}
+
+/// How bad it looks to put a group somewhere (lower is better).
+#[derive(Default)]
+struct Score {
+  effective_indent: i32,
+  penalty: i32,
+}
+
+impl Score {
+  /// Adds the badness of one of the two splits around a group.
+  fn add(&mut self, m: &Measurement) {
+    if m.pre_indent.is_none() && m.pre_blank == 0 {
+      self.penalty += START_OF_FILE_PENALTY;
+    }
+    if m.end_of_file {
+      self.penalty += END_OF_FILE_PENALTY;
+    }
+
+    let post_blank = match m.indent {
+      None => 1 + m.post_blank,
+      Some(_) => 0,
+    };
+    let total_blank = m.pre_blank + post_blank;
+    self.penalty += TOTAL_BLANK_WEIGHT * total_blank;
+    self.penalty += POST_BLANK_WEIGHT * post_blank;
+
+    let indent = m.indent.or(m.post_indent);
+    let any_blanks = total_blank != 0;
+    self.effective_indent += indent.unwrap_or(-1);
+
+    let (Some(indent), Some(pre_indent)) = (indent, m.pre_indent) else {
+      return;
+    };
+    if indent > pre_indent {
+      self.penalty += match any_blanks {
+        true => RELATIVE_INDENT_WITH_BLANK_PENALTY,
+        false => RELATIVE_INDENT_PENALTY,
+      };
+    } else if indent < pre_indent {
+      let outdent = m
+        .post_indent
+        .is_some_and(|post_indent| post_indent > indent);
+      self.penalty += match (outdent, any_blanks) {
+        (true, true) => RELATIVE_OUTDENT_WITH_BLANK_PENALTY,
+        (true, false) => RELATIVE_OUTDENT_PENALTY,
+        (false, true) => RELATIVE_DEDENT_WITH_BLANK_PENALTY,
+        (false, false) => RELATIVE_DEDENT_PENALTY,
+      };
+    }
+  }
+
+  /// Compares two scores: negative if this one is better.
+  fn cmp(&self, other: &Score) -> i32 {
+    let indents = (self.effective_indent - other.effective_indent).signum();
+    INDENT_WEIGHT * indents + (self.penalty - other.penalty)
+  }
+}
@@ -0,0 +1,196 @@
+//! The histogram diff algorithm, as implemented by git's xdiff (which took it
+//! from JGit).
+//!
+//! It works like patience diff, but instead of only matching lines that are
+//! unique, it matches the longest run of lines around the line of the old file
+//! that occurs the fewest times, and then recurses on what is left above and
+//! below that run. Lines that occur too often are never used as anchors, and
+//! if nothing else is left the part is handed to Myers' algorithm.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use super::myers;
+
+/// Lines occurring more often than this in the old file are not used to
+/// anchor a match.
+const MAX_CHAIN_LENGTH: usize = 64;
+
+/// Diffs two files, given as lists of line classes (equal lines have equal
+/// classes), and returns which lines of each file changed.
+pub fn diff(old: &[usize], new: &[usize]) -> (Vec<bool>, Vec<bool>) {
+  let mut old_changed = vec![false; old.len()];
+  let mut new_changed = vec![false; new.len()];
+  let mut old_range = 0..old.len();
+  let mut new_range = 0..new.len();
+
+  let mut pending = Vec::new();
+  loop {
+    if old_range.is_empty() || new_range.is_empty() {
+      old_changed[old_range].fill(true);
+      new_changed[new_range].fill(true);
+    } else {
+      match find_lcs(old, new, old_range.clone(), new_range.clone()) {
+        Found::Region(lcs) => {
+          // diff what is above the match, then what is below it
+          pending.push((lcs.old.end..old_range.end, lcs.new.end..new_range.end));
+          pending.push((
+            old_range.start..lcs.old.start,
+            new_range.start..lcs.new.start,
+          ));
+        }
+        Found::Nothing => {
+          old_changed[old_range].fill(true);
+          new_changed[new_range].fill(true);
+        }
+        Found::TooCommon => {
+          let (old_part, new_part) =
+            myers::diff(&old[old_range.clone()], &new[new_range.clone()], false);
+          old_changed[old_range].copy_from_slice(&old_part);
+          new_changed[new_range].copy_from_slice(&new_part);
+        }
+      }
+    }
+    match pending.pop() {
+      Some((old_next, new_next)) => {
+        old_range = old_next;
+        new_range = new_next;
+      }
+      None => break,
+    }
+  }
+  (old_changed, new_changed)
+}
+
+/// The outcome of looking for the best run of matching lines.
+enum Found {
+  Region(Region),
+
+  /// The two parts have no lines in common.
+  Nothing,
+
+  /// The two parts only have lines in common that occur too often.
+  TooCommon,
+}
+
+/// A run of matching lines.
+struct Region {
+  old: Range<usize>,
+  new: Range<usize>,
+}
+
+/// How often a line occurs in the old part being diffed.
+struct Record {
+  /// Where it first occurs.
+  first: usize,
+
+  /// How many times it occurs (at most `MAX_CHAIN_LENGTH + 1` matter).
+  count: usize,
+}
+
+/// Finds the longest run of matching lines whose rarest line occurs the
+/// fewest times in the old part.
+fn find_lcs(
+  old: &[usize],
+  new: &[usize],
+  old_range: Range<usize>,
+  new_range: Range<usize>,
+) -> Found {
+  // index the old part: where each line first occurs, and where each
+  // occurrence of a line occurs next
+  let mut records: HashMap<usize, Record> = HashMap::new();
+  let mut next: Vec<Option<usize>> = vec![None; old_range.len()];
+  for i in old_range.clone().rev() {
+    match records.get_mut(&old[i]) {
+      Some(record) => {
+        next[i - old_range.start] = Some(record.first);
+        record.first = i;
+        record.count += 1;
+      }
+      None => {
+        records.insert(old[i], Record { first: i, count: 1 });
+      }
+    }
+  }
+  let count_of = |i: usize| records[&old[i]].count;
+  let next_of = |i: usize| next[i - old_range.start];
+
+  let mut lcs: Option<Region> = None;
+  let mut lowest = MAX_CHAIN_LENGTH + 1;
+  let mut has_common = false;
+
+  let mut b = new_range.start;
+  while b < new_range.end {
+    let mut b_next = b + 1;
+    let Some(record) = records.get(&new[b]) else {
+      b = b_next;
+      continue;
+    };
+    if record.count > lowest {
+      has_common = true;
+      b = b_next;
+      continue;
+    }
+    has_common = true;
+
+    let mut a = record.first;
+    loop {
+      let following = next_of(a);
+      let (mut a_start, mut b_start) = (a, b);
+      let (mut a_end, mut b_end) = (a, b);
+      let mut rarest = record.count;
+      while old_range.start < a_start
+        && new_range.start < b_start
+        && old[a_start - 1] == new[b_start - 1]
+      {
+        a_start -= 1;
+        b_start -= 1;
+        if 1 < rarest {
+          rarest = rarest.min(count_of(a_start));
+        }
+      }
+      while a_end + 1 < old_range.end
+        && b_end + 1 < new_range.end
+        && old[a_end + 1] == new[b_end + 1]
+      {
+        a_end += 1;
+        b_end += 1;
+        if 1 < rarest {
+          rarest = rarest.min(count_of(a_end));
+        }
+      }
+
+      if b_next <= b_end {
+        b_next = b_end + 1;
+      }
+      let longest = lcs.as_ref().map_or(0, |lcs| lcs.old.len() - 1);
+      if longest < a_end - a_start || rarest < lowest {
+        lcs = Some(Region {
+          old: a_start..a_end + 1,
+          new: b_start..b_end + 1,
+        });
+        lowest = rarest;
+      }
+
+      // try the next occurrence of the line that isn't part of this run
+      let mut following = following;
+      while let Some(candidate) = following {
+        if candidate > a_end {
+          break;
+        }
+        following = next_of(candidate);
+      }
+      match following {
+        Some(candidate) => a = candidate,
+        None => break,
+      }
+    }
+    b = b_next;
+  }
+
+  match lcs {
+    _ if has_common && MAX_CHAIN_LENGTH < lowest => Found::TooCommon,
+    Some(lcs) => Found::Region(lcs),
+    None => Found::Nothing,
+  }
+}
@@ -0,0 +1,459 @@
+//! Three-way merges of file contents, the way git's xdiff merges them.
+//!
+//! Both sides are diffed against the base. Changes that only one side made are
+//! taken as they are, and so are changes both sides made in the same way.
+//! Changes that overlap (or touch) are conflicts, which are written out
+//! between conflict markers unless a side is favored.
+
+use std::ops::Range;
+use std::str::FromStr;
+
+use super::{diff_with, lines, Algorithm, Change};
+
+/// The length of the conflict markers, unless told otherwise.
+pub const DEFAULT_MARKER_SIZE: usize = 7;
+
+/// How conflicts are written out (`merge.conflictStyle`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+  /// Our and their side of the conflict.
+  Merge,
+
+  /// The base version too, between `|||||||` and `=======`.
+  Diff3,
+
+  /// Like `Diff3`, but the lines both sides start and end with are moved out of
+  /// the conflict.
+  ZealousDiff3,
+}
+
+impl FromStr for Style {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Self, Self::Err> {
+    match name {
+      "merge" => Ok(Style::Merge),
+      "diff3" => Ok(Style::Diff3),
+      "zdiff3" => Ok(Style::ZealousDiff3),
+      _ => Err(format!(
+        "unknown style '{}' given for 'merge.conflictstyle'",
+        name
+      )),
+    }
+  }
+}
+
+/// How conflicts are resolved without markers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Favor {
+  /// Our side of the conflict.
+  Ours,
+
+  /// Their side of the conflict.
+  Theirs,
+
+  /// Both sides, ours first.
+  Union,
+}
+
+/// How hard to try to make conflicts smaller. Each level does what the one
+/// before it does, and more.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+  /// Every overlapping change is a conflict.
+  Minimal,
+
+  /// Both sides making the same change is not a conflict.
+  Eager,
+
+  /// The lines both sides of a conflict have in common are moved out of it,
+  /// and conflicts less than four lines apart are joined.
+  Zealous,
+
+  /// Conflicts are also joined when the lines between them have no letters or
+  /// digits in them.
+  ZealousAlnum,
+}
+
+/// How a three-way merge is made.
+#[derive(Clone, Debug)]
+pub struct Options {
+  pub algorithm: Algorithm,
+  pub level: Level,
+  pub style: Style,
+  pub favor: Option<Favor>,
+
+  /// The length of the conflict markers.
+  pub marker_size: usize,
+
+  /// The names put after the markers of our side, the base and their side.
+  pub ours_label: Option<String>,
+  pub base_label: Option<String>,
+  pub theirs_label: Option<String>,
+}
+
+impl Default for Options {
+  fn default() -> Self {
+    Self {
+      algorithm: Algorithm::Myers,
+      level: Level::Zealous,
+      style: Style::Merge,
+      favor: None,
+      marker_size: DEFAULT_MARKER_SIZE,
+      ours_label: None,
+      base_label: None,
+      theirs_label: None,
+    }
+  }
+}
+
+/// The result of a three-way merge.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Merged {
+  pub data: Vec<u8>,
+
+  /// The number of conflicts left in `data`.
+  pub conflicts: usize,
+}
+
+/// Which side a hunk of the merge is taken from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+  Conflict,
+  Ours,
+  Theirs,
+
+  /// Both sides, ours first.
+  Both,
+
+  /// Both sides made the same change, so the lines of ours are kept.
+  Same,
+}
+
+/// A run of lines the sides changed: the start and length of the lines of the
+/// base, our side and their side.
+///
+/// The starts are signed because, while the hunks are being put together, a
+/// change of one side can be mapped to the other side from before a conflict
+/// it is already a part of, and so end up before the start of the file.
+#[derive(Clone, Copy, Debug)]
+struct Hunk {
+  mode: Mode,
+  base: (isize, isize),
+  ours: (isize, isize),
+  theirs: (isize, isize),
+}
+
+/// Merges the changes `ours` and `theirs` made to `base`.
+pub fn merge(base: &[u8], ours: &[u8], theirs: &[u8], options: &Options) -> Merged {
+  let base_lines = lines(base);
+  let our_lines = lines(ours);
+  let their_lines = lines(theirs);
+  let our_changes = diff_with(&base_lines, &our_lines, options.algorithm, false);
+  let their_changes = diff_with(&base_lines, &their_lines, options.algorithm, false);
+  if our_changes.is_empty() || their_changes.is_empty() {
+    let data = if our_changes.is_empty() { theirs } else { ours };
+    return Merged {
+      data: data.to_vec(),
+      conflicts: 0,
+    };
+  }
+
+  // showing the base only makes sense for conflicts it is the base of
+  let mut level = options.level;
+  if options.style != Style::Merge {
+    level = level.min(Level::Eager);
+  }
+  let sides = Sides {
+    base: &base_lines,
+    ours: &our_lines,
+    theirs: &their_lines,
+  };
+  let mut hunks = sides.hunks(&our_changes, &their_changes, level);
+  if options.style == Style::ZealousDiff3 {
+    sides.trim_conflicts(&mut hunks);
+  } else if level >= Level::Zealous {
+    hunks = sides.refine_conflicts(hunks, options.algorithm);
+    simplify_conflicts(&mut hunks, &our_lines, level >= Level::ZealousAlnum);
+  }
+  if let Some(favor) = options.favor {
+    for hunk in hunks.iter_mut().filter(|hunk| hunk.mode == Mode::Conflict) {
+      hunk.mode = match favor {
+        Favor::Ours => Mode::Ours,
+        Favor::Theirs => Mode::Theirs,
+        Favor::Union => Mode::Both,
+      };
+    }
+  }
+  Merged {
+    data: sides.write(&hunks, options),
+    conflicts: hunks
+      .iter()
+      .filter(|hunk| hunk.mode == Mode::Conflict)
+      .count(),
+  }
+}
+
+/// The lines of the three versions of a file.
+struct Sides<'a> {
+  base: &'a [&'a [u8]],
+  ours: &'a [&'a [u8]],
+  theirs: &'a [&'a [u8]],
+}
+
+impl<'a> Sides<'a> {
+  /// Goes through the changes of both sides in order, and turns them into
+  /// hunks. Overlapping changes become a conflict covering both of them.
+  fn hunks(&self, ours: &[Change], theirs: &[Change], level: Level) -> Vec<Hunk> {
+    let mut hunks = Vec::new();
+    let (mut ours, mut theirs) = (ours.iter().peekable(), theirs.iter().peekable());
+    while let (Some(our), Some(their)) = (ours.peek(), theirs.peek()) {
+      let (our_old, our_new) = (signed(&our.old), signed(&our.new));
+      let (their_old, their_new) = (signed(&their.old), signed(&their.new));
+      if our_old.end < their_old.start {
+        let theirs_start = their_new.start - their_old.start + our_old.start;
+        append(&mut hunks, Mode::Ours, our, theirs_start);
+        ours.next();
+        continue;
+      }
+      if their_old.end < our_old.start {
+        let ours_start = our_new.start - our_old.start + their_old.start;
+        append(&mut hunks, Mode::Theirs, their, ours_start);
+        theirs.next();
+        continue;
+      }
+      let same = level > Level::Minimal
+        && our.old == their.old
+        && self.ours[our.new.clone()] == self.theirs[their.new.clone()];
+      if !same {
+        // widen both sides to cover the base lines the other side changed
+        let start = our_old.start.min(their_old.start);
+        let end = our_old.end.max(their_old.end);
+        let hunk = Hunk {
+          mode: Mode::Conflict,
+          base: (start, end - start),
+          ours: (
+            our_new.start + start - our_old.start,
+            our_new.end - our_new.start + our_old.start - start + end - our_old.end,
+          ),
+          theirs: (
+            their_new.start + start - their_old.start,
+            their_new.end - their_new.start + their_old.start - start + end - their_old.end,
+          ),
+        };
+        push(&mut hunks, hunk);
+      }
+      let (our_end, their_end) = (our.old.end, their.old.end);
+      if our_end >= their_end {
+        theirs.next();
+      }
+      if their_end >= our_end {
+        ours.next();
+      }
+    }
+    for our in ours {
+      let theirs_start = (our.old.start + self.theirs.len()) as isize - self.base.len() as isize;
+      append(&mut hunks, Mode::Ours, our, theirs_start);
+    }
+    for their in theirs {
+      let ours_start = (their.old.start + self.ours.len()) as isize - self.base.len() as isize;
+      append(&mut hunks, Mode::Theirs, their, ours_start);
+    }
+    hunks
+  }
+
+  /// Diffs the two sides of each conflict against each other, so that only
+  /// the lines that differ are left in conflicts.
+  fn refine_conflicts(&self, hunks: Vec<Hunk>, algorithm: Algorithm) -> Vec<Hunk> {
+    let mut refined = Vec::new();
+    for hunk in hunks {
+      if hunk.mode != Mode::Conflict || hunk.ours.1 == 0 || hunk.theirs.1 == 0 {
+        refined.push(hunk);
+        continue;
+      }
+      let (ours, theirs) = (self.our_lines(&hunk), self.their_lines(&hunk));
+      let changes = diff_with(ours, theirs, algorithm, false);
+      if changes.is_empty() {
+        refined.push(Hunk {
+          mode: Mode::Same,
+          ..hunk
+        });
+        continue;
+      }
+      for change in changes {
+        refined.push(Hunk {
+          mode: Mode::Conflict,
+          base: hunk.base,
+          ours: (
+            hunk.ours.0 + change.old.start as isize,
+            change.old.len() as isize,
+          ),
+          theirs: (
+            hunk.theirs.0 + change.new.start as isize,
+            change.new.len() as isize,
+          ),
+        });
+      }
+    }
+    refined
+  }
+
+  /// Moves the lines both sides of a conflict start or end with out of it.
+  fn trim_conflicts(&self, hunks: &mut [Hunk]) {
+    for hunk in hunks.iter_mut().filter(|hunk| hunk.mode == Mode::Conflict) {
+      while hunk.ours.1 > 0
+        && hunk.theirs.1 > 0
+        && self.ours[hunk.ours.0 as usize] == self.theirs[hunk.theirs.0 as usize]
+      {
+        hunk.ours = (hunk.ours.0 + 1, hunk.ours.1 - 1);
+        hunk.theirs = (hunk.theirs.0 + 1, hunk.theirs.1 - 1);
+      }
+      while hunk.ours.1 > 0
+        && hunk.theirs.1 > 0
+        && self.ours[(hunk.ours.0 + hunk.ours.1 - 1) as usize]
+          == self.theirs[(hunk.theirs.0 + hunk.theirs.1 - 1) as usize]
+      {
+        hunk.ours.1 -= 1;
+        hunk.theirs.1 -= 1;
+      }
+    }
+  }
+
+  fn our_lines(&self, hunk: &Hunk) -> &'a [&'a [u8]] {
+    &self.ours[range(hunk.ours)]
+  }
+
+  fn their_lines(&self, hunk: &Hunk) -> &'a [&'a [u8]] {
+    &self.theirs[range(hunk.theirs)]
+  }
+
+  /// Writes out the merged file: our side, with the hunks put in.
+  fn write(&self, hunks: &[Hunk], options: &Options) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut next = 0;
+    for hunk in hunks {
+      if hunk.mode == Mode::Same {
+        continue;
+      }
+      copy(&mut out, &self.ours[next..hunk.ours.0 as usize], false);
+      match hunk.mode {
+        Mode::Conflict => {
+          let size = options.marker_size;
+          marker(&mut out, '<', size, options.ours_label.as_deref());
+          copy(&mut out, self.our_lines(hunk), true);
+          if options.style != Style::Merge {
+            marker(&mut out, '|', size, options.base_label.as_deref());
+            copy(&mut out, &self.base[range(hunk.base)], true);
+          }
+          marker(&mut out, '=', size, None);
+          copy(&mut out, self.their_lines(hunk), true);
+          marker(&mut out, '>', size, options.theirs_label.as_deref());
+        }
+        Mode::Ours => copy(&mut out, self.our_lines(hunk), false),
+        Mode::Theirs => copy(&mut out, self.their_lines(hunk), false),
+        Mode::Both => {
+          copy(&mut out, self.our_lines(hunk), true);
+          copy(&mut out, self.their_lines(hunk), false);
+        }
+        Mode::Same => unreachable!(),
+      }
+      next = range(hunk.ours).end;
+    }
+    copy(&mut out, &self.ours[next..], false);
+    out
+  }
+}
+
+/// Adds a hunk for a change only one side made. `other_start` is where the
+/// changed base lines start in the other side.
+fn append(hunks: &mut Vec<Hunk>, mode: Mode, change: &Change, other_start: isize) {
+  let changed = (change.new.start as isize, change.new.len() as isize);
+  let unchanged = (other_start, change.old.len() as isize);
+  let (ours, theirs) = match mode {
+    Mode::Ours => (changed, unchanged),
+    _ => (unchanged, changed),
+  };
+  let hunk = Hunk {
+    mode,
+    base: (change.old.start as isize, change.old.len() as isize),
+    ours,
+    theirs,
+  };
+  push(hunks, hunk);
+}
+
+/// Adds a hunk, joining it with the last one if they overlap or touch (which
+/// makes a conflict, unless both come from the same side).
+fn push(hunks: &mut Vec<Hunk>, hunk: Hunk) {
+  let last = match hunks.last_mut() {
+    Some(last)
+      if hunk.ours.0 <= last.ours.0 + last.ours.1
+        || hunk.theirs.0 <= last.theirs.0 + last.theirs.1 =>
+    {
+      last
+    }
+    _ => return hunks.push(hunk),
+  };
+  if last.mode != hunk.mode {
+    last.mode = Mode::Conflict;
+  }
+  last.base.1 = hunk.base.0 + hunk.base.1 - last.base.0;
+  last.ours.1 = hunk.ours.0 + hunk.ours.1 - last.ours.0;
+  last.theirs.1 = hunk.theirs.0 + hunk.theirs.1 - last.theirs.0;
+}
+
+/// Joins conflicts that have at most three lines between them, since one
+/// conflict takes up no more lines than two conflicts and those lines. With
+/// `alnum`, conflicts are also joined when the lines between them have no
+/// letters or digits (blank lines, braces, ...).
+fn simplify_conflicts(hunks: &mut Vec<Hunk>, ours: &[&[u8]], alnum: bool) {
+  let mut i = 0;
+  while i + 1 < hunks.len() {
+    let (hunk, next) = (hunks[i], hunks[i + 1]);
+    let between = &ours[range(hunk.ours).end..next.ours.0 as usize];
+    let keep_apart = hunk.mode != Mode::Conflict
+      || next.mode != Mode::Conflict
+      || (between.len() > 3
+        && (!alnum
+          || between
+            .iter()
+            .any(|line| line.iter().any(u8::is_ascii_alphanumeric))));
+    if keep_apart {
+      i += 1;
+      continue;
+    }
+    hunks[i].ours.1 = next.ours.0 + next.ours.1 - hunk.ours.0;
+    hunks[i].theirs.1 = next.theirs.0 + next.theirs.1 - hunk.theirs.0;
+    hunks.remove(i + 1);
+  }
+}
+
+fn signed(range: &Range<usize>) -> Range<isize> {
+  range.start as isize..range.end as isize
+}
+
+/// The lines a hunk covers, from its start and length.
+fn range((start, len): (isize, isize)) -> Range<usize> {
+  start as usize..(start + len) as usize
+}
+
+/// Copies lines to the output. With `end_line`, a missing `\n` at the end is
+/// added, so that whatever follows starts on a line of its own.
+fn copy(out: &mut Vec<u8>, lines: &[&[u8]], end_line: bool) {
+  for line in lines {
+    out.extend_from_slice(line);
+  }
+  if end_line && lines.last().is_some_and(|line| !line.ends_with(b"\n")) {
+    out.push(b'\n');
+  }
+}
+
+/// Writes a conflict marker line, like `<<<<<<< ours`.
+fn marker(out: &mut Vec<u8>, character: char, size: usize, label: Option<&str>) {
+  out.extend(std::iter::repeat_n(character as u8, size));
+  if let Some(label) = label {
+    out.push(b' ');
+    out.extend_from_slice(label.as_bytes());
+  }
+  out.push(b'\n');
+}
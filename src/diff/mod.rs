@@ -0,0 +1,157 @@
+pub mod compact;
+pub mod histogram;
+pub mod merge;
+pub mod myers;
+pub mod patch;
+pub mod patience;
+pub mod pickaxe;
+pub mod rename;
+pub mod stat;
+pub mod tree;
+pub mod unified;
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// How many bytes at the start of a file are checked for NUL bytes to decide
+/// whether it is binary (the same as git).
+const BINARY_CHECK_LEN: usize = 8000;
+
+/// The algorithm used to find the lines two files have in common.
+///
+/// All of them produce a minimal (or close to minimal) diff, but they differ in
+/// which lines they prefer to match when there are several choices:
+/// - `Myers` is git's default. It finds the shortest edit script, giving up on
+///   minimality for very large inputs (unless `Minimal` is used).
+/// - `Patience` first matches the lines that are unique in both files, which
+///   tends to line up the structure of the code (function signatures, closing
+///   braces) better.
+/// - `Histogram` extends patience to lines that occur rarely rather than only
+///   once, and is usually faster.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+  Myers,
+  Minimal,
+  Patience,
+  Histogram,
+}
+
+impl FromStr for Algorithm {
+  type Err = String;
+
+  /// Parses an algorithm by the name `--diff-algorithm` uses for it.
+  fn from_str(name: &str) -> Result<Self, Self::Err> {
+    match name {
+      "myers" | "default" => Ok(Algorithm::Myers),
+      "minimal" => Ok(Algorithm::Minimal),
+      "patience" => Ok(Algorithm::Patience),
+      "histogram" => Ok(Algorithm::Histogram),
+      _ => Err(format!("unknown diff algorithm: {}", name)),
+    }
+  }
+}
+
+/// A run of lines that differ between two files: `old` lines of the old file
+/// were replaced by `new` lines of the new file (either may be empty).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+  pub old: Range<usize>,
+  pub new: Range<usize>,
+}
+
+/// Splits a file into lines, each including its `\n` (except possibly the
+/// last one).
+pub fn lines(data: &[u8]) -> Vec<&[u8]> {
+  data.split_inclusive(|&byte| byte == b'\n').collect()
+}
+
+/// Returns true if the data looks binary, ie. if it has a NUL byte near the
+/// start. Binary files are not diffed line by line.
+pub fn is_binary(data: &[u8]) -> bool {
+  data[..data.len().min(BINARY_CHECK_LEN)].contains(&0)
+}
+
+/// Compares two lists of lines and returns the runs of lines that differ, in
+/// order.
+///
+/// The changes are placed the way git places them. When a block of added or
+/// removed lines could be slid up or down (because the lines around it
+/// repeat), it is lined up with a change in the other file if possible, and
+/// otherwise put where it best follows the indentation of the code (git's
+/// "indent heuristic").
+pub fn diff(old: &[&[u8]], new: &[&[u8]], algorithm: Algorithm) -> Vec<Change> {
+  diff_with(old, new, algorithm, true)
+}
+
+/// Like `diff`, but the indent heuristic can be turned off, in which case
+/// blocks that could be slid are put as far down as they go. Merges are made
+/// without it, as git makes them.
+pub fn diff_with(
+  old: &[&[u8]],
+  new: &[&[u8]],
+  algorithm: Algorithm,
+  indent_heuristic: bool,
+) -> Vec<Change> {
+  let (old_classes, new_classes) = classify(old, new);
+  let (mut old_changed, mut new_changed) = match algorithm {
+    Algorithm::Myers => myers::diff(&old_classes, &new_classes, false),
+    Algorithm::Minimal => myers::diff(&old_classes, &new_classes, true),
+    Algorithm::Patience => patience::diff(&old_classes, &new_classes),
+    Algorithm::Histogram => histogram::diff(&old_classes, &new_classes),
+  };
+  compact::compact(
+    old,
+    &old_classes,
+    &mut old_changed,
+    &new_changed,
+    indent_heuristic,
+  );
+  compact::compact(
+    new,
+    &new_classes,
+    &mut new_changed,
+    &old_changed,
+    indent_heuristic,
+  );
+  changes(&old_changed, &new_changed)
+}
+
+/// Numbers the distinct lines of both files, so that lines can be compared
+/// as integers: equal lines get the same number.
+fn classify(old: &[&[u8]], new: &[&[u8]]) -> (Vec<usize>, Vec<usize>) {
+  let mut classes: HashMap<&[u8], usize> = HashMap::new();
+  let mut number = |line| {
+    let next = classes.len();
+    *classes.entry(line).or_insert(next)
+  };
+  let old = old.iter().map(|line| number(line)).collect();
+  let new = new.iter().map(|line| number(line)).collect();
+  (old, new)
+}
+
+/// Turns the per-line "changed" flags of both files into a list of changes.
+fn changes(old_changed: &[bool], new_changed: &[bool]) -> Vec<Change> {
+  let mut changes = Vec::new();
+  let (mut i, mut j) = (0, 0);
+  while i < old_changed.len() || j < new_changed.len() {
+    let old_start = i;
+    let new_start = j;
+    while i < old_changed.len() && old_changed[i] {
+      i += 1;
+    }
+    while j < new_changed.len() && new_changed[j] {
+      j += 1;
+    }
+    if i == old_start && j == new_start {
+      i += 1;
+      j += 1;
+    } else {
+      changes.push(Change {
+        old: old_start..i,
+        new: new_start..j,
+      });
+    }
+  }
+  changes
+}
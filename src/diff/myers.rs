@@ -0,0 +1,459 @@
+//! Myers' O(ND) difference algorithm, as implemented by git's xdiff.
+//!
+//! The search runs from both ends of the files at once until the two paths
+//! meet in the middle, and then recurses on the two halves. Before it starts,
+//! lines that appear in only one of the files are set aside (they can only be
+//! changes), which shrinks the problem a lot for typical edits. For inputs
+//! that would take too long, a few heuristics give up on the shortest edit
+//! script in exchange for speed, unless a minimal diff is asked for.
+
+use std::collections::HashMap;
+
+/// The length of a run of matching lines that is considered a good sign the
+/// search is on the right track.
+const SNAKE_COUNT: isize = 20;
+
+/// The edit cost after which the snake heuristic kicks in.
+const HEURISTIC_MIN_COST: isize = 256;
+
+/// The lowest edit cost after which the search gives up and takes the
+/// furthest reaching path.
+const MAX_COST_MIN: isize = 256;
+
+/// How much better than the edit cost a diagonal must be for the snake
+/// heuristic to split there.
+const HEURISTIC_FACTOR: isize = 4;
+
+/// The most times a line may appear in the other file before it counts as
+/// matching "too many" lines.
+const MAX_EQUAL_LIMIT: usize = 1024;
+
+/// How far around a line to look when deciding whether to set it aside.
+const SIMILAR_SCAN_WINDOW: usize = 100;
+
+/// Lines matching too many others are set aside if the run around them has
+/// fewer than one in this many such lines.
+const KEEP_DISCARDED_RUN: usize = 4;
+
+/// Diffs two files, given as lists of line classes (equal lines have equal
+/// classes), and returns which lines of each file changed.
+///
+/// Unless `minimal` is set, very expensive diffs are cut short by heuristics.
+pub fn diff(old: &[usize], new: &[usize], minimal: bool) -> (Vec<bool>, Vec<bool>) {
+  let mut old_changed = vec![false; old.len()];
+  let mut new_changed = vec![false; new.len()];
+
+  // the lines both files start and end with are never changes
+  let mut start = 0;
+  while start < old.len().min(new.len()) && old[start] == new[start] {
+    start += 1;
+  }
+  let mut end = 0;
+  while end < old.len().min(new.len()) - start
+    && old[old.len() - 1 - end] == new[new.len() - 1 - end]
+  {
+    end += 1;
+  }
+
+  let mut counts: HashMap<usize, (usize, usize)> = HashMap::new();
+  for &class in old {
+    counts.entry(class).or_default().0 += 1;
+  }
+  for &class in new {
+    counts.entry(class).or_default().1 += 1;
+  }
+  let old_count = |class| counts.get(&class).map_or(0, |count| count.0);
+  let new_count = |class| counts.get(&class).map_or(0, |count| count.1);
+
+  let old_kept = discard(
+    old,
+    start..old.len() - end,
+    new_count,
+    &mut old_changed,
+    minimal,
+  );
+  let new_kept = discard(
+    new,
+    start..new.len() - end,
+    old_count,
+    &mut new_changed,
+    minimal,
+  );
+  let old_lines: Vec<usize> = old_kept.iter().map(|&i| old[i]).collect();
+  let new_lines: Vec<usize> = new_kept.iter().map(|&i| new[i]).collect();
+
+  let mut search = Search::new(&old_lines, &new_lines);
+  let mut boxes = vec![(0, old_lines.len(), 0, new_lines.len(), minimal)];
+  while let Some((mut off1, mut lim1, mut off2, mut lim2, minimal)) = boxes.pop() {
+    // shrink the box by skipping the lines it starts and ends with
+    while off1 < lim1 && off2 < lim2 && old_lines[off1] == new_lines[off2] {
+      off1 += 1;
+      off2 += 1;
+    }
+    while off1 < lim1 && off2 < lim2 && old_lines[lim1 - 1] == new_lines[lim2 - 1] {
+      lim1 -= 1;
+      lim2 -= 1;
+    }
+
+    if off1 == lim1 {
+      for &i in &new_kept[off2..lim2] {
+        new_changed[i] = true;
+      }
+    } else if off2 == lim2 {
+      for &i in &old_kept[off1..lim1] {
+        old_changed[i] = true;
+      }
+    } else {
+      let split = search.split(off1, lim1, off2, lim2, minimal);
+      boxes.push((off1, split.i1, off2, split.i2, split.minimal_low));
+      boxes.push((split.i1, lim1, split.i2, lim2, split.minimal_high));
+    }
+  }
+  (old_changed, new_changed)
+}
+
+/// Sets aside the lines of a file that can't be matched, marking them as
+/// changed, and returns the indices of the lines that are left.
+///
+/// A line is set aside if it doesn't appear in the other file at all, or if
+/// it appears there many times and sits among lines that don't appear there
+/// (it would most likely be matched by accident).
+fn discard(
+  lines: &[usize],
+  range: std::ops::Range<usize>,
+  other_count: impl Fn(usize) -> usize,
+  changed: &mut [bool],
+  minimal: bool,
+) -> Vec<usize> {
+  let limit = bogosqrt(lines.len()).min(MAX_EQUAL_LIMIT);
+  let mut kinds = vec![Kind::Unique; lines.len()];
+  for i in range.clone() {
+    kinds[i] = match other_count(lines[i]) {
+      0 => Kind::Unique,
+      count if count >= limit && !minimal => Kind::Common,
+      _ => Kind::Matched,
+    };
+  }
+
+  let mut kept = Vec::new();
+  for i in range.clone() {
+    let keep = match kinds[i] {
+      Kind::Matched => true,
+      Kind::Common => !among_unique(&kinds, i, range.start, range.end - 1),
+      Kind::Unique => false,
+    };
+    match keep {
+      true => kept.push(i),
+      false => changed[i] = true,
+    }
+  }
+  kept
+}
+
+/// How often a line appears in the other file.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+  /// It doesn't.
+  Unique,
+
+  /// A reasonable number of times.
+  Matched,
+
+  /// So many times that matching it means little.
+  Common,
+}
+
+/// Returns true if the common line at `i` is surrounded (within `start` and
+/// `end`, inclusive) by runs of unique and common lines with enough unique
+/// lines in them that it should be set aside too.
+fn among_unique(kinds: &[Kind], i: usize, start: usize, end: usize) -> bool {
+  let start = start.max(i.saturating_sub(SIMILAR_SCAN_WINDOW));
+  let end = end.min(i + SIMILAR_SCAN_WINDOW);
+
+  let (mut unique, mut common) = (0, 1);
+  for kind in kinds[start..i].iter().rev() {
+    match kind {
+      Kind::Unique => unique += 1,
+      Kind::Common => common += 1,
+      Kind::Matched => break,
+    }
+  }
+  if unique == 0 {
+    return false;
+  }
+  let (mut unique_after, mut common_after) = (0, 1);
+  for kind in &kinds[i + 1..=end] {
+    match kind {
+      Kind::Unique => unique_after += 1,
+      Kind::Common => common_after += 1,
+      Kind::Matched => break,
+    }
+  }
+  if unique_after == 0 {
+    return false;
+  }
+  let unique = unique + unique_after;
+  let common = common + common_after;
+  common * KEEP_DISCARDED_RUN < common + unique
+}
+
+/// An approximate square root (the power of two near it), used to scale
+/// limits with the size of the input.
+fn bogosqrt(mut n: usize) -> usize {
+  let mut root = 1;
+  while n > 0 {
+    n >>= 2;
+    root <<= 1;
+  }
+  root
+}
+
+/// Where to split a box in two, and whether each half must be diffed
+/// minimally.
+struct Split {
+  i1: usize,
+  i2: usize,
+  minimal_low: bool,
+  minimal_high: bool,
+}
+
+/// The state of the search for the middle of the shortest edit script.
+///
+/// Diagonal `d` holds the points where `i1 - i2 == d`. For each diagonal, the
+/// forward search stores the furthest `i1` reached from the top left corner
+/// of the box, and the backward search the furthest one reached from the
+/// bottom right corner.
+struct Search<'a> {
+  old: &'a [usize],
+  new: &'a [usize],
+  forward: Vec<isize>,
+  backward: Vec<isize>,
+
+  /// Added to a diagonal to index `forward` and `backward`.
+  offset: isize,
+
+  /// The edit cost after which the search settles for the best path so far.
+  max_cost: isize,
+}
+
+impl<'a> Search<'a> {
+  fn new(old: &'a [usize], new: &'a [usize]) -> Self {
+    let diagonals = old.len() + new.len() + 3;
+    Self {
+      old,
+      new,
+      forward: vec![0; diagonals],
+      backward: vec![0; diagonals],
+      offset: new.len() as isize + 1,
+      max_cost: (bogosqrt(diagonals) as isize).max(MAX_COST_MIN),
+    }
+  }
+
+  fn fwd(&mut self, d: isize) -> &mut isize {
+    &mut self.forward[(d + self.offset) as usize]
+  }
+
+  fn bwd(&mut self, d: isize) -> &mut isize {
+    &mut self.backward[(d + self.offset) as usize]
+  }
+
+  fn matches(&self, i1: isize, i2: isize) -> bool {
+    self.old[i1 as usize] == self.new[i2 as usize]
+  }
+
+  /// Finds where to split the box `off1..lim1` x `off2..lim2` so that the
+  /// two halves can be diffed separately.
+  fn split(&mut self, off1: usize, lim1: usize, off2: usize, lim2: usize, minimal: bool) -> Split {
+    let (off1, lim1) = (off1 as isize, lim1 as isize);
+    let (off2, lim2) = (off2 as isize, lim2 as isize);
+    let split = |i1: isize, i2: isize, minimal_low, minimal_high| Split {
+      i1: i1 as usize,
+      i2: i2 as usize,
+      minimal_low,
+      minimal_high,
+    };
+
+    let (dmin, dmax) = (off1 - lim2, lim1 - off2);
+    let (fmid, bmid) = (off1 - off2, lim1 - lim2);
+    let odd = (fmid - bmid) & 1 != 0;
+    let (mut fmin, mut fmax) = (fmid, fmid);
+    let (mut bmin, mut bmax) = (bmid, bmid);
+    *self.fwd(fmid) = off1;
+    *self.bwd(bmid) = lim1;
+
+    let mut cost = 1;
+    loop {
+      let mut got_snake = false;
+
+      // widen the range of diagonals by one on each side (or narrow it, if
+      // that would leave the box), with sentinels outside of it
+      if fmin > dmin {
+        fmin -= 1;
+        *self.fwd(fmin - 1) = -1;
+      } else {
+        fmin += 1;
+      }
+      if fmax < dmax {
+        fmax += 1;
+        *self.fwd(fmax + 1) = -1;
+      } else {
+        fmax -= 1;
+      }
+
+      let mut d = fmax;
+      while d >= fmin {
+        let mut i1 = match *self.fwd(d - 1) >= *self.fwd(d + 1) {
+          true => *self.fwd(d - 1) + 1,
+          false => *self.fwd(d + 1),
+        };
+        let start = i1;
+        let mut i2 = i1 - d;
+        while i1 < lim1 && i2 < lim2 && self.matches(i1, i2) {
+          i1 += 1;
+          i2 += 1;
+        }
+        if i1 - start > SNAKE_COUNT {
+          got_snake = true;
+        }
+        *self.fwd(d) = i1;
+        if odd && bmin <= d && d <= bmax && *self.bwd(d) <= i1 {
+          return split(i1, i2, true, true);
+        }
+        d -= 2;
+      }
+
+      if bmin > dmin {
+        bmin -= 1;
+        *self.bwd(bmin - 1) = isize::MAX;
+      } else {
+        bmin += 1;
+      }
+      if bmax < dmax {
+        bmax += 1;
+        *self.bwd(bmax + 1) = isize::MAX;
+      } else {
+        bmax -= 1;
+      }
+
+      let mut d = bmax;
+      while d >= bmin {
+        let mut i1 = match *self.bwd(d - 1) < *self.bwd(d + 1) {
+          true => *self.bwd(d - 1),
+          false => *self.bwd(d + 1) - 1,
+        };
+        let start = i1;
+        let mut i2 = i1 - d;
+        while i1 > off1 && i2 > off2 && self.matches(i1 - 1, i2 - 1) {
+          i1 -= 1;
+          i2 -= 1;
+        }
+        if start - i1 > SNAKE_COUNT {
+          got_snake = true;
+        }
+        *self.bwd(d) = i1;
+        if !odd && fmin <= d && d <= fmax && i1 <= *self.fwd(d) {
+          return split(i1, i2, true, true);
+        }
+        d -= 2;
+      }
+
+      if minimal {
+        cost += 1;
+        continue;
+      }
+
+      // past a certain cost, split on a diagonal that went far enough and
+      // ends in a long run of matching lines
+      if got_snake && cost > HEURISTIC_MIN_COST {
+        let mut best = 0;
+        let mut found = (0, 0);
+        let mut d = fmax;
+        while d >= fmin {
+          let distance = (d - fmid).abs();
+          let i1 = *self.fwd(d);
+          let i2 = i1 - d;
+          let value = (i1 - off1) + (i2 - off2) - distance;
+          if value > HEURISTIC_FACTOR * cost
+            && value > best
+            && off1 + SNAKE_COUNT <= i1
+            && i1 < lim1
+            && off2 + SNAKE_COUNT <= i2
+            && i2 < lim2
+            && (1..=SNAKE_COUNT).all(|k| self.matches(i1 - k, i2 - k))
+          {
+            best = value;
+            found = (i1, i2);
+          }
+          d -= 2;
+        }
+        if best > 0 {
+          return split(found.0, found.1, true, false);
+        }
+
+        let mut d = bmax;
+        while d >= bmin {
+          let distance = (d - bmid).abs();
+          let i1 = *self.bwd(d);
+          let i2 = i1 - d;
+          let value = (lim1 - i1) + (lim2 - i2) - distance;
+          if value > HEURISTIC_FACTOR * cost
+            && value > best
+            && off1 < i1
+            && i1 <= lim1 - SNAKE_COUNT
+            && off2 < i2
+            && i2 <= lim2 - SNAKE_COUNT
+            && (0..SNAKE_COUNT).all(|k| self.matches(i1 + k, i2 + k))
+          {
+            best = value;
+            found = (i1, i2);
+          }
+          d -= 2;
+        }
+        if best > 0 {
+          return split(found.0, found.1, false, true);
+        }
+      }
+
+      // enough is enough: take whichever path got furthest
+      if cost >= self.max_cost {
+        let (mut fbest, mut fbest1) = (-1, -1);
+        let mut d = fmax;
+        while d >= fmin {
+          let mut i1 = (*self.fwd(d)).min(lim1);
+          let mut i2 = i1 - d;
+          if lim2 < i2 {
+            i1 = lim2 + d;
+            i2 = lim2;
+          }
+          if fbest < i1 + i2 {
+            fbest = i1 + i2;
+            fbest1 = i1;
+          }
+          d -= 2;
+        }
+
+        let (mut bbest, mut bbest1) = (isize::MAX, isize::MAX);
+        let mut d = bmax;
+        while d >= bmin {
+          let mut i1 = off1.max(*self.bwd(d));
+          let mut i2 = i1 - d;
+          if i2 < off2 {
+            i1 = off2 + d;
+            i2 = off2;
+          }
+          if i1 + i2 < bbest {
+            bbest = i1 + i2;
+            bbest1 = i1;
+          }
+          d -= 2;
+        }
+
+        return match (lim1 + lim2) - bbest < fbest - (off1 + off2) {
+          true => split(fbest1, fbest - fbest1, true, false),
+          false => split(bbest1, bbest - bbest1, false, true),
+        };
+      }
+      cost += 1;
+    }
+  }
+}
@@ -0,0 +1,160 @@
+//! Writes the changes to files in git's patch format, and identifies patches by
+//! what they change (patch IDs).
+
+use std::io::{self, Write};
+
+use colored::Colorize;
+
+use super::is_binary;
+use super::tree::{FileChange, Side, Status};
+use super::unified::{self, Options};
+use crate::crypto;
+use crate::index::pathspec::quote;
+use crate::object::refs::NULL_HASH;
+
+/// Writes the diff of one file: a `diff --git` header, the mode changes and
+/// the rename (or copy) it is, the blob hashes and the hunks.
+///
+/// `old` and `new` are the contents of the two sides (empty for a missing
+/// side) and `abbrev` shortens a hash for the `index` line. With `color` set
+/// the header is bold and the hunks are colored the way git colors them.
+///
+/// A type change (eg. a file that became a symbolic link) is shown as the old
+/// file being deleted and the new one added.
+pub fn write(
+  out: &mut impl Write,
+  change: &FileChange,
+  (old, new): (&[u8], &[u8]),
+  abbrev: &dyn Fn(&str) -> String,
+  options: &Options,
+  color: bool,
+) -> io::Result<()> {
+  if change.status == Status::TypeChanged {
+    let deleted = FileChange {
+      status: Status::Deleted,
+      old: change.old.clone(),
+      new: None,
+    };
+    let added = FileChange {
+      status: Status::Added,
+      old: None,
+      new: change.new.clone(),
+    };
+    write(out, &deleted, (old, b""), abbrev, options, color)?;
+    return write(out, &added, (b"", new), abbrev, options, color);
+  }
+
+  let meta = |text: String| match color {
+    true => text.bold().to_string(),
+    false => text,
+  };
+  let new_path = change.path();
+  let old_path = change.old.as_ref().map_or(new_path, |side| &side.path);
+  let header = format!(
+    "diff --git {} {}",
+    quote(&format!("a/{}", old_path), false),
+    quote(&format!("b/{}", new_path), false)
+  );
+  writeln!(out, "{}", meta(header))?;
+  match (&change.old, &change.new) {
+    (None, Some(new)) => writeln!(out, "{}", meta(format!("new file mode {}", new.mode)))?,
+    (Some(old), None) => writeln!(out, "{}", meta(format!("deleted file mode {}", old.mode)))?,
+    (Some(old), Some(new)) if old.mode != new.mode => {
+      writeln!(out, "{}", meta(format!("old mode {}", old.mode)))?;
+      writeln!(out, "{}", meta(format!("new mode {}", new.mode)))?;
+    }
+    _ => (),
+  }
+  if let Some(similarity) = change.status.similarity() {
+    let verb = match change.status {
+      Status::Copied(_) => "copy",
+      _ => "rename",
+    };
+    writeln!(out, "{}", meta(format!("similarity index {}%", similarity)))?;
+    writeln!(
+      out,
+      "{}",
+      meta(format!("{} from {}", verb, quote(old_path, false)))
+    )?;
+    writeln!(
+      out,
+      "{}",
+      meta(format!("{} to {}", verb, quote(new_path, false)))
+    )?;
+  }
+
+  if hash(&change.old) == hash(&change.new) {
+    return Ok(());
+  }
+  let mut index = format!(
+    "index {}..{}",
+    abbrev(hash(&change.old)),
+    abbrev(hash(&change.new))
+  );
+  if let (Some(old), Some(new)) = (&change.old, &change.new) {
+    if old.mode == new.mode {
+      index.push_str(&format!(" {}", old.mode));
+    }
+  }
+  writeln!(out, "{}", meta(index))?;
+
+  let name = |prefix: &str, side: &Option<Side>| match side {
+    Some(side) => quote(&format!("{}{}", prefix, side.path), false),
+    None => "/dev/null".to_string(),
+  };
+  let (old_name, new_name) = (name("a/", &change.old), name("b/", &change.new));
+  if is_binary(old) || is_binary(new) {
+    return writeln!(out, "Binary files {} and {} differ", old_name, new_name);
+  }
+  // an empty file that was added or deleted has no hunks, and no names either
+  let hunks = unified::hunks(old, new, options);
+  if hunks.is_empty() {
+    return Ok(());
+  }
+  writeln!(out, "{}", meta(format!("--- {}", old_name)))?;
+  writeln!(out, "{}", meta(format!("+++ {}", new_name)))?;
+  unified::write(out, &hunks, color)
+}
+
+/// The hash of one side of a change, or zeros if it is missing.
+fn hash(side: &Option<Side>) -> &str {
+  side.as_ref().map_or(NULL_HASH, |side| &side.hash)
+}
+
+/// Hashes what a diff changes in a way that doesn't depend on where it
+/// applies (git's patch ID): the blob hashes, the line numbers of the hunks
+/// and all whitespace are left out. Commits with the same patch ID make the
+/// same change, eg. when one was cherry-picked from the other.
+///
+/// `load` reads the contents of one side of a change.
+pub fn patch_id(
+  changes: &[FileChange],
+  mut load: impl FnMut(&Side) -> Result<Vec<u8>, String>,
+) -> Result<String, String> {
+  let mut patch = Vec::new();
+  for change in changes {
+    let mut read = |side: &Option<Side>| match side {
+      Some(side) => load(side),
+      None => Ok(Vec::new()),
+    };
+    let (old, new) = (read(&change.old)?, read(&change.new)?);
+    let mut out = Vec::new();
+    let abbrev = |hash: &str| hash.to_string();
+    write(
+      &mut out,
+      change,
+      (&old, &new),
+      &abbrev,
+      &Options::default(),
+      false,
+    )
+    .map_err(|msg| msg.to_string())?;
+    for line in out.split(|&byte| byte == b'\n') {
+      if line.starts_with(b"index ") || line.starts_with(b"@@") {
+        continue;
+      }
+      patch.extend(line.iter().filter(|byte| !byte.is_ascii_whitespace()));
+    }
+  }
+  Ok(crypto::sha_1(&patch))
+}
@@ -0,0 +1,171 @@
+//! The patience diff algorithm, as implemented by git's xdiff.
+//!
+//! Lines that appear exactly once in both files are matched first, keeping
+//! the longest sequence of them that is in the same order in both. The gaps
+//! between those matches are then diffed the same way, and whatever has no
+//! unique lines at all is left to Myers' algorithm.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use super::myers;
+
+/// Diffs two files, given as lists of line classes (equal lines have equal
+/// classes), and returns which lines of each file changed.
+pub fn diff(old: &[usize], new: &[usize]) -> (Vec<bool>, Vec<bool>) {
+  let mut old_changed = vec![false; old.len()];
+  let mut new_changed = vec![false; new.len()];
+  let mut patience = Patience {
+    old,
+    new,
+    old_changed: &mut old_changed,
+    new_changed: &mut new_changed,
+  };
+  patience.diff(0..old.len(), 0..new.len());
+  (old_changed, new_changed)
+}
+
+struct Patience<'a> {
+  old: &'a [usize],
+  new: &'a [usize],
+  old_changed: &'a mut [bool],
+  new_changed: &'a mut [bool],
+}
+
+/// A line that appears in the old part being diffed, in order of first
+/// appearance.
+struct Entry {
+  /// Where it first appears in the old file.
+  old: usize,
+
+  /// Where it appears in the new file, if it is unique in both.
+  new: Option<usize>,
+
+  /// Whether it appears more than once in either file.
+  repeated: bool,
+
+  /// The previous line of the longest sequence ending here.
+  previous: Option<usize>,
+}
+
+impl Patience<'_> {
+  fn diff(&mut self, old: Range<usize>, new: Range<usize>) {
+    if old.is_empty() || new.is_empty() {
+      self.old_changed[old].fill(true);
+      self.new_changed[new].fill(true);
+      return;
+    }
+
+    // find the lines that are unique in both parts
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut by_class: HashMap<usize, usize> = HashMap::new();
+    for i in old.clone() {
+      match by_class.get(&self.old[i]) {
+        Some(&found) => entries[found].repeated = true,
+        None => {
+          by_class.insert(self.old[i], entries.len());
+          entries.push(Entry {
+            old: i,
+            new: None,
+            repeated: false,
+            previous: None,
+          });
+        }
+      }
+    }
+    let mut has_matching = false;
+    for j in new.clone() {
+      if let Some(&found) = by_class.get(&self.new[j]) {
+        has_matching = true;
+        let entry = &mut entries[found];
+        match entry.new {
+          Some(_) => entry.repeated = true,
+          None => entry.new = Some(j),
+        }
+      }
+    }
+    if !has_matching {
+      self.old_changed[old].fill(true);
+      self.new_changed[new].fill(true);
+      return;
+    }
+
+    match longest_common_sequence(&mut entries) {
+      Some(sequence) => self.walk(&entries, &sequence, old, new),
+      None => self.fall_back(old, new),
+    }
+  }
+
+  /// Diffs the gaps around and between the matched unique lines.
+  fn walk(&mut self, entries: &[Entry], sequence: &[usize], old: Range<usize>, new: Range<usize>) {
+    let (mut line1, mut line2) = (old.start, new.start);
+    for next in sequence.iter().map(Some).chain([None]) {
+      // extend the matches around the gap as far as the lines are equal
+      let (mut next1, mut next2) = match next {
+        Some(&entry) => (entries[entry].old, entries[entry].new.unwrap()),
+        None => (old.end, new.end),
+      };
+      if next.is_some() {
+        while next1 > line1 && next2 > line2 && self.old[next1 - 1] == self.new[next2 - 1] {
+          next1 -= 1;
+          next2 -= 1;
+        }
+      }
+      while line1 < next1 && line2 < next2 && self.old[line1] == self.new[line2] {
+        line1 += 1;
+        line2 += 1;
+      }
+      if line1 < next1 || line2 < next2 {
+        self.diff(line1..next1, line2..next2);
+      }
+      match next {
+        Some(&entry) => {
+          line1 = entries[entry].old + 1;
+          line2 = entries[entry].new.unwrap() + 1;
+        }
+        None => break,
+      }
+    }
+  }
+
+  /// Diffs a part of the files with Myers' algorithm.
+  fn fall_back(&mut self, old: Range<usize>, new: Range<usize>) {
+    let (old_changed, new_changed) =
+      myers::diff(&self.old[old.clone()], &self.new[new.clone()], false);
+    self.old_changed[old].copy_from_slice(&old_changed);
+    self.new_changed[new].copy_from_slice(&new_changed);
+  }
+}
+
+/// Finds the longest sequence of unique lines that is in the same order in
+/// both files, and returns their entries in order (or `None` if there are no
+/// unique lines).
+///
+/// This is the patience sort: each line is put on the first pile whose top
+/// comes after it in the new file, and remembers the top of the pile before
+/// it.
+fn longest_common_sequence(entries: &mut [Entry]) -> Option<Vec<usize>> {
+  let mut piles: Vec<usize> = Vec::new();
+  for i in 0..entries.len() {
+    let entry = &entries[i];
+    let new = match entry.new {
+      Some(new) if !entry.repeated => new,
+      _ => continue,
+    };
+    let pile = piles.partition_point(|&top| entries[top].new.unwrap() < new);
+    entries[i].previous = pile.checked_sub(1).map(|pile| piles[pile]);
+    match pile == piles.len() {
+      true => piles.push(i),
+      false => piles[pile] = i,
+    }
+  }
+
+  let mut sequence = Vec::new();
+  let mut next = piles.last().copied();
+  while let Some(entry) = next {
+    sequence.push(entry);
+    next = entries[entry].previous;
+  }
+  sequence.reverse();
+  Some(sequence).filter(|sequence| !sequence.is_empty())
+}
@@ -0,0 +1,56 @@
+//! Finds the changes that add or remove some text, which is what git calls
+//! the pickaxe (`log -S` and `log -G`).
+
+use regex::bytes::Regex;
+
+use super::tree::{FileChange, Side};
+use super::{diff, is_binary, lines, Algorithm};
+
+/// What a change must do to be picked.
+#[derive(Debug)]
+pub enum Pickaxe {
+  /// Change the number of times the pattern occurs in a file (`-S`), ie. add
+  /// or remove a string rather than move it around.
+  Count(Regex),
+
+  /// Add or remove a line that matches the pattern (`-G`).
+  Lines(Regex),
+}
+
+impl Pickaxe {
+  /// Whether any of the changes is picked. `load` reads the contents of one
+  /// side of a change. Binary files are never picked.
+  pub fn matches(
+    &self,
+    changes: &[FileChange],
+    mut load: impl FnMut(&Side) -> Result<Vec<u8>, String>,
+  ) -> Result<bool, String> {
+    for change in changes {
+      let mut read = |side: &Option<Side>| match side {
+        Some(side) => load(side),
+        None => Ok(Vec::new()),
+      };
+      let (old, new) = (read(&change.old)?, read(&change.new)?);
+      if is_binary(&old) || is_binary(&new) {
+        continue;
+      }
+      let picked = match self {
+        Pickaxe::Count(pattern) => {
+          pattern.find_iter(&old).count() != pattern.find_iter(&new).count()
+        }
+        Pickaxe::Lines(pattern) => {
+          let (old, new) = (lines(&old), lines(&new));
+          let matches = |line: &[u8]| pattern.is_match(line.strip_suffix(b"\n").unwrap_or(line));
+          diff(&old, &new, Algorithm::Myers).iter().any(|change| {
+            old[change.old.clone()].iter().any(|line| matches(line))
+              || new[change.new.clone()].iter().any(|line| matches(line))
+          })
+        }
+      };
+      if picked {
+        return Ok(true);
+      }
+    }
+    Ok(false)
+  }
+}
@@ -0,0 +1,474 @@
+//! Finds the added files that are really renames or copies of other files,
+//! the way git's diffcore-rename does.
+//!
+//! Files are matched in three rounds:
+//! 1. exact renames, where the contents didn't change at all,
+//! 2. (for renames only) a file moved to another directory under the same
+//!    name, if the two are similar enough,
+//! 3. every remaining pair of files, best matches first.
+//!
+//! How similar two files are is estimated by cutting both into chunks (lines,
+//! or 64 bytes if lines are longer) and counting how many bytes of the new
+//! file are in chunks that also appear in the old one.
+
+use std::collections::HashMap;
+
+use super::is_binary;
+use super::tree::{FileChange, Side, Status};
+use crate::object::mode::Mode;
+
+/// The score of two identical files. Scores are given as a fraction of this.
+pub const MAX_SCORE: u32 = 60000;
+
+/// Files must be at least 50% similar to be matched by default.
+const DEFAULT_MINIMUM_SCORE: u32 = 30000;
+
+/// How many of the best sources are remembered for each destination.
+const CANDIDATES_PER_DESTINATION: usize = 4;
+
+/// Above this many sources and destinations (each), only exact renames are
+/// looked for (git's default `diff.renameLimit`).
+const RENAME_LIMIT: usize = 1000;
+
+/// How many identical sources are considered for an exact rename.
+const MAX_IDENTICAL_CANDIDATES: usize = 100;
+
+/// The number of buckets chunks are hashed into (a prime).
+const HASH_BASE: u32 = 107927;
+
+/// How renames and copies are detected.
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+  /// How similar (out of `MAX_SCORE`) two files must be to be matched.
+  pub minimum_score: u32,
+
+  /// Whether to look for copies as well as renames. Files that were modified
+  /// can then be the source of a copy, as well as deleted ones.
+  pub copies: bool,
+}
+
+impl Default for Options {
+  fn default() -> Self {
+    Self {
+      minimum_score: DEFAULT_MINIMUM_SCORE,
+      copies: false,
+    }
+  }
+}
+
+/// Parses a similarity threshold as given to `-M` or `-C`.
+///
+/// A number followed by `%` is a percentage, and anything else is read as the
+/// digits after a decimal point (so `-M5` and `-M50%` both mean 50%, and `-M05`
+/// means 5%).
+pub fn parse_score(arg: &str) -> Result<u32, String> {
+  let (mut number, mut scale): (u64, u64) = (0, 1);
+  let mut dot = false;
+  let mut rest = arg;
+  while let Some(c) = rest.chars().next() {
+    match c {
+      '.' if !dot => {
+        scale = 1;
+        dot = true;
+      }
+      '%' => {
+        scale = if dot { scale * 100 } else { 100 };
+        rest = &rest[1..];
+        break;
+      }
+      '0'..='9' => {
+        if scale < 100000 {
+          scale *= 10;
+          number = number * 10 + c.to_digit(10).unwrap() as u64;
+        }
+      }
+      _ => break,
+    }
+    rest = &rest[1..];
+  }
+  if !rest.is_empty() {
+    return Err(format!("invalid similarity score '{}'", arg));
+  }
+  Ok(match number >= scale {
+    true => MAX_SCORE,
+    false => (MAX_SCORE as u64 * number / scale) as u32,
+  })
+}
+
+/// A file that other files may have been renamed or copied from.
+struct Source {
+  side: Side,
+
+  /// The number of renames and copies made from the file so far, plus one if
+  /// the file is still there (when looking for copies).
+  used: u32,
+
+  /// The index of the change the file came from.
+  change: usize,
+}
+
+/// An added file, which may turn out to be a rename or a copy.
+struct Destination {
+  side: Side,
+
+  /// The source and score it was matched with.
+  found: Option<(usize, u32)>,
+}
+
+/// A possible match between a destination and a source.
+#[derive(Clone, Copy)]
+struct Candidate {
+  destination: usize,
+  source: usize,
+  score: u32,
+
+  /// Whether the two files have the same name.
+  same_name: bool,
+}
+
+/// Turns pairs of deleted and added files (and, when looking for copies,
+/// modified and added files) that are similar enough into renames (or
+/// copies).
+///
+/// `load` reads the contents of a file; it is only called for files that
+/// aren't matched exactly.
+pub fn detect(
+  changes: Vec<FileChange>,
+  options: &Options,
+  mut load: impl FnMut(&Side) -> Result<Vec<u8>, String>,
+) -> Result<Vec<FileChange>, String> {
+  let mut sources = Vec::new();
+  let mut destinations = Vec::new();
+  let mut destination_of = HashMap::new();
+  for (i, change) in changes.iter().enumerate() {
+    match (&change.old, &change.new) {
+      (None, Some(new)) => {
+        destination_of.insert(i, destinations.len());
+        destinations.push(Destination {
+          side: new.clone(),
+          found: None,
+        });
+      }
+      (Some(old), None) => sources.push(Source {
+        side: old.clone(),
+        used: 0,
+        change: i,
+      }),
+      (Some(old), Some(_)) if options.copies => sources.push(Source {
+        side: old.clone(),
+        used: 1,
+        change: i,
+      }),
+      _ => (),
+    }
+  }
+  if destinations.is_empty() || sources.is_empty() {
+    return Ok(changes);
+  }
+
+  let mut contents = Contents {
+    load: &mut load,
+    spans: HashMap::new(),
+  };
+  find_exact(&mut sources, &mut destinations, options);
+  let mut remaining: Vec<usize> = (0..sources.len()).collect();
+  if !options.copies {
+    remaining.retain(|&i| sources[i].used == 0);
+    let minimum_score = options.minimum_score + (MAX_SCORE - options.minimum_score) / 2;
+    find_same_names(
+      &mut sources,
+      &remaining,
+      &mut destinations,
+      minimum_score,
+      &mut contents,
+    )?;
+    remaining.retain(|&i| sources[i].used == 0);
+  }
+  find_similar(
+    &mut sources,
+    &remaining,
+    &mut destinations,
+    options,
+    &mut contents,
+  )?;
+
+  // put the renames and copies in place of the added files, and drop the
+  // deletions of the files that were renamed
+  let deleted_and_used: Vec<usize> = sources
+    .iter()
+    .filter(|source| source.used > 0 && changes[source.change].new.is_none())
+    .map(|source| source.change)
+    .collect();
+  let mut result = Vec::new();
+  for (i, change) in changes.into_iter().enumerate() {
+    if deleted_and_used.contains(&i) {
+      continue;
+    }
+    match destination_of.get(&i).and_then(|&d| destinations[d].found) {
+      Some((source, score)) => {
+        let source = &mut sources[source];
+        source.used -= 1;
+        let status = match source.used > 0 {
+          true => Status::Copied(score),
+          false => Status::Renamed(score),
+        };
+        result.push(FileChange {
+          status,
+          old: Some(source.side.clone()),
+          new: change.new,
+        });
+      }
+      None => result.push(change),
+    }
+  }
+  Ok(result)
+}
+
+/// Matches destinations with sources that have the same contents, preferring
+/// sources that weren't used yet and have the same name.
+fn find_exact(sources: &mut [Source], destinations: &mut [Destination], options: &Options) {
+  for destination in destinations.iter_mut() {
+    let target = &destination.side;
+    let mut best: Option<(usize, u32)> = None;
+    let identical = sources.iter().enumerate().filter(|(_, source)| {
+      source.side.hash == target.hash
+        && (is_regular(source.side.mode) && is_regular(target.mode)
+          || source.side.mode == target.mode)
+        && (source.used == 0 || options.copies)
+    });
+    for (i, source) in identical.take(MAX_IDENTICAL_CANDIDATES) {
+      let score = (source.used == 0) as u32 + same_name(&source.side, target) as u32;
+      if best.is_none_or(|(_, best)| score > best) {
+        best = Some((i, score));
+        if score == 2 {
+          break;
+        }
+      }
+    }
+    if let Some((i, _)) = best {
+      sources[i].used += 1;
+      destination.found = Some((i, MAX_SCORE));
+    }
+  }
+}
+
+/// Matches destinations with sources that have the same file name (when the
+/// name is unique among both), if they are similar enough.
+fn find_same_names(
+  sources: &mut [Source],
+  remaining: &[usize],
+  destinations: &mut [Destination],
+  minimum_score: u32,
+  contents: &mut Contents,
+) -> Result<(), String> {
+  let mut source_names: HashMap<&str, Option<usize>> = HashMap::new();
+  for &i in remaining {
+    let name = file_name(&sources[i].side.path);
+    source_names
+      .entry(name)
+      .and_modify(|unique| *unique = None)
+      .or_insert(Some(i));
+  }
+  let mut destination_names: HashMap<&str, Option<usize>> = HashMap::new();
+  for (i, destination) in destinations.iter().enumerate() {
+    if destination.found.is_none() {
+      destination_names
+        .entry(file_name(&destination.side.path))
+        .and_modify(|unique| *unique = None)
+        .or_insert(Some(i));
+    }
+  }
+
+  let mut pairs = Vec::new();
+  for (name, source) in source_names {
+    if let (Some(source), Some(Some(destination))) = (source, destination_names.get(name)) {
+      pairs.push((source, *destination));
+    }
+  }
+  for (source, destination) in pairs {
+    let score = contents.similarity(
+      &sources[source].side,
+      &destinations[destination].side,
+      minimum_score,
+    )?;
+    if score >= minimum_score {
+      sources[source].used += 1;
+      destinations[destination].found = Some((source, score));
+    }
+  }
+  Ok(())
+}
+
+/// Compares every remaining destination with every remaining source and
+/// matches them, best pairs first.
+fn find_similar(
+  sources: &mut [Source],
+  remaining: &[usize],
+  destinations: &mut [Destination],
+  options: &Options,
+  contents: &mut Contents,
+) -> Result<(), String> {
+  let unmatched: Vec<usize> = (0..destinations.len())
+    .filter(|&i| destinations[i].found.is_none())
+    .collect();
+  let too_many = unmatched.len() > RENAME_LIMIT
+    && remaining.len() > RENAME_LIMIT
+    && unmatched.len() * remaining.len() > RENAME_LIMIT * RENAME_LIMIT;
+  if unmatched.is_empty() || remaining.is_empty() || too_many {
+    return Ok(());
+  }
+
+  let mut candidates: Vec<Option<Candidate>> = Vec::new();
+  for &destination in &unmatched {
+    let mut best = [None; CANDIDATES_PER_DESTINATION];
+    for &source in remaining {
+      let (old, new) = (&sources[source].side, &destinations[destination].side);
+      let candidate = Candidate {
+        destination,
+        source,
+        score: contents.similarity(old, new, options.minimum_score)?,
+        same_name: same_name(old, new),
+      };
+      keep_if_better(&mut best, candidate);
+    }
+    candidates.extend(best);
+  }
+  candidates.sort_by(|a, b| compare(a, b).cmp(&0));
+
+  let passes: &[bool] = match options.copies {
+    true => &[false, true],
+    false => &[false],
+  };
+  for &copies in passes {
+    for candidate in &candidates {
+      let Some(candidate) = candidate.filter(|c| c.score >= options.minimum_score) else {
+        break;
+      };
+      let destination = &mut destinations[candidate.destination];
+      if destination.found.is_some() || !copies && sources[candidate.source].used > 0 {
+        continue;
+      }
+      sources[candidate.source].used += 1;
+      destination.found = Some((candidate.source, candidate.score));
+    }
+  }
+  Ok(())
+}
+
+/// Replaces the worst of the best candidates so far, if the new one is
+/// better.
+fn keep_if_better(best: &mut [Option<Candidate>], candidate: Candidate) {
+  let mut worst = 0;
+  for i in 1..best.len() {
+    if compare(&best[i], &best[worst]) > 0 {
+      worst = i;
+    }
+  }
+  if compare(&best[worst], &Some(candidate)) > 0 {
+    best[worst] = Some(candidate);
+  }
+}
+
+/// Orders candidates from best to worst (as a C comparison function: the
+/// result is positive if `a` comes after `b`), with empty slots last.
+fn compare(a: &Option<Candidate>, b: &Option<Candidate>) -> i64 {
+  match (a, b) {
+    (None, b) => b.is_some() as i64,
+    (Some(_), None) => -1,
+    (Some(a), Some(b)) if a.score == b.score => b.same_name as i64 - a.same_name as i64,
+    (Some(a), Some(b)) => b.score as i64 - a.score as i64,
+  }
+}
+
+/// Returns true for regular files (executable or not).
+fn is_regular(mode: Mode) -> bool {
+  matches!(mode, Mode::Normal | Mode::Executable)
+}
+
+/// The last component of a path.
+fn file_name(path: &str) -> &str {
+  path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Returns true if two paths have the same last component.
+fn same_name(a: &Side, b: &Side) -> bool {
+  file_name(&a.path) == file_name(&b.path)
+}
+
+/// The contents of the files being compared, summarized as the number of
+/// bytes in each bucket of chunks (loaded once per blob).
+struct Contents<'a> {
+  load: &'a mut dyn FnMut(&Side) -> Result<Vec<u8>, String>,
+  spans: HashMap<String, (usize, HashMap<u32, u64>)>,
+}
+
+impl Contents<'_> {
+  fn get(&mut self, side: &Side) -> Result<&(usize, HashMap<u32, u64>), String> {
+    if !self.spans.contains_key(&side.hash) {
+      let data = (self.load)(side)?;
+      self
+        .spans
+        .insert(side.hash.clone(), (data.len(), spans(&data)));
+    }
+    Ok(&self.spans[&side.hash])
+  }
+
+  /// Estimates how much of the new file comes from the old one, out of
+  /// `MAX_SCORE`. Files whose sizes are too different to reach
+  /// `minimum_score` aren't compared, and only regular files are.
+  fn similarity(&mut self, old: &Side, new: &Side, minimum_score: u32) -> Result<u32, String> {
+    if !is_regular(old.mode) || !is_regular(new.mode) {
+      return Ok(0);
+    }
+    let old_size = self.get(old)?.0 as u64;
+    let new_size = self.get(new)?.0 as u64;
+    let max_size = old_size.max(new_size);
+    let delta_size = max_size - old_size.min(new_size);
+    if max_size * ((MAX_SCORE - minimum_score) as u64) < delta_size * MAX_SCORE as u64 {
+      return Ok(0);
+    }
+    if new_size == 0 {
+      return Ok(0);
+    }
+
+    let old_spans = &self.spans[&old.hash].1;
+    let new_spans = &self.spans[&new.hash].1;
+    let copied: u64 = new_spans
+      .iter()
+      .map(|(hash, &count)| count.min(old_spans.get(hash).copied().unwrap_or(0)))
+      .sum();
+    Ok((copied * MAX_SCORE as u64 / max_size) as u32)
+  }
+}
+
+/// Cuts data into chunks that end at a newline or after 64 bytes, and
+/// returns the number of bytes in chunks that hash to each bucket.
+///
+/// In text, a `\r` before a `\n` is ignored. Like git, the bytes after the
+/// last newline (when they are less than a full chunk) aren't counted.
+fn spans(data: &[u8]) -> HashMap<u32, u64> {
+  let is_text = !is_binary(data);
+  let mut spans = HashMap::new();
+  let mut add = |accum1: u32, accum2: u32, length: u64| {
+    let hash = accum1.wrapping_add(accum2.wrapping_mul(0x61)) % HASH_BASE;
+    *spans.entry(hash).or_insert(0) += length;
+  };
+
+  let (mut accum1, mut accum2, mut length): (u32, u32, u64) = (0, 0, 0);
+  for (i, &byte) in data.iter().enumerate() {
+    if is_text && byte == b'\r' && data.get(i + 1) == Some(&b'\n') {
+      continue;
+    }
+    let old1 = accum1;
+    accum1 = (accum1 << 7) ^ (accum2 >> 25);
+    accum2 = (accum2 << 7) ^ (old1 >> 25);
+    accum1 = accum1.wrapping_add(byte as u32);
+    length += 1;
+    if length < 64 && byte != b'\n' {
+      continue;
+    }
+    add(accum1, accum2, length);
+    (accum1, accum2, length) = (0, 0, 0);
+  }
+  spans
+}
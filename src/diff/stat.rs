@@ -0,0 +1,340 @@
+//! Summaries of how much each file of a diff changed, as `--stat` and
+//! `--numstat` show them, and of the files that were created, deleted or
+//! renamed, as `--summary` shows them.
+
+use std::io::{self, Write};
+
+use colored::Colorize;
+
+use super::tree::{FileChange, Status};
+use super::{diff, is_binary, lines, Algorithm};
+use crate::index::pathspec::quote;
+
+/// How much one file changed.
+pub struct FileStat {
+  /// The name the file is shown under (see `name`).
+  pub name: String,
+  pub counts: Counts,
+}
+
+/// What changed in a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Counts {
+  /// The number of lines added and deleted.
+  Lines { added: usize, deleted: usize },
+
+  /// The sizes of a binary file before and after (both zero if the contents
+  /// didn't change).
+  Bytes { old: usize, new: usize },
+
+  /// The file has merge conflicts, so there is nothing to count.
+  Unmerged,
+}
+
+impl Counts {
+  /// Counts the changes between two versions of a file.
+  pub fn between(old: &[u8], new: &[u8], algorithm: Algorithm) -> Self {
+    if is_binary(old) || is_binary(new) {
+      return match old == new {
+        true => Counts::Bytes { old: 0, new: 0 },
+        false => Counts::Bytes {
+          old: old.len(),
+          new: new.len(),
+        },
+      };
+    }
+    let (mut added, mut deleted) = (0, 0);
+    if old != new {
+      for change in diff(&lines(old), &lines(new), algorithm) {
+        added += change.new.len();
+        deleted += change.old.len();
+      }
+    }
+    Counts::Lines { added, deleted }
+  }
+}
+
+/// The name a file is shown under: its (quoted) path, or both paths of a
+/// rename with the parts they have in common written once, as in
+/// `src/{old => new}/main.rs`.
+pub fn name(old: &str, new: &str) -> String {
+  if old == new {
+    return quote(new, false);
+  }
+  let (quoted_old, quoted_new) = (quote(old, false), quote(new, false));
+  if quoted_old != old || quoted_new != new {
+    return format!("{} => {}", quoted_old, quoted_new);
+  }
+
+  // the common prefix and suffix are whole directories
+  let (old, new) = (old.as_bytes(), new.as_bytes());
+  let prefix = old
+    .iter()
+    .zip(new)
+    .take_while(|(a, b)| a == b)
+    .enumerate()
+    .filter(|(_, (&a, _))| a == b'/')
+    .last()
+    .map_or(0, |(i, _)| i + 1);
+  // the suffix may reach back into the slash that ends the prefix
+  let limit = old.len().min(new.len()) - prefix.saturating_sub(1);
+  let suffix = old
+    .iter()
+    .rev()
+    .zip(new.iter().rev())
+    .take(limit)
+    .take_while(|(a, b)| a == b)
+    .enumerate()
+    .filter(|(_, (&a, _))| a == b'/')
+    .last()
+    .map_or(0, |(i, _)| i + 1);
+
+  let middle = |path: &[u8]| {
+    let end = path.len().saturating_sub(suffix).max(prefix);
+    String::from_utf8_lossy(&path[prefix..end]).into_owned()
+  };
+  let renamed = format!("{} => {}", middle(old), middle(new));
+  match prefix + suffix {
+    0 => renamed,
+    _ => format!(
+      "{}{{{}}}{}",
+      String::from_utf8_lossy(&old[..prefix]),
+      renamed,
+      String::from_utf8_lossy(&old[old.len() - suffix..])
+    ),
+  }
+}
+
+/// Writes the `--numstat` lines: the lines added and deleted and the name of
+/// each file (with dashes for binary files).
+pub fn write_numstat(out: &mut impl Write, stats: &[FileStat]) -> io::Result<()> {
+  for stat in stats {
+    match stat.counts {
+      Counts::Lines { added, deleted } => write!(out, "{}\t{}\t", added, deleted)?,
+      Counts::Bytes { .. } => write!(out, "-\t-\t")?,
+      Counts::Unmerged => write!(out, "0\t0\t")?,
+    }
+    writeln!(out, "{}", stat.name)?;
+  }
+  Ok(())
+}
+
+/// Writes the `--stat` lines, a graph of the changes to each file that fits
+/// in `width` columns, followed by a summary of the totals.
+///
+/// Names that don't fit are cut at the front (at a `/`, if there is one) and
+/// graphs that don't fit are scaled down, the way git does.
+pub fn write_stat(
+  out: &mut impl Write,
+  stats: &[FileStat],
+  width: usize,
+  color: bool,
+) -> io::Result<()> {
+  if stats.is_empty() {
+    return Ok(());
+  }
+
+  let mut max_name = 0;
+  let mut max_change = 0;
+  let mut number_width = 0;
+  let mut binary_width = 0;
+  for stat in stats {
+    max_name = max_name.max(stat.name.chars().count());
+    match stat.counts {
+      Counts::Lines { added, deleted } => max_change = max_change.max(added + deleted),
+      Counts::Bytes { old, new } => {
+        // "Bin XXX -> YYY bytes", with the counts aligned with "Bin"
+        binary_width = binary_width.max(14 + decimal_width(old) + decimal_width(new));
+        number_width = 3;
+      }
+      Counts::Unmerged => binary_width = binary_width.max("Unmerged".len()),
+    }
+  }
+  number_width = number_width.max(decimal_width(max_change));
+
+  // the name gets what it needs, unless that leaves less than 3/8 of the
+  // width for the rest of the line
+  let width = width.max(16 + 6 + number_width) as isize;
+  let fixed = number_width as isize + 6;
+  let mut graph_width = match max_change + 4 > binary_width {
+    true => max_change as isize,
+    false => binary_width as isize - 4,
+  };
+  let mut name_width = max_name as isize;
+  if name_width + fixed + graph_width > width {
+    if graph_width > width * 3 / 8 - fixed {
+      graph_width = (width * 3 / 8 - fixed).max(6);
+    }
+    match name_width > width - fixed - graph_width {
+      true => name_width = width - fixed - graph_width,
+      false => graph_width = width - fixed - name_width,
+    }
+  }
+  let (name_width, graph_width) = (name_width.max(0) as usize, graph_width as usize);
+
+  let (mut files, mut insertions, mut deletions) = (0, 0, 0);
+  for stat in stats {
+    let mut name = stat.name.as_str();
+    let mut prefix = "";
+    let mut len = name_width;
+    if name.chars().count() > name_width {
+      prefix = "...";
+      len = len.saturating_sub(3);
+      let skip = name.chars().count() - len;
+      name = &name[name.char_indices().nth(skip).map_or(name.len(), |(i, _)| i)..];
+      if let Some(slash) = name.find('/') {
+        name = &name[slash..];
+      }
+    }
+    let padding = len.saturating_sub(name.chars().count());
+    write!(
+      out,
+      " {}{}{:padding$} | ",
+      prefix,
+      name,
+      "",
+      padding = padding
+    )?;
+
+    match stat.counts {
+      Counts::Unmerged => writeln!(out, "{:>width$}", "Unmerged", width = number_width)?,
+      Counts::Bytes { old, new } => {
+        files += 1;
+        write!(out, "{:>width$}", "Bin", width = number_width)?;
+        match old == 0 && new == 0 {
+          true => writeln!(out)?,
+          false => writeln!(
+            out,
+            " {} -> {} bytes",
+            paint(old.to_string(), color, false),
+            paint(new.to_string(), color, true)
+          )?,
+        }
+      }
+      Counts::Lines { added, deleted } => {
+        files += 1;
+        insertions += added;
+        deletions += deleted;
+        let (mut plus, mut minus) = (added, deleted);
+        if graph_width <= max_change {
+          let mut total = scale(added + deleted, graph_width, max_change);
+          if total < 2 && added > 0 && deleted > 0 {
+            // one column isn't enough to show both
+            total = 2;
+          }
+          if added < deleted {
+            plus = scale(added, graph_width, max_change);
+            minus = total - plus;
+          } else {
+            minus = scale(deleted, graph_width, max_change);
+            plus = total - minus;
+          }
+        }
+        write!(out, "{:>width$}", added + deleted, width = number_width)?;
+        if added + deleted > 0 {
+          write!(out, " ")?;
+        }
+        writeln!(
+          out,
+          "{}{}",
+          paint("+".repeat(plus), color, true),
+          paint("-".repeat(minus), color, false)
+        )?;
+      }
+    }
+  }
+  writeln!(out, "{}", summary(files, insertions, deletions))
+}
+
+/// The last line of `--stat`, eg. ` 2 files changed, 3 insertions(+)`.
+pub fn summary(files: usize, insertions: usize, deletions: usize) -> String {
+  if files == 0 {
+    return " 0 files changed".to_string();
+  }
+  let plural = |n: usize, one: &str, many: &str| match n {
+    1 => format!("{} {}", n, one),
+    _ => format!("{} {}", n, many),
+  };
+  let mut summary = format!(" {}", plural(files, "file changed", "files changed"));
+  // "0 insertions" is only left out if there are deletions, and vice versa
+  if insertions > 0 || deletions == 0 {
+    summary.push_str(&format!(
+      ", {}",
+      plural(insertions, "insertion(+)", "insertions(+)")
+    ));
+  }
+  if deletions > 0 || insertions == 0 {
+    summary.push_str(&format!(
+      ", {}",
+      plural(deletions, "deletion(-)", "deletions(-)")
+    ));
+  }
+  summary
+}
+
+/// Writes the `--summary` lines, which list the files that were created or
+/// deleted, renamed or copied, or that changed mode, eg.
+/// ` create mode 100644 README.md`.
+pub fn write_summary(out: &mut impl Write, changes: &[FileChange]) -> io::Result<()> {
+  for change in changes {
+    match (&change.status, &change.old, &change.new) {
+      (Status::Added, _, Some(new)) => {
+        writeln!(out, " create mode {} {}", new.mode, quote(&new.path, false))?
+      }
+      (Status::Deleted, Some(old), _) => {
+        writeln!(out, " delete mode {} {}", old.mode, quote(&old.path, false))?
+      }
+      (status @ (Status::Renamed(_) | Status::Copied(_)), Some(old), Some(new)) => {
+        let kind = match status {
+          Status::Renamed(_) => "rename",
+          _ => "copy",
+        };
+        let similarity = status.similarity().unwrap_or(0);
+        writeln!(
+          out,
+          " {} {} ({}%)",
+          kind,
+          name(&old.path, &new.path),
+          similarity
+        )?;
+      }
+      _ => (),
+    }
+    if let (Some(old), Some(new)) = (&change.old, &change.new) {
+      if old.mode != new.mode && change.status != Status::TypeChanged {
+        writeln!(
+          out,
+          " mode change {} => {} {}",
+          old.mode,
+          new.mode,
+          quote(&new.path, false)
+        )?;
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Scales a count of changes so that `max_change` fills `width` columns,
+/// keeping anything that changed at least one column wide.
+fn scale(count: usize, width: usize, max_change: usize) -> usize {
+  match count {
+    0 => 0,
+    _ => 1 + count * (width.saturating_sub(1)) / max_change,
+  }
+}
+
+/// The number of digits of a number.
+fn decimal_width(n: usize) -> usize {
+  n.to_string().len()
+}
+
+/// Colors added (green) or deleted (red) counts, if color is on.
+fn paint(text: String, color: bool, added: bool) -> String {
+  match (color, added) {
+    (false, _) => text,
+    _ if text.is_empty() => text,
+    (true, true) => text.green().to_string(),
+    (true, false) => text.red().to_string(),
+  }
+}
@@ -0,0 +1,235 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::index::pathspec::quote;
+use crate::object::blob::Blob;
+use crate::object::mode::Mode;
+use crate::object::serializable::Unbox;
+use crate::object::tree::{Tree, TreeEntry};
+use crate::object::{self};
+use crate::repo::Repo;
+
+use super::rename::MAX_SCORE;
+
+/// One side of a changed path: the file as it was or as it is now.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Side {
+  pub path: String,
+  pub mode: Mode,
+  pub hash: String,
+}
+
+/// How a path changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+  Added,
+  Deleted,
+  Modified,
+
+  /// The path changed kind, eg. from a regular file to a symbolic link.
+  TypeChanged,
+
+  /// The file was moved, with the given similarity score (out of
+  /// `MAX_SCORE`) between its old and new contents.
+  Renamed(u32),
+
+  /// The file was copied from another file that is still there.
+  Copied(u32),
+}
+
+impl Status {
+  /// The letter `--name-status` and `--raw` show for the change.
+  pub fn code(&self) -> char {
+    match self {
+      Status::Added => 'A',
+      Status::Deleted => 'D',
+      Status::Modified => 'M',
+      Status::TypeChanged => 'T',
+      Status::Renamed(_) => 'R',
+      Status::Copied(_) => 'C',
+    }
+  }
+
+  /// The similarity of a rename or copy as a percentage.
+  pub fn similarity(&self) -> Option<u32> {
+    match self {
+      Status::Renamed(score) | Status::Copied(score) => Some(score * 100 / MAX_SCORE),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for Status {
+  /// Formats the status as `--raw` and `--name-status` show it: its letter,
+  /// followed by the similarity of a rename or copy (eg. `R087`).
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.similarity() {
+      Some(similarity) => write!(f, "{}{:03}", self.code(), similarity),
+      None => write!(f, "{}", self.code()),
+    }
+  }
+}
+
+/// A path that differs between two trees.
+///
+/// Added files have no `old` side and deleted files no `new` side. Renames
+/// and copies have both, with different paths.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileChange {
+  pub status: Status,
+  pub old: Option<Side>,
+  pub new: Option<Side>,
+}
+
+impl FileChange {
+  /// Describes the change between two versions of a path, if there is one.
+  pub fn between(old: Option<Side>, new: Option<Side>) -> Option<Self> {
+    let status = match (&old, &new) {
+      (None, None) => return None,
+      (None, Some(_)) => Status::Added,
+      (Some(_), None) => Status::Deleted,
+      (Some(old), Some(new)) if kind(old.mode) != kind(new.mode) => Status::TypeChanged,
+      (Some(old), Some(new)) if old.mode != new.mode || old.hash != new.hash => Status::Modified,
+      (Some(_), Some(_)) => return None,
+    };
+    Some(Self { status, old, new })
+  }
+
+  /// The path the change is shown under: the new path if there is one.
+  pub fn path(&self) -> &str {
+    match (&self.new, &self.old) {
+      (Some(side), _) | (None, Some(side)) => &side.path,
+      (None, None) => unreachable!("a change has at least one side"),
+    }
+  }
+
+  /// The quoted path of the change, or both paths (separated by a tab) if it
+  /// is a rename or a copy.
+  pub fn paths(&self) -> String {
+    match (&self.old, &self.new) {
+      (Some(old), Some(new)) if old.path != new.path => {
+        format!("{}\t{}", quote(&old.path, false), quote(&new.path, false))
+      }
+      _ => quote(self.path(), false),
+    }
+  }
+}
+
+/// The kind of object a mode describes (regular files, whether executable or
+/// not, are the same kind).
+fn kind(mode: Mode) -> Mode {
+  match mode {
+    Mode::Executable => Mode::Normal,
+    mode => mode,
+  }
+}
+
+/// Compares two trees and returns the paths that differ, in tree order.
+///
+/// Either tree may be missing (eg. for a root commit), in which case every
+/// path of the other is added or deleted. Subtrees with the same hash are
+/// skipped without being read. If `recursive` is false, a differing subtree
+/// is reported as a single change instead of being descended into.
+pub fn diff_trees(
+  repo: &Repo,
+  old: Option<&str>,
+  new: Option<&str>,
+  recursive: bool,
+) -> Result<Vec<FileChange>, String> {
+  let mut changes = Vec::new();
+  walk(repo, old, new, "", recursive, &mut changes)?;
+  Ok(changes)
+}
+
+/// Compares two sets of files, each mapping paths to a mode and hash (eg. a
+/// flattened tree, or the entries of the index), and returns the paths that
+/// differ, sorted by path.
+pub fn diff_files(
+  old: &BTreeMap<String, (Mode, String)>,
+  new: &BTreeMap<String, (Mode, String)>,
+) -> Vec<FileChange> {
+  let side = |path: &String, (mode, hash): &(Mode, String)| Side {
+    path: path.clone(),
+    mode: *mode,
+    hash: hash.clone(),
+  };
+  let mut paths: Vec<&String> = old.keys().chain(new.keys()).collect();
+  paths.sort();
+  paths.dedup();
+  paths
+    .into_iter()
+    .filter_map(|path| {
+      let old = old.get(path).map(|file| side(path, file));
+      let new = new.get(path).map(|file| side(path, file));
+      FileChange::between(old, new)
+    })
+    .collect()
+}
+
+fn walk(
+  repo: &Repo,
+  old: Option<&str>,
+  new: Option<&str>,
+  prefix: &str,
+  recursive: bool,
+  changes: &mut Vec<FileChange>,
+) -> Result<(), String> {
+  let old_entries = entries(repo, old)?;
+  let new_entries = entries(repo, new)?;
+  let side = |entry: &TreeEntry| Side {
+    path: format!("{}{}", prefix, entry.path),
+    mode: entry.mode,
+    hash: entry.hash.clone(),
+  };
+
+  let (mut i, mut j) = (0, 0);
+  while i < old_entries.len() || j < new_entries.len() {
+    let order = match (old_entries.get(i), new_entries.get(j)) {
+      (Some(old), Some(new)) => old.sort_key().cmp(&new.sort_key()),
+      (Some(_), None) => Ordering::Less,
+      _ => Ordering::Greater,
+    };
+    let (old, new) = match order {
+      Ordering::Less => (Some(&old_entries[i]), None),
+      Ordering::Greater => (None, Some(&new_entries[j])),
+      Ordering::Equal => (Some(&old_entries[i]), Some(&new_entries[j])),
+    };
+    i += old.is_some() as usize;
+    j += new.is_some() as usize;
+
+    let is_tree = |entry: Option<&TreeEntry>| entry.is_some_and(|e| e.mode == Mode::Directory);
+    if recursive && (is_tree(old) || is_tree(new)) {
+      if old.map(|e| &e.hash) != new.map(|e| &e.hash) {
+        let path = format!("{}{}/", prefix, old.or(new).unwrap().path);
+        let (old, new) = (old.map(|e| e.hash.as_str()), new.map(|e| e.hash.as_str()));
+        walk(repo, old, new, &path, recursive, changes)?;
+      }
+    } else if let Some(change) = FileChange::between(old.map(side), new.map(side)) {
+      changes.push(change);
+    }
+  }
+  Ok(())
+}
+
+/// Reads the entries of a tree (none if there is no tree).
+fn entries(repo: &Repo, hash: Option<&str>) -> Result<Vec<TreeEntry>, String> {
+  let Some(hash) = hash else {
+    return Ok(Vec::new());
+  };
+  let object = object::read(repo.clone(), hash, Some("tree"))?;
+  let tree = object.unbox::<Tree>()?;
+  Ok(
+    tree
+      .entries()
+      .iter()
+      .map(|entry| TreeEntry::new(entry.mode, &entry.path, &entry.hash))
+      .collect(),
+  )
+}
+
+/// Reads the contents of a blob.
+pub fn read_blob(repo: &Repo, hash: &str) -> Result<Vec<u8>, String> {
+  let object = object::read(repo.clone(), hash, Some("blob"))?;
+  Ok(object.unbox::<Blob>()?.data().clone())
+}
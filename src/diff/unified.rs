@@ -0,0 +1,246 @@
+use std::io::{self, Write};
+use std::ops::Range;
+
+use colored::Colorize;
+
+use super::{diff, lines, Algorithm};
+
+/// The most bytes of a line shown as the function name of a hunk.
+const MAX_FUNCTION_NAME: usize = 80;
+
+/// How a unified diff is made.
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+  pub algorithm: Algorithm,
+
+  /// The number of unchanged lines shown around each change (`-U<n>`).
+  pub context: usize,
+}
+
+impl Default for Options {
+  fn default() -> Self {
+    Self {
+      algorithm: Algorithm::Myers,
+      context: 3,
+    }
+  }
+}
+
+/// A line of a hunk, with the text of the line (including its `\n`, if it
+/// has one).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Line<'a> {
+  Context(&'a [u8]),
+  Removed(&'a [u8]),
+  Added(&'a [u8]),
+}
+
+impl<'a> Line<'a> {
+  /// The character the line starts with in a unified diff.
+  pub fn prefix(&self) -> char {
+    match self {
+      Line::Context(_) => ' ',
+      Line::Removed(_) => '-',
+      Line::Added(_) => '+',
+    }
+  }
+
+  pub fn text(&self) -> &'a [u8] {
+    match self {
+      Line::Context(text) | Line::Removed(text) | Line::Added(text) => text,
+    }
+  }
+}
+
+/// A group of changes that are close together, with the lines around them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk<'a> {
+  /// The lines of the old file the hunk covers.
+  pub old: Range<usize>,
+
+  /// The lines of the new file the hunk covers.
+  pub new: Range<usize>,
+
+  /// The closest line above the hunk that looks like the start of a
+  /// function (one that starts with a letter, `_` or `$`).
+  pub function: Option<&'a [u8]>,
+
+  pub lines: Vec<Line<'a>>,
+}
+
+impl Hunk<'_> {
+  /// Formats the hunk's `@@ -1,2 +1,3 @@ function` header (without a
+  /// newline).
+  ///
+  /// Like git, the header is cut short at the first byte that isn't part of
+  /// a valid UTF-8 character (or at a U+FFFE or U+FFFF), so that a function
+  /// name from a binary-looking file can't mess up the terminal.
+  pub fn header(&self) -> Vec<u8> {
+    let mut header = format!("@@ -{} +{} @@", range(&self.old), range(&self.new)).into_bytes();
+    if let Some(function) = self.function {
+      header.push(b' ');
+      header.extend_from_slice(function);
+    }
+    let mut valid = match std::str::from_utf8(&header) {
+      Ok(_) => header.len(),
+      Err(error) => error.valid_up_to(),
+    };
+    if let Some(i) = header[..valid]
+      .windows(3)
+      .position(|bytes| bytes[..2] == [0xef, 0xbf] && bytes[2] & 0xfe == 0xbe)
+    {
+      valid = i;
+    }
+    header.truncate(valid);
+    header
+  }
+}
+
+/// Formats a range of lines as a hunk header shows it: the first line
+/// (counting from 1, or the line before for an empty range) and the number of
+/// lines, unless it is 1.
+fn range(lines: &Range<usize>) -> String {
+  match lines.len() {
+    0 => format!("{},0", lines.start),
+    1 => format!("{}", lines.start + 1),
+    count => format!("{},{}", lines.start + 1, count),
+  }
+}
+
+/// Diffs two files and groups the changes into hunks.
+pub fn hunks<'a>(old: &'a [u8], new: &'a [u8], options: &Options) -> Vec<Hunk<'a>> {
+  let old_lines = lines(old);
+  let new_lines = lines(new);
+  let changes = diff(&old_lines, &new_lines, options.algorithm);
+  let context = options.context;
+
+  let mut hunks = Vec::new();
+  let mut function = None;
+  let mut searched = 0;
+  let mut rest = &changes[..];
+  while let Some(first) = rest.first() {
+    // changes with no more than twice the context between them are shown in
+    // the same hunk
+    let mut count = 1;
+    while count < rest.len() && rest[count].old.start - rest[count - 1].old.end <= 2 * context {
+      count += 1;
+    }
+    let (group, others) = rest.split_at(count);
+    rest = others;
+    let last = group.last().unwrap();
+
+    let old_start = first.old.start.saturating_sub(context);
+    let new_start = first.new.start.saturating_sub(context);
+    let after = context
+      .min(old_lines.len() - last.old.end)
+      .min(new_lines.len() - last.new.end);
+    let old_end = last.old.end + after;
+    let new_end = last.new.end + after;
+
+    // look for a function line above the hunk, in the lines not searched for
+    // the hunks before it
+    if let Some(found) = old_lines[searched..old_start]
+      .iter()
+      .rev()
+      .find_map(|line| function_name(line))
+    {
+      function = Some(found);
+    }
+    searched = old_start;
+
+    let mut hunk_lines = Vec::new();
+    let mut unchanged = new_start..first.new.start;
+    for change in group {
+      hunk_lines.extend(
+        new_lines[unchanged.start..change.new.start]
+          .iter()
+          .map(|&line| Line::Context(line)),
+      );
+      hunk_lines.extend(
+        old_lines[change.old.clone()]
+          .iter()
+          .map(|&line| Line::Removed(line)),
+      );
+      hunk_lines.extend(
+        new_lines[change.new.clone()]
+          .iter()
+          .map(|&line| Line::Added(line)),
+      );
+      unchanged = change.new.end..new_end;
+    }
+    hunk_lines.extend(new_lines[unchanged].iter().map(|&line| Line::Context(line)));
+
+    hunks.push(Hunk {
+      old: old_start..old_end,
+      new: new_start..new_end,
+      function,
+      lines: hunk_lines,
+    });
+  }
+  hunks
+}
+
+/// Returns the start of a line if it looks like the start of a function
+/// (git's default rule when no other is configured).
+fn function_name(line: &[u8]) -> Option<&[u8]> {
+  match line.first() {
+    Some(byte) if byte.is_ascii_alphabetic() || *byte == b'_' || *byte == b'$' => {
+      let line = &line[..line.len().min(MAX_FUNCTION_NAME)];
+      let end = line
+        .iter()
+        .rposition(|byte| !byte.is_ascii_whitespace() && *byte != 0x0b)
+        .map_or(0, |i| i + 1);
+      Some(&line[..end])
+    }
+    _ => None,
+  }
+}
+
+/// Writes the hunks of a diff between two files as a unified diff (without
+/// the `---` and `+++` lines), with git's colors if `color` is set.
+pub fn write(out: &mut impl Write, hunks: &[Hunk], color: bool) -> io::Result<()> {
+  for hunk in hunks {
+    if color {
+      // only the `@@ -1,2 +1,3 @@` part is colored
+      let header = hunk.header();
+      let end = header[2..]
+        .iter()
+        .position(|&byte| byte == b'@')
+        .map_or(header.len(), |i| i + 4);
+      write!(out, "{}", String::from_utf8_lossy(&header[..end]).cyan())?;
+      out.write_all(&header[end..])?;
+    } else {
+      out.write_all(&hunk.header())?;
+    }
+    out.write_all(b"\n")?;
+    for line in &hunk.lines {
+      write_line(out, line, color)?;
+    }
+  }
+  Ok(())
+}
+
+/// Writes a line of a hunk with its prefix (removed lines in red and added
+/// ones in green, if `color` is set), noting when it doesn't end in a
+/// newline.
+pub fn write_line(out: &mut impl Write, line: &Line, color: bool) -> io::Result<()> {
+  let text = line.text().strip_suffix(b"\n").unwrap_or(line.text());
+  match (color, line) {
+    (false, _) | (true, Line::Context(_)) => {
+      write!(out, "{}", line.prefix())?;
+      out.write_all(text)?;
+    }
+    (true, _) => {
+      let text = format!("{}{}", line.prefix(), String::from_utf8_lossy(text));
+      match line {
+        Line::Removed(_) => write!(out, "{}", text.red())?,
+        _ => write!(out, "{}", text.green())?,
+      }
+    }
+  }
+  out.write_all(b"\n")?;
+  if !line.text().ends_with(b"\n") {
+    out.write_all(b"\\ No newline at end of file\n")?;
+  }
+  Ok(())
+}
@@ -0,0 +1,257 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::{symlink, OpenOptionsExt};
+use std::path::Path;
+
+use super::worktree::{self, FileState};
+use super::{Index, IndexEntry};
+use crate::object::blob::Blob;
+use crate::object::commit::Commit;
+use crate::object::mode::Mode;
+use crate::object::serializable::Unbox;
+use crate::object::{read, tree};
+use crate::repo::Repo;
+
+/// The files of a tree, as returned by `tree::flatten`.
+pub type Files = BTreeMap<String, (Mode, String)>;
+
+/// Reads the files of a commit.
+pub fn files(repo: &Repo, commit: &str) -> Result<Files, String> {
+  let object = read(repo.clone(), commit, Some("commit"))?;
+  tree::flatten(repo, object.unbox::<Commit>()?.tree())
+}
+
+/// Moves the index and the working tree from the `old` tree to the `new` one
+/// (git's two-way merge, `read-tree -m -u`).
+///
+/// Only the paths that differ between the two trees are touched, so local
+/// changes to any other path are carried over. Unless `force` is set, nothing
+/// at all is changed if that would throw away a local change (a staged or
+/// unstaged change to one of those paths, or an untracked file in the way);
+/// the error lists the paths and `action` (eg. `checkout`) says what was
+/// refused. With `force`, the index and the working tree are simply reset to
+/// the `new` tree.
+pub fn switch_trees(
+  repo: &Repo,
+  index: &mut Index,
+  old: &Files,
+  new: &Files,
+  force: bool,
+  action: &str,
+) -> Result<(), String> {
+  if !force && index.has_conflicts() {
+    let mut msg = String::from("you need to resolve your current index first");
+    let unmerged: BTreeSet<&str> = index
+      .entries
+      .iter()
+      .filter(|entry| entry.stage > 0)
+      .map(|entry| entry.path.as_str())
+      .collect();
+    for path in unmerged {
+      msg.push_str(&format!("\n{}: needs merge", path));
+    }
+    return Err(msg);
+  }
+
+  let mut paths: BTreeSet<String> = old.keys().chain(new.keys()).cloned().collect();
+  paths.extend(index.entries.iter().map(|entry| entry.path.clone()));
+
+  let mut updates: Vec<(String, Option<(Mode, String)>)> = Vec::new();
+  let mut dirty = Vec::new();
+  let mut untracked = Vec::new();
+  let mut directories = Vec::new();
+  for path in paths {
+    let (old, new) = (old.get(&path), new.get(&path));
+    let entry = index.entry(&path);
+    let staged = entry.map(|entry| (entry.mode, entry.hash.clone()));
+    let state = || match entry {
+      Some(entry) => worktree::compare(repo, index, entry).map(Some),
+      None => Ok(None),
+    };
+
+    if force {
+      let conflicted = entry.is_none() && index.entries.iter().any(|e| e.path == path);
+      let unchanged = matches!(state()?, Some(FileState::Unchanged(_)));
+      if staged.as_ref() != new || conflicted || !unchanged {
+        updates.push((path, new.cloned()));
+      }
+    } else if old == new || staged.as_ref() == new {
+      // the trees agree, or the change is staged already: keep what is there
+    } else if staged.as_ref() != old || matches!(state()?, Some(FileState::Modified(_))) {
+      dirty.push(path);
+    } else {
+      if staged.is_none() && is_in_the_way(repo, index, &path) {
+        match fs::symlink_metadata(repo.work_tree.join(&path)) {
+          Ok(metadata) if metadata.is_dir() => directories.push(path.clone()),
+          _ => untracked.push(path.clone()),
+        }
+      }
+      updates.push((path, new.cloned()));
+    }
+  }
+
+  if !dirty.is_empty() || !untracked.is_empty() || !directories.is_empty() {
+    let hint = match action {
+      "checkout" => "switch branches",
+      action => action,
+    };
+    let mut msg = Vec::new();
+    if !dirty.is_empty() {
+      msg.push(format!(
+        "Your local changes to the following files would be overwritten by {}:",
+        action
+      ));
+      msg.extend(dirty.iter().map(|path| format!("\t{}", path)));
+      msg.push(format!(
+        "Please commit your changes or stash them before you {}.",
+        hint
+      ));
+    }
+    if !untracked.is_empty() {
+      msg.push(format!(
+        "The following untracked working tree files would be overwritten by {}:",
+        action
+      ));
+      msg.extend(untracked.iter().map(|path| format!("\t{}", path)));
+      msg.push(format!("Please move or remove them before you {}.", hint));
+    }
+    if !directories.is_empty() {
+      msg.push("Updating the following directories would lose untracked files in them:".into());
+      msg.extend(directories.iter().map(|path| format!("\t{}", path)));
+      msg.push(String::new());
+    }
+    msg.push("Aborting".to_string());
+    return Err(msg.join("\n"));
+  }
+
+  // remove files first, so that directories can take their place
+  for (path, _) in updates.iter().filter(|(_, new)| new.is_none()) {
+    index.remove(path);
+    remove_file(repo, path)?;
+  }
+  for (path, new) in &updates {
+    if let Some((mode, hash)) = new {
+      index.add(write_file(repo, path, *mode, hash)?);
+    }
+  }
+  Ok(())
+}
+
+/// Writes an object to the working tree with the given mode, replacing
+/// whatever is in the way, and returns the index entry for it.
+///
+/// Blobs are written as files (or as symbolic links, for `Mode::Symbolic`)
+/// and a gitlink becomes an empty directory for the submodule.
+pub fn write_file(repo: &Repo, path: &str, mode: Mode, hash: &str) -> Result<IndexEntry, String> {
+  let full_path = repo.work_tree.join(path);
+  let error = |msg: std::io::Error| format!("unable to write {} ({})", path, msg);
+
+  // files in the way of the leading directories go, and so does whatever is
+  // at the path itself
+  let mut dir = Path::new(path).parent();
+  while let Some(parent) = dir.filter(|parent| !parent.as_os_str().is_empty()) {
+    let full_parent = repo.work_tree.join(parent);
+    if fs::symlink_metadata(&full_parent).is_ok_and(|metadata| !metadata.is_dir()) {
+      fs::remove_file(&full_parent).map_err(error)?;
+    }
+    dir = parent.parent();
+  }
+  if let Ok(metadata) = fs::symlink_metadata(&full_path) {
+    let result = match metadata.is_dir() {
+      true if mode == Mode::Gitlink => Ok(()),
+      true => fs::remove_dir_all(&full_path),
+      false => fs::remove_file(&full_path),
+    };
+    result.map_err(error)?;
+  }
+  if let Some(parent) = full_path.parent() {
+    fs::create_dir_all(parent).map_err(error)?;
+  }
+
+  let data = || -> Result<Vec<u8>, String> {
+    let object = read(repo.clone(), hash, Some("blob"))?;
+    Ok(object.unbox::<Blob>()?.data().clone())
+  };
+  match mode {
+    Mode::Symbolic => {
+      let target = String::from_utf8_lossy(&data()?).into_owned();
+      symlink(target, &full_path).map_err(error)?;
+    }
+    Mode::Gitlink => fs::create_dir_all(&full_path).map_err(error)?,
+    _ => {
+      // the permissions are subject to the umask, like for any new file
+      let permissions = match mode {
+        Mode::Executable => 0o777,
+        _ => 0o666,
+      };
+      let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(permissions)
+        .open(&full_path)
+        .map_err(error)?;
+      file.write_all(&data()?).map_err(error)?;
+    }
+  }
+  let metadata = fs::symlink_metadata(&full_path).map_err(error)?;
+  Ok(IndexEntry::new(path, hash, mode, &metadata))
+}
+
+/// Removes a file from the working tree, along with the directories that it
+/// leaves empty.
+pub fn remove_file(repo: &Repo, path: &str) -> Result<(), String> {
+  let full_path = repo.work_tree.join(path);
+  let result = match fs::symlink_metadata(&full_path) {
+    // a submodule's directory is only removed if there is nothing in it
+    Ok(metadata) if metadata.is_dir() => fs::remove_dir(&full_path).or(Ok(())),
+    Ok(_) => fs::remove_file(&full_path),
+    Err(_) => Ok(()),
+  };
+  if let Err(msg) = result {
+    return Err(format!("unable to remove {} ({})", path, msg));
+  }
+  let mut dir = Path::new(path).parent();
+  while let Some(parent) = dir.filter(|parent| !parent.as_os_str().is_empty()) {
+    if fs::remove_dir(repo.work_tree.join(parent)).is_err() {
+      break;
+    }
+    dir = parent.parent();
+  }
+  Ok(())
+}
+
+/// Whether writing a new file at a path would overwrite an untracked file,
+/// either at the path itself (a directory only counts if it has untracked
+/// files in it) or in place of one of its leading directories.
+fn is_in_the_way(repo: &Repo, index: &Index, path: &str) -> bool {
+  let occupied = match fs::symlink_metadata(repo.work_tree.join(path)) {
+    Ok(metadata) if metadata.is_dir() => has_untracked(repo, index, path),
+    Ok(_) => true,
+    Err(_) => false,
+  };
+  let mut dir = Path::new(path).parent();
+  let mut blocked = false;
+  while let Some(parent) = dir.filter(|parent| !parent.as_os_str().is_empty()) {
+    let is_file =
+      fs::symlink_metadata(repo.work_tree.join(parent)).is_ok_and(|metadata| !metadata.is_dir());
+    blocked |= is_file && index.entry(&parent.to_string_lossy()).is_none();
+    dir = parent.parent();
+  }
+  occupied || blocked
+}
+
+/// Whether a directory has any files in it that aren't in the index.
+fn has_untracked(repo: &Repo, index: &Index, dir: &str) -> bool {
+  let entries = match fs::read_dir(repo.work_tree.join(dir)) {
+    Ok(entries) => entries,
+    Err(_) => return false,
+  };
+  entries.flatten().any(|entry| {
+    let path = format!("{}/{}", dir, entry.file_name().to_string_lossy());
+    match entry.file_type() {
+      Ok(file_type) if file_type.is_dir() => has_untracked(repo, index, &path),
+      _ => !index.entries.iter().any(|entry| entry.path == path),
+    }
+  })
+}
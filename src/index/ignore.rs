@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use super::pathspec::wildmatch;
+use crate::repo::Repo;
+
+/// The rules that decide which untracked files git pretends not to see.
+///
+/// Patterns come from three kinds of files, from most to least important:
+/// 1. `.gitignore` files in the working tree, which apply to the directory
+///    they are in (and those below it), with deeper files taking precedence,
+/// 2. `.git/info/exclude`, for rules that are local to the repository,
+/// 3. the file named by `core.excludesFile` (by default `git/ignore` in the
+///    user's config directory).
+///
+/// Within a file, the last matching pattern wins. A pattern starting with `!`
+/// re-includes what an earlier pattern excluded, except that nothing inside an
+/// excluded directory can be re-included.
+///
+/// `.gitignore` files are read lazily, as the directories they are in are
+/// visited.
+pub struct Ignore {
+  work_tree: PathBuf,
+
+  /// `.git/info/exclude` and `core.excludesFile`.
+  global: Vec<PatternList>,
+
+  /// The `.gitignore` of each directory seen so far (by path from the root,
+  /// `""` for the root itself).
+  directories: HashMap<String, PatternList>,
+}
+
+/// The patterns of a single ignore file.
+struct PatternList {
+  /// Where the patterns came from, as shown by `check-ignore -v`.
+  source: String,
+
+  /// The directory the patterns are relative to (`""` or ending in `/`).
+  base: String,
+
+  patterns: Vec<Pattern>,
+}
+
+/// A single line of an ignore file.
+pub struct Pattern {
+  /// The line as written (without trailing spaces).
+  pub text: String,
+
+  /// The line number in the file, starting at 1.
+  pub line: usize,
+
+  /// Whether the pattern starts with `!`.
+  pub negated: bool,
+
+  /// The glob, without the leading `!` or `/` and the trailing `/`.
+  glob: String,
+
+  /// Whether the pattern only matches directories (it ended with `/`).
+  directory_only: bool,
+
+  /// Whether the pattern is matched against the whole path relative to the
+  /// ignore file's directory (if it has a `/` anywhere but at the end) or
+  /// only against the last component.
+  anchored: bool,
+}
+
+/// The pattern that decided whether a path is ignored.
+pub struct Match<'a> {
+  /// The file the pattern is in.
+  pub source: &'a str,
+
+  pub pattern: &'a Pattern,
+}
+
+impl Match<'_> {
+  /// Returns true if the path is ignored (the pattern isn't a negation).
+  pub fn is_ignored(&self) -> bool {
+    !self.pattern.negated
+  }
+}
+
+impl Ignore {
+  /// Reads `.git/info/exclude` and `core.excludesFile`.
+  pub fn new(repo: &Repo) -> Self {
+    let mut global = Vec::new();
+    let exclude = repo.git_dir.join("info").join("exclude");
+    if let Ok(data) = fs::read_to_string(&exclude) {
+      global.push(PatternList::parse(".git/info/exclude", "", &data));
+    }
+    if let Some(path) = excludes_file(repo) {
+      if let Ok(data) = fs::read_to_string(&path) {
+        global.push(PatternList::parse(&path.to_string_lossy(), "", &data));
+      }
+    }
+    Self {
+      work_tree: repo.work_tree.clone(),
+      global,
+      directories: HashMap::new(),
+    }
+  }
+
+  /// Returns true if the path (relative to the root of the working tree) is
+  /// ignored.
+  pub fn is_ignored(&mut self, path: &str, is_dir: bool) -> bool {
+    self
+      .find(path, is_dir)
+      .is_some_and(|found| found.is_ignored())
+  }
+
+  /// Finds the pattern that decides whether a path (relative to the root of
+  /// the working tree) is ignored, if there is one.
+  ///
+  /// If a directory above the path is ignored, the pattern that ignores the
+  /// directory is returned.
+  pub fn find(&mut self, path: &str, is_dir: bool) -> Option<Match<'_>> {
+    let path = path.trim_end_matches('/');
+    let mut dirs = vec![""];
+    dirs.extend(path.match_indices('/').map(|(i, _)| &path[..i]));
+    for dir in &dirs {
+      self.load(dir);
+    }
+
+    // an ignored directory hides everything inside it
+    for (depth, dir) in dirs.iter().enumerate().skip(1) {
+      if let Some((list, i)) = self.lookup(&dirs[..depth], dir, true) {
+        if !self.list(&list).patterns[i].negated {
+          return Some(self.resolve(&list, i));
+        }
+      }
+    }
+    self
+      .lookup(&dirs, path, is_dir)
+      .map(|(list, i)| self.resolve(&list, i))
+  }
+
+  /// Finds the last pattern matching a path in the most important list that
+  /// has one, given the directories whose `.gitignore` applies to it.
+  fn lookup(&self, dirs: &[&str], path: &str, is_dir: bool) -> Option<(ListId, usize)> {
+    let directories = dirs.iter().rev().filter_map(|dir| {
+      self
+        .directories
+        .get(*dir)
+        .map(|list| (ListId::Directory(dir.to_string()), list))
+    });
+    let global = self
+      .global
+      .iter()
+      .enumerate()
+      .map(|(i, list)| (ListId::Global(i), list));
+    directories.chain(global).find_map(|(id, list)| {
+      let found = list.patterns.iter().rposition(|pattern| {
+        path
+          .strip_prefix(list.base.as_str())
+          .is_some_and(|relative| pattern.matches(relative, is_dir))
+      });
+      found.map(|i| (id, i))
+    })
+  }
+
+  fn list(&self, id: &ListId) -> &PatternList {
+    match id {
+      ListId::Directory(dir) => &self.directories[dir],
+      ListId::Global(i) => &self.global[*i],
+    }
+  }
+
+  fn resolve(&self, id: &ListId, i: usize) -> Match<'_> {
+    let list = self.list(id);
+    Match {
+      source: &list.source,
+      pattern: &list.patterns[i],
+    }
+  }
+
+  /// Reads the `.gitignore` of a directory, unless it was already read.
+  fn load(&mut self, dir: &str) {
+    if self.directories.contains_key(dir) {
+      return;
+    }
+    let base = match dir {
+      "" => String::new(),
+      _ => format!("{}/", dir),
+    };
+    let source = format!("{}.gitignore", base);
+    let data = fs::read_to_string(self.work_tree.join(&source)).unwrap_or_default();
+    let list = PatternList::parse(&source, &base, &data);
+    self.directories.insert(dir.to_string(), list);
+  }
+}
+
+/// Identifies a pattern list of an `Ignore` without borrowing it.
+enum ListId {
+  Directory(String),
+  Global(usize),
+}
+
+impl PatternList {
+  /// Parses the lines of an ignore file. Blank lines and `#` comments are
+  /// skipped.
+  fn parse(source: &str, base: &str, data: &str) -> Self {
+    let patterns = data
+      .lines()
+      .enumerate()
+      .filter_map(|(i, line)| Pattern::parse(line, i + 1))
+      .collect();
+    Self {
+      source: source.to_string(),
+      base: base.to_string(),
+      patterns,
+    }
+  }
+}
+
+impl Pattern {
+  /// Parses a line of an ignore file, or returns `None` if it holds no
+  /// pattern.
+  ///
+  /// Trailing spaces are dropped unless they are escaped with a backslash, and
+  /// a leading `\#` or `\!` stands for a literal `#` or `!`.
+  fn parse(line: &str, number: usize) -> Option<Self> {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let mut text = line.trim_end_matches(' ');
+    if text.ends_with('\\') && text.len() < line.len() {
+      text = &line[..text.len() + 1];
+    }
+    if text.is_empty() || text.starts_with('#') {
+      return None;
+    }
+
+    let (negated, mut glob) = match text.strip_prefix('!') {
+      Some(rest) => (true, rest),
+      None => (false, text),
+    };
+    let directory_only = glob.ends_with('/');
+    glob = glob.trim_end_matches('/');
+    let anchored = glob.contains('/');
+    glob = glob.strip_prefix('/').unwrap_or(glob);
+    if glob.is_empty() {
+      return None;
+    }
+    let glob = match glob.as_bytes()[0] {
+      b'\\' if matches!(glob.as_bytes().get(1), Some(b'#' | b'!')) => &glob[1..],
+      _ => glob,
+    };
+    Some(Self {
+      text: text.to_string(),
+      line: number,
+      negated,
+      glob: glob.to_string(),
+      directory_only,
+      anchored,
+    })
+  }
+
+  /// Matches the pattern against a path relative to the directory of the
+  /// ignore file.
+  fn matches(&self, path: &str, is_dir: bool) -> bool {
+    if self.directory_only && !is_dir {
+      return false;
+    }
+    let text = match self.anchored {
+      true => path,
+      false => path.rsplit('/').next().unwrap_or(path),
+    };
+    wildmatch(self.glob.as_bytes(), text.as_bytes(), true)
+  }
+}
+
+/// Returns the path of the user's global ignore file.
+///
+/// This is `core.excludesFile` if it is set (a leading `~/` stands for the
+/// home directory), or `$XDG_CONFIG_HOME/git/ignore` (`~/.config/git/ignore`).
+fn excludes_file(repo: &Repo) -> Option<PathBuf> {
+  let home = env::var_os("HOME").map(PathBuf::from);
+  let configured = repo
+    .config_value("core", None, "excludesFile")
+    .or_else(|| repo.config_value("core", None, "excludesfile"));
+  match configured {
+    Some(path) => match path.strip_prefix("~/") {
+      Some(rest) => home.map(|home| home.join(rest)),
+      None => Some(PathBuf::from(path)),
+    },
+    None => match env::var_os("XDG_CONFIG_HOME") {
+      Some(config_home) if !config_home.is_empty() => {
+        Some(PathBuf::from(config_home).join("git/ignore"))
+      }
+      _ => home.map(|home| home.join(".config/git/ignore")),
+    },
+  }
+}
@@ -0,0 +1,523 @@
+//! Three-way merges of trees, the way git's `ort` strategy merges them.
+//!
+//! Each path is merged on its own: a side that didn't change a path takes
+//! the other side's version, and contents both sides changed are merged line
+//! by line. Renames are detected on both sides first, so that changes made
+//! to a file on one side follow it to where the other side moved it.
+//! Whatever can't be merged is left as a conflict, with the base, our and
+//! their version of the path for the index (stages 1, 2 and 3).
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::checkout::{switch_trees, Files};
+use super::Index;
+use crate::diff::merge::{self, Style};
+use crate::diff::rename;
+use crate::diff::tree::{diff_files, read_blob, Status};
+use crate::diff::{is_binary, Algorithm};
+use crate::object::blob::Blob;
+use crate::object::commit::Commit;
+use crate::object::merge_base::merge_bases;
+use crate::object::mode::Mode;
+use crate::object::serializable::Unbox;
+use crate::object::{self, abbreviate, tree, DEFAULT_ABBREV};
+use crate::repo::Repo;
+
+/// A version of a path: its mode and the hash of its contents.
+type File = (Mode, String);
+
+/// The base, our and their version of a conflicted path (index stages 1, 2
+/// and 3).
+pub type Stages = [Option<File>; 3];
+
+/// How a tree merge is made.
+#[derive(Clone, Debug)]
+pub struct Options {
+  /// The names of our and their side (eg. `HEAD` and the branch that is
+  /// merged), used in messages and conflict markers.
+  pub ours: String,
+  pub theirs: String,
+
+  /// The name of the merge base, used in `diff3` conflict markers.
+  pub base: String,
+
+  /// How conflicts are written out (`merge.conflictStyle`).
+  pub style: Style,
+}
+
+impl Options {
+  /// The options for merging `theirs` into `ours`, with the conflict style
+  /// from the config.
+  pub fn new(repo: &Repo, ours: &str, theirs: &str) -> Result<Self, String> {
+    let style = repo
+      .config_value("merge", None, "conflictStyle")
+      .or_else(|| repo.config_value("merge", None, "conflictstyle"));
+    Ok(Self {
+      ours: ours.to_string(),
+      theirs: theirs.to_string(),
+      base: String::new(),
+      style: match style {
+        Some(style) => style.parse()?,
+        None => Style::Merge,
+      },
+    })
+  }
+}
+
+/// The result of a tree merge.
+#[derive(Debug, Default)]
+pub struct TreeMerge {
+  /// The merged files. A conflicted path has the version that is left in the
+  /// working tree: its merged contents with conflict markers, or the side
+  /// that kept it.
+  pub files: Files,
+
+  /// The conflicted paths.
+  pub conflicts: BTreeMap<String, Stages>,
+
+  /// What was done to the paths that needed more than taking one side (eg.
+  /// `Auto-merging README.md`), in path order.
+  pub messages: Vec<String>,
+}
+
+impl TreeMerge {
+  pub fn is_clean(&self) -> bool {
+    self.conflicts.is_empty()
+  }
+}
+
+/// Merges commit `theirs` into commit `ours`.
+///
+/// The merge is made against the best common ancestor of the two. When there
+/// are several (a criss-cross merge), they are merged together first, and
+/// the result is used as the base (which is what makes the merge
+/// "recursive").
+pub fn merge_commits(
+  repo: &Repo,
+  ours: &str,
+  theirs: &str,
+  options: &Options,
+) -> Result<TreeMerge, String> {
+  let mut bases = merge_bases(repo, ours, &[theirs.to_string()])?;
+  let mut options = options.clone();
+  let base = match bases.len() {
+    0 => {
+      options.base = "empty tree".to_string();
+      Files::new()
+    }
+    1 => {
+      options.base = abbreviate(repo, &bases[0], DEFAULT_ABBREV)?;
+      files(repo, &bases[0])?
+    }
+    _ => {
+      options.base = "merged common ancestors".to_string();
+      bases.reverse();
+      virtual_base(repo, &bases, options.style)?
+    }
+  };
+  merge_trees(
+    repo,
+    &base,
+    &files(repo, ours)?,
+    &files(repo, theirs)?,
+    &options,
+  )
+}
+
+/// Merges several merge bases into one set of files, oldest first. Conflicts
+/// are kept in the files as they would be left in the working tree.
+fn virtual_base(repo: &Repo, bases: &[String], style: Style) -> Result<Files, String> {
+  let options = Options {
+    ours: "Temporary merge branch 1".to_string(),
+    theirs: "Temporary merge branch 2".to_string(),
+    base: "merged common ancestors".to_string(),
+    style,
+  };
+  let mut merged = files(repo, &bases[0])?;
+  for (i, next) in bases.iter().enumerate().skip(1) {
+    let mut inner = merge_bases(repo, next, &bases[..i])?;
+    let base = match inner.len() {
+      0 => Files::new(),
+      1 => files(repo, &inner[0])?,
+      _ => {
+        inner.reverse();
+        virtual_base(repo, &inner, style)?
+      }
+    };
+    merged = merge_trees(repo, &base, &merged, &files(repo, next)?, &options)?.files;
+  }
+  Ok(merged)
+}
+
+/// Merges the changes from `base` to `theirs` into `ours`.
+pub fn merge_trees(
+  repo: &Repo,
+  base: &Files,
+  ours: &Files,
+  theirs: &Files,
+  options: &Options,
+) -> Result<TreeMerge, String> {
+  let mut merger = Merger {
+    repo,
+    options,
+    result: TreeMerge::default(),
+    messages: Vec::new(),
+  };
+
+  // the versions of each path to merge, after following renames
+  let mut triples: BTreeMap<String, [Option<Side>; 3]> = BTreeMap::new();
+  let our_renames = renames(repo, base, ours)?;
+  let their_renames = renames(repo, base, theirs)?;
+  let mut moved = [BTreeSet::new(), BTreeSet::new(), BTreeSet::new()];
+  for (old, new) in &our_renames {
+    let original = Side::new(old, &base[old]);
+    let renamed = Side::new(new, &ours[new]);
+    match their_renames.get(old) {
+      Some(other) if other == new => {
+        triples.insert(
+          new.clone(),
+          [
+            Some(original),
+            Some(renamed),
+            Some(Side::new(other, &theirs[other])),
+          ],
+        );
+      }
+      Some(other) => {
+        // renamed to different paths: each side's version is left at its
+        // own path
+        merger.message(
+          old,
+          format!(
+            "CONFLICT (rename/rename): {} renamed to {} in {} and to {} in {}.",
+            old, new, options.ours, other, options.theirs
+          ),
+        );
+        merger.conflict(old, [Some(original.file.clone()), None, None], None);
+        merger.conflict(
+          new,
+          [None, Some(renamed.file.clone()), None],
+          Some(&renamed.file),
+        );
+        let their_file = &theirs[other];
+        merger.conflict(
+          other,
+          [None, None, Some(their_file.clone())],
+          Some(their_file),
+        );
+        moved[2].insert(other.clone());
+      }
+      None => match theirs.get(old) {
+        Some(file) => {
+          let kept = Side::new(old, file);
+          triples.insert(new.clone(), [Some(original), Some(renamed), Some(kept)]);
+          moved[2].insert(old.clone());
+        }
+        None => {
+          merger.message(
+            old,
+            format!(
+              "CONFLICT (rename/delete): {} renamed to {} in {}, but deleted in {}.",
+              old, new, options.ours, options.theirs
+            ),
+          );
+          let stages = [Some(original.file), Some(renamed.file.clone()), None];
+          merger.conflict(new, stages, Some(&renamed.file));
+        }
+      },
+    }
+    moved[0].insert(old.clone());
+    moved[1].insert(new.clone());
+  }
+  for (old, new) in &their_renames {
+    if our_renames.contains_key(old) {
+      continue;
+    }
+    let original = Side::new(old, &base[old]);
+    let renamed = Side::new(new, &theirs[new]);
+    match ours.get(old) {
+      Some(file) => {
+        let kept = Side::new(old, file);
+        triples.insert(new.clone(), [Some(original), Some(kept), Some(renamed)]);
+        moved[1].insert(old.clone());
+      }
+      None => {
+        merger.message(
+          old,
+          format!(
+            "CONFLICT (rename/delete): {} renamed to {} in {}, but deleted in {}.",
+            old, new, options.theirs, options.ours
+          ),
+        );
+        let stages = [Some(original.file), None, Some(renamed.file.clone())];
+        merger.conflict(new, stages, Some(&renamed.file));
+      }
+    }
+    moved[0].insert(old.clone());
+    moved[2].insert(new.clone());
+  }
+
+  // every other path is merged where it is
+  let sides = [base, ours, theirs];
+  for (i, files) in sides.iter().enumerate() {
+    for (path, file) in files.iter() {
+      if moved[i].contains(path) || merger.result.conflicts.contains_key(path) {
+        continue;
+      }
+      let triple = triples.entry(path.clone()).or_default();
+      if triple[i].is_none() {
+        triple[i] = Some(Side::new(path, file));
+      }
+    }
+  }
+
+  // a file that is in the way of a directory of the other side is moved
+  // aside, to `<path>~<side>`; paths the merge deletes cleanly don't count,
+  // neither as files nor as directories
+  let deleted = |triple: &[Option<Side>; 3]| {
+    let stages = triple.clone().map(|side| side.map(|side| side.file));
+    matches!(trivial_merge(&stages), Some(None))
+  };
+  let mut directories = BTreeSet::new();
+  for (path, triple) in &triples {
+    if !deleted(triple) {
+      let mut parent = path.as_str();
+      while let Some((dir, _)) = parent.rsplit_once('/') {
+        directories.insert(dir.to_string());
+        parent = dir;
+      }
+    }
+  }
+  for (path, triple) in triples {
+    if !directories.contains(&path) || deleted(&triple) {
+      merger.merge_path(&path, triple)?;
+      continue;
+    }
+    let side = match triple[1].is_some() {
+      true => &options.ours,
+      false => &options.theirs,
+    };
+    let new_path = format!("{}~{}", path, side);
+    merger.message(
+      &path,
+      format!(
+        "CONFLICT (file/directory): directory in the way of {} from {}; moving it to {} instead.",
+        path, side, new_path
+      ),
+    );
+    let triple = triple.map(|side| side.map(|side| Side::new(&new_path, &side.file)));
+    let stages = triple.clone().map(|side| side.map(|side| side.file));
+    merger.merge_path(&new_path, triple)?;
+    if !merger.result.conflicts.contains_key(&new_path) {
+      let file = merger.result.files.get(&new_path).cloned();
+      merger.conflict(&new_path, stages, file.as_ref());
+    }
+  }
+
+  merger.messages.sort_by(|(a, _), (b, _)| a.cmp(b));
+  let mut result = merger.result;
+  result.messages = merger.messages.into_iter().map(|(_, msg)| msg).collect();
+  Ok(result)
+}
+
+/// The version of a path that wins without merging any contents: the one both
+/// sides agree on, or the one of the side that changed it (`Some(None)` if
+/// that change deletes it).
+fn trivial_merge(stages: &Stages) -> Option<&Option<File>> {
+  match stages {
+    [_, ours, theirs] if ours == theirs => Some(ours),
+    [base, ours, theirs] if base == ours => Some(theirs),
+    [base, ours, theirs] if base == theirs => Some(ours),
+    _ => None,
+  }
+}
+
+/// Updates the index and the working tree from `head` to the result of a
+/// merge, and records its conflicts in the index.
+///
+/// Like `switch_trees`, nothing is changed if that would throw away a local
+/// change.
+pub fn checkout_merge(
+  repo: &Repo,
+  index: &mut Index,
+  head: &Files,
+  merge: &TreeMerge,
+) -> Result<(), String> {
+  switch_trees(repo, index, head, &merge.files, false, "merge")?;
+  for (path, stages) in &merge.conflicts {
+    index.add_conflict(path, stages);
+  }
+  Ok(())
+}
+
+/// Reads the files of a commit.
+fn files(repo: &Repo, commit: &str) -> Result<Files, String> {
+  let object = object::read(repo.clone(), commit, Some("commit"))?;
+  tree::flatten(repo, object.unbox::<Commit>()?.tree())
+}
+
+/// Finds the files that were renamed from `base` to `side`, by their old path.
+fn renames(repo: &Repo, base: &Files, side: &Files) -> Result<BTreeMap<String, String>, String> {
+  let changes = rename::detect(
+    diff_files(base, side),
+    &rename::Options::default(),
+    |side| read_blob(repo, &side.hash),
+  )?;
+  Ok(
+    changes
+      .into_iter()
+      .filter(|change| matches!(change.status, Status::Renamed(_)))
+      .filter_map(|change| Some((change.old?.path, change.new?.path)))
+      .collect(),
+  )
+}
+
+/// A version of a path, along with the path it has on its side.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Side {
+  path: String,
+  file: File,
+}
+
+impl Side {
+  fn new(path: &str, file: &File) -> Self {
+    Self {
+      path: path.to_string(),
+      file: file.clone(),
+    }
+  }
+}
+
+/// The state of a tree merge that is being made.
+struct Merger<'a> {
+  repo: &'a Repo,
+  options: &'a Options,
+  result: TreeMerge,
+
+  /// The messages, with the path each is about (they are sorted by it).
+  messages: Vec<(String, String)>,
+}
+
+impl Merger<'_> {
+  fn message(&mut self, path: &str, msg: String) {
+    self.messages.push((path.to_string(), msg));
+  }
+
+  /// Records a conflict, and the file left in the working tree for it.
+  fn conflict(&mut self, path: &str, stages: Stages, file: Option<&File>) {
+    match file {
+      Some(file) => self.result.files.insert(path.to_string(), file.clone()),
+      None => self.result.files.remove(path),
+    };
+    self.result.conflicts.insert(path.to_string(), stages);
+  }
+
+  /// Merges the base, our and their version of a path (any of which may be
+  /// missing).
+  fn merge_path(
+    &mut self,
+    path: &str,
+    [base, ours, theirs]: [Option<Side>; 3],
+  ) -> Result<(), String> {
+    let file = |side: &Option<Side>| side.as_ref().map(|side| side.file.clone());
+    let stages = [file(&base), file(&ours), file(&theirs)];
+    if let Some(file) = trivial_merge(&stages) {
+      if let Some(file) = file {
+        self.result.files.insert(path.to_string(), file.clone());
+      }
+      return Ok(());
+    }
+
+    let (ours, theirs) = match (ours, theirs) {
+      (Some(ours), Some(theirs)) => (ours, theirs),
+      (ours, _) => {
+        // one side deleted what the other side modified
+        let (deleted, modified) = match ours.is_some() {
+          true => (&self.options.theirs, &self.options.ours),
+          false => (&self.options.ours, &self.options.theirs),
+        };
+        let msg = format!(
+          "CONFLICT (modify/delete): {} deleted in {} and modified in {}.  Version {} of {} left \
+           in tree.",
+          path, deleted, modified, modified, path
+        );
+        self.message(path, msg);
+        let kept = stages[1].clone().or(stages[2].clone());
+        self.conflict(path, stages, kept.as_ref());
+        return Ok(());
+      }
+    };
+    let (merged, clean) = self.merge_contents(path, base.as_ref(), &ours, &theirs)?;
+    if clean {
+      self.result.files.insert(path.to_string(), merged);
+    } else {
+      let kind = if base.is_some() { "content" } else { "add/add" };
+      self.message(
+        path,
+        format!("CONFLICT ({}): Merge conflict in {}", kind, path),
+      );
+      self.conflict(path, stages, Some(&merged));
+    }
+    Ok(())
+  }
+
+  /// Merges a file both sides changed. Returns the merged file, and whether
+  /// it merged cleanly; if it didn't, the file is the version to leave in the
+  /// working tree (eg. with conflict markers).
+  fn merge_contents(
+    &mut self,
+    path: &str,
+    base: Option<&Side>,
+    ours: &Side,
+    theirs: &Side,
+  ) -> Result<(File, bool), String> {
+    let mode = match base.map(|base| base.file.0) {
+      Some(mode) if mode == ours.file.0 => theirs.file.0,
+      _ => ours.file.0,
+    };
+    let regular = |mode: Mode| matches!(mode, Mode::Normal | Mode::Executable);
+    if !regular(ours.file.0) || !regular(theirs.file.0) {
+      return Ok((ours.file.clone(), false));
+    }
+    if ours.file.1 == theirs.file.1 {
+      return Ok(((mode, ours.file.1.clone()), true));
+    }
+
+    self.message(path, format!("Auto-merging {}", path));
+    let base_data = match base {
+      Some(base) => read_blob(self.repo, &base.file.1)?,
+      None => Vec::new(),
+    };
+    let our_data = read_blob(self.repo, &ours.file.1)?;
+    let their_data = read_blob(self.repo, &theirs.file.1)?;
+    if is_binary(&base_data) || is_binary(&our_data) || is_binary(&their_data) {
+      let msg = format!(
+        "warning: Cannot merge binary files: {} ({} vs. {})",
+        path, self.options.ours, self.options.theirs
+      );
+      self.message(path, msg);
+      return Ok((ours.file.clone(), false));
+    }
+
+    // the paths are only named when the file was renamed
+    let renamed = ours.path != theirs.path || base.is_some_and(|base| base.path != ours.path);
+    let label = |name: &str, side: &Side| match renamed {
+      true => format!("{}:{}", name, side.path),
+      false => name.to_string(),
+    };
+    let options = merge::Options {
+      algorithm: Algorithm::Myers,
+      style: self.options.style,
+      ours_label: Some(label(&self.options.ours, ours)),
+      base_label: Some(match base {
+        Some(base) => label(&self.options.base, base),
+        None => self.options.base.clone(),
+      }),
+      theirs_label: Some(label(&self.options.theirs, theirs)),
+      ..merge::Options::default()
+    };
+    let merged = merge::merge(&base_data, &our_data, &their_data, &options);
+    let hash = object::write(&Blob::new(self.repo.clone(), &merged.data), false)?;
+    Ok(((mode, hash), merged.conflicts == 0))
+  }
+}
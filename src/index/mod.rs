@@ -0,0 +1,660 @@
+use std::fs::{self, File, Metadata};
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
+
+use crate::crypto;
+use crate::object;
+use crate::object::mode::Mode;
+use crate::object::tree::{Tree, TreeEntry};
+use crate::repo::Repo;
+
+pub mod checkout;
+pub mod ignore;
+pub mod merge;
+pub mod pathspec;
+pub mod sequencer;
+pub mod status;
+pub mod worktree;
+
+/// The signature at the start of every index file ("dircache").
+const SIGNATURE: &[u8; 4] = b"DIRC";
+
+/// The size in bytes of a raw SHA-1 hash.
+const HASH_LEN: usize = 20;
+
+/// The size in bytes of the fixed-width part of an entry (stat data, mode,
+/// hash and flags), not counting the extended flags.
+const ENTRY_FIXED_LEN: usize = 62;
+
+/// Flag bits of an entry (the high bits of the 16-bit flags field).
+const FLAG_ASSUME_VALID: u16 = 0x8000;
+const FLAG_EXTENDED: u16 = 0x4000;
+const FLAG_STAGE_MASK: u16 = 0x3000;
+const FLAG_NAME_MASK: u16 = 0x0fff;
+
+/// Extended flag bits of an entry (index versions 3 and up).
+const EXT_FLAG_SKIP_WORKTREE: u16 = 0x4000;
+const EXT_FLAG_INTENT_TO_ADD: u16 = 0x2000;
+
+/// Extensions that cache information about the entries (the cached tree, the
+/// untracked cache, the fsmonitor bitmap and the entry offset tables). They go
+/// stale as soon as the entries change, so they are dropped instead; git
+/// rebuilds them when it needs them.
+const CACHE_EXTENSIONS: [&[u8; 4]; 5] = [b"TREE", b"UNTR", b"FSMN", b"EOIE", b"IEOT"];
+
+/// The staging area (`.git/index`).
+///
+/// The index is a binary file that lists every tracked path along with the hash
+/// of its staged contents and the stat data of the file in the working tree at
+/// the time it was staged (so that unchanged files don't have to be rehashed).
+///
+/// ```text
+/// | "DIRC" | version (2, 3 or 4) | number of entries |
+/// | entries, sorted by path and then by stage        |
+/// | extensions: 4-byte signature, u32 size, data     |
+/// | SHA-1 checksum of everything above               |
+/// ```
+///
+/// Extensions are optional caches and bookkeeping (eg. `TREE`, the cached tree
+/// hashes, or `REUC`, the resolve-undo data). We don't interpret any of them,
+/// but they are kept verbatim so that writing the index back out doesn't lose
+/// anything that stock git put there.
+pub struct Index {
+  pub version: u32,
+  pub entries: Vec<IndexEntry>,
+  pub extensions: Vec<Extension>,
+
+  /// When the index file was last modified (seconds and nanoseconds), if it
+  /// was read from disk. See `IndexEntry::stat_matches`.
+  pub mtime: Option<(u32, u32)>,
+}
+
+/// A single entry of the index.
+#[derive(Clone, Debug)]
+pub struct IndexEntry {
+  pub ctime_seconds: u32,
+  pub ctime_nanoseconds: u32,
+  pub mtime_seconds: u32,
+  pub mtime_nanoseconds: u32,
+  pub dev: u32,
+  pub ino: u32,
+  pub mode: Mode,
+  pub uid: u32,
+  pub gid: u32,
+  pub size: u32,
+  pub hash: String,
+  pub assume_valid: bool,
+  pub stage: u8,
+  pub skip_worktree: bool,
+  pub intent_to_add: bool,
+  pub path: String,
+}
+
+/// An index extension, kept as raw bytes.
+#[derive(Clone, Debug)]
+pub struct Extension {
+  pub signature: [u8; 4],
+  pub data: Vec<u8>,
+}
+
+impl Index {
+  /// Creates an empty index.
+  pub fn new() -> Self {
+    Self {
+      version: 2,
+      entries: Vec::new(),
+      extensions: Vec::new(),
+      mtime: None,
+    }
+  }
+
+  /// Reads the repository's index, or returns an empty one if there is none.
+  pub fn read(repo: &Repo) -> Result<Self, String> {
+    let path = repo.git_dir.join("index");
+    if !path.exists() {
+      return Ok(Self::new());
+    }
+    let data = match fs::read(&path) {
+      Ok(data) => data,
+      Err(msg) => return Err(format!("unable to read {} ({})", path.display(), msg)),
+    };
+    let mut index = Self::parse(&data)?;
+    index.mtime = fs::metadata(&path)
+      .ok()
+      .map(|metadata| (metadata.mtime() as u32, metadata.mtime_nsec() as u32));
+    Ok(index)
+  }
+
+  /// Writes the index to the repository.
+  ///
+  /// Like git, the new index is written to `index.lock` first and then renamed
+  /// over the old one, so a failed write never leaves a half-written index.
+  pub fn write(&self, repo: &Repo) -> Result<(), String> {
+    let path = repo.git_dir.join("index");
+    let lock = repo.git_dir.join("index.lock");
+    let mut file = match File::options().write(true).create_new(true).open(&lock) {
+      Ok(file) => file,
+      Err(msg) => return Err(format!("unable to create {} ({})", lock.display(), msg)),
+    };
+    let result = file
+      .write_all(&self.to_bytes()?)
+      .and_then(|_| file.sync_all());
+    if let Err(msg) = result.and_then(|_| fs::rename(&lock, &path)) {
+      let _ = fs::remove_file(&lock);
+      return Err(format!("unable to write {} ({})", path.display(), msg));
+    }
+    Ok(())
+  }
+
+  /// Returns the stage 0 entry for a path, if there is one.
+  pub fn entry(&self, path: &str) -> Option<&IndexEntry> {
+    self
+      .find(path, 0)
+      .ok()
+      .map(|position| &self.entries[position])
+  }
+
+  /// Returns the stage 0 entry for a path for modification.
+  ///
+  /// This is meant for refreshing stat data. Anything that changes what is
+  /// staged should go through `add` instead.
+  pub fn entry_mut(&mut self, path: &str) -> Option<&mut IndexEntry> {
+    match self.find(path, 0) {
+      Ok(position) => Some(&mut self.entries[position]),
+      Err(_) => None,
+    }
+  }
+
+  /// Adds an entry to the index.
+  ///
+  /// The entry replaces every entry for the same path (in any stage), as well
+  /// as any entries that can no longer exist next to it: adding `a/b` removes
+  /// a file `a`, and adding a file `a` removes everything under `a/`.
+  pub fn add(&mut self, entry: IndexEntry) {
+    let path = entry.path.as_str();
+    let directory = format!("{}/", path);
+    self.entries.retain(|other| {
+      other.path != path
+        && !other.path.starts_with(&directory)
+        && !path
+          .strip_prefix(&other.path)
+          .is_some_and(|rest| rest.starts_with('/'))
+    });
+    let position = match self.find(&entry.path, entry.stage) {
+      Ok(position) | Err(position) => position,
+    };
+    self.entries.insert(position, entry);
+    self.invalidate();
+  }
+
+  /// Records a conflict for a path: its entries are replaced with one for each
+  /// version of it that there is (the base, ours and theirs, in stages 1 to
+  /// 3). Unmerged entries have no stat data.
+  pub fn add_conflict(&mut self, path: &str, stages: &[Option<(Mode, String)>; 3]) {
+    self.entries.retain(|entry| entry.path != path);
+    for (stage, version) in (1..).zip(stages) {
+      let Some((mode, hash)) = version else {
+        continue;
+      };
+      let entry = IndexEntry {
+        ctime_seconds: 0,
+        ctime_nanoseconds: 0,
+        mtime_seconds: 0,
+        mtime_nanoseconds: 0,
+        dev: 0,
+        ino: 0,
+        mode: *mode,
+        uid: 0,
+        gid: 0,
+        size: 0,
+        hash: hash.clone(),
+        assume_valid: false,
+        stage,
+        skip_worktree: false,
+        intent_to_add: false,
+        path: path.to_string(),
+      };
+      let position = match self.find(path, stage) {
+        Ok(position) | Err(position) => position,
+      };
+      self.entries.insert(position, entry);
+    }
+    self.invalidate();
+  }
+
+  /// Removes every entry (in any stage) for a path. Returns true if anything
+  /// was removed.
+  pub fn remove(&mut self, path: &str) -> bool {
+    let count = self.entries.len();
+    self.entries.retain(|entry| entry.path != path);
+    if self.entries.len() == count {
+      return false;
+    }
+    self.invalidate();
+    true
+  }
+
+  /// Returns true if any path has entries in a conflict stage.
+  pub fn has_conflicts(&self) -> bool {
+    self.entries.iter().any(|entry| entry.stage != 0)
+  }
+
+  /// Writes the staged contents as tree objects and returns the hash of the
+  /// root tree (`git write-tree`).
+  ///
+  /// Every directory in the index becomes a tree of its own. Entries that are
+  /// only marked with `intent_to_add` have no contents yet and are left out.
+  pub fn write_tree(&self, repo: &Repo) -> Result<String, String> {
+    if self.has_conflicts() {
+      return Err("unable to write a tree from an index with conflicts".to_string());
+    }
+    let entries: Vec<&IndexEntry> = self
+      .entries
+      .iter()
+      .filter(|entry| !entry.intent_to_add)
+      .collect();
+    write_subtree(repo, &entries, 0)
+  }
+
+  /// Finds the position of an entry, or where it would be inserted.
+  ///
+  /// Entries are sorted by the bytes of their path and then by stage.
+  fn find(&self, path: &str, stage: u8) -> Result<usize, usize> {
+    self.entries.binary_search_by(|entry| {
+      entry
+        .path
+        .as_bytes()
+        .cmp(path.as_bytes())
+        .then(entry.stage.cmp(&stage))
+    })
+  }
+
+  /// Drops the extensions that depend on the entries.
+  fn invalidate(&mut self) {
+    self
+      .extensions
+      .retain(|extension| !CACHE_EXTENSIONS.contains(&&extension.signature));
+  }
+
+  /// Parses an index file.
+  pub fn parse(data: &[u8]) -> Result<Self, String> {
+    if data.len() < 12 + HASH_LEN || &data[..4] != SIGNATURE {
+      return Err("index file is corrupt (bad signature)".to_string());
+    }
+    let (body, checksum) = data.split_at(data.len() - HASH_LEN);
+    if crypto::sha_1(body) != hex::encode(checksum) {
+      return Err("index file is corrupt (bad checksum)".to_string());
+    }
+    let version = read_u32(body, 4)?;
+    if !(2..=4).contains(&version) {
+      return Err(format!("unsupported index version {}", version));
+    }
+    let count = read_u32(body, 8)? as usize;
+
+    let mut offset = 12;
+    let mut entries = Vec::with_capacity(count);
+    let mut previous_path = String::new();
+    for _ in 0..count {
+      let (entry, next) = IndexEntry::parse(body, offset, version, &previous_path)?;
+      previous_path = entry.path.clone();
+      entries.push(entry);
+      offset = next;
+    }
+
+    // Whatever is left before the checksum is a list of extensions.
+    let mut extensions = Vec::new();
+    while offset < body.len() {
+      if offset + 8 > body.len() {
+        return Err("index file is corrupt (truncated extension)".to_string());
+      }
+      let signature: [u8; 4] = body[offset..offset + 4].try_into().unwrap();
+      let size = read_u32(body, offset + 4)? as usize;
+      let start = offset + 8;
+      if start + size > body.len() {
+        return Err("index file is corrupt (truncated extension)".to_string());
+      }
+      extensions.push(Extension {
+        signature,
+        data: body[start..start + size].to_vec(),
+      });
+      offset = start + size;
+    }
+
+    Ok(Self {
+      version,
+      entries,
+      extensions,
+      mtime: None,
+    })
+  }
+
+  /// Serializes the index, including the trailing checksum.
+  ///
+  /// Version 2 cannot store extended flags, so an index that has any is
+  /// written as version 3 instead (which is what git does too).
+  pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+    let extended = self
+      .entries
+      .iter()
+      .any(|entry| entry.skip_worktree || entry.intent_to_add);
+    let version = match self.version {
+      2 if extended => 3,
+      version => version,
+    };
+
+    let mut data: Vec<u8> = Vec::new();
+    data.extend_from_slice(SIGNATURE);
+    data.extend_from_slice(&version.to_be_bytes());
+    data.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
+
+    let mut previous_path = "";
+    for entry in &self.entries {
+      entry.write(&mut data, version, previous_path)?;
+      previous_path = &entry.path;
+    }
+
+    for extension in &self.extensions {
+      data.extend_from_slice(&extension.signature);
+      data.extend_from_slice(&(extension.data.len() as u32).to_be_bytes());
+      data.extend_from_slice(&extension.data);
+    }
+
+    let checksum = hex::decode(crypto::sha_1(&data)).unwrap();
+    data.extend_from_slice(&checksum);
+    Ok(data)
+  }
+}
+
+impl Default for Index {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl IndexEntry {
+  /// Creates a stage 0 entry for a file with the given stat data.
+  pub fn new(path: &str, hash: &str, mode: Mode, metadata: &Metadata) -> Self {
+    Self {
+      ctime_seconds: metadata.ctime() as u32,
+      ctime_nanoseconds: metadata.ctime_nsec() as u32,
+      mtime_seconds: metadata.mtime() as u32,
+      mtime_nanoseconds: metadata.mtime_nsec() as u32,
+      dev: metadata.dev() as u32,
+      ino: metadata.ino() as u32,
+      mode,
+      uid: metadata.uid(),
+      gid: metadata.gid(),
+      size: metadata.size() as u32,
+      hash: hash.to_string(),
+      assume_valid: false,
+      stage: 0,
+      skip_worktree: false,
+      intent_to_add: false,
+      path: path.to_string(),
+    }
+  }
+
+  /// Returns true if the file's stat data still matches the entry, which means
+  /// the file is unchanged and doesn't need to be rehashed.
+  ///
+  /// A file that was modified in the same instant as (or after) the index was
+  /// written can't be trusted: it may have changed again right after it was
+  /// staged without its stat data changing ("racily clean"). Such entries
+  /// never match, so they always get rehashed.
+  pub fn stat_matches(&self, metadata: &Metadata, index_mtime: Option<(u32, u32)>) -> bool {
+    let mtime = (self.mtime_seconds, self.mtime_nanoseconds);
+    let racy = index_mtime.is_none_or(|index_mtime| mtime >= index_mtime);
+    let same_type = match self.mode {
+      Mode::Symbolic => metadata.file_type().is_symlink(),
+      Mode::Normal | Mode::Executable => metadata.is_file(),
+      _ => false,
+    };
+    !racy
+      && same_type
+      && mtime == (metadata.mtime() as u32, metadata.mtime_nsec() as u32)
+      && (self.ctime_seconds, self.ctime_nanoseconds)
+        == (metadata.ctime() as u32, metadata.ctime_nsec() as u32)
+      && self.ino == metadata.ino() as u32
+      && self.uid == metadata.uid()
+      && self.gid == metadata.gid()
+      && self.size == metadata.size() as u32
+  }
+
+  /// Takes the stat data of a fresh entry for the same (unchanged) file, so
+  /// that the file doesn't have to be rehashed next time.
+  pub fn refresh(&mut self, fresh: &IndexEntry) {
+    self.ctime_seconds = fresh.ctime_seconds;
+    self.ctime_nanoseconds = fresh.ctime_nanoseconds;
+    self.mtime_seconds = fresh.mtime_seconds;
+    self.mtime_nanoseconds = fresh.mtime_nanoseconds;
+    self.dev = fresh.dev;
+    self.ino = fresh.ino;
+    self.uid = fresh.uid;
+    self.gid = fresh.gid;
+    self.size = fresh.size;
+  }
+
+  /// Parses the entry starting at `offset` and returns it along with the offset
+  /// of the next entry.
+  ///
+  /// In versions 2 and 3 the path is stored in full and the entry is padded
+  /// with 1 to 8 NUL bytes so its length is a multiple of 8. In version 4 the
+  /// path is prefix-compressed against the previous entry's path (a varint of
+  /// bytes to strip from the end of it, followed by the NUL-terminated suffix
+  /// to append) and there is no padding.
+  fn parse(
+    data: &[u8],
+    offset: usize,
+    version: u32,
+    previous_path: &str,
+  ) -> Result<(Self, usize), String> {
+    if offset + ENTRY_FIXED_LEN > data.len() {
+      return Err("index file is corrupt (truncated entry)".to_string());
+    }
+    let field = |i: usize| read_u32(data, offset + i * 4);
+    let raw_mode = field(6)?;
+    let mode = match Mode::from_octal(raw_mode) {
+      Ok(mode) => mode,
+      Err(_) => return Err(format!("invalid mode {:o} in index", raw_mode)),
+    };
+    let hash = hex::encode(&data[offset + 40..offset + 40 + HASH_LEN]);
+    let flags = u16::from_be_bytes(data[offset + 60..offset + 62].try_into().unwrap());
+
+    let mut pos = offset + ENTRY_FIXED_LEN;
+    let mut extended_flags = 0;
+    if flags & FLAG_EXTENDED != 0 {
+      if version < 3 {
+        return Err("index file is corrupt (extended flags in version 2)".to_string());
+      }
+      extended_flags = u16::from_be_bytes(data[pos..pos + 2].try_into().unwrap());
+      pos += 2;
+    }
+
+    let path = if version == 4 {
+      let strip = read_varint(data, &mut pos)?;
+      if strip > previous_path.len() {
+        return Err("index file is corrupt (bad path prefix)".to_string());
+      }
+      let end = find_nul(data, pos)?;
+      let mut path = previous_path.as_bytes()[..previous_path.len() - strip].to_vec();
+      path.extend_from_slice(&data[pos..end]);
+      pos = end + 1;
+      path
+    } else {
+      let end = find_nul(data, pos)?;
+      let path = data[pos..end].to_vec();
+      pos = offset + padded_len(end - offset);
+      path
+    };
+    let path = match String::from_utf8(path) {
+      Ok(path) => path,
+      Err(_) => return Err("index file contains a non utf-8 path".to_string()),
+    };
+
+    let entry = Self {
+      ctime_seconds: field(0)?,
+      ctime_nanoseconds: field(1)?,
+      mtime_seconds: field(2)?,
+      mtime_nanoseconds: field(3)?,
+      dev: field(4)?,
+      ino: field(5)?,
+      mode,
+      uid: field(7)?,
+      gid: field(8)?,
+      size: field(9)?,
+      hash,
+      assume_valid: flags & FLAG_ASSUME_VALID != 0,
+      stage: ((flags & FLAG_STAGE_MASK) >> 12) as u8,
+      skip_worktree: extended_flags & EXT_FLAG_SKIP_WORKTREE != 0,
+      intent_to_add: extended_flags & EXT_FLAG_INTENT_TO_ADD != 0,
+      path,
+    };
+    Ok((entry, pos))
+  }
+
+  /// Appends the serialized entry to `data`.
+  fn write(&self, data: &mut Vec<u8>, version: u32, previous_path: &str) -> Result<(), String> {
+    let start = data.len();
+    for field in [
+      self.ctime_seconds,
+      self.ctime_nanoseconds,
+      self.mtime_seconds,
+      self.mtime_nanoseconds,
+      self.dev,
+      self.ino,
+      self.mode.to_octal(),
+      self.uid,
+      self.gid,
+      self.size,
+    ] {
+      data.extend_from_slice(&field.to_be_bytes());
+    }
+    match hex::decode(&self.hash) {
+      Ok(hash) if hash.len() == HASH_LEN => data.extend_from_slice(&hash),
+      _ => return Err(format!("invalid hash {} for {}", self.hash, self.path)),
+    }
+
+    let extended = self.skip_worktree || self.intent_to_add;
+    let mut flags = (self.path.len().min(FLAG_NAME_MASK as usize)) as u16;
+    flags |= ((self.stage as u16) << 12) & FLAG_STAGE_MASK;
+    if self.assume_valid {
+      flags |= FLAG_ASSUME_VALID;
+    }
+    if extended {
+      flags |= FLAG_EXTENDED;
+    }
+    data.extend_from_slice(&flags.to_be_bytes());
+    if extended {
+      let mut extended_flags = 0;
+      if self.skip_worktree {
+        extended_flags |= EXT_FLAG_SKIP_WORKTREE;
+      }
+      if self.intent_to_add {
+        extended_flags |= EXT_FLAG_INTENT_TO_ADD;
+      }
+      data.extend_from_slice(&extended_flags.to_be_bytes());
+    }
+
+    if version == 4 {
+      let common = common_prefix_len(previous_path, &self.path);
+      write_varint(data, previous_path.len() - common);
+      data.extend_from_slice(&self.path.as_bytes()[common..]);
+      data.push(0);
+    } else {
+      data.extend_from_slice(self.path.as_bytes());
+      let len = data.len() - start;
+      data.resize(start + padded_len(len), 0);
+    }
+    Ok(())
+  }
+}
+
+/// Writes the tree for a directory whose entries all share a prefix of
+/// `prefix_len` bytes (the directory's path and a `/`).
+///
+/// Since the index is sorted by path, the entries of each subdirectory are
+/// next to each other.
+fn write_subtree(
+  repo: &Repo,
+  entries: &[&IndexEntry],
+  prefix_len: usize,
+) -> Result<String, String> {
+  let mut tree_entries = Vec::new();
+  let mut i = 0;
+  while i < entries.len() {
+    let name = &entries[i].path[prefix_len..];
+    match name.find('/') {
+      None => {
+        tree_entries.push(TreeEntry::new(entries[i].mode, name, &entries[i].hash));
+        i += 1;
+      }
+      Some(slash) => {
+        let directory = &name[..=slash];
+        let count = entries[i..]
+          .iter()
+          .take_while(|entry| entry.path[prefix_len..].starts_with(directory))
+          .count();
+        let hash = write_subtree(repo, &entries[i..i + count], prefix_len + slash + 1)?;
+        tree_entries.push(TreeEntry::new(Mode::Directory, &name[..slash], &hash));
+        i += count;
+      }
+    }
+  }
+  object::write(&Tree::from_entries(repo.clone(), tree_entries), false)
+}
+
+/// Returns the length of an entry once it is padded with 1 to 8 NUL bytes.
+fn padded_len(len: usize) -> usize {
+  (len + 8) & !7
+}
+
+/// Returns the length (in bytes) of the common prefix of two paths.
+fn common_prefix_len(a: &str, b: &str) -> usize {
+  a.bytes().zip(b.bytes()).take_while(|(x, y)| x == y).count()
+}
+
+/// Returns the index of the next NUL byte at or after `offset`.
+fn find_nul(data: &[u8], offset: usize) -> Result<usize, String> {
+  match data[offset..].iter().position(|&byte| byte == 0) {
+    Some(i) => Ok(offset + i),
+    None => Err("index file is corrupt (unterminated path)".to_string()),
+  }
+}
+
+/// Reads a big-endian `u32` starting at the given offset.
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, String> {
+  match data.get(offset..offset + 4) {
+    Some(bytes) => Ok(u32::from_be_bytes(bytes.try_into().unwrap())),
+    None => Err("index file is corrupt (truncated)".to_string()),
+  }
+}
+
+/// Reads a variable-length integer in the same "offset" encoding used by
+/// `OFS_DELTA` pack entries (each continuation byte adds one before shifting).
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<usize, String> {
+  let mut next = || match data.get(*pos) {
+    Some(byte) => {
+      *pos += 1;
+      Ok(*byte)
+    }
+    None => Err("index file is corrupt (truncated varint)".to_string()),
+  };
+  let mut byte = next()?;
+  let mut value = (byte & 0x7f) as usize;
+  while byte & 0x80 != 0 {
+    byte = next()?;
+    value = ((value + 1) << 7) | (byte & 0x7f) as usize;
+  }
+  Ok(value)
+}
+
+/// Writes a variable-length integer in the encoding read by `read_varint`.
+fn write_varint(data: &mut Vec<u8>, mut value: usize) {
+  let mut bytes = vec![(value & 0x7f) as u8];
+  while value >= 0x80 {
+    value = (value >> 7) - 1;
+    bytes.push(0x80 | (value & 0x7f) as u8);
+  }
+  bytes.reverse();
+  data.extend_from_slice(&bytes);
+}
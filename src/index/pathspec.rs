@@ -0,0 +1,333 @@
+use std::env;
+
+use crate::repo::Repo;
+
+/// A list of paths and patterns that limits a command to part of the tree.
+///
+/// Pathspecs are given relative to the current directory and are stored
+/// relative to the root of the working tree. A path matches a pathspec item if
+/// it is the item itself, if it lives in a directory named by the item, or if
+/// it matches the item as a glob. Like in git, a `*` in a pathspec also matches
+/// slashes, so `src/*.rs` matches `src/cli/add.rs`.
+///
+/// An empty pathspec matches every path.
+#[derive(Clone)]
+pub struct Pathspec {
+  items: Vec<Item>,
+}
+
+/// A single pathspec item.
+#[derive(Clone)]
+struct Item {
+  /// The item as the user spelled it (used in error messages).
+  original: String,
+
+  /// The item relative to the root of the working tree.
+  pattern: String,
+
+  /// Whether the item contains any glob characters.
+  glob: bool,
+}
+
+impl Pathspec {
+  /// Parses pathspecs given relative to the current directory.
+  pub fn new(repo: &Repo, args: &[String]) -> Result<Self, String> {
+    let prefix = prefix(repo)?;
+    let mut items = Vec::new();
+    for arg in args {
+      let pattern = resolve(repo, &prefix, arg)?;
+      items.push(Item {
+        original: arg.to_string(),
+        glob: pattern.contains(['*', '?', '[', '\\']),
+        pattern,
+      });
+    }
+    Ok(Self { items })
+  }
+
+  /// Returns true if no pathspec was given.
+  pub fn is_empty(&self) -> bool {
+    self.items.is_empty()
+  }
+
+  /// Returns true if the path (relative to the root of the working tree)
+  /// matches any of the items.
+  pub fn matches(&self, path: &str) -> bool {
+    self.is_empty() || self.items.iter().any(|item| item.matches(path))
+  }
+
+  /// Returns the items (as the user spelled them) that match none of the
+  /// given paths.
+  pub fn unmatched<'a>(&self, paths: impl IntoIterator<Item = &'a str>) -> Vec<&str> {
+    let mut matched = vec![false; self.items.len()];
+    for path in paths {
+      for (i, item) in self.items.iter().enumerate() {
+        if !matched[i] && item.matches(path) {
+          matched[i] = true;
+        }
+      }
+    }
+    self
+      .items
+      .iter()
+      .zip(matched)
+      .filter(|(_, matched)| !matched)
+      .map(|(item, _)| item.original.as_str())
+      .collect()
+  }
+}
+
+impl Item {
+  fn matches(&self, path: &str) -> bool {
+    if self.pattern.is_empty() || self.pattern == path {
+      return true;
+    }
+    if let Some(rest) = path.strip_prefix(&self.pattern) {
+      if rest.starts_with('/') {
+        return true;
+      }
+    }
+    self.glob && wildmatch(self.pattern.as_bytes(), path.as_bytes(), false)
+  }
+}
+
+/// Returns the directories leading from the root of the working tree to the
+/// current directory.
+pub fn prefix(repo: &Repo) -> Result<Vec<String>, String> {
+  let cwd = match env::current_dir().and_then(|cwd| cwd.canonicalize()) {
+    Ok(cwd) => cwd,
+    Err(msg) => return Err(format!("unable to read current directory ({})", msg)),
+  };
+  Ok(match cwd.strip_prefix(&repo.work_tree) {
+    Ok(prefix) => prefix
+      .components()
+      .map(|part| part.as_os_str().to_string_lossy().into_owned())
+      .collect(),
+    Err(_) => Vec::new(),
+  })
+}
+
+/// Turns a path given relative to the directory named by `prefix` (or an
+/// absolute path) into one relative to the root of the working tree.
+///
+/// Errors if the path is outside the working tree.
+pub fn resolve(repo: &Repo, prefix: &[String], arg: &str) -> Result<String, String> {
+  let outside = || {
+    format!(
+      "'{}' is outside repository at '{}'",
+      arg,
+      repo.work_tree.display()
+    )
+  };
+  let work_tree = repo.work_tree.to_string_lossy();
+  let (mut parts, relative) = if arg.starts_with('/') {
+    match arg.strip_prefix(&*work_tree) {
+      Some(rest) if rest.is_empty() || rest.starts_with('/') => (Vec::new(), rest),
+      _ => return Err(outside()),
+    }
+  } else {
+    (prefix.to_vec(), arg)
+  };
+  for part in relative.split('/') {
+    match part {
+      "" | "." => (),
+      ".." => {
+        if parts.pop().is_none() {
+          return Err(outside());
+        }
+      }
+      _ => parts.push(part.to_string()),
+    }
+  }
+  Ok(parts.join("/"))
+}
+
+/// Turns a path relative to the root of the working tree into one relative to
+/// the directory given by `prefix` (see `prefix`).
+///
+/// A trailing `/` is kept, and the directory itself is spelled `./`.
+pub fn relative(path: &str, prefix: &[String]) -> String {
+  let (path, slash) = match path.strip_suffix('/') {
+    Some(path) => (path, "/"),
+    None => (path, ""),
+  };
+  let parts: Vec<&str> = path.split('/').collect();
+  let common = parts
+    .iter()
+    .zip(prefix)
+    .take_while(|(part, dir)| *part == dir)
+    .count();
+  let mut result = "../".repeat(prefix.len() - common);
+  result.push_str(&parts[common..].join("/"));
+  match result.is_empty() {
+    true => "./".to_string(),
+    false if result.ends_with('/') => result,
+    false => result + slash,
+  }
+}
+
+/// Quotes a path for display the way git does when `core.quotePath` is on.
+///
+/// Paths with double quotes, backslashes, control characters or non-ASCII
+/// bytes are wrapped in double quotes, with the offending bytes escaped in C
+/// style (non-ASCII bytes become octal escapes). When `quote_spaces` is set,
+/// paths with spaces are wrapped in quotes too, as in `git status --short`.
+pub fn quote(path: &str, quote_spaces: bool) -> String {
+  let needs_quotes = path
+    .bytes()
+    .any(|byte| !(0x20..0x7f).contains(&byte) || byte == b'"' || byte == b'\\')
+    || (quote_spaces && path.contains(' '));
+  if !needs_quotes {
+    return path.to_string();
+  }
+  let mut quoted = String::from("\"");
+  for byte in path.bytes() {
+    match byte {
+      b'"' => quoted.push_str("\\\""),
+      b'\\' => quoted.push_str("\\\\"),
+      b'\t' => quoted.push_str("\\t"),
+      b'\n' => quoted.push_str("\\n"),
+      0x07 => quoted.push_str("\\a"),
+      0x08 => quoted.push_str("\\b"),
+      0x0b => quoted.push_str("\\v"),
+      0x0c => quoted.push_str("\\f"),
+      b'\r' => quoted.push_str("\\r"),
+      byte if !(0x20..0x7f).contains(&byte) => quoted.push_str(&format!("\\{:03o}", byte)),
+      byte => quoted.push(byte as char),
+    }
+  }
+  quoted.push('"');
+  quoted
+}
+
+/// Matches a path against a shell glob.
+///
+/// Supports `?`, `*`, bracket expressions (`[a-z]`, `[!0-9]`, `[[:alpha:]]`)
+/// and backslash escapes. When `pathname` is set, wildcards never match a `/`
+/// except for `**`, which matches any number of whole directories when it
+/// makes up an entire path component (`**/foo`, `foo/**/bar` or `foo/**`).
+pub fn wildmatch(pattern: &[u8], text: &[u8], pathname: bool) -> bool {
+  let (mut p, mut t) = (0, 0);
+  while p < pattern.len() {
+    match pattern[p] {
+      b'?' => {
+        if t >= text.len() || (pathname && text[t] == b'/') {
+          return false;
+        }
+        p += 1;
+        t += 1;
+      }
+      b'*' => {
+        let start = p;
+        while p < pattern.len() && pattern[p] == b'*' {
+          p += 1;
+        }
+        let whole_component =
+          (start == 0 || pattern[start - 1] == b'/') && (p == pattern.len() || pattern[p] == b'/');
+        let anything = !pathname || (p - start >= 2 && whole_component);
+
+        // `foo/**/bar` also matches `foo/bar`
+        if pathname
+          && anything
+          && p < pattern.len()
+          && wildmatch(&pattern[p + 1..], &text[t..], true)
+        {
+          return true;
+        }
+        if p == pattern.len() {
+          return anything || !text[t..].contains(&b'/');
+        }
+        for i in t..=text.len() {
+          if wildmatch(&pattern[p..], &text[i..], pathname) {
+            return true;
+          }
+          if !anything && i < text.len() && text[i] == b'/' {
+            return false;
+          }
+        }
+        return false;
+      }
+      b'[' => {
+        let ch = match text.get(t) {
+          Some(&ch) if !(pathname && ch == b'/') => ch,
+          _ => return false,
+        };
+        match match_bracket(pattern, p, ch) {
+          Some((true, next)) => {
+            p = next;
+            t += 1;
+          }
+          _ => return false,
+        }
+      }
+      ch => {
+        let (literal, len) = match ch {
+          b'\\' if p + 1 < pattern.len() => (pattern[p + 1], 2),
+          _ => (ch, 1),
+        };
+        if text.get(t) != Some(&literal) {
+          return false;
+        }
+        p += len;
+        t += 1;
+      }
+    }
+  }
+  t == text.len()
+}
+
+/// Matches a character against the bracket expression starting at `start`.
+///
+/// Returns whether the character matched along with the position just past
+/// the closing `]`, or `None` if the expression is never closed.
+fn match_bracket(pattern: &[u8], start: usize, ch: u8) -> Option<(bool, usize)> {
+  let mut p = start + 1;
+  let negated = matches!(pattern.get(p), Some(b'!') | Some(b'^'));
+  if negated {
+    p += 1;
+  }
+  let mut matched = false;
+  let mut first = true;
+  loop {
+    let mut c = *pattern.get(p)?;
+    if c == b']' && !first {
+      return Some((matched != negated, p + 1));
+    }
+    first = false;
+    if c == b'[' && pattern.get(p + 1) == Some(&b':') {
+      if let Some(len) = pattern[p + 2..].windows(2).position(|w| w == b":]") {
+        let class = &pattern[p + 2..p + 2 + len];
+        matched |= match class {
+          b"alnum" => ch.is_ascii_alphanumeric(),
+          b"alpha" => ch.is_ascii_alphabetic(),
+          b"digit" => ch.is_ascii_digit(),
+          b"lower" => ch.is_ascii_lowercase(),
+          b"upper" => ch.is_ascii_uppercase(),
+          b"space" => ch.is_ascii_whitespace(),
+          b"punct" => ch.is_ascii_punctuation(),
+          b"xdigit" => ch.is_ascii_hexdigit(),
+          _ => return None,
+        };
+        p += len + 4;
+        continue;
+      }
+    }
+    if c == b'\\' {
+      p += 1;
+      c = *pattern.get(p)?;
+    }
+    if pattern.get(p + 1) == Some(&b'-') && pattern.get(p + 2).is_some_and(|&end| end != b']') {
+      let mut end = pattern[p + 2];
+      let mut len = 3;
+      if end == b'\\' {
+        end = *pattern.get(p + 3)?;
+        len = 4;
+      }
+      matched |= (c..=end).contains(&ch);
+      p += len;
+    } else {
+      matched |= c == ch;
+      p += 1;
+    }
+  }
+}
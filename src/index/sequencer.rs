@@ -0,0 +1,870 @@
+//! Applies commits one after the other onto HEAD, the way `git cherry-pick`,
+//! `git revert` and `git rebase` do, and keeps track of a sequence that
+//! stopped for conflicts (in `.git/sequencer`) so that it can be carried on.
+
+use std::fs;
+use std::path::Path;
+
+use super::checkout::{files, switch_trees, Files};
+use super::merge::{checkout_merge, merge_trees, Options};
+use super::Index;
+use crate::object::commit::{Commit, CommitBuilder};
+use crate::object::identity::Identity;
+use crate::object::pretty::title;
+use crate::object::revision::{self, Revision};
+use crate::object::serializable::Unbox;
+use crate::object::walk::{Options as WalkOptions, Walk};
+use crate::object::{self, abbreviate, refs, tree, DEFAULT_ABBREV};
+use crate::repo::editor::cleanup;
+use crate::repo::{state, Repo};
+
+/// What to do with a commit, in a todo list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+  /// Apply the commit.
+  Pick,
+
+  /// Apply the reverse of the commit.
+  Revert,
+
+  /// Apply the commit, and edit its message.
+  Reword,
+
+  /// Apply the commit, and stop to amend it.
+  Edit,
+
+  /// Meld the commit into the previous one, along with its message.
+  Squash,
+
+  /// Meld the commit into the previous one, dropping its message.
+  Fixup,
+
+  /// Leave the commit out.
+  Drop,
+
+  /// Run a shell command.
+  Exec,
+}
+
+impl Action {
+  /// The name of the action, and its one-letter abbreviation.
+  fn names(&self) -> (&'static str, &'static str) {
+    match self {
+      Action::Pick => ("pick", "p"),
+      Action::Revert => ("revert", ""),
+      Action::Reword => ("reword", "r"),
+      Action::Edit => ("edit", "e"),
+      Action::Squash => ("squash", "s"),
+      Action::Fixup => ("fixup", "f"),
+      Action::Drop => ("drop", "d"),
+      Action::Exec => ("exec", "x"),
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    self.names().0
+  }
+
+  fn parse(word: &str) -> Option<Self> {
+    let actions = [
+      Action::Pick,
+      Action::Revert,
+      Action::Reword,
+      Action::Edit,
+      Action::Squash,
+      Action::Fixup,
+      Action::Drop,
+      Action::Exec,
+    ];
+    actions.into_iter().find(|action| {
+      let (name, abbreviation) = action.names();
+      word == name || (!abbreviation.is_empty() && word == abbreviation)
+    })
+  }
+}
+
+/// A line of a todo list, eg. `pick 6ebf049 update readme`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instruction {
+  pub action: Action,
+
+  /// The full hash of the commit, or the command of an `exec`.
+  pub argument: String,
+
+  /// The subject of the commit (only there for the reader).
+  pub subject: String,
+}
+
+impl Instruction {
+  /// An instruction to do something with a commit.
+  pub fn new(repo: &Repo, action: Action, hash: &str) -> Result<Self, String> {
+    let object = object::read(repo.clone(), hash, Some("commit"))?;
+    Ok(Self {
+      action,
+      argument: hash.to_string(),
+      subject: title(object.unbox::<Commit>()?.message()),
+    })
+  }
+
+  /// The line for the instruction, with the hash abbreviated (as the user
+  /// gets to see it) or not (as it is saved).
+  pub fn line(&self, repo: &Repo, abbreviated: bool) -> Result<String, String> {
+    let argument = match (self.action, abbreviated) {
+      (Action::Exec, _) | (_, false) => self.argument.clone(),
+      (_, true) => abbreviate(repo, &self.argument, DEFAULT_ABBREV)?,
+    };
+    Ok(match self.subject.is_empty() {
+      true => format!("{} {}", self.action.name(), argument),
+      false => format!("{} {} {}", self.action.name(), argument, self.subject),
+    })
+  }
+}
+
+/// Parses a todo list. Blank lines and `#` comments are skipped, and the
+/// commits are resolved to full hashes. Returns the errors for the lines that
+/// can't be parsed, if there are any.
+pub fn parse_todo(repo: &Repo, text: &str) -> Result<Vec<Instruction>, Vec<String>> {
+  let mut todo = Vec::new();
+  let mut errors = Vec::new();
+  for (number, line) in text.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    let action = match Action::parse(word) {
+      Some(action) => action,
+      None => {
+        errors.push(format!("invalid line {}: {}", number + 1, line));
+        continue;
+      }
+    };
+    if rest.is_empty() {
+      errors.push(format!("missing arguments for {}", action.name()));
+      errors.push(format!("invalid line {}: {}", number + 1, line));
+      continue;
+    }
+    if action == Action::Exec {
+      todo.push(Instruction {
+        action,
+        argument: rest.to_string(),
+        subject: String::new(),
+      });
+      continue;
+    }
+    let (name, subject) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    match revision::resolve_commit(repo, name) {
+      Ok(hash) => todo.push(Instruction {
+        action,
+        argument: hash,
+        subject: subject.trim().to_string(),
+      }),
+      Err(_) => errors.push(format!("invalid line {}: {}", number + 1, line)),
+    }
+  }
+  match errors.is_empty() {
+    true => Ok(todo),
+    false => Err(errors),
+  }
+}
+
+/// Reads a saved todo list (with full hashes).
+pub fn read_todo(repo: &Repo, path: &Path) -> Result<Vec<Instruction>, String> {
+  let text = fs::read_to_string(path).unwrap_or_default();
+  parse_todo(repo, &text).map_err(|errors| errors.join("\n"))
+}
+
+/// Saves a todo list, with the hashes abbreviated or not.
+pub fn write_todo(
+  repo: &Repo,
+  path: &Path,
+  todo: &[Instruction],
+  abbreviated: bool,
+) -> Result<(), String> {
+  let mut text = String::new();
+  for instruction in todo {
+    text.push_str(&format!("{}\n", instruction.line(repo, abbreviated)?));
+  }
+  write(path, &text)
+}
+
+/// Writes a state file.
+pub fn write(path: &Path, data: &str) -> Result<(), String> {
+  fs::write(path, data).map_err(|msg| format!("could not write '{}' ({})", path.display(), msg))
+}
+
+/// How applying a commit went.
+pub enum Applied {
+  /// The changes applied cleanly, and the index has the given tree.
+  Clean(String),
+
+  /// The changes conflict, and the conflicts were left in the index and the
+  /// working tree.
+  Conflicts,
+
+  /// The changes couldn't be applied at all (eg. because they would have
+  /// overwritten local changes).
+  Failed,
+}
+
+/// How a cherry-pick or revert ended.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+  /// All the commits were applied.
+  Done,
+
+  /// A commit stopped for conflicts, or because HEAD already had its
+  /// changes, and is left to the user (who has been told what to do).
+  Stopped,
+}
+
+/// Applies the changes a commit made (or the reverse of them, with `revert`)
+/// to the files `ours` (what the index has), in the index and the working
+/// tree, with a three-way merge.
+///
+/// The merge base is the commit's parent (`mainline` picks which one for a
+/// merge commit), or the commit itself for a revert. The messages of the
+/// merge are printed, unless it is clean and `quiet` is set.
+pub fn apply(
+  repo: &Repo,
+  ours: &Files,
+  hash: &str,
+  mainline: Option<usize>,
+  revert: bool,
+  quiet: bool,
+) -> Result<Applied, String> {
+  let object = object::read(repo.clone(), hash, Some("commit"))?;
+  let commit = object.unbox::<Commit>()?;
+  let parent = parent(commit, hash, mainline)?;
+
+  let label = format!(
+    "{} ({})",
+    abbreviate(repo, hash, DEFAULT_ABBREV)?,
+    title(commit.message())
+  );
+  let parent_label = format!("parent of {}", label);
+  let parent_files = match &parent {
+    Some(parent) => files(repo, parent)?,
+    None => Default::default(),
+  };
+  let (base, theirs, theirs_label, base_label) = match revert {
+    false => (parent_files, files(repo, hash)?, label, parent_label),
+    true => (files(repo, hash)?, parent_files, parent_label, label),
+  };
+  let mut options = Options::new(repo, "HEAD", &theirs_label)?;
+  options.base = base_label;
+  let merge = merge_trees(repo, &base, ours, &theirs, &options)?;
+
+  let mut index = Index::read(repo)?;
+  if let Err(msg) = checkout_merge(repo, &mut index, ours, &merge) {
+    println!("error: {}", msg);
+    return Ok(Applied::Failed);
+  }
+  index.write(repo)?;
+  if !quiet || !merge.is_clean() {
+    for msg in &merge.messages {
+      println!("{}", msg);
+    }
+  }
+  match merge.is_clean() {
+    true => Ok(Applied::Clean(index.write_tree(repo)?)),
+    false => Ok(Applied::Conflicts),
+  }
+}
+
+/// The parent a commit's changes are taken against: its only parent, or the
+/// `mainline` parent of a merge commit (counting from 1).
+fn parent(commit: &Commit, hash: &str, mainline: Option<usize>) -> Result<Option<String>, String> {
+  let parents = commit.parents();
+  match (parents.len(), mainline) {
+    (0 | 1, None | Some(1)) => Ok(parents.first().cloned()),
+    (_, None) => Err(format!(
+      "commit {} is a merge but no -m option was given.",
+      hash
+    )),
+    (count, Some(n)) if n == 0 || n > count => {
+      Err(format!("commit {} does not have parent {}", hash, n))
+    }
+    (_, Some(n)) => Ok(parents.get(n - 1).cloned()),
+  }
+}
+
+/// The message of the commit that applies (or reverts) a commit. A revert
+/// says what it reverts, and with `record_origin`, a cherry-picked commit
+/// says where it came from.
+pub fn message(
+  repo: &Repo,
+  hash: &str,
+  revert: bool,
+  mainline: Option<usize>,
+  record_origin: bool,
+) -> Result<String, String> {
+  let object = object::read(repo.clone(), hash, Some("commit"))?;
+  let commit = object.unbox::<Commit>()?;
+  if revert {
+    let reversing = match (commit.parents().len(), parent(commit, hash, mainline)?) {
+      (2.., Some(parent)) => format!(", reversing\nchanges made to {}", parent),
+      _ => String::new(),
+    };
+    return Ok(format!(
+      "Revert \"{}\"\n\nThis reverts commit {}{}.\n",
+      title(commit.message()),
+      hash,
+      reversing
+    ));
+  }
+  let mut message = commit.message().to_string();
+  if record_origin {
+    // the line joins the trailers at the end of the message, if there are any
+    let last = message.trim_end().rsplit("\n\n").next().unwrap_or_default();
+    let trailers = !last.is_empty()
+      && last.lines().all(|line| {
+        line.starts_with("(cherry picked from commit ")
+          || line
+            .split_once(": ")
+            .is_some_and(|(token, _)| !token.is_empty() && !token.contains(' '))
+      });
+    if !trailers {
+      message.push('\n');
+    }
+    message.push_str(&format!("(cherry picked from commit {})\n", hash));
+  }
+  Ok(message)
+}
+
+/// Creates a commit with the current committer, and returns its hash.
+pub fn commit(
+  repo: &Repo,
+  tree: &str,
+  parents: &[String],
+  author: &str,
+  message: &str,
+) -> Result<String, String> {
+  let committer = Identity::committer(repo)?.to_string();
+  let mut builder = CommitBuilder::new(tree)
+    .author(author)
+    .committer(&committer)
+    .message(message);
+  for parent in parents {
+    builder = builder.parent(parent);
+  }
+  object::write(&builder.build(repo.clone()), false)
+}
+
+/// Resets the index and the working tree from commit `from` to commit `to`,
+/// throwing away any changes (including conflicts) to the paths that differ.
+pub fn reset(repo: &Repo, from: &str, to: &str) -> Result<(), String> {
+  let mut index = Index::read(repo)?;
+  switch_trees(
+    repo,
+    &mut index,
+    &files(repo, from)?,
+    &files(repo, to)?,
+    true,
+    "reset",
+  )?;
+  index.write(repo)
+}
+
+/// Lists the unmerged paths, the way `git add` hints at them, and returns
+/// true if there are any.
+pub fn needs_merge(repo: &Repo) -> Result<bool, String> {
+  let paths = unmerged(repo)?;
+  for path in &paths {
+    println!("{}: needs merge", path);
+  }
+  Ok(!paths.is_empty())
+}
+
+/// The paths with conflicts in the index.
+fn unmerged(repo: &Repo) -> Result<Vec<String>, String> {
+  let mut paths: Vec<String> = Index::read(repo)?
+    .entries
+    .into_iter()
+    .filter(|entry| entry.stage > 0)
+    .map(|entry| entry.path)
+    .collect();
+  paths.dedup();
+  Ok(paths)
+}
+
+/// The options of a cherry-pick or a revert, which are saved (in
+/// `.git/sequencer/opts`) for the commits still to come.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PickOptions {
+  /// Apply the reverse of the commits.
+  pub revert: bool,
+
+  /// The parent of merge commits to take their changes against (`-m`).
+  pub mainline: Option<usize>,
+
+  /// Say which commit a cherry-picked commit came from in its message (`-x`).
+  pub record_origin: bool,
+
+  /// Leave the changes in the index and the working tree, without committing
+  /// them (`-n`).
+  pub no_commit: bool,
+}
+
+impl PickOptions {
+  fn name(&self) -> &'static str {
+    kind(self.revert)
+  }
+
+  /// Reads the saved options, in git's config format.
+  fn read(repo: &Repo, revert: bool) -> Self {
+    let data = fs::read_to_string(repo.git_dir.join("sequencer/opts")).unwrap_or_default();
+    let mut opts = Self {
+      revert,
+      ..Self::default()
+    };
+    for line in data.lines() {
+      match line.trim().split_once(" = ") {
+        Some(("mainline", n)) => opts.mainline = n.parse().ok(),
+        Some(("record-origin", value)) => opts.record_origin = value == "true",
+        Some(("no-commit", value)) => opts.no_commit = value == "true",
+        _ => (),
+      }
+    }
+    opts
+  }
+
+  /// Saves the options that aren't the defaults.
+  fn write(&self, repo: &Repo) -> Result<(), String> {
+    let mut data = String::new();
+    if self.no_commit {
+      data.push_str("\tno-commit = true\n");
+    }
+    if self.record_origin {
+      data.push_str("\trecord-origin = true\n");
+    }
+    if let Some(n) = self.mainline {
+      data.push_str(&format!("\tmainline = {}\n", n));
+    }
+    match data.is_empty() {
+      true => Ok(()),
+      false => write(
+        &repo.git_dir.join("sequencer/opts"),
+        &format!("[options]\n{}", data),
+      ),
+    }
+  }
+}
+
+/// Whether a cherry-pick (`Some(false)`) or a revert (`Some(true)`) is in
+/// progress, either stopped at a commit or with more to come.
+fn in_progress(repo: &Repo) -> Option<bool> {
+  if state::pick_head(repo, true).is_some() {
+    return Some(true);
+  }
+  if state::pick_head(repo, false).is_some() {
+    return Some(false);
+  }
+  let todo = read_todo(repo, &repo.git_dir.join("sequencer/todo")).ok()?;
+  todo.first().map(|first| first.action == Action::Revert)
+}
+
+/// The name of the kind of sequence, for messages.
+fn kind(revert: bool) -> &'static str {
+  match revert {
+    true => "revert",
+    false => "cherry-pick",
+  }
+}
+
+/// Makes sure that a cherry-pick or revert is in progress, and that a
+/// sequence of them is the kind `opts` asks for.
+fn check_progress(repo: &Repo, opts: &PickOptions) -> Result<(), String> {
+  let sequence = repo.git_dir.join("sequencer").exists();
+  match in_progress(repo) {
+    Some(revert) if revert == opts.revert || !sequence => Ok(()),
+    Some(revert) => {
+      println!("error: cannot {} during a {}.", opts.name(), kind(revert));
+      Err(format!("{} failed", opts.name()))
+    }
+    None => {
+      println!("error: no cherry-pick or revert in progress");
+      Err(format!("{} failed", opts.name()))
+    }
+  }
+}
+
+/// Applies (or reverts) the changes of some commits onto HEAD, one by one,
+/// committing each (unless `no_commit` is set). A single revision names a
+/// commit; with ranges, the commits of the range are applied oldest first
+/// (reverted newest first).
+///
+/// When there is more than one commit, what is left to do is recorded in
+/// `.git/sequencer`, so that the sequence can be picked up again after a
+/// commit stops for conflicts.
+pub fn pick(repo: &Repo, opts: &PickOptions, revisions: &[String]) -> Result<Outcome, String> {
+  let revisions = revisions
+    .iter()
+    .map(|expr| revision::parse(repo, expr))
+    .collect::<Result<Vec<_>, _>>()?;
+  let commits = match revisions
+    .iter()
+    .all(|rev| matches!(rev, Revision::Single(_)))
+  {
+    true => revisions
+      .iter()
+      .map(|rev| match rev {
+        Revision::Single(expr) => revision::resolve_commit(repo, expr),
+        _ => unreachable!(),
+      })
+      .collect::<Result<Vec<_>, _>>()?,
+    false => {
+      let options = WalkOptions {
+        reverse: !opts.revert,
+        ..WalkOptions::default()
+      };
+      let mut walk = Walk::new(repo, options);
+      for rev in revisions {
+        walk.add(rev)?;
+      }
+      walk.run()?
+    }
+  };
+  if commits.is_empty() {
+    return Err("empty commit set passed".to_string());
+  }
+
+  let dir = repo.git_dir.join("sequencer");
+  if commits.len() > 1 && dir.exists() {
+    let revert = in_progress(repo).unwrap_or(opts.revert);
+    println!("error: {} is already in progress", kind(revert));
+    println!(
+      "hint: try \"git {} (--continue | --skip | --abort | --quit)\"",
+      kind(revert)
+    );
+    return Err(format!("{} failed", opts.name()));
+  }
+  if Index::read(repo)?.has_conflicts() {
+    let verb = match opts.revert {
+      true => "Reverting",
+      false => "Cherry-picking",
+    };
+    println!(
+      "error: {} is not possible because you have unmerged files.",
+      verb
+    );
+    println!("hint: Fix them up in the work tree, and then use 'git add/rm <file>'");
+    println!("hint: as appropriate to mark resolution and make a commit.");
+    return Err(format!("{} failed", opts.name()));
+  }
+  let head = refs::resolve(repo, Path::new("HEAD"))?;
+
+  let action = match opts.revert {
+    true => Action::Revert,
+    false => Action::Pick,
+  };
+  let mut todo = Vec::new();
+  for hash in &commits {
+    todo.push(Instruction::new(repo, action, hash)?);
+  }
+  if todo.len() > 1 {
+    if let Err(msg) = fs::create_dir_all(&dir) {
+      return Err(format!(
+        "could not create directory '{}' ({})",
+        dir.display(),
+        msg
+      ));
+    }
+    write(&dir.join("head"), &format!("{}\n", head))?;
+    write(&dir.join("abort-safety"), &format!("{}\n", head))?;
+    write_todo(repo, &dir.join("todo"), &todo, true)?;
+    opts.write(repo)?;
+  }
+  run_picks(repo, opts, todo)
+}
+
+/// Applies the commits of the todo list, until they are done or one stops
+/// for conflicts.
+fn run_picks(
+  repo: &Repo,
+  opts: &PickOptions,
+  mut todo: Vec<Instruction>,
+) -> Result<Outcome, String> {
+  let dir = repo.git_dir.join("sequencer");
+  let sequence = dir.exists();
+  while let Some(instruction) = todo.first() {
+    let hash = &instruction.argument;
+    if local_changes(repo, opts)? {
+      return Err(format!("{} failed", opts.name()));
+    }
+    let head = refs::resolve(repo, Path::new("HEAD"))?;
+    let ours = match opts.no_commit {
+      true => tree::flatten(repo, &Index::read(repo)?.write_tree(repo)?)?,
+      false => files(repo, &head)?,
+    };
+    // the message can't be made for a commit that can't be applied either
+    let applied = apply(repo, &ours, hash, opts.mainline, opts.revert, false).and_then(|applied| {
+      let message = message(repo, hash, opts.revert, opts.mainline, opts.record_origin)?;
+      Ok((applied, message))
+    });
+    let (applied, message) = match applied {
+      Ok(applied) => applied,
+      Err(msg) => {
+        println!("error: {}", msg);
+        return Err(format!("{} failed", opts.name()));
+      }
+    };
+    match applied {
+      Applied::Failed => return Err(format!("{} failed", opts.name())),
+      Applied::Conflicts => {
+        let mut merge_msg = message;
+        merge_msg.push_str("\n# Conflicts:\n");
+        for path in unmerged(repo)? {
+          merge_msg.push_str(&format!("#\t{}\n", path));
+        }
+        state::write_merge_msg(repo, &merge_msg)?;
+        // a revert is remembered even when it isn't going to be committed
+        if !opts.no_commit || opts.revert {
+          state::write_pick_head(repo, opts.revert, hash)?;
+        }
+        let verb = match opts.revert {
+          true => "revert",
+          false => "apply",
+        };
+        let short = abbreviate(repo, hash, DEFAULT_ABBREV)?;
+        println!(
+          "error: could not {} {}... {}",
+          verb, short, instruction.subject
+        );
+        match opts.no_commit {
+          true => {
+            println!("hint: after resolving the conflicts, mark the corrected paths");
+            println!("hint: with 'git add <paths>' or 'git rm <paths>'");
+          }
+          false => {
+            let name = opts.name();
+            println!("hint: After resolving the conflicts, mark them with");
+            println!("hint: \"git add/rm <pathspec>\", then run");
+            println!("hint: \"git {} --continue\".", name);
+            println!(
+              "hint: You can instead skip this commit with \"git {} --skip\".",
+              name
+            );
+            println!(
+              "hint: To abort and get back to the state before \"git {}\",",
+              name
+            );
+            println!("hint: run \"git {} --abort\".", name);
+          }
+        }
+        return Ok(Outcome::Stopped);
+      }
+      Applied::Clean(_) if opts.no_commit => {
+        state::write_merge_msg(repo, &message)?;
+        if opts.revert {
+          state::write_pick_head(repo, opts.revert, hash)?;
+        }
+      }
+      Applied::Clean(tree) => {
+        if tree == tree_of(repo, &head)? {
+          state::write_merge_msg(repo, &message)?;
+          if !opts.revert {
+            state::write_pick_head(repo, opts.revert, hash)?;
+          }
+          return Ok(stop_empty(opts));
+        }
+        let reason = format!("{}: ", opts.name());
+        commit_pick(repo, opts, hash, &tree, &message, &reason)?;
+      }
+    }
+    todo.remove(0);
+    if sequence {
+      let head = refs::resolve(repo, Path::new("HEAD"))?;
+      write(&dir.join("abort-safety"), &format!("{}\n", head))?;
+      write_todo(repo, &dir.join("todo"), &todo, true)?;
+    }
+  }
+  if sequence {
+    if let Err(msg) = fs::remove_dir_all(&dir) {
+      return Err(format!("could not remove '{}' ({})", dir.display(), msg));
+    }
+  }
+  Ok(Outcome::Done)
+}
+
+/// Commits a tree on top of HEAD for a picked (or reverted) commit, and says
+/// so like `git commit` does. The reflog message is `reason` followed by the
+/// subject.
+fn commit_pick(
+  repo: &Repo,
+  opts: &PickOptions,
+  hash: &str,
+  tree: &str,
+  message: &str,
+  reason: &str,
+) -> Result<(), String> {
+  let author = match opts.revert {
+    true => Identity::author(repo)?.to_string(),
+    false => {
+      let object = object::read(repo.clone(), hash, Some("commit"))?;
+      let author = object.unbox::<Commit>()?.author().to_string();
+      author
+    }
+  };
+  let head = refs::resolve(repo, Path::new("HEAD"))?;
+  let new = commit(repo, tree, &[head], &author, message)?;
+  let subject = message.lines().next().unwrap_or("");
+  let identity = Identity::committer(repo)?.to_string();
+  refs::update_head(repo, &new, &identity, &format!("{}{}", reason, subject))?;
+  let branch = match refs::current_branch(repo) {
+    Some(branch) => branch.trim_start_matches("refs/heads/").to_string(),
+    None => "detached HEAD".to_string(),
+  };
+  let short = abbreviate(repo, &new, DEFAULT_ABBREV)?;
+  println!("[{} {}] {}", branch, short, subject);
+  Ok(())
+}
+
+/// Stops at a commit whose changes HEAD already has, leaving it to the user
+/// to commit it anyway (as an empty commit) or skip it.
+fn stop_empty(opts: &PickOptions) -> Outcome {
+  match opts.revert {
+    true => println!("nothing to commit, working tree clean"),
+    false => {
+      println!("The previous cherry-pick is now empty, possibly due to conflict resolution.");
+      println!("If you wish to commit it anyway, use:\n\n    git commit --allow-empty\n");
+      println!("Otherwise, please use 'git cherry-pick --skip'");
+    }
+  }
+  Outcome::Stopped
+}
+
+/// Commits the resolution of the commit a cherry-pick or revert stopped at,
+/// and carries on with the rest.
+pub fn continue_picks(repo: &Repo, revert: bool) -> Result<Outcome, String> {
+  let opts = PickOptions::read(repo, revert);
+  check_progress(repo, &opts)?;
+  if Index::read(repo)?.has_conflicts() {
+    println!("error: Committing is not possible because you have unmerged files.");
+    println!("hint: Fix them up in the work tree, and then use 'git add/rm <file>'");
+    println!("hint: as appropriate to mark resolution and make a commit.");
+    return Err("Exiting because of an unresolved conflict.".to_string());
+  }
+  // a single commit is committed for whichever kind stopped
+  let stopped = [false, true].into_iter().find_map(|revert| {
+    let hash = state::pick_head(repo, revert)?;
+    Some((PickOptions { revert, ..opts }, hash))
+  });
+  if let Some((stopped, hash)) = stopped {
+    let head = refs::resolve(repo, Path::new("HEAD"))?;
+    let tree = Index::read(repo)?.write_tree(repo)?;
+    if tree == tree_of(repo, &head)? {
+      return Ok(stop_empty(&opts));
+    }
+    let merge_msg = state::merge_msg(repo).unwrap_or_default();
+    let reason = match stopped.revert {
+      true => "commit: ",
+      false => "commit (cherry-pick): ",
+    };
+    let message = cleanup(&merge_msg, true);
+    commit_pick(repo, &stopped, &hash, &tree, &message, reason)?;
+    state::clear_pick(repo);
+  } else if local_changes(repo, &opts)? {
+    return Err(format!("{} failed", opts.name()));
+  }
+  state::clear_pick(repo);
+  next_pick(repo, &opts)
+}
+
+/// Leaves out the commit a cherry-pick or revert stopped at, throwing away
+/// its changes, and carries on with the rest.
+pub fn skip_pick(repo: &Repo, revert: bool) -> Result<Outcome, String> {
+  let opts = PickOptions::read(repo, revert);
+  if in_progress(repo).is_none() {
+    check_progress(repo, &opts)?;
+  }
+  let head = refs::resolve(repo, Path::new("HEAD"))?;
+  // without the stopped commit, HEAD must still be where the sequence left
+  // it, or the commit may have been committed by hand
+  if state::pick_head(repo, opts.revert).is_none() {
+    let dir = repo.git_dir.join("sequencer");
+    let safe = fs::read_to_string(dir.join("abort-safety")).unwrap_or_default();
+    if in_progress(repo) != Some(opts.revert) {
+      println!("error: no {} in progress", opts.name());
+      return Err(format!("{} failed", opts.name()));
+    } else if safe.trim() != head {
+      println!("error: there is nothing to skip");
+      println!("hint: have you committed already?");
+      println!("hint: try \"git {} --continue\"", opts.name());
+      return Err(format!("{} failed", opts.name()));
+    }
+  }
+  reset_to(repo, &head, &head)?;
+  state::clear_pick(repo);
+  next_pick(repo, &opts)
+}
+
+/// Moves past the commit a cherry-pick or revert stopped at, and carries on
+/// with the commits after it.
+fn next_pick(repo: &Repo, opts: &PickOptions) -> Result<Outcome, String> {
+  let dir = repo.git_dir.join("sequencer");
+  if !dir.exists() {
+    return Ok(Outcome::Done);
+  }
+  let mut todo = read_todo(repo, &dir.join("todo"))?;
+  if !todo.is_empty() {
+    todo.remove(0);
+  }
+  let head = refs::resolve(repo, Path::new("HEAD"))?;
+  write(&dir.join("abort-safety"), &format!("{}\n", head))?;
+  write_todo(repo, &dir.join("todo"), &todo, true)?;
+  run_picks(repo, opts, todo)
+}
+
+/// Checks that the index has no changes of its own, since the changes of the
+/// commits are merged from their trees (unless they aren't being committed
+/// anyway), and says so if it does.
+fn local_changes(repo: &Repo, opts: &PickOptions) -> Result<bool, String> {
+  let head = refs::resolve(repo, Path::new("HEAD"))?;
+  if opts.no_commit || Index::read(repo)?.write_tree(repo)? == tree_of(repo, &head)? {
+    return Ok(false);
+  }
+  println!(
+    "error: your local changes would be overwritten by {}.",
+    opts.name()
+  );
+  println!("hint: commit your changes or stash them to proceed.");
+  Ok(true)
+}
+
+/// Gives up on a cherry-pick or revert: HEAD goes back to where it was
+/// before the first commit was applied.
+pub fn abort_picks(repo: &Repo, revert: bool) -> Result<(), String> {
+  let opts = PickOptions::read(repo, revert);
+  check_progress(repo, &opts)?;
+  let head = refs::resolve(repo, Path::new("HEAD"))?;
+  let dir = repo.git_dir.join("sequencer");
+  let target = match fs::read_to_string(dir.join("head")) {
+    Ok(hash) => hash.trim().to_string(),
+    Err(_) => head.clone(),
+  };
+  reset_to(repo, &head, &target)?;
+  state::clear_pick(repo);
+  if dir.exists() {
+    if let Err(msg) = fs::remove_dir_all(&dir) {
+      return Err(format!("could not remove '{}' ({})", dir.display(), msg));
+    }
+  }
+  Ok(())
+}
+
+/// Moves HEAD from one commit to another, like `git reset --hard` does.
+fn reset_to(repo: &Repo, from: &str, to: &str) -> Result<(), String> {
+  let identity = Identity::reflog(repo)?.to_string();
+  write(&repo.git_dir.join("ORIG_HEAD"), &format!("{}\n", from))?;
+  reset(repo, from, to)?;
+  refs::update_head(repo, to, &identity, &format!("reset: moving to {}", to))
+}
+
+/// The tree of a commit.
+fn tree_of(repo: &Repo, commit: &str) -> Result<String, String> {
+  let object = object::read(repo.clone(), commit, Some("commit"))?;
+  let tree = object.unbox::<Commit>()?.tree().to_string();
+  Ok(tree)
+}
@@ -0,0 +1,276 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use super::ignore::Ignore;
+use super::worktree::{self, FileState};
+use super::Index;
+use crate::object::commit::Commit;
+use crate::object::mode::Mode;
+use crate::object::serializable::Unbox;
+use crate::object::{self, refs, tree};
+use crate::repo::Repo;
+
+/// How a path differs between two of HEAD, the index and the working tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+  Added,
+  Modified,
+  Deleted,
+
+  /// The path changed between a file, a symlink and a submodule.
+  TypeChanged,
+}
+
+impl Change {
+  /// The letter used for the change in `--short` and `--porcelain` output.
+  pub fn code(self) -> char {
+    match self {
+      Change::Added => 'A',
+      Change::Modified => 'M',
+      Change::Deleted => 'D',
+      Change::TypeChanged => 'T',
+    }
+  }
+}
+
+/// A tracked path that differs between HEAD, the index or the working tree.
+pub struct FileStatus {
+  pub path: String,
+
+  /// The mode and hash of the path in HEAD.
+  pub head: Option<(Mode, String)>,
+
+  /// The mode and hash of the path in the index.
+  pub index: Option<(Mode, String)>,
+
+  /// The mode of the path in the working tree.
+  pub worktree: Option<Mode>,
+
+  /// The change between HEAD and the index.
+  pub staged: Option<Change>,
+
+  /// The change between the index and the working tree.
+  pub unstaged: Option<Change>,
+}
+
+/// A path with unresolved merge conflicts (ie. with entries in stages 1-3).
+pub struct Conflict {
+  pub path: String,
+
+  /// The mode and hash of the common ancestor, ours and theirs.
+  pub stages: [Option<(Mode, String)>; 3],
+
+  /// The mode of the path in the working tree.
+  pub worktree: Option<Mode>,
+}
+
+impl Conflict {
+  /// The two letter code of the conflict in `--short` output, eg. `UU` when
+  /// both sides modified the path or `DU` when we deleted it.
+  pub fn code(&self) -> &'static str {
+    match [0, 1, 2].map(|i| self.stages[i].is_some()) {
+      [true, false, false] => "DD",
+      [false, true, false] => "AU",
+      [true, true, false] => "UD",
+      [false, false, true] => "UA",
+      [true, false, true] => "DU",
+      [false, true, true] => "AA",
+      _ => "UU",
+    }
+  }
+
+  /// How the conflict is described in the long format.
+  pub fn description(&self) -> &'static str {
+    match self.code() {
+      "DD" => "both deleted:",
+      "AU" => "added by us:",
+      "UD" => "deleted by them:",
+      "UA" => "added by them:",
+      "DU" => "deleted by us:",
+      "AA" => "both added:",
+      _ => "both modified:",
+    }
+  }
+}
+
+/// The differences between HEAD, the index and the working tree.
+pub struct Status {
+  /// The commit HEAD points at, or `None` on an unborn branch.
+  pub head: Option<String>,
+
+  /// Tracked paths with changes, sorted by path.
+  pub changes: Vec<FileStatus>,
+
+  /// Paths with merge conflicts, sorted by path.
+  pub conflicts: Vec<Conflict>,
+
+  /// Files that are neither tracked nor ignored. A directory without any
+  /// tracked files is listed once, with a trailing `/`, instead of listing its
+  /// contents.
+  pub untracked: Vec<String>,
+
+  /// Whether the stat data of any index entry was refreshed, ie. whether the
+  /// index is worth writing back out.
+  pub refreshed: bool,
+}
+
+impl Status {
+  /// Compares HEAD with the index and the index with the working tree.
+  ///
+  /// Files whose stat data matches the index are not read at all. Files that
+  /// had to be rehashed but turned out to be unchanged get their stat data
+  /// refreshed in `index`, so that writing it back out saves the work next
+  /// time.
+  pub fn collect(repo: &Repo, index: &mut Index) -> Result<Self, String> {
+    let head = refs::resolve(repo, Path::new("HEAD")).ok();
+    let head_files = match &head {
+      Some(hash) => {
+        let object = object::read(repo.clone(), hash, Some("commit"))?;
+        let tree = object.unbox::<Commit>()?.tree();
+        tree::flatten(repo, tree)?
+      }
+      None => BTreeMap::new(),
+    };
+
+    let mut changes: BTreeMap<String, FileStatus> = BTreeMap::new();
+    let mut conflicts: BTreeMap<String, Conflict> = BTreeMap::new();
+    let mut refreshed = Vec::new();
+    for entry in &index.entries {
+      let worktree_mode =
+        || worktree::entry(repo, &entry.path, Some(entry), false).map(|e| e.map(|e| e.mode));
+      if entry.stage > 0 {
+        if !conflicts.contains_key(&entry.path) {
+          let conflict = Conflict {
+            path: entry.path.clone(),
+            stages: [None, None, None],
+            worktree: worktree_mode()?,
+          };
+          conflicts.insert(entry.path.clone(), conflict);
+        }
+        let conflict = conflicts.get_mut(&entry.path).unwrap();
+        conflict.stages[entry.stage as usize - 1] = Some((entry.mode, entry.hash.clone()));
+        continue;
+      }
+
+      let in_head = head_files.get(&entry.path);
+      let in_index = (entry.mode, entry.hash.clone());
+      let staged = match in_head {
+        _ if entry.intent_to_add => None,
+        None => Some(Change::Added),
+        Some(old) => compare(old, &in_index),
+      };
+      let (unstaged, worktree) = match worktree::compare(repo, index, entry)? {
+        FileState::Deleted => (Some(Change::Deleted), None),
+        FileState::Modified(new) => (compare(&in_index, &(new.mode, new.hash)), Some(new.mode)),
+        FileState::Unchanged(fresh) => {
+          refreshed.extend(fresh);
+          (None, Some(entry.mode))
+        }
+      };
+      let unstaged = match entry.intent_to_add {
+        true => Some(Change::Added),
+        false => unstaged,
+      };
+      if staged.is_some() || unstaged.is_some() {
+        let status = FileStatus {
+          path: entry.path.clone(),
+          head: in_head.cloned(),
+          // an intent-to-add entry is only a placeholder
+          index: Some(in_index).filter(|_| !entry.intent_to_add),
+          worktree,
+          staged,
+          unstaged,
+        };
+        changes.insert(entry.path.clone(), status);
+      }
+    }
+
+    // paths that were deleted from the index
+    for (path, file) in &head_files {
+      if index.entry(path).is_none() && !conflicts.contains_key(path) {
+        let status = FileStatus {
+          path: path.clone(),
+          head: Some(file.clone()),
+          index: None,
+          worktree: worktree::entry(repo, path, None, false)?.map(|e| e.mode),
+          staged: Some(Change::Deleted),
+          unstaged: None,
+        };
+        changes.insert(path.clone(), status);
+      }
+    }
+
+    let any_refreshed = !refreshed.is_empty();
+    for fresh in refreshed {
+      index.entry_mut(&fresh.path).unwrap().refresh(&fresh);
+    }
+
+    Ok(Self {
+      head,
+      changes: changes.into_values().collect(),
+      conflicts: conflicts.into_values().collect(),
+      untracked: untracked(repo, index)?,
+      refreshed: any_refreshed,
+    })
+  }
+
+  /// Returns true if there is anything to commit.
+  pub fn has_staged_changes(&self) -> bool {
+    self.changes.iter().any(|change| change.staged.is_some())
+  }
+
+  /// Returns true if a tracked file was changed without being staged.
+  pub fn has_unstaged_changes(&self) -> bool {
+    self.changes.iter().any(|change| change.unstaged.is_some())
+  }
+}
+
+/// Classifies the change between two versions of a path, if there is one.
+fn compare(old: &(Mode, String), new: &(Mode, String)) -> Option<Change> {
+  let kind = |mode: Mode| match mode {
+    Mode::Normal | Mode::Executable => Mode::Normal,
+    mode => mode,
+  };
+  if kind(old.0) != kind(new.0) {
+    Some(Change::TypeChanged)
+  } else if old != new {
+    Some(Change::Modified)
+  } else {
+    None
+  }
+}
+
+/// Lists the untracked files in the working tree, collapsing directories that
+/// don't hold any tracked files into a single `dir/`.
+fn untracked(repo: &Repo, index: &Index) -> Result<Vec<String>, String> {
+  let mut tracked = HashSet::new();
+  let mut tracked_dirs = HashSet::new();
+  for entry in &index.entries {
+    tracked.insert(entry.path.as_str());
+    let mut path = entry.path.as_str();
+    while let Some((dir, _)) = path.rsplit_once('/') {
+      tracked_dirs.insert(dir);
+      path = dir;
+    }
+  }
+
+  let mut untracked: Vec<String> = Vec::new();
+  let mut ignore = Ignore::new(repo);
+  for path in worktree::files(repo, Some(&mut ignore))? {
+    if tracked.contains(path.as_str()) {
+      continue;
+    }
+    let untracked_dir = path
+      .match_indices('/')
+      .map(|(i, _)| &path[..i])
+      .find(|dir| !tracked_dirs.contains(dir));
+    let name = match untracked_dir {
+      Some(dir) => format!("{}/", dir),
+      None if repo.work_tree.join(&path).is_dir() => format!("{}/", path),
+      None => path,
+    };
+    untracked.push(name);
+  }
+  untracked.dedup();
+  Ok(untracked)
+}
@@ -0,0 +1,163 @@
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use super::ignore::Ignore;
+use super::{Index, IndexEntry};
+use crate::object::blob::Blob;
+use crate::object::mode::Mode;
+use crate::object::refs;
+use crate::object::write;
+use crate::repo::Repo;
+
+/// Lists the files in the working tree.
+///
+/// Paths are relative to the root of the working tree and sorted the way the
+/// index sorts them. A directory that holds a repository of its own (ie. it
+/// has its own `.git`) is listed as a single path, which is how git records
+/// submodules.
+///
+/// When `ignore` is given, ignored files are left out and ignored directories
+/// are not entered at all.
+pub fn files(repo: &Repo, ignore: Option<&mut Ignore>) -> Result<Vec<String>, String> {
+  let mut files = Vec::new();
+  walk(&repo.work_tree, "", ignore, &mut files)?;
+  files.sort();
+  Ok(files)
+}
+
+/// Builds the index entry for a path in the working tree, or returns `None` if
+/// there is no file there.
+///
+/// The contents are hashed as a blob (and stored in the object database when
+/// `write` is set). If `core.filemode` is turned off the executable bit of the
+/// file can't be trusted, so the mode of the `previous` entry for the path is
+/// kept instead.
+pub fn entry(
+  repo: &Repo,
+  path: &str,
+  previous: Option<&IndexEntry>,
+  write: bool,
+) -> Result<Option<IndexEntry>, String> {
+  let full_path = repo.work_tree.join(path);
+  let metadata = match fs::symlink_metadata(&full_path) {
+    Ok(metadata) => metadata,
+    Err(_) => return Ok(None),
+  };
+
+  let (mode, hash) = if metadata.file_type().is_symlink() {
+    let target = match fs::read_link(&full_path) {
+      Ok(target) => target,
+      Err(msg) => return Err(format!("unable to read {} ({})", path, msg)),
+    };
+    let blob = Blob::new(repo.clone(), target.as_os_str().as_bytes());
+    (Mode::Symbolic, self::write(&blob, !write)?)
+  } else if metadata.is_dir() {
+    if !full_path.join(".git").exists() {
+      return Ok(None);
+    }
+    let nested = Repo::from_existing(&full_path)?;
+    match refs::resolve(&nested, Path::new("HEAD")) {
+      Ok(hash) => (Mode::Gitlink, hash),
+      Err(_) => return Err(format!("'{}' does not have a commit checked out", path)),
+    }
+  } else {
+    let data = match fs::read(&full_path) {
+      Ok(data) => data,
+      Err(msg) => return Err(format!("unable to read {} ({})", path, msg)),
+    };
+    let executable = metadata.mode() & 0o100 != 0;
+    let mode = match previous.map(|entry| entry.mode) {
+      _ if trust_executable_bit(repo) => match executable {
+        true => Mode::Executable,
+        false => Mode::Normal,
+      },
+      Some(mode @ (Mode::Normal | Mode::Executable)) => mode,
+      _ => Mode::Normal,
+    };
+    (mode, self::write(&Blob::new(repo.clone(), &data), !write)?)
+  };
+  Ok(Some(IndexEntry::new(path, &hash, mode, &metadata)))
+}
+
+/// The state of a tracked file in the working tree, compared to its entry in
+/// the index.
+pub enum FileState {
+  /// The contents are the same. If the file had to be rehashed to find out,
+  /// this holds a fresh entry whose stat data can replace the stale one.
+  Unchanged(Option<IndexEntry>),
+
+  /// The contents (or the mode) differ; holds the entry the file would get if
+  /// it were staged.
+  Modified(IndexEntry),
+
+  /// The file is gone.
+  Deleted,
+}
+
+/// Compares a tracked file with its entry in the index.
+///
+/// Files whose stat data matches the entry are assumed unchanged without
+/// reading them, everything else is rehashed (without writing the blob).
+pub fn compare(repo: &Repo, index: &Index, entry: &IndexEntry) -> Result<FileState, String> {
+  let metadata = match fs::symlink_metadata(repo.work_tree.join(&entry.path)) {
+    Ok(metadata) => metadata,
+    Err(_) => return Ok(FileState::Deleted),
+  };
+  if entry.skip_worktree || entry.stat_matches(&metadata, index.mtime) {
+    return Ok(FileState::Unchanged(None));
+  }
+  Ok(match self::entry(repo, &entry.path, Some(entry), false)? {
+    None => FileState::Deleted,
+    Some(new) if new.hash == entry.hash && new.mode == entry.mode => {
+      FileState::Unchanged(Some(new))
+    }
+    Some(new) => FileState::Modified(new),
+  })
+}
+
+/// Returns false if `core.filemode` is turned off.
+fn trust_executable_bit(repo: &Repo) -> bool {
+  let value = repo.config_value("core", None, "filemode");
+  !matches!(
+    value.as_deref(),
+    Some("false") | Some("no") | Some("off") | Some("0")
+  )
+}
+
+/// Recursively collects the files under `dir` (whose path relative to the root
+/// of the working tree is `prefix`).
+fn walk(
+  dir: &Path,
+  prefix: &str,
+  mut ignore: Option<&mut Ignore>,
+  files: &mut Vec<String>,
+) -> Result<(), String> {
+  let entries = match fs::read_dir(dir) {
+    Ok(entries) => entries,
+    Err(msg) => return Err(format!("unable to read {} ({})", dir.display(), msg)),
+  };
+  for entry in entries.flatten() {
+    let name = entry.file_name().to_string_lossy().into_owned();
+    if name == ".git" {
+      continue;
+    }
+    let path = match prefix {
+      "" => name,
+      _ => format!("{}/{}", prefix, name),
+    };
+    let is_dir = entry.file_type().map(|kind| kind.is_dir()).unwrap_or(false);
+    if let Some(ignore) = ignore.as_deref_mut() {
+      if ignore.is_ignored(&path, is_dir) {
+        continue;
+      }
+    }
+    if is_dir && !entry.path().join(".git").exists() {
+      walk(&entry.path(), &path, ignore.as_deref_mut(), files)?;
+    } else {
+      files.push(path);
+    }
+  }
+  Ok(())
+}
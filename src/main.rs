@@ -1,48 +1,70 @@
 pub mod cli;
 mod crypto;
+mod diff;
+mod index;
 mod object;
 pub mod repo;
 
 use self::cli::{Arguments, Command};
 use clap::Parser;
+use std::process;
 
 use crate::cli::add::cmd_add;
+use crate::cli::branch::cmd_branch;
 use crate::cli::cat_file::cmd_cat_file;
+use crate::cli::check_ignore::cmd_check_ignore;
 use crate::cli::checkout::cmd_checkout;
+use crate::cli::cherry_pick::cmd_cherry_pick;
 use crate::cli::commit::cmd_commit;
+use crate::cli::diff::cmd_diff;
+use crate::cli::diff_tree::cmd_diff_tree;
 use crate::cli::hash_object::cmd_hash_object;
 use crate::cli::init::cmd_init;
 use crate::cli::log::cmd_log;
 use crate::cli::merge::cmd_merge;
+use crate::cli::merge_base::cmd_merge_base;
+use crate::cli::merge_file::cmd_merge_file;
 use crate::cli::rebase::cmd_rebase;
 use crate::cli::rev_parse::cmd_rev_parse;
+use crate::cli::revert::cmd_revert;
 use crate::cli::rm::cmd_rm;
 use crate::cli::show_ref::cmd_show_ref;
 use crate::cli::show_tree::cmd_show_tree;
+use crate::cli::status::cmd_status;
 use crate::cli::tag::cmd_tag;
 
 fn main() {
   // multiplex the command line args
   let args: Arguments = Arguments::parse();
   let response: Result<(), String> = match &args.command {
-    Command::Add(_) => cmd_add(),
+    Command::Add(opts) => cmd_add(opts),
+    Command::Branch(opts) => cmd_branch(opts),
     Command::CatFile(opts) => cmd_cat_file(opts),
+    Command::CheckIgnore(opts) => cmd_check_ignore(opts),
     Command::Checkout(opts) => cmd_checkout(opts),
-    Command::Commit(_) => cmd_commit(),
+    Command::CherryPick(opts) => cmd_cherry_pick(opts),
+    Command::Commit(opts) => cmd_commit(opts),
+    Command::Diff(opts) => cmd_diff(opts),
+    Command::DiffTree(opts) => cmd_diff_tree(opts),
     Command::HashObject(opts) => cmd_hash_object(opts),
     Command::Init(opts) => cmd_init(opts),
     Command::Log(opts) => cmd_log(opts),
     Command::LsTree(opts) => cmd_show_tree(opts),
-    Command::Merge(_) => cmd_merge(),
-    Command::Rebase(_) => cmd_rebase(),
-    Command::RevParse(_) => cmd_rev_parse(),
+    Command::Merge(opts) => cmd_merge(opts),
+    Command::MergeBase(opts) => cmd_merge_base(opts),
+    Command::MergeFile(opts) => cmd_merge_file(opts),
+    Command::Rebase(opts) => cmd_rebase(opts),
+    Command::RevParse(opts) => cmd_rev_parse(opts),
+    Command::Revert(opts) => cmd_revert(opts),
     Command::Rm(_) => cmd_rm(),
     Command::ShowRef(_) => cmd_show_ref(),
+    Command::Status(opts) => cmd_status(opts),
     Command::Tag(opts) => cmd_tag(opts),
   };
 
   // handle the response type if it errored out
-  if let Some(err) = response.err() {
-    println!("fatal: {}", err);
+  if let Err(err) = response {
+    eprintln!("fatal: {}", err);
+    process::exit(128);
   }
 }
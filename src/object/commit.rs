@@ -2,7 +2,7 @@ use std::ops::Deref;
 
 use crate::repo::Repo;
 
-use super::{mail_map::MailMap, serializable::Serializable};
+use super::{identity::Identity, mail_map::MailMap, serializable::Serializable};
 
 pub struct Commit {
   format: String,
@@ -17,9 +17,34 @@ impl Commit {
       map: MailMap::new(),
       repo,
     };
-    new_commit.map.parse_bytes(data, 0);
+    new_commit.map.parse_bytes(data);
     new_commit
   }
+
+  /// Returns the hash of the commit's tree.
+  pub fn tree(&self) -> &str {
+    self.map.get("tree").unwrap_or("")
+  }
+
+  /// Returns the `author` header (`Name <email> timestamp timezone`).
+  pub fn author(&self) -> &str {
+    self.map.get("author").unwrap_or("")
+  }
+
+  /// Returns the hashes of the commit's parents, in order.
+  pub fn parents(&self) -> Vec<String> {
+    self.map.get_all("parent").map(str::to_string).collect()
+  }
+
+  /// Returns the `committer` header (`Name <email> timestamp timezone`).
+  pub fn committer(&self) -> &str {
+    self.map.get("committer").unwrap_or("")
+  }
+
+  /// Returns the committer timestamp (seconds since the epoch).
+  pub fn commit_time(&self) -> i64 {
+    Identity::parse(self.committer()).map_or(0, |committer| committer.time)
+  }
 }
 
 impl Deref for Commit {
@@ -36,7 +61,7 @@ impl Serializable for Commit {
   }
 
   fn deserialize(&mut self, data: &[u8]) {
-    self.map.parse_bytes(data, 0)
+    self.map.parse_bytes(data)
   }
 
   fn format(&self) -> &String {
@@ -47,3 +72,75 @@ impl Serializable for Commit {
     &self.repo
   }
 }
+
+/// Builds a new commit out of its parts.
+///
+/// ```ignore
+/// let commit = CommitBuilder::new(&tree)
+///   .parent(&head)
+///   .author(&author)
+///   .committer(&committer)
+///   .message("update readme\n")
+///   .build(repo);
+/// ```
+pub struct CommitBuilder {
+  tree: String,
+  parents: Vec<String>,
+  author: String,
+  committer: String,
+  message: String,
+}
+
+impl CommitBuilder {
+  /// Starts a commit that records the given tree.
+  pub fn new(tree: &str) -> Self {
+    Self {
+      tree: tree.to_string(),
+      parents: Vec::new(),
+      author: String::new(),
+      committer: String::new(),
+      message: String::new(),
+    }
+  }
+
+  /// Adds a parent (parents are recorded in the order they are added).
+  pub fn parent(mut self, hash: &str) -> Self {
+    self.parents.push(hash.to_string());
+    self
+  }
+
+  /// Sets the author (`Name <email> timestamp timezone`).
+  pub fn author(mut self, author: &str) -> Self {
+    self.author = author.to_string();
+    self
+  }
+
+  /// Sets the committer (`Name <email> timestamp timezone`).
+  pub fn committer(mut self, committer: &str) -> Self {
+    self.committer = committer.to_string();
+    self
+  }
+
+  /// Sets the commit message.
+  pub fn message(mut self, message: &str) -> Self {
+    self.message = message.to_string();
+    self
+  }
+
+  /// Adds the headers in the order git writes them, followed by the message.
+  pub fn build(self, repo: Repo) -> Commit {
+    let mut map = MailMap::new();
+    map.push("tree", &self.tree);
+    for parent in &self.parents {
+      map.push("parent", parent);
+    }
+    map.push("author", &self.author);
+    map.push("committer", &self.committer);
+    map.set_message(&self.message);
+    Commit {
+      format: String::from("commit"),
+      map,
+      repo,
+    }
+  }
+}
@@ -0,0 +1,660 @@
+use std::env;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The number of seconds in a day.
+const DAY: i64 = 24 * 60 * 60;
+
+/// Month names, as used by RFC 2822 dates.
+const MONTHS: [&str; 12] = [
+  "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Day names, starting from Sunday.
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// Returns the current time in seconds since the epoch.
+pub fn now() -> i64 {
+  match SystemTime::now().duration_since(UNIX_EPOCH) {
+    Ok(duration) => duration.as_secs() as i64,
+    Err(_) => 0,
+  }
+}
+
+/// Parses a date in one of the formats git accepts for `GIT_AUTHOR_DATE` and
+/// `GIT_COMMITTER_DATE`, and returns its timestamp along with its timezone
+/// offset (in minutes east of UTC).
+///
+/// - git's internal format: `1112911993 +0200` or `@1112911993 +0200`
+/// - RFC 2822: `Thu, 07 Apr 2005 22:13:13 +0200`
+/// - ISO 8601: `2005-04-07T22:13:13+02:00` or `2005-04-07 22:13:13 +0200`
+///
+/// Dates without a timezone are taken to be in the local timezone.
+pub fn parse(date: &str) -> Result<(i64, i32), String> {
+  let date = date.trim();
+  let invalid = || format!("invalid date format: {}", date);
+  let (time, zone) = match date.rsplit_once(' ') {
+    Some((time, zone)) if parse_offset(zone).is_some() => (time.trim_end(), Some(zone)),
+    _ => (date, None),
+  };
+
+  // raw timestamps
+  let digits = time.strip_prefix('@').unwrap_or(time);
+  if !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()) {
+    let timestamp: i64 = digits.parse().map_err(|_| invalid())?;
+    let offset = match zone {
+      Some(zone) => parse_offset(zone).unwrap(),
+      None => local_offset(timestamp),
+    };
+    return Ok((timestamp, offset));
+  }
+
+  let (fields, zone) = match parse_iso8601(time).or_else(|| parse_rfc2822(time)) {
+    Some((fields, iso_zone)) => (fields, zone.and_then(parse_offset).or(iso_zone)),
+    None => return Err(invalid()),
+  };
+  let (year, month, day, hour, minute, second) = fields;
+  if !(1..=12).contains(&month)
+    || !(1..=31).contains(&day)
+    || hour > 24
+    || minute > 59
+    || second > 60
+  {
+    return Err(invalid());
+  }
+  let local = days_from_civil(year, month, day) * DAY + hour * 3600 + minute * 60 + second;
+  let offset = match zone {
+    Some(offset) => offset,
+    None => {
+      // the offset depends on the (utc) time we are trying to find
+      let guess = local - local_offset(local) as i64 * 60;
+      local_offset(guess)
+    }
+  };
+  Ok((local - offset as i64 * 60, offset))
+}
+
+/// Parses a date given on the command line (eg. to `--since`): either one of
+/// the formats `parse` accepts, or a date relative to now, like `now`,
+/// `yesterday` or `2.weeks.ago` (any number of `<n> <unit>` pairs).
+///
+/// Like git, a date without a time of day is taken at the current time of
+/// day.
+pub fn approxidate(date: &str) -> Result<i64, String> {
+  let now = now();
+  if let Ok((time, _)) = parse(date) {
+    let date_only = !date.trim().contains([' ', 'T']) && parse_iso8601(date.trim()).is_some();
+    return Ok(match date_only {
+      true => time + (now + local_offset(now) as i64 * 60).rem_euclid(DAY),
+      false => time,
+    });
+  }
+
+  let invalid = || format!("invalid date format: {}", date);
+  let lower = date.to_ascii_lowercase().replace('.', " ");
+  let words: Vec<&str> = lower.split_whitespace().collect();
+  match words[..] {
+    ["now"] => return Ok(now),
+    ["yesterday"] => return Ok(now - DAY),
+    _ => (),
+  }
+  let pairs = match words.split_last() {
+    Some((&"ago", pairs)) if !pairs.is_empty() && pairs.len() % 2 == 0 => pairs,
+    _ => return Err(invalid()),
+  };
+  let (mut seconds, mut months) = (0, 0);
+  for pair in pairs.chunks(2) {
+    let count: i64 = pair[0].parse().map_err(|_| invalid())?;
+    match pair[1].strip_suffix('s').unwrap_or(pair[1]) {
+      "second" => seconds += count,
+      "minute" => seconds += count * 60,
+      "hour" => seconds += count * 3600,
+      "day" => seconds += count * DAY,
+      "week" => seconds += count * 7 * DAY,
+      "month" => months += count,
+      "year" => months += count * 12,
+      _ => return Err(invalid()),
+    }
+  }
+
+  // months and years go back in the calendar, keeping the day and the time
+  let time = now - seconds;
+  let offset = local_offset(time) as i64 * 60;
+  let local = time + offset;
+  let (year, month, day) = civil_from_days(local.div_euclid(DAY));
+  let month = year * 12 + month - 1 - months;
+  let days = days_from_civil(month.div_euclid(12), month.rem_euclid(12) + 1, day);
+  Ok(days * DAY + local.rem_euclid(DAY) - offset)
+}
+
+/// Formats a timezone offset (in minutes east of UTC) as `+hhmm`.
+pub fn format_offset(offset: i32) -> String {
+  let sign = if offset < 0 { '-' } else { '+' };
+  format!("{}{:02}{:02}", sign, offset.abs() / 60, offset.abs() % 60)
+}
+
+/// The ways a date can be shown (`--date=<style>`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Style {
+  /// `Thu Apr 7 22:13:13 2005 +0200`
+  #[default]
+  Default,
+
+  /// The default style in the local timezone, without the offset.
+  Local,
+
+  /// `2005-04-07 22:13:13 +0200`
+  Iso,
+
+  /// `2005-04-07T22:13:13+02:00`
+  IsoStrict,
+
+  /// `Thu, 7 Apr 2005 22:13:13 +0200`
+  Rfc,
+
+  /// `2005-04-07`
+  Short,
+
+  /// `1112911993 +0200`
+  Raw,
+
+  /// `1112911993`
+  Unix,
+
+  /// `2 hours ago`
+  Relative,
+}
+
+impl Style {
+  /// Parses the name of a style, as given to `--date`.
+  pub fn parse(name: &str) -> Result<Self, String> {
+    match name {
+      "default" => Ok(Style::Default),
+      "local" | "default-local" => Ok(Style::Local),
+      "iso" | "iso8601" => Ok(Style::Iso),
+      "iso-strict" | "iso8601-strict" => Ok(Style::IsoStrict),
+      "rfc" | "rfc2822" => Ok(Style::Rfc),
+      "short" => Ok(Style::Short),
+      "raw" => Ok(Style::Raw),
+      "unix" => Ok(Style::Unix),
+      "relative" => Ok(Style::Relative),
+      _ => Err(format!("unknown date format {}", name)),
+    }
+  }
+}
+
+/// Formats a date in the given style, in the timezone it was recorded in
+/// (except for `Style::Local`).
+pub fn format(time: i64, offset: i32, style: Style) -> String {
+  let offset = match style {
+    Style::Local => local_offset(time),
+    _ => offset,
+  };
+  let local = time + offset as i64 * 60;
+  let days = local.div_euclid(DAY);
+  let seconds = local.rem_euclid(DAY);
+  let (year, month, day) = civil_from_days(days);
+  let (hour, minute, second) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+  // the epoch was a Thursday
+  let weekday = WEEKDAYS[(days + 4).rem_euclid(7) as usize];
+  let month_name = MONTHS[month as usize - 1];
+  let clock = format!("{:02}:{:02}:{:02}", hour, minute, second);
+  match style {
+    Style::Default => format!(
+      "{} {} {} {} {} {}",
+      weekday,
+      month_name,
+      day,
+      clock,
+      year,
+      format_offset(offset)
+    ),
+    Style::Local => format!("{} {} {} {} {}", weekday, month_name, day, clock, year),
+    Style::Iso => format!(
+      "{}-{:02}-{:02} {} {}",
+      year,
+      month,
+      day,
+      clock,
+      format_offset(offset)
+    ),
+    Style::IsoStrict => {
+      let zone = format_offset(offset);
+      format!(
+        "{}-{:02}-{:02}T{}{}:{}",
+        year,
+        month,
+        day,
+        clock,
+        &zone[..3],
+        &zone[3..]
+      )
+    }
+    Style::Rfc => format!(
+      "{}, {} {} {} {} {}",
+      weekday,
+      day,
+      month_name,
+      year,
+      clock,
+      format_offset(offset)
+    ),
+    Style::Short => format!("{}-{:02}-{:02}", year, month, day),
+    Style::Raw => format!("{} {}", time, format_offset(offset)),
+    Style::Unix => time.to_string(),
+    Style::Relative => relative(time, now()),
+  }
+}
+
+/// Describes how long before `now` a time was, the way git does (`3 days
+/// ago`, `1 year, 2 months ago`).
+fn relative(time: i64, now: i64) -> String {
+  if now < time {
+    return "in the future".to_string();
+  }
+  let plural = |n: i64, unit: &str| match n {
+    1 => format!("{} {}", n, unit),
+    _ => format!("{} {}s", n, unit),
+  };
+  let seconds = now - time;
+  if seconds < 90 {
+    return format!("{} ago", plural(seconds, "second"));
+  }
+  let minutes = (seconds + 30) / 60;
+  if minutes < 90 {
+    return format!("{} ago", plural(minutes, "minute"));
+  }
+  let hours = (minutes + 30) / 60;
+  if hours < 36 {
+    return format!("{} ago", plural(hours, "hour"));
+  }
+  let days = (hours + 12) / 24;
+  if days < 14 {
+    return format!("{} ago", plural(days, "day"));
+  }
+  if days < 70 {
+    return format!("{} ago", plural((days + 3) / 7, "week"));
+  }
+  if days < 365 {
+    return format!("{} ago", plural((days + 15) / 30, "month"));
+  }
+  if days < 1825 {
+    let months = (days * 12 * 2 + 365) / (365 * 2);
+    return match months % 12 {
+      0 => format!("{} ago", plural(months / 12, "year")),
+      rest => format!(
+        "{}, {} ago",
+        plural(months / 12, "year"),
+        plural(rest, "month")
+      ),
+    };
+  }
+  format!("{} ago", plural((days + 183) / 365, "year"))
+}
+
+/// Parses a `+hhmm` (or `+hh:mm`, or `Z`) timezone into minutes east of UTC.
+pub fn parse_offset(zone: &str) -> Option<i32> {
+  if zone == "Z" || zone == "z" {
+    return Some(0);
+  }
+  let sign = match zone.as_bytes().first()? {
+    b'+' => 1,
+    b'-' => -1,
+    _ => return None,
+  };
+  let digits: String = zone[1..].chars().filter(|&ch| ch != ':').collect();
+  if digits.len() != 4 || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+    return None;
+  }
+  let hours: i32 = digits[..2].parse().ok()?;
+  let minutes: i32 = digits[2..].parse().ok()?;
+  Some(sign * (hours * 60 + minutes))
+}
+
+/// The broken-down fields of a date (year, month, day, hour, minute, second).
+type Fields = (i64, i64, i64, i64, i64, i64);
+
+/// Parses `2005-04-07T22:13:13.123+02:00` and friends (the time and the zone
+/// are optional, and the time may also be separated by a space).
+fn parse_iso8601(date: &str) -> Option<(Fields, Option<i32>)> {
+  let (day_part, rest) = match date.find(['T', ' ']) {
+    Some(i) => (&date[..i], date[i + 1..].trim_start()),
+    None => (date, ""),
+  };
+  let mut ymd = day_part.splitn(3, '-');
+  let year: i64 = ymd.next()?.parse().ok()?;
+  let month: i64 = ymd.next()?.parse().ok()?;
+  let day: i64 = ymd.next()?.parse().ok()?;
+  if rest.is_empty() {
+    return Some(((year, month, day, 0, 0, 0), None));
+  }
+
+  let zone_start = rest.find(['Z', 'z', '+', '-']).unwrap_or(rest.len());
+  let zone = match rest[zone_start..].trim() {
+    "" => None,
+    zone => Some(parse_offset(zone)?),
+  };
+  let (hour, minute, second) = parse_time(rest[..zone_start].trim())?;
+  Some(((year, month, day, hour, minute, second), zone))
+}
+
+/// Parses `Thu, 07 Apr 2005 22:13:13` (the weekday is optional; the zone has
+/// already been split off).
+fn parse_rfc2822(date: &str) -> Option<(Fields, Option<i32>)> {
+  let date = match date.split_once(',') {
+    Some((_weekday, rest)) => rest.trim_start(),
+    None => date,
+  };
+  let mut fields = date.split_whitespace();
+  let day: i64 = fields.next()?.parse().ok()?;
+  let month = fields.next()?;
+  let month = MONTHS
+    .iter()
+    .position(|name| name.eq_ignore_ascii_case(&month[..month.len().min(3)]))? as i64
+    + 1;
+  let year: i64 = fields.next()?.parse().ok()?;
+  let (hour, minute, second) = match fields.next() {
+    Some(time) => parse_time(time)?,
+    None => (0, 0, 0),
+  };
+  Some(((year, month, day, hour, minute, second), None))
+}
+
+/// Parses `hh:mm[:ss[.fraction]]`.
+fn parse_time(time: &str) -> Option<(i64, i64, i64)> {
+  let mut parts = time.splitn(3, ':');
+  let hour = parts.next()?.parse().ok()?;
+  let minute = parts.next()?.parse().ok()?;
+  let second = match parts.next() {
+    Some(second) => second.split('.').next()?.parse().ok()?,
+    None => 0,
+  };
+  Some((hour, minute, second))
+}
+
+/// Returns the number of days between the epoch and a date of the proleptic
+/// Gregorian calendar.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year.div_euclid(400);
+  let year_of_era = year - era * 400;
+  let month_index = (month + 9) % 12;
+  let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+  let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+  era * 146097 + day_of_era - 719468
+}
+
+/// Returns the (year, month, day) of a number of days since the epoch.
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+  let days = days + 719468;
+  let era = days.div_euclid(146097);
+  let day_of_era = days - era * 146097;
+  let year_of_era =
+    (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month_index = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+  let month = if month_index < 10 {
+    month_index + 3
+  } else {
+    month_index - 9
+  };
+  let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+  (year, month, day)
+}
+
+/// Returns the offset (in minutes east of UTC) of the local timezone at the
+/// given time.
+///
+/// The timezone comes from `TZ` (either the name of a zoneinfo file or a POSIX
+/// timezone string like `EST5EDT,M3.2.0,M11.1.0`) or else `/etc/localtime`.
+/// Anything we can't make sense of is treated as UTC.
+pub fn local_offset(time: i64) -> i32 {
+  let (data, rule) = match env::var("TZ") {
+    Ok(tz) => {
+      let name = tz.strip_prefix(':').unwrap_or(&tz);
+      let path = match name.starts_with('/') {
+        true => name.to_string(),
+        false => format!("/usr/share/zoneinfo/{}", name),
+      };
+      match fs::read(path) {
+        Ok(data) if !name.is_empty() => (Some(data), None),
+        _ => (None, Some(name.to_string())),
+      }
+    }
+    Err(_) => (fs::read("/etc/localtime").ok(), None),
+  };
+  let offset = match (data, rule) {
+    (Some(data), _) => tzif_offset(&data, time),
+    (None, Some(rule)) => PosixTz::parse(&rule).map(|tz| tz.offset(time)),
+    (None, None) => None,
+  };
+  (offset.unwrap_or(0) / 60) as i32
+}
+
+/// Looks up the UTC offset (in seconds) at a given time in a compiled zoneinfo
+/// (TZif) file.
+///
+/// Version 2 and later files repeat the data with 64-bit transition times
+/// after the version 1 data and end with a POSIX timezone string describing
+/// the times after the last transition.
+fn tzif_offset(data: &[u8], time: i64) -> Option<i64> {
+  if data.get(..4)? != b"TZif" {
+    return None;
+  }
+  let read_counts = |header: usize| -> Option<[usize; 6]> {
+    let mut counts = [0; 6];
+    for (i, count) in counts.iter_mut().enumerate() {
+      let start = header + 20 + i * 4;
+      *count = u32::from_be_bytes(data.get(start..start + 4)?.try_into().ok()?) as usize;
+    }
+    Some(counts)
+  };
+  let block_len = |counts: [usize; 6], time_len: usize| {
+    let [isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt] = counts;
+    timecnt * (time_len + 1) + typecnt * 6 + charcnt + leapcnt * (time_len + 4) + isstdcnt + isutcnt
+  };
+
+  let mut header = 0;
+  let mut time_len = 4;
+  let mut counts = read_counts(header)?;
+  if data[4] >= b'2' {
+    header = 44 + block_len(counts, 4);
+    time_len = 8;
+    counts = read_counts(header)?;
+  }
+  let timecnt = counts[3];
+  let times = header + 44;
+  let indices = times + timecnt * time_len;
+  let types = indices + timecnt;
+
+  let transition = |i: usize| -> Option<i64> {
+    let bytes = data.get(times + i * time_len..times + (i + 1) * time_len)?;
+    Some(match time_len {
+      4 => i32::from_be_bytes(bytes.try_into().ok()?) as i64,
+      _ => i64::from_be_bytes(bytes.try_into().ok()?),
+    })
+  };
+  let utoff = |index: usize| -> Option<i64> {
+    let start = types + index * 6;
+    Some(i32::from_be_bytes(data.get(start..start + 4)?.try_into().ok()?) as i64)
+  };
+
+  // the footer takes over after the last transition
+  let last = match timecnt {
+    0 => None,
+    _ => Some(transition(timecnt - 1)?),
+  };
+  if header != 0 && last.is_none_or(|last| time >= last) {
+    let footer = data.get(header + 44 + block_len(counts, 8)..)?;
+    let footer = String::from_utf8_lossy(footer);
+    if let Some(tz) = PosixTz::parse(footer.trim()) {
+      return Some(tz.offset(time));
+    }
+  }
+
+  let mut found = None;
+  for i in 0..timecnt {
+    if transition(i)? > time {
+      break;
+    }
+    found = Some(i);
+  }
+  match found {
+    Some(i) => utoff(*data.get(indices + i)? as usize),
+    None => utoff(0),
+  }
+}
+
+/// A POSIX timezone string, eg. `CET-1CEST,M3.5.0,M10.5.0/3`.
+///
+/// Offsets in these strings count hours *west* of UTC, the opposite of what
+/// everything else uses.
+struct PosixTz {
+  /// The UTC offset (in seconds) of standard time.
+  std_offset: i64,
+
+  /// The UTC offset of daylight saving time, and when it starts and ends.
+  dst: Option<(i64, Rule, Rule)>,
+}
+
+/// The day (and time of day, in seconds) a daylight saving period starts or
+/// ends.
+enum Rule {
+  /// `Jn`: day 1 to 365, never counting February 29th.
+  Julian(i64, i64),
+
+  /// `n`: day 0 to 365, counting February 29th.
+  Day(i64, i64),
+
+  /// `Mm.w.d`: weekday d (0 is Sunday) of week w (5 is the last) of month m.
+  Month(i64, i64, i64, i64),
+}
+
+impl PosixTz {
+  fn parse(spec: &str) -> Option<Self> {
+    let mut rest = spec;
+    skip_name(&mut rest)?;
+    let std_offset = -parse_duration(&mut rest)?;
+    if rest.is_empty() {
+      return Some(Self {
+        std_offset,
+        dst: None,
+      });
+    }
+    skip_name(&mut rest)?;
+    let dst_offset = match rest.starts_with(',') || rest.is_empty() {
+      true => std_offset + 3600,
+      false => -parse_duration(&mut rest)?,
+    };
+    // without rules, daylight saving time follows the US rules
+    let rules = rest.strip_prefix(',').unwrap_or("M3.2.0,M11.1.0");
+    let (start, end) = rules.split_once(',')?;
+    Some(Self {
+      std_offset,
+      dst: Some((dst_offset, Rule::parse(start)?, Rule::parse(end)?)),
+    })
+  }
+
+  /// Returns the UTC offset (in seconds) at the given time.
+  fn offset(&self, time: i64) -> i64 {
+    let (dst_offset, start, end) = match &self.dst {
+      Some(dst) => dst,
+      None => return self.std_offset,
+    };
+    let (year, _, _) = civil_from_days((time + self.std_offset).div_euclid(DAY));
+    // transitions are given in the local time that is in effect before them
+    let start = start.local_time(year) - self.std_offset;
+    let end = end.local_time(year) - dst_offset;
+    let in_dst = match start < end {
+      true => start <= time && time < end,
+      false => !(end <= time && time < start),
+    };
+    match in_dst {
+      true => *dst_offset,
+      false => self.std_offset,
+    }
+  }
+}
+
+impl Rule {
+  fn parse(spec: &str) -> Option<Self> {
+    let (date, time) = match spec.split_once('/') {
+      Some((date, time)) => (date, parse_duration(&mut &*time)?),
+      None => (spec, 2 * 3600),
+    };
+    if let Some(day) = date.strip_prefix('J') {
+      return Some(Rule::Julian(day.parse().ok()?, time));
+    }
+    if let Some(month) = date.strip_prefix('M') {
+      let mut fields = month.split('.').map(|field| field.parse::<i64>().ok());
+      return Some(Rule::Month(
+        fields.next()??,
+        fields.next()??,
+        fields.next()??,
+        time,
+      ));
+    }
+    Some(Rule::Day(date.parse().ok()?, time))
+  }
+
+  /// Returns the local time (seconds since the epoch, as if local time was
+  /// UTC) the rule describes in the given year.
+  fn local_time(&self, year: i64) -> i64 {
+    let jan_1 = days_from_civil(year, 1, 1);
+    let leap = days_from_civil(year, 3, 1) - days_from_civil(year, 2, 28) == 2;
+    let (days, time) = match *self {
+      Rule::Julian(day, time) => (jan_1 + day - 1 + (leap && day >= 60) as i64, time),
+      Rule::Day(day, time) => (jan_1 + day, time),
+      Rule::Month(month, week, weekday, time) => {
+        let first = days_from_civil(year, month, 1);
+        let first_weekday = (first + 4).rem_euclid(7); // the epoch was a Thursday
+        let mut day = first + (weekday - first_weekday).rem_euclid(7) + (week - 1) * 7;
+        let next_month = match month {
+          12 => days_from_civil(year + 1, 1, 1),
+          _ => days_from_civil(year, month + 1, 1),
+        };
+        while day >= next_month {
+          day -= 7;
+        }
+        (day, time)
+      }
+    };
+    days * DAY + time
+  }
+}
+
+/// Skips over the name of a zone (`CET` or `<+0330>`).
+fn skip_name(spec: &mut &str) -> Option<()> {
+  let len = match spec.strip_prefix('<') {
+    Some(quoted) => quoted.find('>')? + 2,
+    None => spec
+      .find(|ch: char| !ch.is_ascii_alphabetic())
+      .unwrap_or(spec.len()),
+  };
+  if len < 3 {
+    return None;
+  }
+  *spec = &spec[len..];
+  Some(())
+}
+
+/// Parses a `[+-]hh[:mm[:ss]]` duration into seconds.
+fn parse_duration(spec: &mut &str) -> Option<i64> {
+  let (sign, rest) = match spec.as_bytes().first()? {
+    b'-' => (-1, &spec[1..]),
+    b'+' => (1, &spec[1..]),
+    _ => (1, *spec),
+  };
+  let len = rest
+    .find(|ch: char| !ch.is_ascii_digit() && ch != ':')
+    .unwrap_or(rest.len());
+  let mut seconds = 0;
+  let mut scale = 3600;
+  for part in rest[..len].split(':') {
+    seconds += part.parse::<i64>().ok()? * scale;
+    scale /= 60;
+  }
+  *spec = &rest[len..];
+  Some(sign * seconds)
+}
@@ -0,0 +1,102 @@
+/// Applies a git delta to a base object and returns the resulting object.
+///
+/// A delta starts with two little-endian base-128 numbers, the size of the base
+/// and the size of the result, followed by a series of instructions. Each
+/// instruction starts with a single opcode byte:
+///
+/// ```text
+/// 1xxxxxxx  copy:   the low 4 bits say which offset bytes follow and bits 4-6
+///                   say which size bytes follow (little-endian, missing bytes
+///                   are zero); copies `size` bytes of the base from `offset`.
+///                   A size of zero means 0x10000.
+/// 0xxxxxxx  insert: appends the next `x` bytes of the delta verbatim.
+/// 00000000  reserved (invalid).
+/// ```
+pub fn apply(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, String> {
+  let mut pos = 0;
+  let base_size = read_size(delta, &mut pos)?;
+  if base_size != base.len() {
+    return Err(format!(
+      "delta base size mismatch (expected {}, got {})",
+      base_size,
+      base.len()
+    ));
+  }
+  let result_size = read_size(delta, &mut pos)?;
+  let mut result = Vec::with_capacity(result_size);
+
+  while pos < delta.len() {
+    let opcode = delta[pos];
+    pos += 1;
+    if opcode & 0x80 != 0 {
+      let offset = read_copy_arg(delta, &mut pos, opcode, 4)?;
+      let size = match read_copy_arg(delta, &mut pos, opcode >> 4, 3)? {
+        0 => 0x10000,
+        size => size,
+      };
+      match offset.checked_add(size) {
+        Some(end) if end <= base.len() => result.extend_from_slice(&base[offset..end]),
+        _ => return Err("delta copy out of bounds".to_string()),
+      }
+    } else if opcode != 0 {
+      let end = pos + opcode as usize;
+      if end > delta.len() {
+        return Err("delta insert out of bounds".to_string());
+      }
+      result.extend_from_slice(&delta[pos..end]);
+      pos = end;
+    } else {
+      return Err("invalid delta opcode 0".to_string());
+    }
+  }
+
+  if result.len() != result_size {
+    return Err(format!(
+      "delta result size mismatch (expected {}, got {})",
+      result_size,
+      result.len()
+    ));
+  }
+  Ok(result)
+}
+
+/// Reads a little-endian base-128 size from the delta header.
+fn read_size(delta: &[u8], pos: &mut usize) -> Result<usize, String> {
+  let mut size = 0;
+  let mut shift = 0;
+  loop {
+    let byte = match delta.get(*pos) {
+      Some(byte) => *byte,
+      None => return Err("truncated delta header".to_string()),
+    };
+    *pos += 1;
+    size |= ((byte & 0x7f) as usize) << shift;
+    shift += 7;
+    if byte & 0x80 == 0 {
+      return Ok(size);
+    }
+  }
+}
+
+/// Reads the optional argument bytes of a copy instruction.
+///
+/// Bit `i` of `present` says whether byte `i` of the (little-endian) argument
+/// is stored in the delta; bytes that are not stored are zero.
+fn read_copy_arg(
+  delta: &[u8],
+  pos: &mut usize,
+  present: u8,
+  count: usize,
+) -> Result<usize, String> {
+  let mut value = 0;
+  for i in 0..count {
+    if present & (1 << i) != 0 {
+      match delta.get(*pos) {
+        Some(byte) => value |= (*byte as usize) << (8 * i),
+        None => return Err("truncated delta copy instruction".to_string()),
+      }
+      *pos += 1;
+    }
+  }
+  Ok(value)
+}
@@ -13,11 +13,6 @@ impl Findable for [u8] {
   /// assert_eq!(my_slice.find('z'), None);
   /// ```
   fn find(&self, ch: u8, offset: usize) -> Option<usize> {
-    for i in offset..self.len() {
-      if self.get(i).unwrap().eq(&ch) {
-        return Some(i);
-      }
-    }
-    None
+    (offset..self.len()).find(|&i| self[i] == ch)
   }
 }
@@ -0,0 +1,573 @@
+//! Draws the history next to the commits `git log --graph` shows.
+//!
+//! The graph is drawn one line at a time. Each commit has one column of the
+//! graph for every line of history that is still open, and is shown on a line
+//! of its own with the edges to its parents drawn below it:
+//! ```text
+//! *   merge
+//! |\
+//! | * side
+//! * | main
+//! |/
+//! * root
+//! ```
+
+/// The lines drawn below the parents of a merge, from the leftmost parent on.
+const MERGE_CHARS: [char; 3] = ['/', '|', '\\'];
+
+/// The kind of line the graph draws next.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum State {
+  /// Every column goes straight down (the commit has been drawn).
+  #[default]
+  Padding,
+
+  /// `...`, for when the previous commit wasn't drawn to the end.
+  Skip,
+
+  /// The columns right of an octopus merge move right, to make room for the
+  /// edges to its parents.
+  PreCommit,
+
+  /// The line of the commit itself.
+  Commit,
+
+  /// The edges from a merge to its parents.
+  PostMerge,
+
+  /// Columns moving left, towards the column they are going to end up in.
+  Collapsing,
+}
+
+/// The state of a graph as it is drawn.
+///
+/// ```ignore
+/// let mut graph = Graph::default();
+/// for (hash, parents) in commits {
+///   graph.update(hash, parents);
+///   graph.show_commit(&mut out);
+///   graph.show_commit_msg(&mut out, &message);
+/// }
+/// ```
+#[derive(Default)]
+pub struct Graph {
+  /// The commit being drawn.
+  commit: String,
+
+  /// The parents of the commit that are drawn (the ones that are shown).
+  parents: Vec<String>,
+
+  /// The width of the widest line drawn for the commit so far.
+  width: isize,
+
+  /// How many lines `PreCommit` has drawn.
+  expansion_row: isize,
+  state: State,
+  prev_state: State,
+
+  /// The column of the commit, and of the commit before it.
+  commit_index: isize,
+  prev_commit_index: isize,
+
+  /// Whether the edge from a merge to its first parent goes left (0) or down
+  /// (1), and how many columns the merge adds.
+  merge_layout: isize,
+  edges_added: isize,
+  prev_edges_added: isize,
+
+  /// The commit each column leads to, before and after the current commit.
+  columns: Vec<String>,
+  new_columns: Vec<String>,
+
+  /// For each character of the line being drawn, the column in
+  /// `new_columns` the edge there is heading for (or -1).
+  mapping: Vec<isize>,
+  mapping_size: usize,
+
+  /// The mapping of the previous collapsing line.
+  old_mapping: Vec<isize>,
+}
+
+impl Graph {
+  /// Moves on to the next commit, with the parents of it that are shown.
+  pub fn update(&mut self, hash: &str, parents: Vec<String>) {
+    self.commit = hash.to_string();
+    self.parents = parents;
+    self.prev_commit_index = self.commit_index;
+    self.update_columns();
+    self.expansion_row = 0;
+
+    // if the previous commit wasn't drawn to the end, a `...` line says so;
+    // prev_state is left alone as no line was drawn in the new state
+    self.state = if self.state != State::Padding {
+      State::Skip
+    } else if self.needs_pre_commit_line() {
+      State::PreCommit
+    } else {
+      State::Commit
+    };
+  }
+
+  /// Whether all of the lines of the current commit have been drawn.
+  pub fn is_commit_finished(&self) -> bool {
+    self.state == State::Padding
+  }
+
+  /// How wide the graph is drawn next to the lines shown after the commit.
+  pub fn width(&self) -> usize {
+    (2 * self.new_columns.len()).max(self.width as usize)
+  }
+
+  /// Draws the graph up to and including the line of the commit, which is
+  /// left unterminated so the commit can be shown after it.
+  pub fn show_commit(&mut self, out: &mut String) {
+    if self.is_commit_finished() {
+      self.padding_line(out);
+      return;
+    }
+    while !self.is_commit_finished() {
+      if self.next_line(out) {
+        break;
+      }
+      out.push('\n');
+    }
+  }
+
+  /// Draws the next line of the graph (without a newline).
+  pub fn show_oneline(&mut self, out: &mut String) {
+    self.next_line(out);
+  }
+
+  /// Draws a line that doesn't move the graph on (without a newline).
+  pub fn show_padding(&mut self, out: &mut String) {
+    self.padding_line(out);
+  }
+
+  /// Draws the remaining lines of the current commit, if there are any, with
+  /// newlines between them (but not after the last one).
+  pub fn show_remainder(&mut self, out: &mut String) -> bool {
+    if self.is_commit_finished() {
+      return false;
+    }
+    loop {
+      self.next_line(out);
+      if self.is_commit_finished() {
+        return true;
+      }
+      out.push('\n');
+    }
+  }
+
+  /// Shows a message with the graph drawn before each of its lines but the
+  /// first, and then the rest of the graph of the commit.
+  pub fn show_commit_msg(&mut self, out: &mut String, message: &str) {
+    let mut rest = message;
+    while let Some(end) = rest.find('\n') {
+      out.push_str(&rest[..=end]);
+      rest = &rest[end + 1..];
+      if !rest.is_empty() {
+        self.show_oneline(out);
+      }
+    }
+    out.push_str(rest);
+
+    if !self.is_commit_finished() {
+      let newline_terminated = message.ends_with('\n');
+      if !newline_terminated {
+        out.push('\n');
+      }
+      self.show_remainder(out);
+      if newline_terminated {
+        out.push('\n');
+      }
+    }
+  }
+
+  /// Works out the columns after the current commit, and how the columns
+  /// before it map onto them.
+  fn update_columns(&mut self) {
+    std::mem::swap(&mut self.columns, &mut self.new_columns);
+    self.new_columns.clear();
+
+    let max_new_columns = self.columns.len() + self.parents.len();
+    self.mapping_size = 2 * max_new_columns;
+    if self.mapping.len() < self.mapping_size {
+      self.mapping.resize(self.mapping_size, -1);
+      self.old_mapping.resize(self.mapping_size, -1);
+    }
+    self.mapping[..self.mapping_size].fill(-1);
+
+    self.width = 0;
+    self.prev_edges_added = self.edges_added;
+    self.edges_added = 0;
+
+    // the commit goes in its own column, or in a new one at the end if none
+    // of the commits drawn so far are its children
+    let mut seen_this = false;
+    self.commit_index = 0;
+    for i in 0..=self.columns.len() {
+      let column = match self.columns.get(i) {
+        Some(column) => column.clone(),
+        None if seen_this => break,
+        None => self.commit.clone(),
+      };
+      if column == self.commit {
+        seen_this = true;
+        self.commit_index = i as isize;
+        self.merge_layout = -1;
+        for parent in self.parents.clone() {
+          self.insert_into_new_columns(&parent, i as isize);
+        }
+        // the commit takes up two characters, even without any parents
+        if self.parents.is_empty() {
+          self.width += 2;
+        }
+      } else {
+        self.insert_into_new_columns(&column, -1);
+      }
+    }
+
+    while self.mapping_size > 1 && self.mapping[self.mapping_size - 1] < 0 {
+      self.mapping_size -= 1;
+    }
+  }
+
+  /// Adds a commit to the new columns (unless it is there already) and maps
+  /// the next character of the line onto it. `index` is the column of the
+  /// current commit when a parent of it is added.
+  fn insert_into_new_columns(&mut self, commit: &str, index: isize) {
+    let i = match self.new_columns.iter().position(|column| column == commit) {
+      Some(i) => i as isize,
+      None => {
+        self.new_columns.push(commit.to_string());
+        self.new_columns.len() as isize - 1
+      }
+    };
+
+    let mapping_index;
+    if self.parents.len() > 1 && index > -1 && self.merge_layout == -1 {
+      // the first parent of a merge: the edges of the merge are laid out
+      // depending on whether the parent is in a column left of the merge
+      let distance = index - i;
+      let shift = if distance > 1 { 2 * distance - 3 } else { 1 };
+      self.merge_layout = if distance > 0 { 0 } else { 1 };
+      self.edges_added = self.parents.len() as isize + self.merge_layout - 2;
+      mapping_index = self.width + (self.merge_layout - 1) * shift;
+      self.width += 2 * self.merge_layout;
+    } else if self.edges_added > 0 && i == self.get_mapping(self.width - 2) {
+      // the edge joins the last one a merge added straight away
+      mapping_index = self.width - 2;
+      self.edges_added = -1;
+    } else {
+      mapping_index = self.width;
+      self.width += 2;
+    }
+    self.set_mapping(mapping_index, i);
+  }
+
+  fn get_mapping(&self, index: isize) -> isize {
+    usize::try_from(index)
+      .ok()
+      .and_then(|index| self.mapping.get(index))
+      .copied()
+      .unwrap_or(-1)
+  }
+
+  fn set_mapping(&mut self, index: isize, value: isize) {
+    let index = index as usize;
+    if self.mapping.len() <= index {
+      self.mapping.resize(index + 1, -1);
+      self.old_mapping.resize(index + 1, -1);
+    }
+    self.mapping[index] = value;
+  }
+
+  /// Whether every edge has reached the column it is heading for.
+  fn is_mapping_correct(&self) -> bool {
+    self.mapping[..self.mapping_size]
+      .iter()
+      .enumerate()
+      .all(|(i, &target)| target < 0 || target == i as isize / 2)
+  }
+
+  /// The number of parents of an octopus merge whose edges are drawn with
+  /// dashes on the line of the commit.
+  fn num_dashed_parents(&self) -> isize {
+    self.parents.len() as isize + self.merge_layout - 3
+  }
+
+  fn needs_pre_commit_line(&self) -> bool {
+    self.parents.len() >= 3
+      && self.commit_index < self.columns.len() as isize - 1
+      && self.expansion_row < self.num_dashed_parents() * 2
+  }
+
+  fn update_state(&mut self, state: State) {
+    self.prev_state = self.state;
+    self.state = state;
+  }
+
+  /// Draws the next line of the graph, padded to the width of the commit, and
+  /// returns whether it was the line of the commit itself.
+  fn next_line(&mut self, line: &mut String) -> bool {
+    let start = line.len();
+    let mut shown_commit_line = false;
+    match self.state {
+      State::Padding => {
+        for _ in &self.new_columns {
+          line.push_str("| ");
+        }
+      }
+      State::Skip => {
+        line.push_str("...");
+        match self.needs_pre_commit_line() {
+          true => self.update_state(State::PreCommit),
+          false => self.update_state(State::Commit),
+        }
+      }
+      State::PreCommit => self.pre_commit_line(line),
+      State::Commit => {
+        self.commit_line(line);
+        shown_commit_line = true;
+      }
+      State::PostMerge => self.post_merge_line(line),
+      State::Collapsing => self.collapsing_line(line),
+    }
+    self.pad(line, start);
+    shown_commit_line
+  }
+
+  /// Pads the line started at `start` with spaces, so that all of the lines
+  /// of a commit are as wide.
+  fn pad(&self, line: &mut String, start: usize) {
+    let width = (line.len() - start) as isize;
+    if width < self.width {
+      line.extend(std::iter::repeat_n(' ', (self.width - width) as usize));
+    }
+  }
+
+  /// Draws a line that leaves every column as it is. On the line of a commit
+  /// this is done without moving on to the next line.
+  fn padding_line(&mut self, line: &mut String) {
+    if self.state != State::Commit {
+      self.next_line(line);
+      return;
+    }
+    let start = line.len();
+    for column in &self.columns {
+      line.push('|');
+      if *column == self.commit && self.parents.len() > 2 {
+        line.extend(std::iter::repeat_n(' ', (self.parents.len() - 2) * 2));
+      } else {
+        line.push(' ');
+      }
+    }
+    self.pad(line, start);
+    self.prev_state = State::Padding;
+  }
+
+  /// Moves the columns right of an octopus merge to the right, two lines for
+  /// every parent drawn with dashes.
+  fn pre_commit_line(&mut self, line: &mut String) {
+    let mut seen_this = false;
+    for (i, column) in self.columns.iter().enumerate() {
+      if *column == self.commit {
+        seen_this = true;
+        line.push('|');
+        line.extend(std::iter::repeat_n(' ', self.expansion_row as usize));
+      } else if seen_this && self.expansion_row == 0 {
+        // the edges right of a merge drawn just before go on as they were
+        if self.prev_state == State::PostMerge && self.prev_commit_index < i as isize {
+          line.push('\\');
+        } else {
+          line.push('|');
+        }
+      } else if seen_this {
+        line.push('\\');
+      } else {
+        line.push('|');
+      }
+      line.push(' ');
+    }
+
+    self.expansion_row += 1;
+    if !self.needs_pre_commit_line() {
+      self.update_state(State::Commit);
+    }
+  }
+
+  fn commit_line(&mut self, line: &mut String) {
+    let mut seen_this = false;
+    for i in 0..=self.columns.len() {
+      let column = match self.columns.get(i) {
+        Some(column) => column,
+        None if seen_this => break,
+        None => &self.commit,
+      };
+      let i = i as isize;
+      if *column == self.commit {
+        seen_this = true;
+        line.push('*');
+        if self.parents.len() > 2 {
+          let dashed = self.num_dashed_parents();
+          for j in 0..dashed {
+            line.push('-');
+            line.push(if j == dashed - 1 { '.' } else { '-' });
+          }
+        }
+      } else if seen_this && self.edges_added > 1 {
+        line.push('\\');
+      } else if seen_this && self.edges_added == 1 {
+        // the first line of a merge with no pre-commit lines: the edges
+        // right of a merge drawn just before go on as they were
+        if self.prev_state == State::PostMerge
+          && self.prev_edges_added > 0
+          && self.prev_commit_index < i
+        {
+          line.push('\\');
+        } else {
+          line.push('|');
+        }
+      } else if self.prev_state == State::Collapsing
+        && self.old_mapping.get(2 * i as usize + 1).copied() == Some(i)
+        && self.get_mapping(2 * i) < i
+      {
+        line.push('/');
+      } else {
+        line.push('|');
+      }
+      line.push(' ');
+    }
+
+    if self.parents.len() > 1 {
+      self.update_state(State::PostMerge);
+    } else if self.is_mapping_correct() {
+      self.update_state(State::Padding);
+    } else {
+      self.update_state(State::Collapsing);
+    }
+  }
+
+  fn post_merge_line(&mut self, line: &mut String) {
+    let mut seen_this = false;
+    for i in 0..=self.columns.len() {
+      let column = match self.columns.get(i) {
+        Some(column) => column,
+        None if seen_this => break,
+        None => &self.commit,
+      };
+      if *column == self.commit {
+        seen_this = true;
+        let mut index = self.merge_layout as usize;
+        for j in 0..self.parents.len() {
+          line.push(MERGE_CHARS[index]);
+          if index == 2 {
+            if self.edges_added > 0 || j < self.parents.len() - 1 {
+              line.push(' ');
+            }
+          } else {
+            index += 1;
+          }
+        }
+        if self.edges_added == 0 {
+          line.push(' ');
+        }
+      } else if seen_this {
+        line.push(if self.edges_added > 0 { '\\' } else { '|' });
+        line.push(' ');
+      } else {
+        line.push('|');
+        if self.merge_layout != 0 || i as isize != self.commit_index - 1 {
+          line.push(' ');
+        }
+      }
+    }
+
+    match self.is_mapping_correct() {
+      true => self.update_state(State::Padding),
+      false => self.update_state(State::Collapsing),
+    }
+  }
+
+  /// Moves every edge that isn't in the column it is heading for one
+  /// character to the left, or across other edges with `_` (only one edge
+  /// does so per line).
+  fn collapsing_line(&mut self, line: &mut String) {
+    std::mem::swap(&mut self.mapping, &mut self.old_mapping);
+    let size = self.mapping_size;
+    self.mapping[..size].fill(-1);
+
+    let mut horizontal_edge = -1;
+    let mut horizontal_edge_target = -1;
+    for i in 0..size {
+      let target = self.old_mapping[i];
+      if target < 0 {
+        continue;
+      }
+      let i = i as isize;
+      let left = self.get_mapping(i - 1);
+      if target * 2 == i {
+        // already in the right place
+        self.set_mapping(i, target);
+      } else if left < 0 {
+        // nothing to the left, so move there
+        self.set_mapping(i - 1, target);
+        if horizontal_edge == -1 {
+          horizontal_edge = i;
+          horizontal_edge_target = target;
+          let mut j = target * 2 + 3;
+          while j < i - 2 {
+            self.set_mapping(j, target);
+            j += 2;
+          }
+        }
+      } else if left == target {
+        // the edge to the left goes to the same commit, so they join
+      } else {
+        // cross over the edge to the left
+        self.set_mapping(i - 2, target);
+        if horizontal_edge == -1 {
+          horizontal_edge_target = target;
+          horizontal_edge = i - 1;
+          let mut j = target * 2 + 3;
+          while j < i - 2 {
+            self.set_mapping(j, target);
+            j += 2;
+          }
+        }
+      }
+    }
+
+    self.old_mapping[..size].copy_from_slice(&self.mapping[..size]);
+    if self.mapping[size - 1] < 0 {
+      self.mapping_size -= 1;
+    }
+
+    let mut used_horizontal = false;
+    for i in 0..self.mapping_size {
+      let target = self.mapping[i];
+      let i = i as isize;
+      if target < 0 {
+        line.push(' ');
+      } else if target * 2 == i {
+        line.push('|');
+      } else if target == horizontal_edge_target && i != horizontal_edge - 1 {
+        // only the first part of a horizontal edge goes on to the next line
+        if i != target * 2 + 3 {
+          self.mapping[i as usize] = -1;
+        }
+        used_horizontal = true;
+        line.push('_');
+      } else {
+        if used_horizontal && i < horizontal_edge {
+          self.mapping[i as usize] = -1;
+        }
+        line.push('/');
+      }
+    }
+
+    if self.is_mapping_correct() {
+      self.update_state(State::Padding);
+    }
+  }
+}
@@ -0,0 +1,141 @@
+use std::env;
+use std::fmt::Display;
+use std::fs;
+
+use crate::repo::Repo;
+
+use super::date;
+
+/// Who made a change and when.
+///
+/// Identities are stored in the `author`, `committer` and `tagger` headers of
+/// commits and tags, and in reflogs, as:
+/// ```text
+/// Justin Shaw <realjustinshaw@gmail.com> 1654631458 -0700
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Identity {
+  pub name: String,
+  pub email: String,
+
+  /// Seconds since the epoch.
+  pub time: i64,
+
+  /// The timezone the change was made in, in minutes east of UTC.
+  pub offset: i32,
+}
+
+impl Identity {
+  /// Parses a `Name <email> timestamp timezone` line.
+  pub fn parse(line: &str) -> Result<Self, String> {
+    let invalid = || format!("invalid identity: {}", line);
+    let (name, rest) = line.split_once('<').ok_or_else(invalid)?;
+    let (email, rest) = rest.split_once('>').ok_or_else(invalid)?;
+    let mut fields = rest.split_whitespace();
+    let time = fields
+      .next()
+      .and_then(|time| time.parse().ok())
+      .ok_or_else(invalid)?;
+    let offset = fields
+      .next()
+      .and_then(date::parse_offset)
+      .ok_or_else(invalid)?;
+    Ok(Self {
+      name: name.trim().to_string(),
+      email: email.to_string(),
+      time,
+      offset,
+    })
+  }
+
+  /// The author of new commits.
+  pub fn author(repo: &Repo) -> Result<Self, String> {
+    Self::from_environment(repo, "AUTHOR", true)
+  }
+
+  /// The committer of new commits (also used for tags).
+  pub fn committer(repo: &Repo) -> Result<Self, String> {
+    Self::from_environment(repo, "COMMITTER", true)
+  }
+
+  /// The committer recorded in reflogs. Unlike `committer`, a missing name or
+  /// email isn't an error: like git, it falls back on the login name and
+  /// `<login>@<hostname>`.
+  pub fn reflog(repo: &Repo) -> Result<Self, String> {
+    Self::from_environment(repo, "COMMITTER", false)
+  }
+
+  /// Looks up the identity for a role (`AUTHOR` or `COMMITTER`), which must
+  /// be configured if `strict` is set.
+  ///
+  /// `GIT_<role>_NAME`, `GIT_<role>_EMAIL` and `GIT_<role>_DATE` take
+  /// precedence over `user.name`, `user.email` and the current time.
+  fn from_environment(repo: &Repo, role: &str, strict: bool) -> Result<Self, String> {
+    let lookup = |key: &str| {
+      env::var(format!("GIT_{}_{}", role, key.to_uppercase()))
+        .ok()
+        .or_else(|| repo.config_value("user", None, key))
+    };
+    let (name, email) = match (lookup("name"), lookup("email")) {
+      (Some(name), Some(email)) if !name.is_empty() => (name, email),
+      (name, email) if !strict => (
+        name.filter(|name| !name.is_empty()).unwrap_or_else(login),
+        email.unwrap_or_else(|| default_email(&login())),
+      ),
+      _ => {
+        return Err(format!(
+          "{}{} identity unknown (please set user.name and user.email)",
+          &role[..1],
+          role[1..].to_lowercase()
+        ))
+      }
+    };
+    let (time, offset) = match env::var(format!("GIT_{}_DATE", role)) {
+      Ok(date) => date::parse(&date)?,
+      Err(_) => {
+        let now = date::now();
+        (now, date::local_offset(now))
+      }
+    };
+    Ok(Self {
+      name,
+      email,
+      time,
+      offset,
+    })
+  }
+}
+
+/// The name the user logged in with.
+fn login() -> String {
+  env::var("USER")
+    .or_else(|_| env::var("LOGNAME"))
+    .unwrap_or_else(|_| String::from("unknown"))
+}
+
+/// `<login>@<hostname>`, where a hostname without a domain gets `.(none)`
+/// appended to it, the way git makes up an email address.
+fn default_email(login: &str) -> String {
+  let hostname = fs::read_to_string("/proc/sys/kernel/hostname")
+    .or_else(|_| fs::read_to_string("/etc/hostname"))
+    .map(|hostname| hostname.trim().to_string())
+    .unwrap_or_default();
+  match hostname.as_str() {
+    "" => format!("{}@(none)", login),
+    _ if !hostname.contains('.') => format!("{}@{}.(none)", login, hostname),
+    _ => format!("{}@{}", login, hostname),
+  }
+}
+
+impl Display for Identity {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{} <{}> {} {}",
+      self.name,
+      self.email,
+      self.time,
+      date::format_offset(self.offset)
+    )
+  }
+}
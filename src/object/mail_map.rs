@@ -1,11 +1,10 @@
 use crate::object::findable::Findable;
-use indexmap::IndexMap;
 
 /// A text-based key-value store.
 ///
 /// This format is a simplified version of mail messages, specified in RFC 2822.
 /// It begins with a series of key-value pairs separated with a single space. An
-/// item may span over multiple lines, subsequent lins start with a space which
+/// item may span over multiple lines, subsequent lines start with a space which
 /// the parser must drop.
 ///
 /// See: https://www.ietf.org/rfc/rfc2822.txt
@@ -37,8 +36,8 @@ use indexmap::IndexMap;
 /// update readme
 /// ```
 ///
-/// This is logically equivalent to an insertion-order-preserving map that holds
-/// the following key value pairs:
+/// This is logically equivalent to a list of key value pairs, in order, plus
+/// the message:
 /// ```text
 /// tree      => 29ff16c..930c147
 /// parent    => 2069413..24d49a0
@@ -46,90 +45,129 @@ use indexmap::IndexMap;
 /// committer => Thibault Polge <thibault@thb.lt> 1527025044 +0200
 /// gpgsig    => -----BEGIN PGP SIGNATURE----- ... -----END PGP SIGNATURE-----
 /// ```
-
+///
+/// A key may appear more than once (a merge commit has a `parent` line for
+/// each of its parents), so the pairs are kept in a list rather than a map.
+/// Parsed data is kept as it was and serialized back byte for byte; values that
+/// aren't valid UTF-8 (eg. a message in the commit's `encoding`) are only
+/// decoded lossily for the accessors.
 pub struct MailMap {
   data: Vec<u8>,
-  pub map: IndexMap<String, String>,
+  headers: Vec<(String, String)>,
+  message: String,
 }
 
 impl MailMap {
   pub fn new() -> Self {
     Self {
-      map: IndexMap::new(),
-      data: Vec::default(),
+      data: map_to_bytes(&[], ""),
+      headers: Vec::new(),
+      message: String::new(),
     }
   }
 
-  pub fn parse_bytes(&mut self, raw: &[u8], offset: usize) {
-    // Search for the next space and newline.
-    let maybe_space = raw.find(b' ', offset);
-    let maybe_newln = raw.find(b'\n', offset);
-
-    // If newline occurs first (or there's no space at all), assume blank line.
-    match (maybe_space, maybe_newln) {
-      (_any, Some(newline)) if newline <= maybe_space.unwrap_or(newline) => {
-        assert_eq!(newline, offset);
-        extract_message(&raw[offset + 1..], &mut self.map);
-      }
-      (None, None) => (), // reached the end of the raw data
-      _ => {
-        let space = maybe_space.unwrap(); // shouldn't panic
-        let next_offset = extract_entry(&raw[offset..], space, &mut self.map);
-        self.parse_bytes(raw, next_offset);
+  /// Parses the headers and the message out of the raw data.
+  pub fn parse_bytes(&mut self, raw: &[u8]) {
+    self.headers.clear();
+    self.message.clear();
+    let mut offset = 0;
+    while offset < raw.len() {
+      if raw[offset] == b'\n' {
+        // a blank line ends the headers, and the rest is the message
+        self.message = String::from_utf8_lossy(&raw[offset + 1..]).into_owned();
+        break;
       }
+      let end = extract_entry(raw, offset, &mut self.headers);
+      offset = end + 1; // skip past the newline
     }
-
-    self.data = map_to_bytes(&self.map);
+    self.data = raw.to_vec();
   }
 
   pub fn to_bytes(&self) -> &[u8] {
     self.data.as_slice()
   }
+
+  /// Returns the value of the first header with the given key.
+  pub fn get(&self, key: &str) -> Option<&str> {
+    self
+      .headers
+      .iter()
+      .find(|(k, _)| k == key)
+      .map(|(_, value)| value.as_str())
+  }
+
+  /// Returns the values of every header with the given key, in order.
+  pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+    self
+      .headers
+      .iter()
+      .filter(move |(k, _)| k == key)
+      .map(|(_, value)| value.as_str())
+  }
+
+  /// Returns the message that follows the headers.
+  pub fn message(&self) -> &str {
+    &self.message
+  }
+
+  /// Adds a header after the others.
+  pub fn push(&mut self, key: &str, value: &str) {
+    self.headers.push((key.to_string(), value.to_string()));
+    self.data = map_to_bytes(&self.headers, &self.message);
+  }
+
+  /// Replaces the message.
+  pub fn set_message(&mut self, message: &str) {
+    self.message = message.to_string();
+    self.data = map_to_bytes(&self.headers, &self.message);
+  }
 }
 
-/// After a blank line, the rest of the file is an optional message.
-fn extract_message(bytes: &[u8], map: &mut IndexMap<String, String>) {
-  let key = String::from("");
-  let value = String::from_utf8(bytes.to_vec()).expect("invalid value");
-  map.entry(key).or_insert(value);
+impl Default for MailMap {
+  fn default() -> Self {
+    Self::new()
+  }
 }
 
-/// Pulls out a single key, value pair from the file.
+/// Pulls out a single key, value pair from the line at `offset` and returns
+/// the index of the newline that ends it (or the end of the data).
 ///
 /// The key and value are separated by a space, and the value may span multiple
 /// lines. The continuation lines must be indented by a space and the space is
 /// not part of the continuation line (ie. it must be removed).
-fn extract_entry(bytes: &[u8], space: usize, map: &mut IndexMap<String, String>) -> usize {
+fn extract_entry(raw: &[u8], offset: usize, headers: &mut Vec<(String, String)>) -> usize {
   // find the first `\n` that is not followed by a space character
-  let mut end = bytes.find(b'\n', 1).unwrap();
-  while bytes[end + 1] == b' ' {
-    end = bytes.find(b'\n', end + 1).unwrap() // try again
+  let mut end = raw.find(b'\n', offset).unwrap_or(raw.len());
+  while end + 1 < raw.len() && raw[end + 1] == b' ' {
+    end = raw.find(b'\n', end + 1).unwrap_or(raw.len()); // try again
   }
 
-  let key = String::from_utf8(bytes[..space].to_vec()).expect("invalid key");
-  let value = String::from_utf8(bytes[space + 1..end].to_vec()).expect("invalid value");
-
-  map.entry(key).or_insert(value.replace("\n ", "\n"));
+  let line = &raw[offset..end];
+  let (key, value) = match line.find(b' ', 0) {
+    Some(space) => (&line[..space], &line[space + 1..]),
+    None => (line, &line[line.len()..]),
+  };
+  let key = String::from_utf8_lossy(key).into_owned();
+  let value = String::from_utf8_lossy(value).replace("\n ", "\n");
+  headers.push((key, value));
   end
 }
 
-/// Walk through the map and build up a byte vector.
-pub fn map_to_bytes(map: &IndexMap<String, String>) -> Vec<u8> {
+/// Walk through the headers and build up a byte vector.
+pub fn map_to_bytes(headers: &[(String, String)], message: &str) -> Vec<u8> {
   let mut result = String::from("");
 
   // append the fields (key-value pairs)
-  for key in map.keys() {
-    if !key.is_empty() {
-      let value = map.get(key).unwrap();
-      result.push_str(key);
-      result.push(' ');
-      result.push_str(&value.replace('\n', "\n "));
-      result.push('\n');
-    }
+  for (key, value) in headers {
+    result.push_str(key);
+    result.push(' ');
+    result.push_str(&value.replace('\n', "\n "));
+    result.push('\n');
   }
 
-  // append the message (the key of the message is the empty string)
-  result.push_str(map.get("").unwrap());
+  // append the message, which is separated from the fields by a blank line
+  result.push('\n');
+  result.push_str(message);
 
   result.into_bytes()
 }
@@ -0,0 +1,187 @@
+//! Finds the best common ancestors of commits, the way `git merge-base` does.
+
+use std::collections::{HashMap, HashSet};
+use std::slice;
+
+use crate::repo::Repo;
+
+use super::commit::Commit;
+use super::read;
+use super::revision;
+use super::serializable::Unbox;
+use super::walk::Queue;
+
+/// Reachable from the first commit.
+const PARENT1: u8 = 1;
+
+/// Reachable from one of the other commits.
+const PARENT2: u8 = 2;
+
+/// Reachable from a common ancestor that was already found.
+const STALE: u8 = 4;
+
+/// Found to be a common ancestor.
+const RESULT: u8 = 8;
+
+/// Returns the best common ancestors of `one` and any of `others`, newest
+/// first (ie. the merge bases of `one` and a merge of all of `others`).
+///
+/// A common ancestor is one of the best if no other common ancestor is
+/// reachable from it. There is usually only one of them, but a criss-cross
+/// history has several.
+pub fn merge_bases(repo: &Repo, one: &str, others: &[String]) -> Result<Vec<String>, String> {
+  if others.iter().any(|other| other == one) {
+    return Ok(vec![one.to_string()]);
+  }
+  let mut graph = Graph::new(repo);
+  let found = graph.paint_down_to_common(one, others)?;
+  let mut bases = Vec::new();
+  for hash in found {
+    if graph.flags(&hash) & STALE == 0 {
+      graph.insert_by_date(&mut bases, hash)?;
+    }
+  }
+  if bases.len() <= 1 {
+    return Ok(bases);
+  }
+
+  // a common ancestor can still be reachable from another one that was found
+  // through a different line of history
+  let mut best = Vec::new();
+  for hash in independent(repo, &bases)? {
+    graph.insert_by_date(&mut best, hash)?;
+  }
+  Ok(best)
+}
+
+/// Returns the best common ancestors of all of the commits (`--octopus`).
+///
+/// The bases of the first two commits are found first, then the bases of each
+/// of those and the third commit, and so on. Of the bases found, the ones that
+/// are reachable from another are left out.
+pub fn octopus_merge_bases(repo: &Repo, commits: &[String]) -> Result<Vec<String>, String> {
+  let mut bases: Vec<String> = commits.iter().take(1).cloned().collect();
+  for commit in commits.iter().skip(1) {
+    let mut next = Vec::new();
+    for base in &bases {
+      next.extend(merge_bases(repo, commit, slice::from_ref(base))?);
+    }
+    bases = next;
+  }
+  independent(repo, &bases)
+}
+
+/// Leaves out the commits that are reachable from another one of them (and
+/// the duplicates), keeping the others in order.
+pub fn independent(repo: &Repo, commits: &[String]) -> Result<Vec<String>, String> {
+  let mut unique: Vec<String> = Vec::new();
+  for commit in commits {
+    if !unique.contains(commit) {
+      unique.push(commit.clone());
+    }
+  }
+  let mut reachable = HashSet::new();
+  for commit in &unique {
+    let mut ancestors = revision::ancestors(repo, commit)?;
+    ancestors.remove(commit);
+    reachable.extend(ancestors);
+  }
+  unique.retain(|commit| !reachable.contains(commit));
+  Ok(unique)
+}
+
+/// Whether `ancestor` is reachable from `descendant` (or is the same commit).
+pub fn is_ancestor(repo: &Repo, ancestor: &str, descendant: &str) -> Result<bool, String> {
+  let bases = merge_bases(repo, ancestor, &[descendant.to_string()])?;
+  Ok(bases.iter().any(|base| base == ancestor))
+}
+
+/// The commits a merge base search has come across.
+struct Graph<'a> {
+  repo: &'a Repo,
+
+  /// The commit date and the parents of each commit that was read.
+  commits: HashMap<String, (i64, Vec<String>)>,
+
+  flags: HashMap<String, u8>,
+}
+
+impl<'a> Graph<'a> {
+  fn new(repo: &'a Repo) -> Self {
+    Self {
+      repo,
+      commits: HashMap::new(),
+      flags: HashMap::new(),
+    }
+  }
+
+  fn load(&mut self, hash: &str) -> Result<&(i64, Vec<String>), String> {
+    if !self.commits.contains_key(hash) {
+      let object = read(self.repo.clone(), hash, Some("commit"))?;
+      let commit = object.unbox::<Commit>()?;
+      let info = (commit.commit_time(), commit.parents());
+      self.commits.insert(hash.to_string(), info);
+    }
+    Ok(&self.commits[hash])
+  }
+
+  fn flags(&self, hash: &str) -> u8 {
+    self.flags.get(hash).copied().unwrap_or(0)
+  }
+
+  /// Walks down from `one` and `others` at the same time, newest first, and
+  /// returns the commits reachable from both sides, newest first.
+  ///
+  /// The ancestors of a common ancestor are marked as stale as they are found,
+  /// and the walk stops once only stale commits are left, so it doesn't have
+  /// to go through the whole history. Some of the commits returned may turn
+  /// out to be stale.
+  fn paint_down_to_common(&mut self, one: &str, others: &[String]) -> Result<Vec<String>, String> {
+    let mut queue = Queue::default();
+    *self.flags.entry(one.to_string()).or_default() |= PARENT1;
+    queue.push(self.load(one)?.0, one.to_string());
+    for other in others {
+      *self.flags.entry(other.clone()).or_default() |= PARENT2;
+      queue.push(self.load(other)?.0, other.clone());
+    }
+
+    let mut found = Vec::new();
+    while queue.hashes().any(|hash| self.flags(hash) & STALE == 0) {
+      let hash = match queue.pop() {
+        Some(hash) => hash,
+        None => break,
+      };
+      let mut flags = self.flags(&hash) & (PARENT1 | PARENT2 | STALE);
+      if flags == PARENT1 | PARENT2 {
+        if self.flags(&hash) & RESULT == 0 {
+          *self.flags.entry(hash.clone()).or_default() |= RESULT;
+          self.insert_by_date(&mut found, hash.clone())?;
+        }
+        flags |= STALE;
+      }
+      for parent in self.load(&hash)?.1.clone() {
+        if self.flags(&parent) & flags == flags {
+          continue;
+        }
+        *self.flags.entry(parent.clone()).or_default() |= flags;
+        queue.push(self.load(&parent)?.0, parent);
+      }
+    }
+    Ok(found)
+  }
+
+  /// Inserts a commit into a list of commits sorted newest first, after the
+  /// commits with the same date.
+  fn insert_by_date(&mut self, list: &mut Vec<String>, hash: String) -> Result<(), String> {
+    let date = self.load(&hash)?.0;
+    let mut position = list.len();
+    for (i, other) in list.iter().enumerate() {
+      if self.commits[other].0 < date {
+        position = i;
+        break;
+      }
+    }
+    list.insert(position, hash);
+    Ok(())
+  }
+}
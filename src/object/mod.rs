@@ -1,22 +1,32 @@
 pub(crate) mod blob;
 pub(crate) mod commit;
+pub(crate) mod date;
+pub(crate) mod delta;
 pub(crate) mod findable;
+pub(crate) mod graph;
+pub(crate) mod identity;
 pub(crate) mod mail_map;
+pub(crate) mod merge_base;
 pub(crate) mod mode;
+pub(crate) mod pack;
+pub(crate) mod pretty;
 pub(crate) mod refs;
+pub(crate) mod revision;
 pub(crate) mod serializable;
 pub(crate) mod tag;
 pub(crate) mod tree;
+pub(crate) mod walk;
 
 use crate::crypto;
 use crate::object::blob::Blob;
 use crate::object::commit::Commit;
 use crate::object::findable::Findable;
-use crate::object::serializable::Serializable;
+use crate::object::serializable::{Serializable, Unbox};
 use crate::object::tree::Tree;
 use crate::repo::{repo_file, Repo};
 use std::fs::{self, File};
 use std::io::prelude::*;
+use std::path::Path;
 
 use self::tag::Tag;
 
@@ -44,31 +54,61 @@ use self::tag::Tag;
 /// `tag` or `tree`. This header is followed by an ASCII space (0x20), then the
 /// size of the object in bytes as an ASCII number, then null (0x00) (the null
 /// byte), then the contents of the object.
-
+///
 /// Reads object object_id from the repository repo and returns an object
 /// whose exact type depends on the object read from memory.
+///
+/// Loose objects are looked up first; if there is no loose file for the hash,
+/// the packfiles in `.git/objects/pack` are searched instead.
 pub fn read(
   repo: Repo,
   hash: &str,
   typename: Option<&str>,
 ) -> Result<Box<dyn Serializable>, String> {
+  let (object_type, payload) = match read_raw(&repo, hash)? {
+    Some(object) => object,
+    None => return Err(format!("object not found {}", hash)),
+  };
+  match typename {
+    Some(name) if object_type != name => {
+      return Err(format!("invalid object type \"{}\"", typename.unwrap()))
+    }
+    _ => (),
+  }
+
+  match object_type.as_str() {
+    "blob" => Ok(Box::new(Blob::new(repo, &payload))),
+    "commit" => Ok(Box::new(Commit::new(repo, &payload))),
+    "tag" => Ok(Box::new(Tag::new(repo, &payload))),
+    "tree" => Ok(Box::new(Tree::new(repo, &payload))),
+    _ => Err(format!("unsupported type \"{}\"", object_type)),
+  }
+}
+
+/// Reads the type and payload of an object, whether it is loose or packed.
+pub(crate) fn read_raw(repo: &Repo, hash: &str) -> Result<Option<(String, Vec<u8>)>, String> {
+  match read_loose(repo, hash)? {
+    Some(object) => Ok(Some(object)),
+    None => pack::read(repo, hash),
+  }
+}
+
+/// Reads a loose object file and returns its type and payload, if it exists.
+fn read_loose(repo: &Repo, hash: &str) -> Result<Option<(String, Vec<u8>)>, String> {
+  if hash.len() != 40 {
+    return Ok(None);
+  }
   let directories = ["objects", &hash[0..2], &hash[2..]];
   let path = match repo_file(&repo.git_dir, &directories, false) {
     Some(p) => p,
-    None => return Err(format!("object not found {}", hash)),
+    None => return Ok(None),
   };
   if let Ok(file) = fs::read(path) {
     let raw = crypto::decompress(&file)?;
 
     // Read the object type
     let first_space: usize = raw.find(b' ', 0).unwrap();
-    let object_type: &str = &String::from_utf8(raw[0..first_space].to_vec()).unwrap();
-    match typename {
-      Some(name) if object_type != name => {
-        return Err(format!("invalid object type \"{}\"", typename.unwrap()))
-      }
-      _ => (),
-    }
+    let object_type: &str = std::str::from_utf8(&raw[0..first_space]).unwrap();
 
     // Read and validate the object size
     let null_byte: usize = raw.find(b'\0', 0).unwrap();
@@ -81,16 +121,12 @@ pub fn read(
       return Err("size does not match size of raw data".to_string());
     }
 
-    let payload = &raw[null_byte + 1..];
-    match object_type {
-      "blob" => Ok(Box::new(Blob::new(repo, payload))),
-      "commit" => Ok(Box::new(Commit::new(repo, payload))),
-      "tag" => Ok(Box::new(Tag::new(repo, payload))),
-      "tree" => Ok(Box::new(Tree::new(repo, payload))),
-      _ => Err(format!("unsupported type \"{}\"", object_type)),
-    }
+    Ok(Some((
+      object_type.to_string(),
+      raw[null_byte + 1..].to_vec(),
+    )))
   } else {
-    Err("object not found".to_string())
+    Ok(None)
   }
 }
 
@@ -98,7 +134,8 @@ pub fn read(
 ///
 /// The object is written to the repository that the object represents. If the
 /// dry_run flag is set to true, the hash will be calculated but not written
-/// to the directory.
+/// to the directory. Objects never change once they are written, so an object
+/// that is already stored as a loose object is left alone.
 pub fn write(object: &dyn Serializable, dry_run: bool) -> Result<String, String> {
   let payload = object.serialize();
   let header = format!("{} {}\0", object.format(), payload.len());
@@ -107,15 +144,167 @@ pub fn write(object: &dyn Serializable, dry_run: bool) -> Result<String, String>
 
   if !dry_run {
     let directories = ["objects", &hash[0..2], &hash[2..]];
-    let path = repo_file(&object.repo().git_dir, &directories, true);
-    let mut file = File::create(path.unwrap()).unwrap();
+    let path = repo_file(&object.repo().git_dir, &directories, true).unwrap();
+    if path.exists() {
+      return Ok(hash);
+    }
+    let mut file = File::create(path).unwrap();
     let compressed_data = crypto::compress(&data)?;
     file.write_all(&compressed_data[..]).unwrap();
   }
   Ok(hash)
 }
 
-/// Finds object.
-pub fn find_object<'a>(_repo: Repo, name: &'a str, _type: Option<&str>, _follow: bool) -> &'a str {
-  name
+/// The shortest abbreviated hash that `find_object` will try to expand.
+pub const MIN_ABBREV: usize = 4;
+
+/// The length hashes are abbreviated to when they are shown to the user.
+pub const DEFAULT_ABBREV: usize = 7;
+
+/// Resolves a name into the full hash of an object.
+///
+/// The name may be any revision expression (see `revision::resolve`), whose
+/// base names can be any of the following, tried in this order:
+/// - `HEAD`, which is resolved through the ref it points at
+/// - a full 40-character hash
+/// - a full ref (`refs/heads/main`) or a special ref (`ORIG_HEAD`)
+/// - a short ref, looked up in `refs/heads/`, `refs/tags/` and `refs/remotes/`
+///   (where `origin` also stands for `refs/remotes/origin/HEAD`)
+/// - an abbreviated hash of at least `MIN_ABBREV` characters, which must match
+///   exactly one loose or packed object
+///
+/// If `typename` is given, the object must be of that type. When `follow` is
+/// set, the object is peeled until it is: annotated tags are followed through
+/// their `object` header and commits are followed to their `tree`.
+pub fn find_object(
+  repo: Repo,
+  name: &str,
+  typename: Option<&str>,
+  follow: bool,
+) -> Result<String, String> {
+  let hash = revision::resolve(&repo, name)?;
+  match typename {
+    Some(typename) => peel(repo, &hash, typename, follow),
+    None => Ok(hash),
+  }
+}
+
+/// Peels an object until it reaches an object of the given type.
+fn peel(repo: Repo, hash: &str, typename: &str, follow: bool) -> Result<String, String> {
+  let mut hash = hash.to_string();
+  loop {
+    let object = read(repo.clone(), &hash, None)?;
+    let format = object.format().as_str();
+    if format == typename {
+      return Ok(hash);
+    }
+    let next = match format {
+      "tag" if follow => object.unbox::<Tag>()?.get("object").map(str::to_string),
+      "commit" if follow && typename == "tree" => {
+        Some(object.unbox::<Commit>()?.tree().to_string())
+      }
+      _ => None,
+    };
+    match next {
+      Some(next) => hash = next,
+      None => return Err(format!("{} is a {}, not a {}", hash, format, typename)),
+    }
+  }
+}
+
+/// Resolves a name into a full hash without looking at the object itself.
+fn resolve_name(repo: &Repo, name: &str) -> Result<String, String> {
+  if name == "HEAD" {
+    return refs::resolve(repo, Path::new("HEAD"));
+  }
+  let is_hex = !name.is_empty() && name.chars().all(|c| c.is_ascii_hexdigit());
+  if is_hex && name.len() == 40 {
+    return Ok(name.to_lowercase());
+  }
+
+  if let Some(refname) = find_ref(repo, name) {
+    return refs::resolve(repo, Path::new(&refname));
+  }
+
+  if is_hex && name.len() >= MIN_ABBREV {
+    let prefix = name.to_lowercase();
+    let mut candidates = find_loose_prefix(repo, &prefix);
+    candidates.extend(pack::find_prefix(repo, &prefix)?);
+    candidates.sort();
+    candidates.dedup();
+    return match candidates.len() {
+      0 => Err(format!("{} is not a valid object name", name)),
+      1 => Ok(candidates.remove(0)),
+      _ => {
+        let mut msg = format!("short object ID {} is ambiguous", name);
+        msg.push_str("\nhint: The candidates are:");
+        for candidate in candidates {
+          let format = match read_raw(repo, &candidate)? {
+            Some((format, _)) => format,
+            None => String::from("unknown"),
+          };
+          msg.push_str(&format!(
+            "\nhint:   {} {}",
+            &candidate[..DEFAULT_ABBREV],
+            format
+          ));
+        }
+        Err(msg)
+      }
+    };
+  }
+  Err(format!("{} is not a valid object name", name))
+}
+
+/// Abbreviates a hash to the shortest prefix of at least `min_len` characters
+/// that does not match any other object in the repository.
+pub fn abbreviate(repo: &Repo, hash: &str, min_len: usize) -> Result<String, String> {
+  let mut len = min_len.clamp(MIN_ABBREV, hash.len());
+  while len < hash.len() {
+    let prefix = &hash[..len];
+    let mut candidates = find_loose_prefix(repo, prefix);
+    candidates.extend(pack::find_prefix(repo, prefix)?);
+    candidates.sort();
+    candidates.dedup();
+    if candidates.len() <= 1 {
+      break;
+    }
+    len += 1;
+  }
+  Ok(hash[..len].to_string())
+}
+
+/// Returns the full name of the ref that a (possibly short) ref name refers to.
+///
+/// Names directly under the git directory are only considered if they look like
+/// special refs (eg. `ORIG_HEAD`, `MERGE_HEAD`) so that a name like `config`
+/// does not resolve to `.git/config`.
+pub fn find_ref(repo: &Repo, name: &str) -> Option<String> {
+  let is_special = name.chars().all(|c| c.is_ascii_uppercase() || c == '_');
+  let mut candidates: Vec<String> = Vec::new();
+  if name.starts_with("refs/") || is_special {
+    candidates.push(name.to_string());
+  }
+  for prefix in ["refs/", "refs/heads/", "refs/tags/", "refs/remotes/"] {
+    candidates.push(format!("{}{}", prefix, name));
+  }
+  candidates.push(format!("refs/remotes/{}/HEAD", name));
+
+  let packed = refs::packed(repo);
+  candidates
+    .into_iter()
+    .find(|refname| repo.git_dir.join(refname).is_file() || packed.contains_key(refname))
+}
+
+/// Returns the hashes of every loose object that starts with `prefix`.
+fn find_loose_prefix(repo: &Repo, prefix: &str) -> Vec<String> {
+  let dir = repo.git_dir.join("objects").join(&prefix[..2]);
+  match dir.read_dir() {
+    Ok(iter) => iter
+      .flatten()
+      .map(|entry| format!("{}{}", &prefix[..2], entry.file_name().to_string_lossy()))
+      .filter(|hash| hash.starts_with(prefix))
+      .collect(),
+    Err(_) => Vec::new(),
+  }
 }
@@ -1,12 +1,28 @@
 use std::convert::TryFrom;
 use std::fmt::Display;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Mode {
   Normal = 100644,
   Directory = 40000,
   Executable = 100755,
   Symbolic = 120000,
+  Gitlink = 160000,
+}
+
+impl Mode {
+  /// Converts a raw (octal) mode, as stored in the index, into a `Mode`.
+  pub fn from_octal(raw: u32) -> Result<Self, ()> {
+    match format!("{:o}", raw).parse::<usize>() {
+      Ok(digits) => Mode::try_from(digits),
+      Err(_) => Err(()),
+    }
+  }
+
+  /// Converts the mode into its raw (octal) value, as stored in the index.
+  pub fn to_octal(self) -> u32 {
+    u32::from_str_radix(&(self as usize).to_string(), 8).unwrap()
+  }
 }
 
 impl Display for Mode {
@@ -25,6 +41,7 @@ impl TryFrom<usize> for Mode {
       x if x == Mode::Directory as usize => Ok(Mode::Directory),
       x if x == Mode::Executable as usize => Ok(Mode::Executable),
       x if x == Mode::Symbolic as usize => Ok(Mode::Symbolic),
+      x if x == Mode::Gitlink as usize => Ok(Mode::Gitlink),
       _ => Err(()),
     }
   }
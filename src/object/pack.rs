@@ -0,0 +1,568 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{prelude::*, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use flate2::Crc;
+
+use crate::crypto;
+use crate::repo::{repo_dir, Repo};
+
+use super::delta;
+
+/// The magic number at the start of a version 2 (or later) pack index.
+const IDX_MAGIC: [u8; 4] = [0xff, b't', b'O', b'c'];
+
+/// The size in bytes of a raw SHA-1 hash.
+const HASH_LEN: usize = 20;
+
+/// The size in bytes of the pack index header (magic + version).
+const IDX_HEADER_LEN: usize = 8;
+
+/// The size in bytes of the fan-out table (256 big-endian `u32`s).
+const FANOUT_LEN: usize = 256 * 4;
+
+/// The size in bytes of the pack file header (`PACK` + version + count).
+const PACK_HEADER_LEN: usize = 12;
+
+/// The most bytes of resolved delta bases kept in memory at once (this is the
+/// default of git's `core.deltaBaseCacheLimit`).
+const DELTA_BASE_CACHE_LIMIT: usize = 96 * 1024 * 1024;
+
+/// The longest delta chain we are willing to follow before assuming the pack is
+/// corrupt (eg. a `REF_DELTA` cycle).
+const MAX_DELTA_CHAIN: usize = 10_000;
+
+thread_local! {
+  /// Pack indices are immutable once written (they are named after the hash of
+  /// their pack), so every index we parse is kept around for the lifetime of
+  /// the process instead of re-reading it for each object lookup.
+  static INDEX_CACHE: RefCell<HashMap<PathBuf, Rc<PackIndex>>> = RefCell::new(HashMap::new());
+
+  /// Resolved delta bases, so that objects deep in the same delta chain do not
+  /// re-inflate (and re-apply) every base on the way down.
+  static DELTA_BASE_CACHE: RefCell<DeltaBaseCache> = RefCell::new(DeltaBaseCache::default());
+}
+
+/// The type of an entry in a packfile.
+///
+/// The type is stored in bits 4-6 of the first byte of an entry header. Types 1
+/// through 4 are whole objects, types 6 and 7 are deltas against another object
+/// (type 5 is reserved).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PackType {
+  Commit = 1,
+  Tree = 2,
+  Blob = 3,
+  Tag = 4,
+  OfsDelta = 6,
+  RefDelta = 7,
+}
+
+impl PackType {
+  /// Returns the object format string of a non-delta pack type.
+  pub fn format(&self) -> Option<&'static str> {
+    match self {
+      PackType::Commit => Some("commit"),
+      PackType::Tree => Some("tree"),
+      PackType::Blob => Some("blob"),
+      PackType::Tag => Some("tag"),
+      PackType::OfsDelta | PackType::RefDelta => None,
+    }
+  }
+}
+
+impl TryFrom<u8> for PackType {
+  type Error = String;
+
+  fn try_from(value: u8) -> Result<Self, Self::Error> {
+    match value {
+      1 => Ok(PackType::Commit),
+      2 => Ok(PackType::Tree),
+      3 => Ok(PackType::Blob),
+      4 => Ok(PackType::Tag),
+      6 => Ok(PackType::OfsDelta),
+      7 => Ok(PackType::RefDelta),
+      _ => Err(format!("invalid pack object type {}", value)),
+    }
+  }
+}
+
+/// A version 2 pack index (`.git/objects/pack/pack-*.idx`).
+///
+/// The index maps object hashes to offsets in the accompanying `.pack` file. It
+/// is laid out as a series of tables, each sorted by object hash:
+///
+/// ```text
+/// | magic (\377tOc) | version (2) |
+/// | fan-out: 256 x u32, the number of objects whose first byte is <= i |
+/// | hashes:    N x 20-byte SHA-1                                        |
+/// | crc32:     N x u32, checksum of the packed (compressed) entry       |
+/// | offsets:   N x u32, MSB set means "index into the 64-bit table"     |
+/// | offsets64: M x u64, for packs larger than 2GiB                      |
+/// | pack checksum (20 bytes) | index checksum (20 bytes) |
+/// ```
+pub struct PackIndex {
+  data: Vec<u8>,
+  count: usize,
+  pack: PathBuf,
+  sorted_offsets: Vec<(u64, usize)>,
+}
+
+impl PackIndex {
+  /// Parses the pack index at the given path.
+  pub fn open(path: &Path) -> Result<Self, String> {
+    let data = match fs::read(path) {
+      Ok(data) => data,
+      Err(msg) => return Err(format!("unable to read {} ({})", path.display(), msg)),
+    };
+    let min_len = IDX_HEADER_LEN + FANOUT_LEN + 2 * HASH_LEN;
+    if data.len() < min_len || data[..4] != IDX_MAGIC {
+      return Err(format!("{} is not a version 2 pack index", path.display()));
+    }
+    let version = read_u32(&data, 4);
+    if version != 2 {
+      return Err(format!("unsupported pack index version {}", version));
+    }
+
+    // The last fan-out entry is the total number of objects in the pack.
+    let count = read_u32(&data, IDX_HEADER_LEN + 255 * 4) as usize;
+    if data.len() < min_len + count * (HASH_LEN + 8) {
+      return Err(format!("{} is truncated", path.display()));
+    }
+
+    // The index checksum covers everything but itself.
+    let (body, checksum) = data.split_at(data.len() - HASH_LEN);
+    if crypto::sha_1(body) != hex::encode(checksum) {
+      return Err(format!("{} has an invalid checksum", path.display()));
+    }
+
+    let mut index = Self {
+      data,
+      count,
+      pack: path.with_extension("pack"),
+      sorted_offsets: Vec::new(),
+    };
+    let mut offsets: Vec<(u64, usize)> = (0..count).map(|i| (index.offset(i), i)).collect();
+    offsets.sort_unstable();
+    index.sorted_offsets = offsets;
+    Ok(index)
+  }
+
+  /// Returns the path to the `.pack` file this index describes.
+  pub fn pack_path(&self) -> &Path {
+    &self.pack
+  }
+
+  /// Returns the raw hash of the `i`th object (in sorted order).
+  pub fn hash(&self, i: usize) -> &[u8] {
+    let start = IDX_HEADER_LEN + FANOUT_LEN + i * HASH_LEN;
+    &self.data[start..start + HASH_LEN]
+  }
+
+  /// Returns the CRC-32 of the `i`th object's packed representation.
+  pub fn crc32(&self, i: usize) -> u32 {
+    read_u32(&self.data, self.crc_table() + i * 4)
+  }
+
+  /// Returns the offset in the pack file of the `i`th object.
+  pub fn offset(&self, i: usize) -> u64 {
+    let offset = read_u32(&self.data, self.offset_table() + i * 4);
+    if offset & 0x8000_0000 == 0 {
+      offset as u64
+    } else {
+      // the low 31 bits index into the table of 64-bit offsets
+      let large_table = self.offset_table() + self.count * 4;
+      let start = large_table + (offset & 0x7fff_ffff) as usize * 8;
+      u64::from_be_bytes(self.data[start..start + 8].try_into().unwrap())
+    }
+  }
+
+  /// Returns the offset of the entry that follows the one at `offset`, if any.
+  pub fn next_offset(&self, offset: u64) -> Option<u64> {
+    let next = self
+      .sorted_offsets
+      .partition_point(|&(other, _)| other <= offset);
+    self.sorted_offsets.get(next).map(|&(other, _)| other)
+  }
+
+  /// Returns the position of the object stored at the given pack offset.
+  pub fn position_at(&self, offset: u64) -> Option<usize> {
+    match self
+      .sorted_offsets
+      .binary_search_by_key(&offset, |&(other, _)| other)
+    {
+      Ok(i) => Some(self.sorted_offsets[i].1),
+      Err(_) => None,
+    }
+  }
+
+  /// Returns the position of the object with the given hash, if it is packed.
+  ///
+  /// The fan-out table narrows the search to the objects that share the first
+  /// byte of the hash, and that range is binary searched.
+  pub fn position(&self, hash: &[u8]) -> Option<usize> {
+    let (mut low, mut high) = self.fanout_range(hash[0]);
+    while low < high {
+      let mid = low + (high - low) / 2;
+      match self.hash(mid).cmp(hash) {
+        std::cmp::Ordering::Less => low = mid + 1,
+        std::cmp::Ordering::Greater => high = mid,
+        std::cmp::Ordering::Equal => return Some(mid),
+      }
+    }
+    None
+  }
+
+  /// Returns the hex hashes of every object whose hash starts with `prefix`.
+  ///
+  /// The prefix must be at least two hex digits long so that the fan-out table
+  /// can narrow down the search.
+  pub fn find_prefix(&self, prefix: &str) -> Vec<String> {
+    let first = match u8::from_str_radix(&prefix[..2], 16) {
+      Ok(first) => first,
+      Err(_) => return Vec::new(),
+    };
+    let (low, high) = self.fanout_range(first);
+    (low..high)
+      .map(|i| hex::encode(self.hash(i)))
+      .filter(|hash| hash.starts_with(prefix))
+      .collect()
+  }
+
+  /// Returns the range of positions whose hash starts with the given byte.
+  fn fanout_range(&self, first: u8) -> (usize, usize) {
+    let high = read_u32(&self.data, IDX_HEADER_LEN + first as usize * 4) as usize;
+    let low = match first {
+      0 => 0,
+      _ => read_u32(&self.data, IDX_HEADER_LEN + (first as usize - 1) * 4) as usize,
+    };
+    (low, high)
+  }
+
+  fn crc_table(&self) -> usize {
+    IDX_HEADER_LEN + FANOUT_LEN + self.count * HASH_LEN
+  }
+
+  fn offset_table(&self) -> usize {
+    self.crc_table() + self.count * 4
+  }
+}
+
+/// A single entry read out of a packfile.
+pub struct PackEntry {
+  /// The type of the entry.
+  pub kind: PackType,
+
+  /// For an `OFS_DELTA`, the offset of the base object in the same pack.
+  pub base_offset: Option<u64>,
+
+  /// For a `REF_DELTA`, the hex hash of the base object.
+  pub base_hash: Option<String>,
+
+  /// The inflated data (the object payload, or the delta instructions).
+  pub data: Vec<u8>,
+}
+
+/// An object resolved out of a pack: its format and its (shared) payload.
+pub type ResolvedObject = (String, Rc<Vec<u8>>);
+
+/// A bounded cache of resolved delta bases, keyed by pack and offset.
+///
+/// Entries are evicted oldest-first once the total size of the cached objects
+/// goes over `DELTA_BASE_CACHE_LIMIT`.
+#[derive(Default)]
+struct DeltaBaseCache {
+  entries: HashMap<(PathBuf, u64), ResolvedObject>,
+  order: VecDeque<(PathBuf, u64)>,
+  size: usize,
+}
+
+impl DeltaBaseCache {
+  fn get(&self, pack: &Path, offset: u64) -> Option<ResolvedObject> {
+    self.entries.get(&(pack.to_path_buf(), offset)).cloned()
+  }
+
+  fn insert(&mut self, pack: &Path, offset: u64, format: &str, data: Rc<Vec<u8>>) {
+    if data.len() > DELTA_BASE_CACHE_LIMIT {
+      return;
+    }
+    let key = (pack.to_path_buf(), offset);
+    self.size += data.len();
+    if let Some((_, old)) = self.entries.insert(key.clone(), (format.to_string(), data)) {
+      self.size -= old.len();
+    } else {
+      self.order.push_back(key);
+    }
+    while self.size > DELTA_BASE_CACHE_LIMIT {
+      match self.order.pop_front() {
+        Some(oldest) => {
+          if let Some((_, evicted)) = self.entries.remove(&oldest) {
+            self.size -= evicted.len();
+          }
+        }
+        None => break,
+      }
+    }
+  }
+}
+
+/// Reads a packed object from the repository.
+///
+/// Looks through every index in `.git/objects/pack` for the given hash and, if
+/// one of them has it, returns the object's type and payload.
+pub fn read(repo: &Repo, hash: &str) -> Result<Option<(String, Vec<u8>)>, String> {
+  let raw_hash = match hex::decode(hash) {
+    Ok(raw) if raw.len() == HASH_LEN => raw,
+    _ => return Ok(None),
+  };
+  for index in indices(repo)? {
+    if let Some(position) = index.position(&raw_hash) {
+      let (format, data) = resolve(repo, &index, index.offset(position))?;
+      return Ok(Some((
+        format,
+        Rc::try_unwrap(data).unwrap_or_else(|rc| (*rc).clone()),
+      )));
+    }
+  }
+  Ok(None)
+}
+
+/// Returns the hashes of every packed object that starts with `prefix`.
+pub fn find_prefix(repo: &Repo, prefix: &str) -> Result<Vec<String>, String> {
+  let mut result = Vec::new();
+  for index in indices(repo)? {
+    result.extend(index.find_prefix(prefix));
+  }
+  Ok(result)
+}
+
+/// Returns every pack index in the repository.
+pub fn indices(repo: &Repo) -> Result<Vec<Rc<PackIndex>>, String> {
+  let pack_dir = match repo_dir(&repo.git_dir, &["objects", "pack"], false) {
+    Some(dir) => dir,
+    None => return Ok(Vec::new()),
+  };
+  let mut paths: Vec<PathBuf> = match pack_dir.read_dir() {
+    Ok(iter) => iter
+      .flatten()
+      .map(|entry| entry.path())
+      .filter(|path| path.extension().is_some_and(|ext| ext == "idx"))
+      .collect(),
+    Err(msg) => return Err(format!("unable to read {} ({})", pack_dir.display(), msg)),
+  };
+  paths.sort();
+
+  let mut result = Vec::with_capacity(paths.len());
+  for path in paths {
+    let cached = INDEX_CACHE.with(|cache| cache.borrow().get(&path).cloned());
+    let index = match cached {
+      Some(index) => index,
+      None => {
+        let index = Rc::new(PackIndex::open(&path)?);
+        INDEX_CACHE.with(|cache| cache.borrow_mut().insert(path, index.clone()));
+        index
+      }
+    };
+    result.push(index);
+  }
+  Ok(result)
+}
+
+/// Resolves the object stored at the given pack offset into its type and data.
+///
+/// If the entry is a delta, its chain is followed down to a whole object (or a
+/// cached base, or a `REF_DELTA` base that lives outside of this pack) and the
+/// deltas are then applied back up the chain. Every intermediate result is put
+/// in the delta base cache since sibling objects are likely to share it.
+pub fn resolve(repo: &Repo, index: &PackIndex, offset: u64) -> Result<ResolvedObject, String> {
+  let pack = index.pack_path();
+  let mut deltas: Vec<(u64, Vec<u8>)> = Vec::new();
+  let mut current = offset;
+  let (format, mut data) = loop {
+    if deltas.len() > MAX_DELTA_CHAIN {
+      return Err(format!("delta chain too long at offset {}", offset));
+    }
+    let cached = DELTA_BASE_CACHE.with(|cache| cache.borrow().get(pack, current));
+    if let Some(base) = cached {
+      break base;
+    }
+
+    let entry = read_entry(index, current)?;
+    if let Some(format) = entry.kind.format() {
+      let data = Rc::new(entry.data);
+      if !deltas.is_empty() {
+        cache_base(pack, current, format, &data);
+      }
+      break (format.to_string(), data);
+    }
+
+    deltas.push((current, entry.data));
+    if let Some(base_offset) = entry.base_offset {
+      current = base_offset;
+    } else {
+      // A `REF_DELTA` base is usually in the same pack, but a thin pack may
+      // refer to an object that is stored loose or in another pack.
+      let base_hash = entry.base_hash.unwrap();
+      let raw_hash = hex::decode(&base_hash).unwrap();
+      match index.position(&raw_hash) {
+        Some(position) => current = index.offset(position),
+        None => match super::read_raw(repo, &base_hash)? {
+          Some((format, data)) => break (format, Rc::new(data)),
+          None => return Err(format!("missing delta base {}", base_hash)),
+        },
+      }
+    }
+  };
+
+  while let Some((delta_offset, delta_data)) = deltas.pop() {
+    data = Rc::new(delta::apply(&data, &delta_data)?);
+    if !deltas.is_empty() {
+      cache_base(pack, delta_offset, &format, &data);
+    }
+  }
+  Ok((format, data))
+}
+
+/// Puts a resolved object into the delta base cache.
+fn cache_base(pack: &Path, offset: u64, format: &str, data: &Rc<Vec<u8>>) {
+  DELTA_BASE_CACHE.with(|cache| {
+    cache
+      .borrow_mut()
+      .insert(pack, offset, format, data.clone())
+  });
+}
+
+/// Reads and inflates the entry at the given offset of a pack.
+///
+/// The packed bytes are checked against the CRC-32 recorded in the index before
+/// they are inflated, so a corrupt pack fails loudly instead of producing a
+/// bogus object.
+pub fn read_entry(index: &PackIndex, offset: u64) -> Result<PackEntry, String> {
+  let position = match index.position_at(offset) {
+    Some(position) => position,
+    None => return Err(format!("no packed object at offset {}", offset)),
+  };
+  let mut file = open_pack(index.pack_path())?;
+
+  // An entry ends where the next one (by offset) starts, or at the trailer.
+  let pack_len = match file.metadata() {
+    Ok(meta) => meta.len(),
+    Err(msg) => return Err(msg.to_string()),
+  };
+  let end = match index.next_offset(offset) {
+    Some(end) => Some(end),
+    None => pack_len.checked_sub(HASH_LEN as u64),
+  };
+  let len = match end.and_then(|end| end.checked_sub(offset)) {
+    Some(len) => len,
+    None => return Err(format!("{} is truncated", index.pack_path().display())),
+  };
+
+  let mut raw = vec![0; len as usize];
+  if let Err(msg) = file
+    .seek(SeekFrom::Start(offset))
+    .and_then(|_| file.read_exact(&mut raw))
+  {
+    return Err(format!(
+      "unable to read {} ({})",
+      index.pack_path().display(),
+      msg
+    ));
+  }
+
+  let mut crc = Crc::new();
+  crc.update(&raw);
+  if crc.sum() != index.crc32(position) {
+    return Err(format!(
+      "crc mismatch for {}",
+      hex::encode(index.hash(position))
+    ));
+  }
+
+  parse_entry(&raw, offset)
+}
+
+/// Opens a pack file and checks its header.
+fn open_pack(path: &Path) -> Result<File, String> {
+  let mut file = match File::open(path) {
+    Ok(file) => file,
+    Err(msg) => return Err(format!("unable to open {} ({})", path.display(), msg)),
+  };
+  let mut header = [0; PACK_HEADER_LEN];
+  if file.read_exact(&mut header).is_err() || &header[..4] != b"PACK" {
+    return Err(format!("{} is not a packfile", path.display()));
+  }
+  match read_u32(&header, 4) {
+    2 | 3 => Ok(file),
+    version => Err(format!("unsupported pack version {}", version)),
+  }
+}
+
+/// Parses an entry header and inflates the data that follows it.
+///
+/// The header is a variable-length integer: the first byte holds the type in
+/// bits 4-6 and the low 4 bits of the size; while the MSB is set, each following
+/// byte contributes 7 more bits of the size.
+fn parse_entry(raw: &[u8], offset: u64) -> Result<PackEntry, String> {
+  let truncated = || format!("truncated packed object at {}", offset);
+  let byte_at = |pos: usize| raw.get(pos).copied().ok_or_else(truncated);
+
+  let mut pos = 0;
+  let mut byte = byte_at(pos)?;
+  let kind = PackType::try_from((byte >> 4) & 0x7)?;
+  let mut size = (byte & 0x0f) as usize;
+  let mut shift = 4;
+  while byte & 0x80 != 0 {
+    pos += 1;
+    byte = byte_at(pos)?;
+    size |= ((byte & 0x7f) as usize) << shift;
+    shift += 7;
+  }
+  pos += 1;
+
+  let mut base_offset = None;
+  let mut base_hash = None;
+  match kind {
+    PackType::OfsDelta => {
+      // A big-endian base-128 number where each continuation adds one, so that
+      // every distance has exactly one encoding.
+      let mut byte = byte_at(pos)?;
+      let mut distance = (byte & 0x7f) as u64;
+      while byte & 0x80 != 0 {
+        pos += 1;
+        byte = byte_at(pos)?;
+        distance = ((distance + 1) << 7) | (byte & 0x7f) as u64;
+      }
+      pos += 1;
+      if distance == 0 || distance > offset {
+        return Err(format!("invalid delta base offset at {}", offset));
+      }
+      base_offset = Some(offset - distance);
+    }
+    PackType::RefDelta => {
+      let hash = raw.get(pos..pos + HASH_LEN).ok_or_else(truncated)?;
+      base_hash = Some(hex::encode(hash));
+      pos += HASH_LEN;
+    }
+    _ => (),
+  }
+
+  let data = crypto::decompress(raw.get(pos..).ok_or_else(truncated)?)?;
+  if data.len() != size {
+    return Err(format!("size mismatch for packed object at {}", offset));
+  }
+  Ok(PackEntry {
+    kind,
+    base_offset,
+    base_hash,
+    data,
+  })
+}
+
+/// Reads a big-endian `u32` starting at the given offset.
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+  u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
@@ -0,0 +1,499 @@
+//! Formats commits the way `git log` shows them: the built-in formats
+//! (`--pretty=medium` and friends), user formats with `%` placeholders and the
+//! ref names commits are decorated with.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::repo::Repo;
+
+use super::commit::Commit;
+use super::date::{self, Style};
+use super::identity::Identity;
+use super::refs;
+use super::serializable::Unbox;
+use super::tag::Tag;
+use super::{abbreviate, read, DEFAULT_ABBREV};
+
+/// How tabs in messages are expanded by the formats that expand them.
+const TAB_WIDTH: usize = 8;
+
+/// How a commit is shown.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+  /// `<hash> <subject>` on a single line.
+  Oneline,
+
+  /// The hash, author and subject.
+  Short,
+
+  /// The hash, author, author date and message (the default).
+  Medium,
+
+  /// The hash, author, committer and message.
+  Full,
+
+  /// The hash, author and committer with their dates, and message.
+  Fuller,
+
+  /// The hash, the headers of the commit as they are stored and the message.
+  Raw,
+
+  /// A format string with `%` placeholders.
+  User(String),
+}
+
+impl Format {
+  /// Parses the value of `--pretty` or `--format`.
+  ///
+  /// Returns the format and whether each commit is followed by a newline
+  /// (`tformat:`, and `oneline`) rather than commits being separated by one
+  /// (`format:`, and the other built-in formats). A value with a `%` in it
+  /// (or an empty one) is taken to be a `tformat:`.
+  pub fn parse(value: &str) -> Result<(Self, bool), String> {
+    if let Some(format) = value.strip_prefix("format:") {
+      return Ok((Format::User(format.to_string()), false));
+    }
+    if let Some(format) = value.strip_prefix("tformat:") {
+      return Ok((Format::User(format.to_string()), true));
+    }
+    match value {
+      "oneline" => Ok((Format::Oneline, true)),
+      "short" => Ok((Format::Short, false)),
+      "medium" => Ok((Format::Medium, false)),
+      "full" => Ok((Format::Full, false)),
+      "fuller" => Ok((Format::Fuller, false)),
+      "raw" => Ok((Format::Raw, false)),
+      _ if value.is_empty() || value.contains('%') => Ok((Format::User(value.to_string()), true)),
+      _ => Err(format!("invalid --pretty format: {}", value)),
+    }
+  }
+}
+
+/// How commits are formatted.
+#[derive(Clone, Debug)]
+pub struct Options {
+  pub format: Format,
+
+  /// Show abbreviated hashes in the header line of the built-in formats.
+  pub abbrev_commit: bool,
+
+  /// How dates are shown by the built-in formats and by `%ad` and `%cd`.
+  pub date: Style,
+
+  /// Show the ref names pointing at a commit after its hash.
+  pub decorate: bool,
+}
+
+/// The refs that point at each commit (or tag), which are shown next to the
+/// commit as in `(HEAD -> main, tag: v1.0, origin/main)`.
+///
+/// Only branches, remote-tracking branches, tags, the stash and HEAD are
+/// shown. Annotated tags decorate the object they point at as well.
+pub struct Decorations {
+  /// The names pointing at each object, in the order they are shown.
+  names: HashMap<String, Vec<String>>,
+
+  /// The branch HEAD points at, if it isn't detached.
+  head: Option<String>,
+
+  /// Show full ref names (`refs/heads/main`) rather than short ones.
+  full: bool,
+}
+
+impl Decorations {
+  pub fn load(repo: &Repo, full: bool) -> Result<Self, String> {
+    let mut names: HashMap<String, Vec<String>> = HashMap::new();
+    let mut add = |hash: &str, name: &str| {
+      names
+        .entry(hash.to_string())
+        .or_default()
+        .push(name.to_string())
+    };
+
+    // HEAD comes first, then the refs in reverse order of their names
+    if let Ok(hash) = refs::resolve(repo, Path::new("HEAD")) {
+      add(&hash, "HEAD");
+    }
+    for (refname, hash) in refs::collect(repo, None).iter().rev() {
+      let shown = ["refs/heads/", "refs/remotes/", "refs/tags/"]
+        .iter()
+        .any(|prefix| refname.starts_with(prefix));
+      if !shown && refname != "refs/stash" {
+        continue;
+      }
+      add(hash, refname);
+      let mut hash = hash.clone();
+      while let Ok(object) = read(repo.clone(), &hash, None) {
+        if object.format() != "tag" {
+          break;
+        }
+        match object.unbox::<Tag>()?.get("object") {
+          Some(target) => hash = target.to_string(),
+          None => break,
+        }
+        add(&hash, refname);
+      }
+    }
+
+    Ok(Self {
+      names,
+      head: refs::current_branch(repo),
+      full,
+    })
+  }
+
+  /// The ref names pointing at an object, separated by commas (or `None` if
+  /// there aren't any). The branch HEAD points at is shown as `HEAD -> main`.
+  pub fn get(&self, hash: &str) -> Option<String> {
+    let names = self.names.get(hash)?;
+    let current = self
+      .head
+      .as_ref()
+      .filter(|head| names.iter().any(|name| name == "HEAD") && names.contains(head));
+    let shown: Vec<String> = names
+      .iter()
+      .filter(|&name| Some(name) != current)
+      .map(|name| match (name.as_str(), current) {
+        ("HEAD", Some(current)) => format!("HEAD -> {}", self.short(current)),
+        _ if name.starts_with("refs/tags/") => format!("tag: {}", self.short(name)),
+        _ => self.short(name),
+      })
+      .collect();
+    Some(shown.join(", "))
+  }
+
+  /// Shortens a ref name (unless full names are shown).
+  fn short(&self, name: &str) -> String {
+    if self.full {
+      return name.to_string();
+    }
+    ["refs/heads/", "refs/tags/", "refs/remotes/"]
+      .iter()
+      .find_map(|prefix| name.strip_prefix(prefix))
+      .unwrap_or(name)
+      .to_string()
+  }
+}
+
+/// Formats a commit.
+///
+/// The built-in formats start with a `commit <hash>` line (or the hash alone
+/// for `Oneline`) and end with a newline, except `Oneline` which has none. A
+/// user format is expanded as it is.
+///
+/// `parents` are the parents the commit is shown with, which aren't the ones
+/// it records once the history has been simplified.
+pub fn format(
+  repo: &Repo,
+  hash: &str,
+  commit: &Commit,
+  parents: &[String],
+  options: &Options,
+  decorations: &Decorations,
+) -> Result<String, String> {
+  let format = match &options.format {
+    Format::User(format) => {
+      return expand(repo, hash, commit, parents, format, options, decorations)
+    }
+    format => format,
+  };
+
+  let mut out = String::new();
+  if *format != Format::Oneline {
+    out.push_str("commit ");
+  }
+  match options.abbrev_commit {
+    true => out.push_str(&abbreviate(repo, hash, DEFAULT_ABBREV)?),
+    false => out.push_str(hash),
+  }
+  if options.decorate {
+    if let Some(names) = decorations.get(hash) {
+      out.push_str(&format!(" ({})", names));
+    }
+  }
+  let message = commit.message();
+  if *format == Format::Oneline {
+    out.push(' ');
+    out.push_str(&subject(skip_blank_lines(message)).0);
+    return Ok(out);
+  }
+  out.push('\n');
+
+  if *format != Format::Raw && parents.len() > 1 {
+    out.push_str("Merge:");
+    for parent in parents {
+      out.push_str(&format!(" {}", abbreviate(repo, parent, DEFAULT_ABBREV)?));
+    }
+    out.push('\n');
+  }
+  let author = Identity::parse(commit.author())?;
+  let committer = Identity::parse(commit.committer())?;
+  let who = |identity: &Identity| format!("{} <{}>", identity.name, identity.email);
+  let when = |identity: &Identity| date::format(identity.time, identity.offset, options.date);
+  match format {
+    Format::Short => out.push_str(&format!("Author: {}\n", who(&author))),
+    Format::Medium => {
+      out.push_str(&format!("Author: {}\n", who(&author)));
+      out.push_str(&format!("Date:   {}\n", when(&author)));
+    }
+    Format::Full => {
+      out.push_str(&format!("Author: {}\n", who(&author)));
+      out.push_str(&format!("Commit: {}\n", who(&committer)));
+    }
+    Format::Fuller => {
+      out.push_str(&format!("Author:     {}\n", who(&author)));
+      out.push_str(&format!("AuthorDate: {}\n", when(&author)));
+      out.push_str(&format!("Commit:     {}\n", who(&committer)));
+      out.push_str(&format!("CommitDate: {}\n", when(&committer)));
+    }
+    _ => {
+      // the headers as they are, up to the blank line before the message
+      let data = String::from_utf8_lossy(commit.to_bytes()).into_owned();
+      let end = data.find("\n\n").map_or(data.len(), |i| i + 1);
+      out.push_str(&data[..end]);
+    }
+  }
+  out.push('\n');
+
+  // the message is indented, without the blank lines around it and (except
+  // for the raw and short formats) with its tabs expanded
+  let expand_tabs = matches!(format, Format::Medium | Format::Full | Format::Fuller);
+  for line in skip_blank_lines(message).lines() {
+    let line = line.trim_end();
+    if line.is_empty() && *format == Format::Short {
+      break;
+    }
+    out.push_str("    ");
+    match expand_tabs {
+      true => out.push_str(&expand_tab(line)),
+      false => out.push_str(line),
+    }
+    out.push('\n');
+  }
+  let end = out.trim_end().len();
+  out.truncate(end);
+  out.push('\n');
+  Ok(out)
+}
+
+/// Expands the `%` placeholders of a user format for a commit.
+///
+/// A placeholder that isn't known is left as it is. A `+` after the `%` adds
+/// a newline before the expansion if it isn't empty, a space does the same
+/// with a space, and a `-` removes the newlines before the expansion if it is
+/// empty.
+fn expand(
+  repo: &Repo,
+  hash: &str,
+  commit: &Commit,
+  parents: &[String],
+  format: &str,
+  options: &Options,
+  decorations: &Decorations,
+) -> Result<String, String> {
+  let mut out = String::new();
+  let mut rest = format;
+  while let Some(percent) = rest.find('%') {
+    out.push_str(&rest[..percent]);
+    rest = &rest[percent + 1..];
+
+    let modifier = match rest.chars().next() {
+      Some(c @ ('+' | '-' | ' ')) => {
+        rest = &rest[1..];
+        Some(c)
+      }
+      _ => None,
+    };
+    match placeholder(repo, hash, commit, parents, rest, options, decorations)? {
+      Some((expansion, len)) => {
+        rest = &rest[len..];
+        match modifier {
+          Some('+') if !expansion.is_empty() => out.push('\n'),
+          Some(' ') if !expansion.is_empty() => out.push(' '),
+          Some('-') if expansion.is_empty() => {
+            let end = out.trim_end_matches('\n').len();
+            out.truncate(end);
+          }
+          _ => (),
+        }
+        out.push_str(&expansion);
+      }
+      None => {
+        out.push('%');
+        out.extend(modifier);
+      }
+    }
+  }
+  out.push_str(rest);
+  Ok(out)
+}
+
+/// Expands the placeholder at the start of `spec` (just after the `%` and its
+/// modifier), returning the expansion and the length of the placeholder, or
+/// `None` if it isn't one.
+fn placeholder(
+  repo: &Repo,
+  hash: &str,
+  commit: &Commit,
+  parents: &[String],
+  spec: &str,
+  options: &Options,
+  decorations: &Decorations,
+) -> Result<Option<(String, usize)>, String> {
+  let short = |hash: &str| abbreviate(repo, hash, DEFAULT_ABBREV);
+  let message = commit.message();
+  let Some(first) = spec.chars().next() else {
+    return Ok(None);
+  };
+  let expansion = match first {
+    '%' => "%".to_string(),
+    'n' => "\n".to_string(),
+    'H' => hash.to_string(),
+    'h' => short(hash)?,
+    'T' => commit.tree().to_string(),
+    't' => short(commit.tree())?,
+    'P' => parents.join(" "),
+    'p' => {
+      let parents: Vec<String> = parents
+        .iter()
+        .map(|parent| short(parent))
+        .collect::<Result<_, _>>()?;
+      parents.join(" ")
+    }
+    'd' => decorations
+      .get(hash)
+      .map_or(String::new(), |names| format!(" ({})", names)),
+    'D' => decorations.get(hash).unwrap_or_default(),
+    's' => subject(skip_blank_lines(message)).0,
+    'f' => sanitize(skip_blank_lines(message).lines().next().unwrap_or_default()),
+    'b' => skip_blank_lines(subject(skip_blank_lines(message)).1).to_string(),
+    'B' => message.to_string(),
+    'e' => commit.get("encoding").unwrap_or_default().to_string(),
+    'm' => ">".to_string(),
+    'x' => {
+      let byte = spec
+        .get(1..3)
+        .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+      return Ok(byte.map(|byte| (char::from(byte).to_string(), 3)));
+    }
+    'C' => {
+      // colors are only shown when color is on, which it never is here
+      let len = match spec[1..].strip_prefix('(') {
+        Some(color) => color.find(')').map(|end| end + 3),
+        None => ["red", "green", "blue", "reset"]
+          .iter()
+          .find(|color| spec[1..].starts_with(*color))
+          .map(|color| color.len() + 1),
+      };
+      return Ok(len.map(|len| (String::new(), len)));
+    }
+    'a' | 'c' => {
+      let identity = match first {
+        'a' => commit.author(),
+        _ => commit.committer(),
+      };
+      let identity = Identity::parse(identity)?;
+      let when = |style| date::format(identity.time, identity.offset, style);
+      let expansion = match spec[1..].chars().next() {
+        Some('n' | 'N') => identity.name.clone(),
+        Some('e' | 'E') => identity.email.clone(),
+        Some('l' | 'L') => identity
+          .email
+          .split('@')
+          .next()
+          .unwrap_or_default()
+          .to_string(),
+        Some('d') => when(options.date),
+        Some('D') => when(Style::Rfc),
+        Some('r') => when(Style::Relative),
+        Some('t') => when(Style::Unix),
+        Some('i') => when(Style::Iso),
+        Some('I') => when(Style::IsoStrict),
+        Some('s') => when(Style::Short),
+        _ => return Ok(None),
+      };
+      return Ok(Some((expansion, 2)));
+    }
+    _ => return Ok(None),
+  };
+  Ok(Some((expansion, 1)))
+}
+
+/// Returns the subject of a commit message, as `%s` prints it.
+pub fn title(message: &str) -> String {
+  subject(skip_blank_lines(message)).0
+}
+
+/// Skips the blank (or whitespace only) lines at the start of a message.
+fn skip_blank_lines(message: &str) -> &str {
+  let mut rest = message;
+  while !rest.is_empty() {
+    let end = rest.find('\n').map_or(rest.len(), |i| i + 1);
+    if !rest[..end].trim().is_empty() {
+      break;
+    }
+    rest = &rest[end..];
+  }
+  rest
+}
+
+/// Splits the subject (the first paragraph, its lines joined by spaces) off a
+/// message, and returns it with the rest of the message.
+fn subject(message: &str) -> (String, &str) {
+  let mut lines = Vec::new();
+  let mut rest = message;
+  while !rest.is_empty() {
+    let end = rest.find('\n').map_or(rest.len(), |i| i + 1);
+    let line = rest[..end].trim_end();
+    rest = &rest[end..];
+    if line.is_empty() {
+      break;
+    }
+    lines.push(line);
+  }
+  (lines.join(" "), rest)
+}
+
+/// Turns the first line of a subject into something that can be used as a
+/// file name (`%f`):
+/// runs of other characters than letters, digits, `.` and `_` become a
+/// single `-`, and runs of dots a single dot.
+fn sanitize(subject: &str) -> String {
+  let mut out = String::new();
+  let mut gap = false;
+  let mut last = None;
+  for c in subject.chars() {
+    if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+      if gap && !out.is_empty() {
+        out.push('-');
+      }
+      gap = false;
+      if !(c == '.' && last == Some('.')) {
+        out.push(c);
+      }
+      last = Some(c);
+    } else {
+      gap = true;
+      last = None;
+    }
+  }
+  out.trim_end_matches(['.', '-']).to_string()
+}
+
+/// Expands the tabs of a line to the next multiple of `TAB_WIDTH` columns.
+fn expand_tab(line: &str) -> String {
+  let mut out = String::new();
+  let mut width = 0;
+  for c in line.chars() {
+    if c == '\t' {
+      let spaces = TAB_WIDTH - width % TAB_WIDTH;
+      out.extend(std::iter::repeat_n(' ', spaces));
+      width += spaces;
+    } else {
+      out.push(c);
+      width += 1;
+    }
+  }
+  out
+}
@@ -1,7 +1,8 @@
 use crate::repo::{repo_dir, Repo};
 use std::collections::BTreeMap;
 use std::{
-  fs,
+  fs::{self, File},
+  io::Write,
   path::{Path, PathBuf},
 };
 
@@ -14,12 +15,20 @@ use std::{
 /// of storing the hash of the object directly in the file, we store a string
 /// which represents the path to another ref (which might, in turn, point at
 /// another indirect ref). Indirect refs must be recursively resolves.
+///
+/// Refs that have no file of their own are looked up in `.git/packed-refs`.
 pub fn resolve(repo: &Repo, refr: &Path) -> Result<String, String> {
   let path: PathBuf = if refr.starts_with(&repo.git_dir) {
     PathBuf::from(refr)
   } else {
     repo.git_dir.join(refr)
   };
+  if !path.is_file() {
+    let name = path.strip_prefix(&repo.git_dir).unwrap_or(&path);
+    if let Some(hash) = packed(repo).get(&name.to_string_lossy().into_owned()) {
+      return Ok(hash.to_owned());
+    }
+  }
   match fs::read(&path) {
     Ok(data) => {
       if data.starts_with("ref: ".as_bytes()) {
@@ -38,21 +47,323 @@ pub fn resolve(repo: &Repo, refr: &Path) -> Result<String, String> {
   }
 }
 
+/// The hash reflogs use for a ref that did not exist yet.
+pub const NULL_HASH: &str = "0000000000000000000000000000000000000000";
+
+/// Points a ref (eg. `refs/heads/main`) at an object and records the change
+/// in the ref's reflog.
+///
+/// `identity` is the `Name <email> timestamp timezone` of whoever made the
+/// change and `message` says why (eg. `commit: update readme`).
+pub fn update(
+  repo: &Repo,
+  refname: &str,
+  new: &str,
+  identity: &str,
+  message: &str,
+) -> Result<(), String> {
+  let old = resolve(repo, Path::new(refname)).unwrap_or_else(|_| NULL_HASH.to_string());
+  write_locked(&repo.git_dir.join(refname), &format!("{}\n", new))?;
+  append_reflog(repo, refname, &old, new, identity, message)
+}
+
+/// Writes the file of a ref (or `packed-refs`).
+///
+/// Like git, the new contents are written to `<file>.lock` first and then
+/// renamed over the old file, so a failed write never leaves a truncated ref.
+/// The lock can only be created once, so if it already exists, someone else
+/// is updating the ref and the write fails.
+fn write_locked(path: &Path, data: &str) -> Result<(), String> {
+  let lock = PathBuf::from(format!("{}.lock", path.display()));
+  let file = fs::create_dir_all(path.parent().unwrap())
+    .and_then(|_| File::options().write(true).create_new(true).open(&lock));
+  let mut file = match file {
+    Ok(file) => file,
+    Err(msg) => return Err(format!("unable to create {} ({})", lock.display(), msg)),
+  };
+  let result = file
+    .write_all(data.as_bytes())
+    .and_then(|_| file.sync_all());
+  if let Err(msg) = result.and_then(|_| fs::rename(&lock, path)) {
+    let _ = fs::remove_file(&lock);
+    return Err(format!("unable to write {} ({})", path.display(), msg));
+  }
+  Ok(())
+}
+
+/// Moves HEAD to a new object.
+///
+/// When HEAD points at a branch, the branch is what actually moves, and the
+/// change is recorded in the reflogs of both the branch and HEAD.
+pub fn update_head(repo: &Repo, new: &str, identity: &str, message: &str) -> Result<(), String> {
+  match current_branch(repo) {
+    Some(branch) => {
+      let old = resolve(repo, Path::new("HEAD")).unwrap_or_else(|_| NULL_HASH.to_string());
+      update(repo, &branch, new, identity, message)?;
+      append_reflog(repo, "HEAD", &old, new, identity, message)
+    }
+    None => update(repo, "HEAD", new, identity, message),
+  }
+}
+
+/// Points HEAD at a branch, or detaches it at `new` when `branch` is `None`,
+/// and records the move in the reflog of HEAD (eg. `checkout: moving from
+/// main to topic`).
+///
+/// Unlike `update_head`, this never moves a branch.
+pub fn move_head(
+  repo: &Repo,
+  branch: Option<&str>,
+  new: &str,
+  identity: &str,
+  message: &str,
+) -> Result<(), String> {
+  let old = resolve(repo, Path::new("HEAD")).unwrap_or_else(|_| NULL_HASH.to_string());
+  match branch {
+    Some(branch) => update_symbolic(repo, "HEAD", branch)?,
+    None => {
+      write_locked(&repo.git_dir.join("HEAD"), &format!("{}\n", new))?;
+    }
+  }
+  append_reflog(repo, "HEAD", &old, new, identity, message)
+}
+
+/// Appends an entry to the reflog of a ref.
+fn append_reflog(
+  repo: &Repo,
+  refname: &str,
+  old: &str,
+  new: &str,
+  identity: &str,
+  message: &str,
+) -> Result<(), String> {
+  let path = repo.git_dir.join("logs").join(refname);
+  let line = format!("{} {} {}\t{}\n", old, new, identity, message);
+  let result = fs::create_dir_all(path.parent().unwrap()).and_then(|_| {
+    fs::OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&path)
+      .and_then(|mut file| file.write_all(line.as_bytes()))
+  });
+  match result {
+    Ok(()) => Ok(()),
+    Err(msg) => Err(format!("unable to write reflog for {} ({})", refname, msg)),
+  }
+}
+
+/// Returns the full name of the branch HEAD points at, or `None` if detached.
+pub fn current_branch(repo: &Repo) -> Option<String> {
+  read_symbolic(repo, "HEAD")
+}
+
+/// Strips `refs/heads/`, `refs/remotes/` or `refs/tags/` off a ref name.
+pub fn short_name(refname: &str) -> &str {
+  ["refs/heads/", "refs/remotes/", "refs/tags/"]
+    .iter()
+    .find_map(|prefix| refname.strip_prefix(prefix))
+    .unwrap_or(refname)
+}
+
+/// Returns the name of the ref a symbolic ref (eg. `HEAD` or
+/// `refs/remotes/origin/HEAD`) points at, or `None` if it isn't symbolic.
+pub fn read_symbolic(repo: &Repo, refname: &str) -> Option<String> {
+  let data = fs::read_to_string(repo.git_dir.join(refname)).ok()?;
+  data
+    .strip_prefix("ref: ")
+    .map(|name| name.trim_end().to_string())
+}
+
+/// Points a symbolic ref (usually `HEAD`) at another ref.
+pub fn update_symbolic(repo: &Repo, refname: &str, target: &str) -> Result<(), String> {
+  write_locked(&repo.git_dir.join(refname), &format!("ref: {}\n", target))
+}
+
+/// Deletes a ref, both its loose file and its entry in `.git/packed-refs`,
+/// along with its reflog.
+pub fn delete(repo: &Repo, refname: &str) -> Result<(), String> {
+  remove_loose(repo, refname)?;
+  remove_file(&repo.git_dir.join("logs"), refname)
+}
+
+/// Renames a ref and its reflog, and records the rename in the reflog.
+///
+/// A ref that already has the new name is overwritten. If HEAD points at the
+/// renamed ref, it is moved along, and the rename is recorded in its reflog as
+/// git does: as a deletion of the old ref followed by a creation of the new
+/// one.
+pub fn rename(
+  repo: &Repo,
+  old: &str,
+  new: &str,
+  identity: &str,
+  message: &str,
+) -> Result<(), String> {
+  let hash = resolve(repo, Path::new(old))?;
+  delete(repo, new)?;
+
+  let (old_log, new_log) = (
+    repo.git_dir.join("logs").join(old),
+    repo.git_dir.join("logs").join(new),
+  );
+  if old_log.is_file() {
+    let result =
+      fs::create_dir_all(new_log.parent().unwrap()).and_then(|_| fs::rename(&old_log, &new_log));
+    if let Err(msg) = result {
+      return Err(format!("unable to move the reflog of {} ({})", old, msg));
+    }
+    prune_empty_dirs(&repo.git_dir.join("logs"), old);
+  }
+  remove_loose(repo, old)?;
+
+  write_locked(&repo.git_dir.join(new), &format!("{}\n", hash))?;
+  append_reflog(repo, new, &hash, &hash, identity, message)?;
+
+  if current_branch(repo).as_deref() == Some(old) {
+    update_symbolic(repo, "HEAD", new)?;
+    append_reflog(repo, "HEAD", &hash, NULL_HASH, identity, message)?;
+    append_reflog(repo, "HEAD", NULL_HASH, &hash, identity, message)?;
+  }
+  Ok(())
+}
+
+/// Removes the loose file of a ref and its entry in `.git/packed-refs`.
+fn remove_loose(repo: &Repo, refname: &str) -> Result<(), String> {
+  remove_file(&repo.git_dir, refname)?;
+  let path = repo.git_dir.join("packed-refs");
+  let data = match fs::read_to_string(&path) {
+    Ok(data) => data,
+    Err(_) => return Ok(()),
+  };
+  let mut kept = String::new();
+  let mut removed = false;
+  for line in data.lines() {
+    // the peeled value of a removed tag goes with it
+    let is_peeled = line.starts_with('^');
+    if is_peeled && removed {
+      continue;
+    }
+    removed = !is_peeled && line.split_once(' ').map(|(_, name)| name) == Some(refname);
+    if !removed {
+      kept.push_str(line);
+      kept.push('\n');
+    }
+  }
+  if kept.len() != data.len() {
+    write_locked(&path, &kept)?;
+  }
+  Ok(())
+}
+
+/// Removes the file of a ref (or of its reflog) under `root`, along with the
+/// directories that it leaves empty.
+fn remove_file(root: &Path, refname: &str) -> Result<(), String> {
+  let path = root.join(refname);
+  if path.is_file() {
+    if let Err(msg) = fs::remove_file(&path) {
+      return Err(format!("unable to remove {} ({})", path.display(), msg));
+    }
+    prune_empty_dirs(root, refname);
+  }
+  Ok(())
+}
+
+/// Removes the directories of a ref's name (or of its reflog) under `root`
+/// that are left empty, but never the top ones (eg. `refs/heads`).
+fn prune_empty_dirs(root: &Path, refname: &str) {
+  let mut dir = Path::new(refname).parent();
+  while let Some(path) = dir {
+    if path.components().count() <= 2 || fs::remove_dir(root.join(path)).is_err() {
+      break;
+    }
+    dir = path.parent();
+  }
+}
+
+/// A single entry of a reflog (`.git/logs/<ref>`).
+///
+/// Each line of a reflog records one update of the ref:
+/// ```text
+/// <old hash> <new hash> <name> <<email>> <timestamp> <timezone>\t<message>
+/// ```
+pub struct ReflogEntry {
+  pub old: String,
+  pub new: String,
+  pub message: String,
+}
+
+/// Reads the reflog of the given ref, oldest entry first.
+pub fn reflog(repo: &Repo, refname: &str) -> Result<Vec<ReflogEntry>, String> {
+  let path = repo.git_dir.join("logs").join(refname);
+  let data = match fs::read_to_string(&path) {
+    Ok(data) => data,
+    Err(_) => return Err(format!("no reflog for {}", refname)),
+  };
+  let mut entries = Vec::new();
+  for line in data.lines() {
+    let (head, message) = line.split_once('\t').unwrap_or((line, ""));
+    let mut fields = head.splitn(3, ' ');
+    match (fields.next(), fields.next(), fields.next()) {
+      (Some(old), Some(new), Some(_identity)) => entries.push(ReflogEntry {
+        old: old.to_string(),
+        new: new.to_string(),
+        message: message.to_string(),
+      }),
+      _ => return Err(format!("invalid reflog line in {}", path.display())),
+    }
+  }
+  Ok(entries)
+}
+
 /// Collects refs and returns them as an ordered dictionary.
 ///
 /// Starts in the `.git/refs` directory and recursively builds up a map between
 /// paths and ref hashes. The paths are stored in a prefixed form starting with
-/// `refs/` and each ref is resolved into a hash before being stored.
+/// `refs/` and each ref is resolved into a hash before being stored. Refs in
+/// `.git/packed-refs` under the same directory are included too, unless a
+/// loose ref of the same name overrides them.
 pub fn collect(repo: &Repo, path: Option<&Path>) -> BTreeMap<String, String> {
   let default_path = repo_dir(&repo.git_dir, &["refs"], true).unwrap();
   let path = path.unwrap_or(&default_path);
+  let mut map = collect_loose(repo, path);
+  let prefix = path.strip_prefix(&repo.git_dir).unwrap_or(path);
+  for (name, hash) in packed(repo) {
+    if Path::new(&name).starts_with(prefix) {
+      map.entry(name).or_insert(hash);
+    }
+  }
+  map
+}
+
+/// Reads `.git/packed-refs` into a map of ref names to object hashes.
+///
+/// The file lists one `<hash> <refname>` pair per line. Comment lines start
+/// with `#` and lines starting with `^` hold the peeled value of the annotated
+/// tag above them, which we have no use for here.
+pub fn packed(repo: &Repo) -> BTreeMap<String, String> {
+  let mut map = BTreeMap::new();
+  if let Ok(data) = fs::read_to_string(repo.git_dir.join("packed-refs")) {
+    for line in data.lines() {
+      if line.starts_with('#') || line.starts_with('^') {
+        continue;
+      }
+      if let Some((hash, name)) = line.split_once(' ') {
+        map.insert(name.to_string(), hash.to_string());
+      }
+    }
+  }
+  map
+}
+
+/// Recursively collects the loose refs (files) under the given directory.
+fn collect_loose(repo: &Repo, path: &Path) -> BTreeMap<String, String> {
   let mut map = BTreeMap::new();
   for entry in path.read_dir().expect("unable to read dir").flatten() {
     let entry_path = entry.path();
     let new_path = path.join(&entry_path);
     if entry.file_type().unwrap().is_dir() {
       // build a map of the sub-directory, then flatten result into this map
-      let sub_map = collect(repo, Some(new_path.as_path()));
+      let sub_map = collect_loose(repo, new_path.as_path());
       for pair in sub_map {
         map.insert(pair.0, pair.1);
       }
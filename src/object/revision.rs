@@ -0,0 +1,415 @@
+use std::collections::{BinaryHeap, HashSet};
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::repo::Repo;
+
+use super::commit::Commit;
+use super::refs;
+use super::serializable::Unbox;
+use super::tag::Tag;
+use super::tree::Tree;
+use super::{find_ref, peel, read, resolve_name};
+
+/// A parsed revision expression.
+///
+/// Most expressions name a single object, but some of them describe a set of
+/// commits in terms of the commits that are (or are not) reachable from them.
+#[derive(Debug)]
+pub enum Revision {
+  /// A single object, eg. `HEAD~2`, `v1.0^{tree}` or `main:src/main.rs`.
+  Single(String),
+
+  /// A commit whose history is excluded from a walk (`^A`).
+  Exclude(String),
+
+  /// The commits reachable from the second commit but not the first (`A..B`).
+  Range(String, String),
+
+  /// The commits reachable from either commit but not both (`A...B`).
+  SymmetricDifference(String, String),
+}
+
+/// Parses a revision expression.
+///
+/// Either side of a range may be left out, in which case it defaults to `HEAD`
+/// (so `main..` means `main..HEAD`). The endpoints of ranges and exclusions are
+/// always peeled to commits.
+pub fn parse(repo: &Repo, expr: &str) -> Result<Revision, String> {
+  // Message searches and tree paths may contain dots of their own.
+  if expr.starts_with(":/") || find_unbraced(expr, ":").is_some() {
+    return Ok(Revision::Single(resolve(repo, expr)?));
+  }
+  if let Some(dots) = find_unbraced(expr, "...") {
+    let left = resolve_commit(repo, or_head(&expr[..dots]))?;
+    let right = resolve_commit(repo, or_head(&expr[dots + 3..]))?;
+    return Ok(Revision::SymmetricDifference(left, right));
+  }
+  if let Some(dots) = find_unbraced(expr, "..") {
+    let from = resolve_commit(repo, or_head(&expr[..dots]))?;
+    let to = resolve_commit(repo, or_head(&expr[dots + 2..]))?;
+    return Ok(Revision::Range(from, to));
+  }
+  match expr.strip_prefix('^') {
+    Some(excluded) if !excluded.starts_with('{') => {
+      Ok(Revision::Exclude(resolve_commit(repo, excluded)?))
+    }
+    _ => Ok(Revision::Single(resolve(repo, expr)?)),
+  }
+}
+
+/// Resolves an expression that names a single object into its hash.
+///
+/// The supported forms are:
+/// - `<name>`: anything `find_object` understands; `@` alone means `HEAD`
+/// - `<rev>~<n>`: the `n`th generation ancestor, following first parents only
+/// - `<rev>^<n>`: the `n`th parent (`^0` is the commit itself)
+/// - `<rev>^{<type>}`: the object peeled to the given type
+/// - `<rev>^{}`: the object with every tag peeled off
+/// - `<rev>^{/<regex>}`: the youngest ancestor whose message matches
+/// - `<ref>@{<n>}`: the `n`th prior value of the ref, read from its reflog
+/// - `@{-<n>}`: the `n`th branch checked out before the current one
+/// - `<branch>@{upstream}` or `@{u}`: the branch's upstream
+/// - `:/<regex>`: the youngest commit reachable from any ref whose message
+///   matches
+/// - `<rev>:<path>`: the blob or tree at the given path in the revision's tree
+pub fn resolve(repo: &Repo, expr: &str) -> Result<String, String> {
+  if let Some(pattern) = expr.strip_prefix(":/") {
+    return find_by_message(repo, all_tips(repo), pattern);
+  }
+  if expr.starts_with(':') {
+    return Err(format!(
+      "{}: path lookups in the index are not supported",
+      expr
+    ));
+  }
+  if let Some(colon) = find_unbraced(expr, ":") {
+    let rev = &expr[..colon];
+    return lookup_path(repo, &resolve(repo, rev)?, rev, &expr[colon + 1..]);
+  }
+
+  // The base name ends at the first operator; ref names can never contain
+  // `^`, `~` or `@{`, so this split is unambiguous.
+  let end = expr
+    .find(['^', '~'])
+    .into_iter()
+    .chain(expr.find("@{"))
+    .min()
+    .unwrap_or(expr.len());
+  let (base, mut rest) = expr.split_at(end);
+
+  let mut hash = if rest.starts_with("@{") {
+    let close = match rest.find('}') {
+      Some(close) => close,
+      None => return Err(format!("invalid revision {}", expr)),
+    };
+    let selector = &rest[2..close];
+    rest = &rest[close + 1..];
+    resolve_at(repo, base, selector)?
+  } else {
+    match base {
+      "" => return Err(format!("invalid revision {}", expr)),
+      "@" => resolve_name(repo, "HEAD")?,
+      name => resolve_name(repo, name)?,
+    }
+  };
+
+  while !rest.is_empty() {
+    let op = rest.as_bytes()[0];
+    rest = &rest[1..];
+    if op == b'^' && rest.starts_with('{') {
+      let close = match rest.find('}') {
+        Some(close) => close,
+        None => return Err(format!("invalid revision {}", expr)),
+      };
+      let inner = &rest[1..close];
+      rest = &rest[close + 1..];
+      hash = match inner {
+        "" => peel_tags(repo, &hash)?,
+        "object" => read(repo.clone(), &hash, None).map(|_| hash)?,
+        _ if inner.starts_with('/') => find_by_message(
+          repo,
+          vec![peel(repo.clone(), &hash, "commit", true)?],
+          &inner[1..],
+        )?,
+        typename => peel(repo.clone(), &hash, typename, true)?,
+      };
+      continue;
+    }
+
+    let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+    let n: usize = match digits {
+      0 => 1,
+      _ => rest[..digits]
+        .parse()
+        .map_err(|_| format!("invalid revision {}", expr))?,
+    };
+    rest = &rest[digits..];
+    match op {
+      b'~' => {
+        for _ in 0..n {
+          hash = nth_parent(repo, &hash, 1)?;
+        }
+      }
+      b'^' if n == 0 => hash = peel(repo.clone(), &hash, "commit", true)?,
+      b'^' => hash = nth_parent(repo, &hash, n)?,
+      _ => return Err(format!("invalid revision {}", expr)),
+    }
+  }
+  Ok(hash)
+}
+
+/// Resolves an expression and peels it to a commit.
+pub fn resolve_commit(repo: &Repo, expr: &str) -> Result<String, String> {
+  let hash = resolve(repo, expr)?;
+  peel(repo.clone(), &hash, "commit", true)
+}
+
+/// Returns the full name of the ref an expression refers to, if it is one.
+///
+/// `HEAD` resolves to the branch it points at (`None` if it is detached) and
+/// `<branch>@{upstream}` resolves to the upstream's remote-tracking branch.
+pub fn ref_name(repo: &Repo, expr: &str) -> Result<Option<String>, String> {
+  if let Some(at) = expr.find("@{") {
+    let selector = expr[at + 2..].trim_end_matches('}');
+    if is_upstream(selector) {
+      return upstream(repo, &expr[..at]).map(Some);
+    }
+  }
+  match expr {
+    "HEAD" | "@" => Ok(refs::current_branch(repo)),
+    _ => Ok(find_ref(repo, expr)),
+  }
+}
+
+/// Returns the full name of the upstream of a branch.
+///
+/// The upstream is configured by `branch.<name>.remote` and `.merge`. A remote
+/// of `.` means the upstream is a local branch, otherwise it is the matching
+/// remote-tracking branch under `refs/remotes/<remote>/`. An empty branch name
+/// (or `HEAD`) means the current branch.
+pub fn upstream(repo: &Repo, branch: &str) -> Result<String, String> {
+  let refname = match branch {
+    "" | "HEAD" | "@" => match refs::current_branch(repo) {
+      Some(refname) => refname,
+      None => return Err("HEAD does not point to a branch".to_string()),
+    },
+    name => find_ref(repo, name).unwrap_or_else(|| format!("refs/heads/{}", name)),
+  };
+  let name = match refname.strip_prefix("refs/heads/") {
+    Some(name) => name,
+    None => return Err(format!("{} is not a branch", branch)),
+  };
+
+  let remote = repo.config_value("branch", Some(name), "remote");
+  let merge = repo.config_value("branch", Some(name), "merge");
+  match (remote, merge) {
+    (Some(remote), Some(merge)) if remote == "." => Ok(merge),
+    (Some(remote), Some(merge)) => {
+      let merge = merge.strip_prefix("refs/heads/").unwrap_or(&merge);
+      Ok(format!("refs/remotes/{}/{}", remote, merge))
+    }
+    _ => Err(format!("no upstream configured for branch '{}'", name)),
+  }
+}
+
+/// Returns every commit reachable from the given commit, including itself.
+pub fn ancestors(repo: &Repo, hash: &str) -> Result<HashSet<String>, String> {
+  let mut seen: HashSet<String> = HashSet::new();
+  let mut stack = vec![hash.to_string()];
+  while let Some(hash) = stack.pop() {
+    if seen.insert(hash.clone()) {
+      let object = read(repo.clone(), &hash, Some("commit"))?;
+      stack.extend(object.unbox::<Commit>()?.parents());
+    }
+  }
+  Ok(seen)
+}
+
+/// Counts the commits reachable from `left` but not from `right`, and the
+/// other way around (ie. how far ahead and behind `right` `left` is).
+pub fn ahead_behind(repo: &Repo, left: &str, right: &str) -> Result<(usize, usize), String> {
+  let (left, right) = (ancestors(repo, left)?, ancestors(repo, right)?);
+  Ok((
+    left.difference(&right).count(),
+    right.difference(&left).count(),
+  ))
+}
+
+/// Returns the index of the first occurrence of `pattern` outside of braces.
+fn find_unbraced(expr: &str, pattern: &str) -> Option<usize> {
+  let mut depth = 0;
+  for (i, c) in expr.char_indices() {
+    match c {
+      '{' => depth += 1,
+      '}' if depth > 0 => depth -= 1,
+      _ if depth == 0 && expr[i..].starts_with(pattern) => return Some(i),
+      _ => (),
+    }
+  }
+  None
+}
+
+/// Empty range endpoints stand for `HEAD`.
+fn or_head(expr: &str) -> &str {
+  match expr {
+    "" => "HEAD",
+    expr => expr,
+  }
+}
+
+fn is_upstream(selector: &str) -> bool {
+  selector.eq_ignore_ascii_case("upstream") || selector.eq_ignore_ascii_case("u")
+}
+
+/// Resolves a `<base>@{<selector>}` expression.
+fn resolve_at(repo: &Repo, base: &str, selector: &str) -> Result<String, String> {
+  if is_upstream(selector) {
+    let refname = upstream(repo, base)?;
+    return refs::resolve(repo, Path::new(&refname));
+  }
+  if let Some(n) = selector.strip_prefix('-') {
+    return match n.parse::<usize>() {
+      Ok(n) if n > 0 && base.is_empty() => previous_branch(repo, n),
+      _ => Err(format!("invalid reflog selector @{{{}}}", selector)),
+    };
+  }
+  let n = match selector.parse::<usize>() {
+    Ok(n) => n,
+    Err(_) => return Err(format!("unsupported reflog selector @{{{}}}", selector)),
+  };
+
+  let refname = match base {
+    "" => refs::current_branch(repo).unwrap_or_else(|| "HEAD".to_string()),
+    "HEAD" | "@" => "HEAD".to_string(),
+    name => match find_ref(repo, name) {
+      Some(refname) => refname,
+      None => return Err(format!("{} is not a ref", name)),
+    },
+  };
+  let entries = refs::reflog(repo, &refname)?;
+  let is_null = |hash: &str| hash.chars().all(|c| c == '0');
+  if n < entries.len() {
+    Ok(entries[entries.len() - 1 - n].new.clone())
+  } else if n == entries.len() && !entries.is_empty() && !is_null(&entries[0].old) {
+    Ok(entries[0].old.clone())
+  } else {
+    Err(format!(
+      "log for '{}' only has {} entries",
+      refname,
+      entries.len()
+    ))
+  }
+}
+
+/// Finds the `n`th previously checked out branch in the reflog of HEAD.
+fn previous_branch(repo: &Repo, n: usize) -> Result<String, String> {
+  resolve_name(repo, &previous_checkout(repo, n)?)
+}
+
+/// Returns the name (a branch, or the hash of a detached HEAD) of the `n`th
+/// previously checked out branch (`@{-<n>}`), as recorded in the reflog of
+/// HEAD.
+pub fn previous_checkout(repo: &Repo, n: usize) -> Result<String, String> {
+  let entries = refs::reflog(repo, "HEAD")?;
+  let previous = entries
+    .iter()
+    .rev()
+    .filter_map(|entry| entry.message.strip_prefix("checkout: moving from "))
+    .filter_map(|moved| moved.split_once(" to ").map(|(from, _)| from))
+    .nth(n - 1);
+  match previous {
+    Some(branch) => Ok(branch.to_string()),
+    None => Err(format!("no branch was checked out {} switches ago", n)),
+  }
+}
+
+/// Returns the `n`th (1-based) parent of a commit.
+fn nth_parent(repo: &Repo, hash: &str, n: usize) -> Result<String, String> {
+  let hash = peel(repo.clone(), hash, "commit", true)?;
+  let object = read(repo.clone(), &hash, Some("commit"))?;
+  match object.unbox::<Commit>()?.parents().get(n - 1) {
+    Some(parent) => Ok(parent.clone()),
+    None => Err(format!("{} has no parent {}", hash, n)),
+  }
+}
+
+/// Peels off annotated tags until a non-tag object is reached.
+fn peel_tags(repo: &Repo, hash: &str) -> Result<String, String> {
+  let mut hash = hash.to_string();
+  loop {
+    let object = read(repo.clone(), &hash, None)?;
+    if object.format() != "tag" {
+      return Ok(hash);
+    }
+    hash = match object.unbox::<Tag>()?.get("object") {
+      Some(next) => next.to_string(),
+      None => return Err(format!("tag {} has no object", hash)),
+    };
+  }
+}
+
+/// Looks up a path in the tree of a revision.
+fn lookup_path(repo: &Repo, hash: &str, rev: &str, path: &str) -> Result<String, String> {
+  let mut hash = peel(repo.clone(), hash, "tree", true)?;
+  for component in path.split('/').filter(|component| !component.is_empty()) {
+    let object = read(repo.clone(), &hash, None)?;
+    let entry = match object.format().as_str() {
+      "tree" => object
+        .unbox::<Tree>()?
+        .entries()
+        .iter()
+        .find(|entry| entry.path == component),
+      _ => None,
+    };
+    match entry {
+      Some(entry) => hash = entry.hash.clone(),
+      None => return Err(format!("path '{}' does not exist in '{}'", path, rev)),
+    }
+  }
+  Ok(hash)
+}
+
+/// Returns the commits that every ref (and HEAD) points at.
+fn all_tips(repo: &Repo) -> Vec<String> {
+  let mut tips: Vec<String> = refs::collect(repo, None).into_values().collect();
+  tips.extend(resolve_name(repo, "HEAD"));
+  tips
+    .into_iter()
+    .filter_map(|hash| peel(repo.clone(), &hash, "commit", true).ok())
+    .collect()
+}
+
+/// Finds the youngest commit reachable from `starts` whose message matches.
+///
+/// Commits are visited newest first (by committer date), so the first match
+/// is the youngest one.
+fn find_by_message(repo: &Repo, starts: Vec<String>, pattern: &str) -> Result<String, String> {
+  let regex = match Regex::new(pattern) {
+    Ok(regex) => regex,
+    Err(msg) => return Err(format!("invalid regex {} ({})", pattern, msg)),
+  };
+  let mut seen: HashSet<String> = HashSet::new();
+  let mut queue: BinaryHeap<(i64, String)> = BinaryHeap::new();
+  for hash in starts {
+    if seen.insert(hash.clone()) {
+      let object = read(repo.clone(), &hash, Some("commit"))?;
+      queue.push((object.unbox::<Commit>()?.commit_time(), hash));
+    }
+  }
+  while let Some((_, hash)) = queue.pop() {
+    let object = read(repo.clone(), &hash, Some("commit"))?;
+    let commit = object.unbox::<Commit>()?;
+    if regex.is_match(commit.message()) {
+      return Ok(hash);
+    }
+    for parent in commit.parents() {
+      if seen.insert(parent.clone()) {
+        let object = read(repo.clone(), &parent, Some("commit"))?;
+        queue.push((object.unbox::<Commit>()?.commit_time(), parent));
+      }
+    }
+  }
+  Err(format!("no commit message matches {}", pattern))
+}
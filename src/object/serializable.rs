@@ -2,7 +2,7 @@ use std::any::Any;
 
 use crate::repo::Repo;
 
-pub trait Serializable {
+pub trait Serializable: Any {
   fn serialize(&self) -> &[u8];
   fn deserialize(&mut self, data: &[u8]);
   fn format(&self) -> &String;
@@ -15,7 +15,7 @@ pub trait Unbox {
 
 impl Unbox for Box<dyn Serializable> {
   fn unbox<T: Any>(&self) -> Result<&T, String> {
-    let upcast_self: &dyn Any = self;
+    let upcast_self: &dyn Any = &**self;
     match upcast_self.downcast_ref::<T>() {
       Some(cmt) => Ok(cmt),
       None => Err("downcast to commit failed".to_string()),
@@ -33,7 +33,7 @@ impl Tag {
       map: MailMap::new(),
       repo,
     };
-    new_tag.map.parse_bytes(data, 0);
+    new_tag.map.parse_bytes(data);
     new_tag
   }
 }
@@ -52,7 +52,7 @@ impl Serializable for Tag {
   }
 
   fn deserialize(&mut self, data: &[u8]) {
-    self.map.parse_bytes(data, 0)
+    self.map.parse_bytes(data)
   }
 
   fn format(&self) -> &String {
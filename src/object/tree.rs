@@ -1,7 +1,9 @@
+use std::collections::BTreeMap;
+
 use crate::repo::Repo;
 
 use super::findable::Findable;
-use super::serializable::Serializable;
+use super::serializable::{Serializable, Unbox};
 
 use super::mode::Mode;
 
@@ -41,11 +43,60 @@ impl Tree {
     new_tree
   }
 
+  /// Builds a tree out of a list of entries.
+  ///
+  /// Git sorts the entries of a tree by name, except that the names of
+  /// subtrees are compared as if they ended with a `/` (so `foo.rs` comes
+  /// before the directory `foo`, which comes before `foo0`).
+  pub fn from_entries(repo: Repo, mut entries: Vec<TreeEntry>) -> Self {
+    entries.sort_by_key(|entry| entry.sort_key());
+    let mut bytes = Vec::new();
+    for entry in &entries {
+      bytes.extend_from_slice(format!("{} {}\0", entry.mode as usize, entry.path).as_bytes());
+      bytes.extend_from_slice(&hex::decode(&entry.hash).expect("invalid hash"));
+    }
+    Self {
+      bytes,
+      entries,
+      format: String::from("tree"),
+      repo,
+    }
+  }
+
   pub fn entries(&self) -> &Vec<TreeEntry> {
     &self.entries
   }
 }
 
+/// Reads a tree and all of its subtrees into a map from the full path of every
+/// blob (or gitlink) to its mode and hash.
+///
+/// The paths are sorted the same way as the index sorts them.
+pub fn flatten(repo: &Repo, hash: &str) -> Result<BTreeMap<String, (Mode, String)>, String> {
+  let mut files = BTreeMap::new();
+  flatten_into(repo, hash, "", &mut files)?;
+  Ok(files)
+}
+
+fn flatten_into(
+  repo: &Repo,
+  hash: &str,
+  prefix: &str,
+  files: &mut BTreeMap<String, (Mode, String)>,
+) -> Result<(), String> {
+  let object = super::read(repo.clone(), hash, Some("tree"))?;
+  for entry in object.unbox::<Tree>()?.entries() {
+    let path = format!("{}{}", prefix, entry.path);
+    match entry.mode {
+      Mode::Directory => flatten_into(repo, &entry.hash, &format!("{}/", path), files)?,
+      mode => {
+        files.insert(path, (mode, entry.hash.clone()));
+      }
+    }
+  }
+  Ok(())
+}
+
 impl Serializable for Tree {
   fn serialize(&self) -> &[u8] {
     &self.bytes
@@ -80,6 +131,17 @@ pub struct TreeEntry {
 }
 
 impl TreeEntry {
+  /// Creates an entry for an object in a tree.
+  pub fn new(mode: Mode, path: &str, hash: &str) -> Self {
+    let mode_len = (mode as usize).to_string().len();
+    Self {
+      mode,
+      path: path.to_string(),
+      hash: hash.to_string(),
+      len: mode_len + 1 + path.len() + 1 + 20,
+    }
+  }
+
   /// Constructs a new TreeEntry from raw bytes starting at offset.
   ///
   /// An entry in the bytes is formatted as: `[mode] 0x20 [path] 0x00 [sha-1]`
@@ -115,4 +177,13 @@ impl TreeEntry {
       len,
     }
   }
+
+  /// The name the entry is sorted by (subtrees get a trailing `/`).
+  pub fn sort_key(&self) -> Vec<u8> {
+    let mut key = self.path.as_bytes().to_vec();
+    if self.mode == Mode::Directory {
+      key.push(b'/');
+    }
+    key
+  }
 }
@@ -0,0 +1,590 @@
+//! Walks the history of a set of commits, the way `git rev-list` does.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::slice;
+
+use regex::Regex;
+
+use crate::diff::tree::diff_trees;
+use crate::index::pathspec::Pathspec;
+use crate::repo::Repo;
+
+use super::commit::Commit;
+use super::merge_base;
+use super::read;
+use super::revision::{self, Revision};
+use super::serializable::Unbox;
+
+/// The order commits are shown in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Order {
+  /// Newest commit first (by committer date), as the history is walked.
+  #[default]
+  Walk,
+
+  /// No parent before all of its children, otherwise newest first
+  /// (`--date-order`).
+  Date,
+
+  /// No parent before all of its children, and the commits of each line of
+  /// history kept together (`--topo-order`).
+  Topo,
+}
+
+/// Which commits a walk shows, and in what order.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Options {
+  pub order: Order,
+
+  /// Show the commits oldest first (after `skip` and `max_count` are applied).
+  pub reverse: bool,
+
+  /// Only follow the first parent of merge commits.
+  pub first_parent: bool,
+
+  /// The number of commits to leave out from the start.
+  pub skip: usize,
+
+  /// The most commits to show.
+  pub max_count: Option<usize>,
+
+  /// Stop at commits committed before this time (`--since`).
+  pub since: Option<i64>,
+
+  /// Leave out commits committed after this time (`--until`).
+  pub until: Option<i64>,
+
+  /// Only show commits with at least this many parents (2 for `--merges`).
+  pub min_parents: usize,
+
+  /// Only show commits with at most this many parents (1 for
+  /// `--no-merges`).
+  pub max_parents: Option<usize>,
+
+  /// When commits are left out because they don't touch the pathspec, keep
+  /// the merges that join the lines of history that do, and make the parents
+  /// of each commit the nearest commits that are shown (for `--graph`).
+  pub rewrite_parents: bool,
+}
+
+/// Patterns that commits must match to be shown.
+///
+/// A commit is shown if its author matches any of `authors` (if there are
+/// any), its committer any of `committers`, and a line of its message any of
+/// `messages` (or all of them, with `all_match`). With `invert`, it is shown
+/// if no line of its message matches instead.
+#[derive(Debug, Default)]
+pub struct Grep {
+  pub authors: Vec<Regex>,
+  pub committers: Vec<Regex>,
+  pub messages: Vec<Regex>,
+  pub all_match: bool,
+  pub invert: bool,
+}
+
+impl Grep {
+  /// Whether a commit matches the patterns. The author and committer are
+  /// matched as `Name <email>`.
+  pub fn matches(&self, commit: &Commit) -> bool {
+    let identity = |header: &str| match header.rsplitn(3, ' ').nth(2) {
+      Some(identity) => identity.to_string(),
+      None => header.to_string(),
+    };
+    let any = |patterns: &[Regex], text: &str| {
+      patterns.is_empty() || patterns.iter().any(|pattern| pattern.is_match(text))
+    };
+    if !any(&self.authors, &identity(commit.author()))
+      || !any(&self.committers, &identity(commit.committer()))
+    {
+      return false;
+    }
+    if self.messages.is_empty() {
+      return true;
+    }
+
+    let message = commit.message();
+    let hit = |pattern: &Regex| message.lines().any(|line| pattern.is_match(line));
+    match (self.invert, self.all_match) {
+      (true, _) => !self.messages.iter().any(hit),
+      (false, true) => self.messages.iter().all(hit),
+      (false, false) => self.messages.iter().any(hit),
+    }
+  }
+}
+
+/// A walk through the commits reachable from some commits (the tips) but not
+/// from others (the hidden commits).
+///
+/// With a pathspec, the history is simplified: commits that change nothing
+/// the pathspec matches are left out, and a merge that has the same files
+/// (that the pathspec matches) as one of its parents only has its history
+/// followed through that parent.
+///
+/// ```ignore
+/// let mut walk = Walk::new(&repo, options);
+/// walk.add(revision::parse(&repo, "main..topic")?)?;
+/// for hash in walk.run()? {
+///   println!("{}", hash);
+/// }
+/// ```
+pub struct Walk {
+  repo: Repo,
+  options: Options,
+  pathspec: Option<Pathspec>,
+  grep: Grep,
+  tips: Vec<String>,
+  hidden: Vec<String>,
+  excluded: HashSet<String>,
+  commits: HashMap<String, Node>,
+}
+
+/// What the walk knows about a commit.
+struct Node {
+  time: i64,
+  tree: String,
+
+  /// The parents of the commit, or the ones left after simplifying the
+  /// history (or after rewriting them).
+  parents: Vec<String>,
+
+  /// Whether the history has been simplified at the commit.
+  simplified: bool,
+
+  /// Whether the commit changes nothing the pathspec matches.
+  treesame: bool,
+}
+
+impl Walk {
+  pub fn new(repo: &Repo, options: Options) -> Self {
+    Self {
+      repo: repo.clone(),
+      options,
+      pathspec: None,
+      grep: Grep::default(),
+      tips: Vec::new(),
+      hidden: Vec::new(),
+      excluded: HashSet::new(),
+      commits: HashMap::new(),
+    }
+  }
+
+  /// Only shows the commits that change the paths a pathspec matches.
+  pub fn set_pathspec(&mut self, pathspec: Pathspec) {
+    if !pathspec.is_empty() {
+      self.pathspec = Some(pathspec);
+    }
+  }
+
+  /// Only shows the commits that match some patterns.
+  pub fn set_grep(&mut self, grep: Grep) {
+    self.grep = grep;
+  }
+
+  /// Walks the history of a commit.
+  pub fn push(&mut self, hash: &str) {
+    self.tips.push(hash.to_string());
+  }
+
+  /// Leaves out a commit and everything reachable from it.
+  pub fn hide(&mut self, hash: &str) {
+    self.hidden.push(hash.to_string());
+  }
+
+  /// Adds the commits a revision expression describes: a single commit is
+  /// walked, `^A` is hidden, `A..B` walks `B` and hides `A` and `A...B` walks
+  /// both and hides their common history.
+  pub fn add(&mut self, revision: Revision) -> Result<(), String> {
+    match revision {
+      Revision::Single(hash) => {
+        let hash = revision::resolve_commit(&self.repo, &hash)?;
+        self.push(&hash);
+      }
+      Revision::Exclude(hash) => self.hide(&hash),
+      Revision::Range(from, to) => {
+        self.hide(&from);
+        self.push(&to);
+      }
+      Revision::SymmetricDifference(left, right) => {
+        for base in merge_base::merge_bases(&self.repo, &left, slice::from_ref(&right))? {
+          self.hide(&base);
+        }
+        self.push(&left);
+        self.push(&right);
+      }
+    }
+    Ok(())
+  }
+
+  /// Walks the history and returns the hashes of the commits to show, in
+  /// order.
+  pub fn run(&mut self) -> Result<Vec<String>, String> {
+    for hash in self.hidden.clone() {
+      if !self.excluded.contains(&hash) {
+        self
+          .excluded
+          .extend(revision::ancestors(&self.repo, &hash)?);
+      }
+    }
+
+    // unless the whole history has to be walked first, the walk can stop as
+    // soon as it has found enough
+    let limit = match self.is_limited() {
+      false => self.options.max_count.map(|n| n + self.options.skip),
+      true => None,
+    };
+    let (mut commits, shown) = self.walk(limit)?;
+    match self.options.order {
+      Order::Walk => (),
+      Order::Date => commits = self.sort(commits, false),
+      Order::Topo => commits = self.sort(commits, true),
+    }
+
+    let mut commits: Vec<String> = commits
+      .into_iter()
+      .filter(|hash| shown.contains(hash))
+      .skip(self.options.skip)
+      .take(self.options.max_count.unwrap_or(usize::MAX))
+      .collect();
+    if self.options.reverse {
+      commits.reverse();
+    }
+    if self.options.rewrite_parents && self.pathspec.is_some() {
+      for hash in &commits {
+        self.rewrite_parents(hash)?;
+      }
+    }
+    Ok(commits)
+  }
+
+  /// The parents of a commit the walk has visited, as the walk sees them:
+  /// only the parent the history was simplified to, or the rewritten parents.
+  pub fn parents(&self, hash: &str) -> &[String] {
+    self.commits.get(hash).map_or(&[], |node| &node.parents)
+  }
+
+  /// Whether a commit would be shown by the walk (if it wasn't for `skip`
+  /// and `max_count`). Commits the walk didn't get to are only known to
+  /// change the paths if they don't.
+  pub fn is_interesting(&mut self, hash: &str) -> Result<bool, String> {
+    let time = self.load(hash)?.time;
+    if self.options.since.is_some_and(|since| time < since) {
+      return Ok(false);
+    }
+    self.is_shown(hash)
+  }
+
+  /// Whether the whole history is walked before any commit is shown, which is
+  /// needed to sort it or to leave out hidden commits.
+  fn is_limited(&self) -> bool {
+    self.options.order != Order::Walk || !self.hidden.is_empty()
+  }
+
+  /// Visits the commits newest first, starting from the tips, until `limit`
+  /// commits to show have been found. Returns the commits visited and the
+  /// ones of them to show.
+  ///
+  /// Commits with the same date are visited in the order they were found.
+  fn walk(&mut self, limit: Option<usize>) -> Result<(Vec<String>, HashSet<String>), String> {
+    let mut queue = Queue::default();
+    let mut seen: HashSet<String> = HashSet::new();
+    for tip in self.tips.clone() {
+      if !self.excluded.contains(&tip) && seen.insert(tip.clone()) {
+        queue.push(self.load(&tip)?.time, tip);
+      }
+    }
+
+    let mut commits = Vec::new();
+    let mut shown = HashSet::new();
+    while let Some(hash) = queue.pop() {
+      if limit.is_some_and(|limit| shown.len() >= limit) {
+        break;
+      }
+      // the history before `since` isn't walked at all, and when the whole
+      // history is walked first, it hides the commits it leads to (even the
+      // ones after `since`), like git
+      if self
+        .options
+        .since
+        .is_some_and(|since| self.commits[&hash].time < since)
+      {
+        if self.is_limited() {
+          self.exclude(&hash)?;
+        }
+        continue;
+      }
+      self.simplify(&hash)?;
+      let mut parents = self.commits[&hash].parents.clone();
+      if self.options.first_parent {
+        parents.truncate(1);
+      }
+      for parent in parents {
+        if !self.excluded.contains(&parent) && seen.insert(parent.clone()) {
+          queue.push(self.load(&parent)?.time, parent);
+        }
+      }
+      // commits after `until` are walked through, but aren't sorted
+      if self
+        .options
+        .until
+        .is_some_and(|until| self.commits[&hash].time > until)
+      {
+        continue;
+      }
+      if self.is_shown(&hash)? {
+        shown.insert(hash.clone());
+      }
+      commits.push(hash);
+    }
+    shown.retain(|hash| !self.excluded.contains(hash));
+    Ok((commits, shown))
+  }
+
+  /// Hides a commit and its ancestors (through the parents the history was
+  /// simplified to).
+  fn exclude(&mut self, hash: &str) -> Result<(), String> {
+    let mut stack = vec![hash.to_string()];
+    while let Some(hash) = stack.pop() {
+      if self.excluded.insert(hash.clone()) {
+        stack.extend(self.load(&hash)?.parents.iter().cloned());
+      }
+    }
+    Ok(())
+  }
+
+  /// Whether a commit that has been visited is shown: it isn't hidden, it
+  /// matches the filters and (with a pathspec) it changes something, or it is
+  /// a merge that joins lines of history that do when parents are rewritten.
+  fn is_shown(&self, hash: &str) -> Result<bool, String> {
+    let node = &self.commits[hash];
+    if self.excluded.contains(hash)
+      || self.options.until.is_some_and(|until| node.time > until)
+      || node.parents.len() < self.options.min_parents
+      || self
+        .options
+        .max_parents
+        .is_some_and(|max| node.parents.len() > max)
+    {
+      return Ok(false);
+    }
+    let object = read(self.repo.clone(), hash, Some("commit"))?;
+    if !self.grep.matches(object.unbox::<Commit>()?) {
+      return Ok(false);
+    }
+    if node.treesame {
+      if !self.options.rewrite_parents {
+        return Ok(false);
+      }
+      let relevant = node.parents.iter().filter(|p| self.is_relevant(p));
+      return Ok(relevant.count() >= 2);
+    }
+    Ok(true)
+  }
+
+  /// Whether a parent counts when simplifying the history: it isn't hidden,
+  /// or it is one of the commits that were hidden explicitly (like `A` in
+  /// `A..B`).
+  fn is_relevant(&self, hash: &str) -> bool {
+    !self.excluded.contains(hash) || self.hidden.iter().any(|hidden| hidden == hash)
+  }
+
+  /// Works out whether a commit changes anything the pathspec matches, and
+  /// simplifies its parents: if it has the same files as a relevant parent,
+  /// that parent becomes its only one.
+  ///
+  /// Other parents don't make a merge differ from its history, unless none
+  /// of its parents are relevant.
+  fn simplify(&mut self, hash: &str) -> Result<(), String> {
+    if self.pathspec.is_none() || self.load(hash)?.simplified {
+      return Ok(());
+    }
+    let node = &self.commits[hash];
+    let (tree, parents) = (node.tree.clone(), node.parents.clone());
+
+    let mut treesame = None;
+    let mut relevant_parents = 0;
+    let mut changed = false;
+    let mut irrelevant_change = false;
+    if parents.is_empty() {
+      treesame = Some(!self.changes(None, &tree)?);
+    }
+    for (i, parent) in parents.iter().enumerate() {
+      let relevant = self.is_relevant(parent);
+      relevant_parents += relevant as usize;
+      if i == 1 && self.options.first_parent {
+        break;
+      }
+      let parent_tree = self.load(parent)?.tree.clone();
+      if !self.changes(Some(&parent_tree), &tree)? {
+        if relevant {
+          let node = self.commits.get_mut(hash).unwrap();
+          node.parents = vec![parent.clone()];
+          treesame = Some(true);
+          break;
+        }
+      } else if relevant {
+        changed = true;
+      } else {
+        irrelevant_change = true;
+      }
+    }
+
+    let node = self.commits.get_mut(hash).unwrap();
+    node.simplified = true;
+    node.treesame = treesame.unwrap_or(match relevant_parents {
+      0 => !irrelevant_change,
+      _ => !changed,
+    });
+    Ok(())
+  }
+
+  /// Whether two trees differ in any path the pathspec matches.
+  fn changes(&self, old: Option<&str>, new: &str) -> Result<bool, String> {
+    let pathspec = self.pathspec.as_ref().unwrap();
+    let changes = diff_trees(&self.repo, old, Some(new), true)?;
+    Ok(changes.iter().any(|change| {
+      [&change.old, &change.new].iter().any(|side| {
+        side
+          .as_ref()
+          .is_some_and(|side| pathspec.matches(&side.path))
+      })
+    }))
+  }
+
+  /// Replaces the parents of a commit that aren't shown with their nearest
+  /// ancestors that are (following a single line of history), and leaves out
+  /// the ones that have none.
+  fn rewrite_parents(&mut self, hash: &str) -> Result<(), String> {
+    let mut parents: Vec<String> = Vec::new();
+    for parent in self.commits[hash].parents.clone() {
+      if let Some(parent) = self.rewrite_parent(parent)? {
+        if !parents.contains(&parent) {
+          parents.push(parent);
+        }
+      }
+    }
+    self.commits.get_mut(hash).unwrap().parents = parents;
+    Ok(())
+  }
+
+  fn rewrite_parent(&mut self, mut hash: String) -> Result<Option<String>, String> {
+    loop {
+      self.load(&hash)?;
+      let node = &self.commits[&hash];
+      if self.excluded.contains(&hash) || !node.treesame {
+        return Ok(Some(hash));
+      }
+      if node.parents.is_empty() {
+        return Ok(None);
+      }
+      // a merge can only be skipped over if a single parent isn't hidden
+      let mut relevant = node.parents.iter().filter(|p| self.is_relevant(p));
+      let next = match (
+        self.options.first_parent || node.parents.len() == 1,
+        relevant.next(),
+      ) {
+        (true, _) => &node.parents[0],
+        (false, Some(parent)) if relevant.next().is_none() => parent,
+        _ => return Ok(Some(hash)),
+      };
+      hash = next.clone();
+    }
+  }
+
+  /// Sorts commits so that every commit comes before its parents (all of
+  /// them, even with `first_parent`).
+  ///
+  /// Of the commits whose children have all been shown, the newest is shown
+  /// next, or with `topo` the one found last (which keeps following the same
+  /// line of history).
+  fn sort(&self, commits: Vec<String>, topo: bool) -> Vec<String> {
+    // the number of children each commit has among the commits, plus one
+    let mut indegree: HashMap<&str, usize> =
+      commits.iter().map(|hash| (hash.as_str(), 1)).collect();
+    for hash in &commits {
+      for parent in &self.commits[hash].parents {
+        if let Some(count) = indegree.get_mut(parent.as_str()) {
+          *count += 1;
+        }
+      }
+    }
+
+    let mut ready = Queue::default();
+    let mut stack: Vec<&str> = Vec::new();
+    for hash in &commits {
+      if indegree[hash.as_str()] == 1 {
+        match topo {
+          true => stack.push(hash),
+          false => ready.push(self.commits[hash].time, hash.clone()),
+        }
+      }
+    }
+    // the tips are taken in the order they were walked
+    stack.reverse();
+
+    let mut sorted = Vec::with_capacity(commits.len());
+    loop {
+      let hash = match topo {
+        true => stack.pop().map(str::to_string),
+        false => ready.pop(),
+      };
+      let Some(hash) = hash else {
+        break;
+      };
+      for parent in &self.commits[&hash].parents {
+        if let Some(count) = indegree.get_mut(parent.as_str()) {
+          *count -= 1;
+          if *count == 1 {
+            match topo {
+              true => stack.push(parent),
+              false => ready.push(self.commits[parent].time, parent.clone()),
+            }
+          }
+        }
+      }
+      sorted.push(hash);
+    }
+    sorted
+  }
+
+  /// Reads the committer date, tree and parents of a commit, once.
+  fn load(&mut self, hash: &str) -> Result<&Node, String> {
+    if !self.commits.contains_key(hash) {
+      let object = read(self.repo.clone(), hash, Some("commit"))?;
+      let commit = object.unbox::<Commit>()?;
+      let node = Node {
+        time: commit.commit_time(),
+        tree: commit.tree().to_string(),
+        parents: commit.parents(),
+        simplified: false,
+        treesame: false,
+      };
+      self.commits.insert(hash.to_string(), node);
+    }
+    Ok(&self.commits[hash])
+  }
+}
+
+/// Commits ordered newest first, and first in first out among commits with
+/// the same date.
+#[derive(Default)]
+pub(crate) struct Queue {
+  heap: BinaryHeap<(i64, Reverse<usize>, String)>,
+  count: usize,
+}
+
+impl Queue {
+  pub(crate) fn push(&mut self, date: i64, hash: String) {
+    self.heap.push((date, Reverse(self.count), hash));
+    self.count += 1;
+  }
+
+  pub(crate) fn pop(&mut self) -> Option<String> {
+    self.heap.pop().map(|(_, _, hash)| hash)
+  }
+
+  /// The commits in the queue, in no particular order.
+  pub(crate) fn hashes(&self) -> impl Iterator<Item = &str> {
+    self.heap.iter().map(|(_, _, hash)| hash.as_str())
+  }
+}
@@ -0,0 +1,165 @@
+use std::fs;
+
+use ini::Ini as ConfigParser;
+
+use super::Repo;
+
+/// Edits `.git/config` in place.
+///
+/// The file is edited line by line, like git does, so that the comments and
+/// the layout of the rest of the file are kept as they are.
+impl Repo {
+  /// Sets a value in the repository's config, or unsets it when `value` is
+  /// `None`.
+  ///
+  /// A value that is already set is replaced where it is; otherwise it is
+  /// added at the end of the last matching section, which is created if
+  /// there is none.
+  pub fn set_config(
+    &mut self,
+    section: &str,
+    subsection: Option<&str>,
+    key: &str,
+    value: Option<&str>,
+  ) -> Result<(), String> {
+    let mut lines = self.read_config()?;
+    let line = value.map(|value| format!("\t{} = {}", key, quote(value)));
+
+    let mut current = false;
+    let mut last_section = None;
+    let mut last_key = None;
+    let mut i = 0;
+    while i < lines.len() {
+      if let Some(header) = parse_header(&lines[i]) {
+        current = header_matches(&header, section, subsection);
+      } else if current && parse_key(&lines[i]).is_some_and(|k| k.eq_ignore_ascii_case(key)) {
+        if line.is_none() {
+          lines.remove(i);
+          continue;
+        }
+        last_key = Some(i);
+      }
+      if current && !lines[i].trim().is_empty() {
+        last_section = Some(i);
+      }
+      i += 1;
+    }
+
+    match (line, last_key, last_section) {
+      (None, _, _) => (),
+      (Some(line), Some(i), _) => lines[i] = line,
+      (Some(line), None, Some(i)) => lines.insert(i + 1, line),
+      (Some(line), None, None) => {
+        lines.push(header(section, subsection));
+        lines.push(line);
+      }
+    }
+    self.write_config(&lines)
+  }
+
+  /// Renames a config section (eg. `branch "old"` to `branch "new"`), or
+  /// removes it with all of its values when `new` is `None`.
+  pub fn rename_config_section(
+    &mut self,
+    section: &str,
+    subsection: Option<&str>,
+    new: Option<&str>,
+  ) -> Result<(), String> {
+    let mut lines = self.read_config()?;
+    let mut current = false;
+    lines.retain_mut(|line| {
+      if let Some(parsed) = parse_header(line) {
+        current = header_matches(&parsed, section, subsection);
+        if current {
+          if let Some(new) = new {
+            *line = header(section, Some(new));
+          }
+        }
+      }
+      !(current && new.is_none())
+    });
+    self.write_config(&lines)
+  }
+
+  /// Reads the lines of `.git/config`.
+  fn read_config(&self) -> Result<Vec<String>, String> {
+    match fs::read_to_string(self.git_dir.join("config")) {
+      Ok(data) => Ok(data.lines().map(str::to_string).collect()),
+      Err(msg) => Err(format!("unable to read config ({})", msg)),
+    }
+  }
+
+  /// Writes the lines of `.git/config` and reloads the parsed config.
+  fn write_config(&mut self, lines: &[String]) -> Result<(), String> {
+    let path = self.git_dir.join("config");
+    let mut data = lines.join("\n");
+    data.push('\n');
+    if let Err(msg) = fs::write(&path, data) {
+      return Err(format!("unable to write config ({})", msg));
+    }
+    match ConfigParser::load_from_file(&path) {
+      Ok(config) => self.config = Some(config),
+      Err(msg) => return Err(msg.to_string()),
+    }
+    Ok(())
+  }
+}
+
+/// Parses a `[section]` or `[section "subsection"]` header into the section
+/// (lowercased, since section names are case-insensitive) and subsection.
+fn parse_header(line: &str) -> Option<(String, Option<String>)> {
+  let line = line.trim_start().strip_prefix('[')?;
+  let inside = &line[..line.find(']')?];
+  match inside.split_once(char::is_whitespace) {
+    Some((name, subsection)) => {
+      let subsection = subsection.trim().trim_matches('"');
+      let subsection = subsection.replace("\\\"", "\"").replace("\\\\", "\\");
+      Some((name.to_lowercase(), Some(subsection)))
+    }
+    None => Some((inside.to_lowercase(), None)),
+  }
+}
+
+/// Returns the key of a `key = value` line, if it is one.
+fn parse_key(line: &str) -> Option<&str> {
+  let line = line.trim_start();
+  if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+    return None;
+  }
+  let end = line
+    .find(|c: char| c == '=' || c.is_whitespace())
+    .unwrap_or(line.len());
+  Some(&line[..end])
+}
+
+fn header_matches(header: &(String, Option<String>), section: &str, sub: Option<&str>) -> bool {
+  header.0.eq_ignore_ascii_case(section) && header.1.as_deref() == sub
+}
+
+/// Writes a section header the way git does.
+fn header(section: &str, subsection: Option<&str>) -> String {
+  match subsection {
+    Some(sub) => format!(
+      "[{} \"{}\"]",
+      section,
+      sub.replace('\\', "\\\\").replace('"', "\\\"")
+    ),
+    None => format!("[{}]", section),
+  }
+}
+
+/// Quotes a value if it would not be read back as is, and escapes the
+/// characters that have to be.
+fn quote(value: &str) -> String {
+  let escaped = value
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n")
+    .replace('\t', "\\t");
+  let needs_quotes = value.starts_with(' ') || value.ends_with(' ') || value.contains(['#', ';']);
+  if needs_quotes {
+    format!("\"{}\"", escaped)
+  } else {
+    escaped
+  }
+}
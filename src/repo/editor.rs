@@ -0,0 +1,81 @@
+//! Lets the user write messages (and todo lists) in their editor, and tidies
+//! up what they wrote.
+
+use std::path::Path;
+use std::process::Command;
+use std::{env, fs};
+
+use super::Repo;
+
+/// The instructions appended to the message template in the editor.
+pub const EDITOR_HELP: &str = "\n\
+# Please enter the commit message for your changes. Lines starting\n\
+# with '#' will be ignored, and an empty message aborts the commit.\n";
+
+/// Lets the user write a message in their editor, starting from `template`
+/// (which is saved as `.git/<file_name>` for the editor to open).
+///
+/// The editor is taken from `GIT_EDITOR`, `core.editor`, `VISUAL` or `EDITOR`
+/// (in that order) and falls back to `vi`. It runs through the shell, so it
+/// may contain arguments.
+pub fn edit(repo: &Repo, file_name: &str, template: &str) -> Result<String, String> {
+  let path = repo.git_dir.join(file_name);
+  if let Err(msg) = fs::write(&path, template) {
+    return Err(format!("could not write {} ({})", path.display(), msg));
+  }
+  run_editor(&editor(repo), &path)?;
+  match fs::read_to_string(&path) {
+    Ok(message) => Ok(message),
+    Err(msg) => Err(format!("could not read {} ({})", path.display(), msg)),
+  }
+}
+
+/// The editor the user wants to write messages in.
+pub fn editor(repo: &Repo) -> String {
+  env::var("GIT_EDITOR")
+    .ok()
+    .or_else(|| repo.config_value("core", None, "editor"))
+    .or_else(|| env::var("VISUAL").ok())
+    .or_else(|| env::var("EDITOR").ok())
+    .unwrap_or_else(|| "vi".to_string())
+}
+
+/// Opens a file in an editor and waits for it to be closed.
+pub fn run_editor(editor: &str, path: &Path) -> Result<(), String> {
+  let status = Command::new("sh")
+    .arg("-c")
+    .arg(format!("{} \"$@\"", editor))
+    .arg(editor)
+    .arg(path)
+    .status();
+  match status {
+    Ok(status) if status.success() => Ok(()),
+    _ => Err(format!("there was a problem with the editor '{}'", editor)),
+  }
+}
+
+/// Tidies up a commit message like git's default cleanup mode: trailing
+/// whitespace is removed, runs of blank lines are squeezed into one, leading
+/// and trailing blank lines are dropped, and so are `#` comment lines when
+/// the message came from the editor.
+pub fn cleanup(message: &str, strip_comments: bool) -> String {
+  let mut result = String::new();
+  let mut pending_blank = false;
+  for line in message.lines() {
+    if strip_comments && line.starts_with('#') {
+      continue;
+    }
+    let line = line.trim_end();
+    if line.is_empty() {
+      pending_blank = !result.is_empty();
+      continue;
+    }
+    if pending_blank {
+      result.push('\n');
+      pending_blank = false;
+    }
+    result.push_str(line);
+    result.push('\n');
+  }
+  result
+}
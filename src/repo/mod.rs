@@ -1,10 +1,15 @@
 extern crate ini;
 
+mod config;
+pub mod editor;
+pub mod state;
+
 use ini::Ini as ConfigParser;
 use std::{
+  env,
   fs::{create_dir_all, File},
   io::Write,
-  path::{PathBuf, Path},
+  path::{Path, PathBuf},
 };
 
 /// A git repository.
@@ -32,7 +37,7 @@ impl Repo {
   ///
   /// * `path` - The path to the working tree.
   /// * `force` - If true, the repository will be created even from an invalid
-  /// filesystem location.
+  ///   filesystem location.
   pub fn init(path: &Path, force: bool) -> Result<Repo, String> {
     // If we are not forcing creation, the path must exist.
     if !force && !path.exists() {
@@ -164,6 +169,45 @@ impl Repo {
     }
   }
 
+  /// Looks up a value in the repository's config.
+  ///
+  /// Subsections are written by git as `[section "subsection"]`, so the remote
+  /// of the `main` branch is `config_value("branch", Some("main"), "remote")`.
+  ///
+  /// Values that aren't set for the repository are looked up in the user's
+  /// global config (`~/.gitconfig`, then `$XDG_CONFIG_HOME/git/config`).
+  pub fn config_value(&self, section: &str, subsection: Option<&str>, key: &str) -> Option<String> {
+    let name = match subsection {
+      Some(subsection) => format!("{} \"{}\"", section, subsection),
+      None => section.to_string(),
+    };
+    if let Some(value) = self
+      .config
+      .as_ref()
+      .and_then(|config| config.get_from(Some(name.as_str()), key))
+    {
+      return Some(value.to_string());
+    }
+    global_config_files().into_iter().find_map(|path| {
+      let config = ConfigParser::load_from_file(path).ok()?;
+      config
+        .get_from(Some(name.as_str()), key)
+        .map(|value| value.to_string())
+    })
+  }
+
+  /// Returns the subsections of a section of the repository's config, eg. the
+  /// names of the remotes for `remote`.
+  pub fn config_subsections(&self, section: &str) -> Vec<String> {
+    let prefix = format!("{} \"", section);
+    let sections = self.config.iter().flat_map(|config| config.sections());
+    sections
+      .flatten()
+      .filter_map(|name| name.strip_prefix(&prefix)?.strip_suffix('"'))
+      .map(str::to_string)
+      .collect()
+  }
+
   /// Write the given data to the given path. Panic on error.
   fn write_to_file(data: &str, path: &PathBuf) {
     match File::create(path) {
@@ -199,6 +243,23 @@ impl Default for Repo {
   }
 }
 
+/// Returns the paths of the user's global config files, most important first.
+fn global_config_files() -> Vec<PathBuf> {
+  let mut files = Vec::new();
+  if let Some(home) = env::var_os("HOME") {
+    files.push(PathBuf::from(&home).join(".gitconfig"));
+  }
+  match env::var_os("XDG_CONFIG_HOME") {
+    Some(config_home) => files.push(PathBuf::from(config_home).join("git/config")),
+    None => {
+      if let Some(home) = env::var_os("HOME") {
+        files.push(PathBuf::from(home).join(".config/git/config"));
+      }
+    }
+  }
+  files
+}
+
 /// Returns a new PathBuf with the given path appended to the given pathbuf.
 fn repo_path(git_dir: &Path, paths: &[&str]) -> PathBuf {
   let mut new_path = git_dir.to_path_buf();
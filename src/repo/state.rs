@@ -0,0 +1,84 @@
+//! The files in the git directory that record a merge, cherry-pick or revert
+//! that stopped for conflicts to be resolved, until a commit concludes it (or
+//! it is given up on).
+
+use std::fs;
+
+use super::Repo;
+
+/// The files that record a merge in progress.
+const MERGE_STATE: [&str; 3] = ["MERGE_HEAD", "MERGE_MSG", "MERGE_MODE"];
+
+/// The files that record a cherry-pick or revert that stopped.
+const PICK_STATE: [&str; 3] = ["CHERRY_PICK_HEAD", "REVERT_HEAD", "MERGE_MSG"];
+
+/// The commit being merged, while a merge is in progress.
+pub fn merge_head(repo: &Repo) -> Option<String> {
+  read_hash(repo, "MERGE_HEAD")
+}
+
+/// Records a merge that stopped for conflicts: the commit being merged, the
+/// message prepared for the merge commit, and the mode (`no-ff` or empty).
+pub fn write_merge(repo: &Repo, head: &str, message: &str, mode: &str) -> Result<(), String> {
+  for (file, data) in MERGE_STATE
+    .iter()
+    .zip([&format!("{}\n", head), message, mode])
+  {
+    write(repo, file, data)?;
+  }
+  Ok(())
+}
+
+/// Removes the files that record a merge in progress.
+pub fn clear_merge(repo: &Repo) {
+  remove(repo, &MERGE_STATE);
+}
+
+/// The commit a revert (or else a cherry-pick) stopped at.
+pub fn pick_head(repo: &Repo, revert: bool) -> Option<String> {
+  read_hash(repo, pick_file(revert))
+}
+
+/// Records the commit a revert (or else a cherry-pick) stopped at.
+pub fn write_pick_head(repo: &Repo, revert: bool, hash: &str) -> Result<(), String> {
+  write(repo, pick_file(revert), &format!("{}\n", hash))
+}
+
+/// Removes the files that record a cherry-pick or revert that stopped.
+pub fn clear_pick(repo: &Repo) {
+  remove(repo, &PICK_STATE);
+}
+
+/// The message prepared for the commit that concludes a merge, cherry-pick
+/// or revert.
+pub fn merge_msg(repo: &Repo) -> Option<String> {
+  fs::read_to_string(repo.git_dir.join("MERGE_MSG")).ok()
+}
+
+/// Saves the message for the commit that concludes a cherry-pick or revert.
+pub fn write_merge_msg(repo: &Repo, message: &str) -> Result<(), String> {
+  write(repo, "MERGE_MSG", message)
+}
+
+fn pick_file(revert: bool) -> &'static str {
+  match revert {
+    true => "REVERT_HEAD",
+    false => "CHERRY_PICK_HEAD",
+  }
+}
+
+fn read_hash(repo: &Repo, file: &str) -> Option<String> {
+  let data = fs::read_to_string(repo.git_dir.join(file)).ok()?;
+  Some(data.trim().to_string())
+}
+
+fn write(repo: &Repo, file: &str, data: &str) -> Result<(), String> {
+  let path = repo.git_dir.join(file);
+  fs::write(&path, data).map_err(|msg| format!("could not write '{}' ({})", path.display(), msg))
+}
+
+fn remove(repo: &Repo, files: &[&str]) {
+  for file in files {
+    let _ = fs::remove_file(repo.git_dir.join(file));
+  }
+}
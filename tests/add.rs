@@ -0,0 +1,102 @@
+use assert_cmd::prelude::*;
+use std::{fs, os::unix::fs::PermissionsExt, path::Path, process::Command};
+use tempdir::TempDir;
+
+mod common;
+use common::{git, git_rs_err};
+
+#[test]
+fn test_add() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  git(&canonical_path, &["init", "-q"])?;
+  fs::create_dir_all(canonical_path.join("src/cli"))?;
+  fs::write(canonical_path.join("hello.txt"), "hello world\n")?;
+  fs::write(canonical_path.join("src/main.rs"), "fn main() {}\n")?;
+  fs::write(canonical_path.join("src/cli/add.rs"), "// add\n")?;
+  fs::write(canonical_path.join("src/notes.txt"), "notes\n")?;
+  fs::write(canonical_path.join("run.sh"), "#!/bin/sh\n")?;
+  fs::set_permissions(
+    canonical_path.join("run.sh"),
+    fs::Permissions::from_mode(0o755),
+  )?;
+  std::os::unix::fs::symlink("hello.txt", canonical_path.join("link"))?;
+
+  // globs match across directories, like in git
+  add_template(
+    &canonical_path,
+    &["-n", "src/*.rs"],
+    "add 'src/cli/add.rs'\nadd 'src/main.rs'\n",
+  )?;
+  assert_eq!(git(&canonical_path, &["ls-files"])?, "");
+  add_template(&canonical_path, &["src/*.rs"], "")?;
+  assert_eq!(
+    git(&canonical_path, &["ls-files"])?,
+    "src/cli/add.rs\nsrc/main.rs"
+  );
+
+  // everything else, with the right modes and valid stat data
+  add_template(
+    &canonical_path,
+    &["-A", "-v"],
+    "add 'hello.txt'\nadd 'link'\nadd 'run.sh'\nadd 'src/notes.txt'\n",
+  )?;
+  assert_eq!(
+    git(
+      &canonical_path,
+      &["ls-files", "-s", "hello.txt", "link", "run.sh"]
+    )?,
+    "100644 3b18e512dba79e4c8300dd08aeb37f8e728b8dad 0\thello.txt\n\
+     120000 a5162f80d4a6782b7cb2a0a197f834e683cb9eb1 0\tlink\n\
+     100755 1a2485251c33a70432394c93fb89330ef214bfc9 0\trun.sh"
+  );
+  assert_eq!(git(&canonical_path, &["diff", "--name-only"])?, "");
+
+  // -u stages modifications and deletions but leaves new files alone
+  git(&canonical_path, &["commit", "-q", "-m", "first commit"])?;
+  fs::remove_file(canonical_path.join("hello.txt"))?;
+  fs::write(canonical_path.join("src/main.rs"), "fn main() { }\n")?;
+  fs::write(canonical_path.join("new.txt"), "new\n")?;
+  add_template(
+    &canonical_path,
+    &["-u", "-v"],
+    "remove 'hello.txt'\nadd 'src/main.rs'\n",
+  )?;
+  assert_eq!(
+    git(&canonical_path, &["status", "--porcelain"])?,
+    "D  hello.txt\nM  src/main.rs\n?? new.txt"
+  );
+
+  // pathspecs are relative to the current directory
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(canonical_path.join("src"));
+  cmd.arg("add").arg("../new.txt");
+  cmd.assert().success();
+  assert_eq!(
+    git(&canonical_path, &["status", "--porcelain", "new.txt"])?,
+    "A  new.txt"
+  );
+
+  assert_eq!(
+    git_rs_err(&canonical_path, &["add", "missing.txt"], 128)?,
+    "fatal: pathspec 'missing.txt' did not match any files"
+  );
+  Ok(())
+}
+
+fn add_template(
+  path: &Path,
+  args: &[&str],
+  expected: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+  // set the current directory and run `git-rs add`
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(path);
+  cmd.arg("add").args(args);
+
+  // verify the module works as expected
+  cmd.assert().success().stdout(expected.to_string());
+
+  Ok(())
+}
@@ -0,0 +1,153 @@
+use assert_cmd::prelude::*;
+use std::{path::Path, process::Command};
+use tempdir::TempDir;
+
+mod common;
+use common::{git, git_rs, git_rs_err};
+
+#[test]
+fn test_branch() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  // two commits on main, and a remote whose main is one commit behind
+  git(&canonical_path, &["init", "-q", "-b", "main"])?;
+  git(
+    &canonical_path,
+    &["commit", "-q", "--allow-empty", "-m", "one"],
+  )?;
+  git(
+    &canonical_path,
+    &["commit", "-q", "--allow-empty", "-m", "two"],
+  )?;
+  git(&canonical_path, &["remote", "add", "origin", "/dev/null"])?;
+  git(
+    &canonical_path,
+    &["update-ref", "refs/remotes/origin/main", "HEAD~1"],
+  )?;
+  git(
+    &canonical_path,
+    &[
+      "symbolic-ref",
+      "refs/remotes/origin/HEAD",
+      "refs/remotes/origin/main",
+    ],
+  )?;
+
+  // branches created at a revision, from a remote-tracking branch (which sets
+  // up tracking by default) and with an explicit upstream
+  git_rs(&canonical_path, &["branch", "feature", "HEAD~1"], 0)?;
+  assert_eq!(
+    git(
+      &canonical_path,
+      &["reflog", "-1", "--format=%gs", "feature"]
+    )?,
+    "branch: Created from HEAD~1"
+  );
+  assert_eq!(
+    git_rs(&canonical_path, &["branch", "topic", "origin/main"], 0)?,
+    "branch 'topic' set up to track 'origin/main'."
+  );
+  git_rs(&canonical_path, &["branch", "-t", "local", "main"], 0)?;
+  git_rs(&canonical_path, &["branch", "-u", "origin/main"], 0)?;
+  git_rs(&canonical_path, &["branch", "-u", "main", "feature"], 0)?;
+  assert_eq!(
+    git(&canonical_path, &["config", "branch.main.remote"])?,
+    "origin"
+  );
+  assert_eq!(
+    git(&canonical_path, &["config", "branch.feature.merge"])?,
+    "refs/heads/main"
+  );
+  assert_eq!(
+    git_rs_err(&canonical_path, &["branch", "feature"], 128)?,
+    "fatal: a branch named 'feature' already exists"
+  );
+  assert_eq!(
+    git_rs_err(&canonical_path, &["branch", "a..b"], 128)?,
+    "fatal: 'a..b' is not a valid branch name"
+  );
+
+  for args in [
+    &[][..],
+    &["-v"],
+    &["-vv"],
+    &["-a"],
+    &["-a", "-vv"],
+    &["-r", "-v"],
+    &["--list", "t*", "f*"],
+    &["--show-current"],
+  ] {
+    branch_template(&canonical_path, args)?;
+  }
+
+  // only merged branches can be deleted without -D
+  git(&canonical_path, &["checkout", "-q", "-b", "side"])?;
+  git(
+    &canonical_path,
+    &["commit", "-q", "--allow-empty", "-m", "three"],
+  )?;
+  git(&canonical_path, &["checkout", "-q", "main"])?;
+  assert!(git_rs(&canonical_path, &["branch", "-d", "side"], 128)?
+    .starts_with("error: The branch 'side' is not fully merged."));
+  assert!(git_rs(&canonical_path, &["branch", "-D", "side"], 0)?.starts_with("Deleted branch side"));
+  assert!(
+    git_rs(&canonical_path, &["branch", "-d", "topic"], 0)?.starts_with("Deleted branch topic")
+  );
+  assert!(git_rs(&canonical_path, &["branch", "-d", "main"], 128)?
+    .starts_with("error: Cannot delete branch 'main' checked out at"));
+  assert!(!git(&canonical_path, &["config", "--list"])?.contains("branch.topic"));
+  assert_eq!(
+    git_rs_err(&canonical_path, &["branch", "-d", "main"], 128)?,
+    "fatal: could not delete main"
+  );
+
+  // renaming the current branch moves HEAD, the reflog and the upstream
+  git_rs(&canonical_path, &["branch", "-m", "trunk"], 0)?;
+  assert_eq!(
+    git(&canonical_path, &["symbolic-ref", "HEAD"])?,
+    "refs/heads/trunk"
+  );
+  assert_eq!(
+    git(&canonical_path, &["config", "branch.trunk.merge"])?,
+    "refs/heads/main"
+  );
+  assert_eq!(
+    git(&canonical_path, &["reflog", "--format=%gs", "trunk"])?
+      .lines()
+      .next(),
+    Some("Branch: renamed refs/heads/main to refs/heads/trunk")
+  );
+  git(&canonical_path, &["checkout", "-q", "--detach", "HEAD~1"])?;
+  branch_template(&canonical_path, &["-vv"])?;
+
+  // the reflog makes do with a made-up identity when none is configured
+  for args in [
+    &["branch", "anonymous", "trunk"][..],
+    &["branch", "-m", "anonymous", "nameless"],
+  ] {
+    let mut cmd = Command::cargo_bin("git-rs")?;
+    cmd
+      .current_dir(&canonical_path)
+      .env("HOME", &canonical_path)
+      .env("USER", "tester")
+      .env_remove("XDG_CONFIG_HOME")
+      .args(args);
+    cmd.assert().success().stdout("");
+  }
+  assert_eq!(
+    git(&canonical_path, &["rev-parse", "nameless"])?,
+    git(&canonical_path, &["rev-parse", "trunk"])?
+  );
+  assert!(git(&canonical_path, &["reflog", "--format=%ge", "nameless"])?.starts_with("tester@"));
+  git(&canonical_path, &["fsck", "--strict"])?;
+  Ok(())
+}
+
+/// Checks that `git-rs branch` prints exactly what `git branch` prints.
+fn branch_template(path: &Path, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+  let mut all = vec!["branch"];
+  all.extend(args);
+  assert_eq!(git_rs(path, &all, 0)?, git(path, &all)?, "{:?}", args);
+  Ok(())
+}
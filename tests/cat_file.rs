@@ -4,7 +4,8 @@ use predicates::prelude::*;
 use std::{
   fs::{self, File},
   io::Write,
-  process::Command,
+  path::Path,
+  process::{Command, Stdio},
 };
 use tempdir::TempDir;
 
@@ -44,8 +45,8 @@ fn cat_file_template(
 
   // Add the object file
   let dir_path = &canonical_path.join(".git").join("objects").join(&hash[..2]);
-  fs::create_dir(&dir_path)?;
-  let mut f = File::create(&dir_path.join(&hash[2..]))?;
+  fs::create_dir(dir_path)?;
+  let mut f = File::create(dir_path.join(&hash[2..]))?;
   f.write_all(compressed_data)?;
   f.flush()?;
 
@@ -57,3 +58,81 @@ fn cat_file_template(
 
   Ok(())
 }
+
+#[test]
+fn test_cat_file_commit_headers() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+  git(&canonical_path, &["init", "-q"], None)?;
+
+  // a merge commit with repeated, multi-line and non-UTF-8 headers
+  let tree = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+  git(
+    &canonical_path,
+    &["hash-object", "-t", "tree", "-w", "--stdin"],
+    Some(b""),
+  )?;
+  let mut data = format!("tree {}\n", tree).into_bytes();
+  for parent in ["1", "2", "3"] {
+    let hash = git(
+      &canonical_path,
+      &["hash-object", "-t", "commit", "-w", "--stdin"],
+      Some(
+        format!(
+          "tree {}\nauthor A <a@b> 1 +0000\ncommitter A <a@b> 1 +0000\n\n{}\n",
+          tree, parent
+        )
+        .as_bytes(),
+      ),
+    )?;
+    data.extend(format!("parent {}", hash).bytes().chain([b'\n']));
+  }
+  data.extend_from_slice(b"author J\xf6rg <j@b> 1654631458 -0700\n");
+  data.extend_from_slice(b"committer J\xf6rg <j@b> 1654631458 -0700\n");
+  data.extend_from_slice(b"encoding ISO-8859-1\n");
+  data.extend_from_slice(b"mergetag object 0000000000000000000000000000000000000000\n type commit\n tag v1\n\n message\n");
+  data.extend_from_slice(
+    b"gpgsig -----BEGIN PGP SIGNATURE-----\n \n iQIzBAABCAAdFiEE\n -----END PGP SIGNATURE-----\n",
+  );
+  data.extend_from_slice(b"\nmerge \xe9t\xe9\n");
+  let hash = git(
+    &canonical_path,
+    &["hash-object", "-t", "commit", "-w", "--stdin"],
+    Some(&data),
+  )?;
+
+  // set the current directory and run `git-rs cat-file`
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(&canonical_path);
+  cmd.args(["cat-file", "commit", &hash]);
+
+  // verify the module works as expected (cat-file prints the contents as
+  // UTF-8, so the Latin-1 bytes come out replaced)
+  cmd
+    .assert()
+    .success()
+    .stdout(String::from_utf8_lossy(&data).into_owned());
+
+  Ok(())
+}
+
+fn git(
+  path: &Path,
+  args: &[&str],
+  input: Option<&[u8]>,
+) -> Result<String, Box<dyn std::error::Error>> {
+  let mut child = Command::new("git")
+    .current_dir(path)
+    .args(args)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .spawn()?;
+  child
+    .stdin
+    .take()
+    .unwrap()
+    .write_all(input.unwrap_or_default())?;
+  let output = child.wait_with_output()?;
+  assert!(output.status.success(), "git {:?} failed", args);
+  Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
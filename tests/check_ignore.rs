@@ -0,0 +1,107 @@
+use assert_cmd::prelude::*;
+use std::{fs, path::Path, process::Command};
+use tempdir::TempDir;
+
+mod common;
+use common::{git, git_rs_err};
+
+#[test]
+fn test_check_ignore() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  git(&canonical_path, &["init", "-q"])?;
+  fs::create_dir_all(canonical_path.join("d/sub"))?;
+  fs::create_dir_all(canonical_path.join("build"))?;
+  fs::write(
+    canonical_path.join(".gitignore"),
+    "*.o\n!keep.o\nbuild/\n/root.txt\nd/**/deep\n\\#hash\ntrail\\ \n# comment\n\n**/twice\n",
+  )?;
+  fs::write(canonical_path.join("d/.gitignore"), "x\n!*.log\n")?;
+  fs::write(canonical_path.join(".git/info/exclude"), "local\n")?;
+  fs::write(canonical_path.join(".git/global"), "global*\n")?;
+  git(
+    &canonical_path,
+    &[
+      "config",
+      "core.excludesFile",
+      &canonical_path.join(".git/global").to_string_lossy(),
+    ],
+  )?;
+  let paths = [
+    "a.o",
+    "keep.o",
+    "d/a.o",
+    "root.txt",
+    "d/root.txt",
+    "d/sub/deep",
+    "#hash",
+    "trail ",
+    "local",
+    "globals",
+    "x",
+    "d/x",
+    "build",
+    "build/f",
+    "d/y.log",
+    "d/sub/twice",
+    "missing",
+  ];
+  for path in paths
+    .iter()
+    .filter(|path| !matches!(**path, "build" | "missing"))
+  {
+    fs::write(canonical_path.join(path), "")?;
+  }
+  check_ignore_template(&canonical_path, &[&["-v", "-n"][..], &paths].concat())?;
+  check_ignore_template(&canonical_path, &paths)?;
+  check_ignore_template(
+    &canonical_path.join("d"),
+    &["-v", "x", "../a.o", "sub/deep"],
+  )?;
+
+  // tracked files are never ignored, unless the index is left out
+  git(&canonical_path, &["add", "-f", "a.o"])?;
+  check_ignore_template(&canonical_path, &["-v", "a.o"])?;
+  check_ignore_template(&canonical_path, &["-v", "--no-index", "a.o"])?;
+
+  // status and add leave ignored files alone
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(&canonical_path);
+  cmd.arg("status").arg("--porcelain");
+  cmd
+    .assert()
+    .success()
+    .stdout(git(&canonical_path, &["status", "--porcelain"])? + "\n");
+
+  assert!(
+    git_rs_err(&canonical_path, &["add", "d/a.o"], 128)?.starts_with(
+      "fatal: The following paths are ignored by one of your .gitignore files:\nd/a.o\n"
+    )
+  );
+  assert_eq!(git(&canonical_path, &["ls-files"])?, "a.o");
+  Ok(())
+}
+
+/// Checks that `git-rs check-ignore` prints exactly what `git check-ignore`
+/// prints.
+fn check_ignore_template(path: &Path, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+  let expected = Command::new("git")
+    .current_dir(path)
+    .arg("check-ignore")
+    .args(args)
+    .output()?;
+
+  // set the current directory and run `git-rs check-ignore`
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(path);
+  cmd.arg("check-ignore").args(args);
+
+  // verify the module works as expected
+  cmd
+    .assert()
+    .success()
+    .stdout(String::from_utf8(expected.stdout)?);
+
+  Ok(())
+}
@@ -0,0 +1,148 @@
+use assert_cmd::prelude::*;
+use std::{fs, path::Path, process::Command};
+use tempdir::TempDir;
+
+mod common;
+use common::{git, git_rs, git_rs_err};
+
+#[test]
+fn test_checkout() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  // main has a file and a directory; topic changes the file and replaces the
+  // directory with a file and a symlink
+  git(&canonical_path, &["init", "-q", "-b", "main"])?;
+  fs::write(canonical_path.join("a.txt"), "one\n")?;
+  fs::write(canonical_path.join("b.txt"), "b\n")?;
+  fs::create_dir(canonical_path.join("dir"))?;
+  fs::write(canonical_path.join("dir/c.txt"), "c\n")?;
+  git(&canonical_path, &["add", "."])?;
+  git(&canonical_path, &["commit", "-q", "-m", "one"])?;
+  git(&canonical_path, &["checkout", "-q", "-b", "topic"])?;
+  fs::write(canonical_path.join("a.txt"), "two\n")?;
+  git(&canonical_path, &["rm", "-q", "-r", "dir"])?;
+  fs::write(canonical_path.join("dir"), "now a file\n")?;
+  std::os::unix::fs::symlink("a.txt", canonical_path.join("link"))?;
+  git(&canonical_path, &["add", "."])?;
+  git(&canonical_path, &["commit", "-q", "-m", "two"])?;
+  git(&canonical_path, &["checkout", "-q", "main"])?;
+  let two = git(&canonical_path, &["rev-parse", "--short", "topic"])?;
+
+  // switching carries local changes to files that don't differ over
+  fs::write(canonical_path.join("b.txt"), "local\n")?;
+  assert_eq!(
+    git_rs(&canonical_path, &["checkout", "topic"], 0)?,
+    "M\tb.txt\nSwitched to branch 'topic'"
+  );
+  assert_eq!(
+    git(&canonical_path, &["symbolic-ref", "HEAD"])?,
+    "refs/heads/topic"
+  );
+  assert_eq!(
+    fs::read_to_string(canonical_path.join("dir"))?,
+    "now a file\n"
+  );
+  assert_eq!(
+    fs::read_link(canonical_path.join("link"))?,
+    Path::new("a.txt")
+  );
+  assert_eq!(git(&canonical_path, &["status", "--porcelain"])?, "M b.txt");
+  assert_eq!(
+    git(&canonical_path, &["reflog", "-1", "--format=%gs"])?,
+    "checkout: moving from main to topic"
+  );
+
+  // but refuses to throw away local changes, unless forced
+  fs::write(canonical_path.join("a.txt"), "local\n")?;
+  assert_eq!(
+    git_rs_err(&canonical_path, &["checkout", "main"], 128)?,
+    "fatal: Your local changes to the following files would be overwritten by checkout:\n\
+     \ta.txt\n\
+     Please commit your changes or stash them before you switch branches.\n\
+     Aborting"
+  );
+  assert_eq!(
+    git(&canonical_path, &["branch", "--show-current"])?,
+    "topic"
+  );
+  git_rs(&canonical_path, &["checkout", "-f", "main"], 0)?;
+  assert_eq!(git(&canonical_path, &["status", "--porcelain"])?, "");
+  assert_eq!(fs::read_to_string(canonical_path.join("dir/c.txt"))?, "c\n");
+
+  // detaching HEAD, then switching back with -
+  let detached = git_rs(&canonical_path, &["checkout", "topic~0"], 0)?;
+  assert!(detached.starts_with("Note: switching to 'topic~0'."));
+  assert!(detached.ends_with(&format!("HEAD is now at {} two", two)));
+  assert_eq!(
+    git_rs(&canonical_path, &["checkout", "-"], 0)?,
+    format!(
+      "Previous HEAD position was {} two\nSwitched to branch 'main'",
+      two
+    )
+  );
+
+  // -b creates the branch where HEAD is, or at a start point
+  assert_eq!(
+    git_rs(&canonical_path, &["checkout", "-b", "feature"], 0)?,
+    "Switched to a new branch 'feature'"
+  );
+  assert_eq!(
+    git(
+      &canonical_path,
+      &["reflog", "-1", "--format=%gs", "feature"]
+    )?,
+    "branch: Created from HEAD"
+  );
+  assert_eq!(
+    git_rs_err(&canonical_path, &["checkout", "-b", "topic"], 128)?,
+    "fatal: a branch named 'topic' already exists"
+  );
+  git_rs(
+    &canonical_path,
+    &["checkout", "-q", "-b", "other", "topic"],
+    0,
+  )?;
+  assert_eq!(
+    git(&canonical_path, &["rev-parse", "HEAD"])?,
+    git(&canonical_path, &["rev-parse", "topic"])?
+  );
+
+  // paths are restored from a commit into the index too, or from the index
+  git(&canonical_path, &["checkout", "-q", "main"])?;
+  git_rs(&canonical_path, &["checkout", "topic", "--", "a.txt"], 0)?;
+  assert_eq!(fs::read_to_string(canonical_path.join("a.txt"))?, "two\n");
+  assert_eq!(
+    git(&canonical_path, &["status", "--porcelain"])?,
+    "M  a.txt"
+  );
+  fs::write(canonical_path.join("a.txt"), "local\n")?;
+  assert_eq!(
+    git_rs(&canonical_path, &["checkout", "a.txt"], 0)?,
+    "Updated 1 path from the index"
+  );
+  assert_eq!(fs::read_to_string(canonical_path.join("a.txt"))?, "two\n");
+  assert_eq!(
+    git_rs(&canonical_path, &["checkout", "--", "nope"], 0)?,
+    "error: pathspec 'nope' did not match any file(s) known to git"
+  );
+
+  // switching branches makes do with a made-up reflog identity when none is
+  // configured
+  git(&canonical_path, &["checkout", "-q", "--", "a.txt"])?;
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd
+    .current_dir(&canonical_path)
+    .env("HOME", &canonical_path)
+    .env("USER", "tester")
+    .env_remove("XDG_CONFIG_HOME")
+    .args(["checkout", "-q", "topic"]);
+  cmd.assert().success().stdout("");
+  assert_eq!(
+    git(&canonical_path, &["symbolic-ref", "HEAD"])?,
+    "refs/heads/topic"
+  );
+  assert!(git(&canonical_path, &["reflog", "-1", "--format=%ge", "HEAD"])?.starts_with("tester@"));
+  git(&canonical_path, &["fsck", "--strict"])?;
+  Ok(())
+}
@@ -0,0 +1,169 @@
+use assert_cmd::cargo::cargo_bin;
+use std::{fs, path::Path, process::Command};
+use tempdir::TempDir;
+
+mod common;
+use common::{git, git_rs, identity};
+
+/// `common::diverged`, with `topic` merged into `merged`, a branch off
+/// `main`, unless `conflict` is set.
+fn diverged(path: &Path, conflict: bool) -> Result<(), Box<dyn std::error::Error>> {
+  common::diverged(path, conflict)?;
+  if !conflict {
+    git(path, &["checkout", "-q", "-b", "merged"])?;
+    git(path, &["merge", "-q", "--no-ff", "-m", "merged", "topic"])?;
+    git(path, &["checkout", "-q", "main"])?;
+  }
+  Ok(())
+}
+
+#[test]
+fn test_cherry_pick() -> Result<(), Box<dyn std::error::Error>> {
+  let clean = |path: &Path| diverged(path, false);
+  pick_template(
+    clean,
+    &[
+      &["cherry-pick", "topic"],
+      &["cherry-pick", "-x", "topic~2", "topic"],
+      &["cherry-pick", "main..topic"],
+      &["cherry-pick", "-n", "topic~2", "topic"],
+      &["cherry-pick", "merged"],
+      &["cherry-pick", "-m", "1", "merged"],
+      &["cherry-pick", "-m", "3", "merged"],
+      &["revert", "HEAD"],
+      &["revert", "-n", "HEAD"],
+      &["revert", "HEAD~1..HEAD"],
+      &["revert", "-m", "2", "merged"],
+    ],
+  )?;
+
+  // stops when a commit conflicts
+  let conflicted = |path: &Path| diverged(path, true);
+  pick_template(
+    conflicted,
+    &[
+      &["cherry-pick", "topic~1"],
+      &["cherry-pick", "-x", "main..topic"],
+      &["cherry-pick", "-n", "topic~1"],
+      &["revert", "topic~1"],
+    ],
+  )?;
+
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+  conflicted(&canonical_path)?;
+  let main = git(&canonical_path, &["rev-parse", "main"])?;
+  git_rs(&canonical_path, &["cherry-pick", "main..topic"], 1)?;
+  assert_eq!(
+    fs::read_to_string(canonical_path.join(".git/sequencer/todo"))?,
+    format!(
+      "pick {} t two\npick {} t three\n",
+      &git(&canonical_path, &["rev-parse", "topic~1"])?[..7],
+      &git(&canonical_path, &["rev-parse", "topic"])?[..7]
+    )
+  );
+  assert_eq!(
+    git_rs(&canonical_path, &["revert", "--continue"], 128)?,
+    "error: cannot revert during a cherry-pick."
+  );
+
+  // --continue commits the resolution and carries on
+  fs::write(canonical_path.join("f"), "a\nresolved\nc\n")?;
+  git(&canonical_path, &["add", "f"])?;
+  git_rs(&canonical_path, &["cherry-pick", "--continue"], 0)?;
+  assert_eq!(
+    git(&canonical_path, &["log", "--format=%s", "topic..main"])?,
+    "t three\nt two\nt one\nm one"
+  );
+  assert_eq!(
+    git(&canonical_path, &["reflog", "-3", "--format=%gs"])?,
+    "cherry-pick: t three\n\
+     commit (cherry-pick): t two\n\
+     cherry-pick: t one"
+  );
+  assert!(!canonical_path.join(".git/sequencer").exists());
+  assert!(!canonical_path.join(".git/CHERRY_PICK_HEAD").exists());
+
+  // --skip leaves the commit out
+  git(&canonical_path, &["reset", "-q", "--hard", &main])?;
+  git_rs(&canonical_path, &["cherry-pick", "main..topic"], 1)?;
+  git_rs(&canonical_path, &["cherry-pick", "--skip"], 0)?;
+  assert_eq!(
+    git(&canonical_path, &["log", "--format=%s", "topic..main"])?,
+    "t three\nt one\nm one"
+  );
+
+  // --abort goes back to where the cherry-pick started
+  git(&canonical_path, &["reset", "-q", "--hard", &main])?;
+  git_rs(&canonical_path, &["cherry-pick", "main..topic"], 1)?;
+  git_rs(&canonical_path, &["cherry-pick", "--abort"], 0)?;
+  assert_eq!(git(&canonical_path, &["rev-parse", "HEAD"])?, main);
+  assert_eq!(git(&canonical_path, &["status", "--porcelain"])?, "");
+  assert_eq!(
+    git_rs(&canonical_path, &["cherry-pick", "--abort"], 128)?,
+    "error: no cherry-pick or revert in progress"
+  );
+  Ok(())
+}
+
+/// Checks that `git-rs` prints what `git` prints for each set of arguments in
+/// a repository made by `setup`, and leaves the same commits, reflog, index,
+/// working tree and state files behind.
+fn pick_template(
+  setup: impl Fn(&Path) -> Result<(), Box<dyn std::error::Error>>,
+  all_args: &[&[&str]],
+) -> Result<(), Box<dyn std::error::Error>> {
+  for args in all_args {
+    let mut results = Vec::new();
+    for program in ["git", "git-rs"] {
+      let temp_dir = TempDir::new("gitrs")?;
+      let path = temp_dir.path().canonicalize().unwrap();
+      setup(&path)?;
+      // git prints most of what it has to say to stderr, where git-rs only
+      // prints fatal errors, so stderr is merged into stdout
+      let program = match program {
+        "git" => "git".into(),
+        _ => cargo_bin("git-rs"),
+      };
+      let mut cmd = Command::new("sh");
+      cmd.arg("-c").arg("\"$0\" \"$@\" 2>&1").arg(program);
+      let output = identity(&mut cmd)
+        .current_dir(&path)
+        .env("GIT_EDITOR", "true")
+        .args(*args)
+        .output()?;
+      let mut worktree = Vec::new();
+      for file in git(&path, &["ls-files"])?.lines() {
+        worktree.push(fs::read_to_string(path.join(file)).unwrap_or_default());
+      }
+      let mut state = Vec::new();
+      for file in [
+        "CHERRY_PICK_HEAD",
+        "REVERT_HEAD",
+        "MERGE_MSG",
+        "sequencer/head",
+        "sequencer/todo",
+        "sequencer/opts",
+      ] {
+        state.push(fs::read_to_string(path.join(".git").join(file)).ok());
+      }
+      // the commit summaries of git list the changes, which git-rs leaves out
+      let stdout: Vec<String> = String::from_utf8(output.stdout)?
+        .lines()
+        .filter(|line| !line.starts_with(' '))
+        .map(String::from)
+        .collect();
+      results.push((
+        stdout,
+        output.status.code(),
+        git(&path, &["log", "--format=%H %P %an %B", "-4"])?,
+        git(&path, &["reflog", "--format=%H %gs"])?,
+        git(&path, &["ls-files", "-s"])?,
+        worktree,
+        state,
+      ));
+    }
+    assert_eq!(results[1], results[0], "{:?}", args);
+  }
+  Ok(())
+}
@@ -0,0 +1,113 @@
+use assert_cmd::prelude::*;
+use std::{fs, path::Path, process::Command};
+use tempdir::TempDir;
+
+mod common;
+use common::{git, git_rs_err};
+
+#[test]
+fn test_commit() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  // names that only sort correctly if directories compare with a trailing `/`
+  git(&canonical_path, &["init", "-q", "-b", "main"])?;
+  fs::create_dir_all(canonical_path.join("foo"))?;
+  fs::create_dir_all(canonical_path.join("src/cli"))?;
+  fs::write(canonical_path.join("foo.rs"), "foo.rs\n")?;
+  fs::write(canonical_path.join("foo/bar"), "foo/bar\n")?;
+  fs::write(canonical_path.join("foo0"), "foo0\n")?;
+  fs::write(canonical_path.join("src/cli/add.rs"), "// add\n")?;
+  git(&canonical_path, &["add", "."])?;
+
+  commit_template(
+    &canonical_path,
+    &["-m", "first commit", "-m", "with a body"],
+    "[main (root-commit) ",
+  )?;
+  assert_eq!(
+    git(&canonical_path, &["rev-parse", "HEAD^{tree}"])?,
+    git(&canonical_path, &["write-tree"])?
+  );
+  assert_eq!(
+    git(&canonical_path, &["log", "--format=%an <%ae>|%s|%b"])?,
+    "Justin Shaw <realjustinshaw@gmail.com>|first commit|with a body"
+  );
+  git(&canonical_path, &["fsck", "--strict"])?;
+
+  // nothing staged
+  assert_eq!(
+    git_rs_err(&canonical_path, &["commit", "-m", "again"], 128)?,
+    "fatal: nothing to commit (use --allow-empty to record an empty commit)"
+  );
+  commit_template(&canonical_path, &["--allow-empty", "-m", "empty"], "[main ")?;
+
+  // messages from a file and from the editor
+  fs::write(canonical_path.join("foo0"), "changed\n")?;
+  git(&canonical_path, &["add", "foo0"])?;
+  fs::write(canonical_path.join(".git/MSG"), "\n\nfrom a file   \n\n\n")?;
+  commit_template(&canonical_path, &["-F", ".git/MSG"], "[main ")?;
+  let parent = git(&canonical_path, &["rev-parse", "HEAD~1"])?;
+
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(&canonical_path);
+  cmd
+    .env("GIT_COMMITTER_NAME", "Justin Shaw")
+    .env("GIT_COMMITTER_EMAIL", "realjustinshaw@gmail.com")
+    .env(
+      "GIT_EDITOR",
+      "sed -i -e '1s/^/amended /' -e '$a# a comment'",
+    );
+  cmd.arg("commit").arg("--amend");
+  cmd.assert().success();
+  assert_eq!(
+    git(&canonical_path, &["log", "-1", "--format=%B"])?,
+    "amended from a file"
+  );
+  assert_eq!(git(&canonical_path, &["rev-parse", "HEAD~1"])?, parent);
+  assert_eq!(
+    git(&canonical_path, &["reflog", "-1", "--format=%gs"])?,
+    "commit (amend): amended from a file"
+  );
+  git(&canonical_path, &["fsck", "--strict"])?;
+
+  // a branch that is locked by someone else is left alone
+  let head = git(&canonical_path, &["rev-parse", "HEAD"])?;
+  fs::write(canonical_path.join(".git/refs/heads/main.lock"), "")?;
+  assert!(git_rs_err(
+    &canonical_path,
+    &["commit", "--allow-empty", "-m", "locked"],
+    128
+  )?
+  .starts_with(&format!(
+    "fatal: unable to create {} (",
+    canonical_path.join(".git/refs/heads/main.lock").display()
+  )));
+  assert_eq!(git(&canonical_path, &["rev-parse", "HEAD"])?, head);
+  assert!(canonical_path.join(".git/refs/heads/main.lock").exists());
+  Ok(())
+}
+
+fn commit_template(
+  path: &Path,
+  args: &[&str],
+  expected: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+  // set the current directory and run `git-rs commit`
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(path);
+  cmd
+    .env("GIT_AUTHOR_NAME", "Justin Shaw")
+    .env("GIT_AUTHOR_EMAIL", "realjustinshaw@gmail.com")
+    .env("GIT_COMMITTER_NAME", "Justin Shaw")
+    .env("GIT_COMMITTER_EMAIL", "realjustinshaw@gmail.com");
+  cmd.arg("commit").args(args);
+
+  // verify the module works as expected
+  cmd
+    .assert()
+    .success()
+    .stdout(predicates::str::starts_with(expected));
+
+  Ok(())
+}
@@ -0,0 +1,84 @@
+//! Helpers shared by the tests that make commits with both git and git-rs and
+//! compare the results (each test crate uses a different subset of them).
+#![allow(dead_code)]
+
+use assert_cmd::prelude::*;
+use std::{fs, path::Path, process::Command};
+
+/// A fixed date, so that git and git-rs create the same commits.
+pub const DATE: &str = "2020-01-01T00:00:00Z";
+
+/// Three commits on `topic`, and one on `main` since they diverged, which
+/// conflicts with the second commit of `topic` when `conflict` is set. `main`
+/// is left checked out.
+pub fn diverged(path: &Path, conflict: bool) -> Result<(), Box<dyn std::error::Error>> {
+  fs::write(path.join("f"), "a\nb\nc\n")?;
+  fs::write(path.join("g"), "g\n")?;
+  commit(path, "base")?;
+  git(path, &["checkout", "-q", "-b", "topic"])?;
+  fs::write(path.join("t1"), "t1\n")?;
+  commit(path, "t one")?;
+  fs::write(path.join("f"), "a\nB-topic\nc\n")?;
+  commit(path, "t two")?;
+  fs::write(path.join("t3"), "t3\n")?;
+  commit(path, "t three")?;
+  git(path, &["checkout", "-q", "main"])?;
+  match conflict {
+    true => fs::write(path.join("f"), "a\nB-main\nc\n")?,
+    false => fs::write(path.join("f"), "a\nb\nc\nd\n")?,
+  }
+  commit(path, "m one")?;
+  Ok(())
+}
+
+/// Commits everything (or nothing, if nothing changed), with the fixed date.
+pub fn commit(path: &Path, message: &str) -> Result<String, Box<dyn std::error::Error>> {
+  if !path.join(".git").exists() {
+    git(path, &["init", "-q", "-b", "main"])?;
+  }
+  git(path, &["add", "-A"])?;
+  git(path, &["commit", "-q", "--allow-empty", "-m", message])
+}
+
+/// Sets the identity and the date of commits.
+pub fn identity(cmd: &mut Command) -> &mut Command {
+  cmd
+    .env("GIT_AUTHOR_NAME", "Justin Shaw")
+    .env("GIT_AUTHOR_EMAIL", "realjustinshaw@gmail.com")
+    .env("GIT_AUTHOR_DATE", DATE)
+    .env("GIT_COMMITTER_NAME", "Justin Shaw")
+    .env("GIT_COMMITTER_EMAIL", "realjustinshaw@gmail.com")
+    .env("GIT_COMMITTER_DATE", DATE)
+}
+
+/// Runs git-rs, expecting it to exit with `code`, and returns its trimmed
+/// stdout.
+pub fn git_rs(path: &Path, args: &[&str], code: i32) -> Result<String, Box<dyn std::error::Error>> {
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  identity(&mut cmd).current_dir(path).args(args);
+  let output = cmd.assert().code(code).get_output().stdout.clone();
+  Ok(String::from_utf8(output)?.trim().to_string())
+}
+
+/// Runs stock git and returns its trimmed stdout.
+pub fn git(path: &Path, args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+  let output = identity(&mut Command::new("git"))
+    .current_dir(path)
+    .args(args)
+    .output()?;
+  assert!(output.status.success(), "git {:?} failed", args);
+  Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+/// Runs git-rs, expecting it to fail with `code`, and returns its trimmed
+/// stderr.
+pub fn git_rs_err(
+  path: &Path,
+  args: &[&str],
+  code: i32,
+) -> Result<String, Box<dyn std::error::Error>> {
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  identity(&mut cmd).current_dir(path).args(args);
+  let output = cmd.assert().code(code).get_output().stderr.clone();
+  Ok(String::from_utf8(output)?.trim().to_string())
+}
@@ -0,0 +1,211 @@
+use assert_cmd::prelude::*;
+use std::{fs, path::Path, process::Command};
+use tempdir::TempDir;
+
+const OLD: &str = "\
+#include <stdio.h>
+
+int add(int a, int b)
+{
+\treturn a + b;
+}
+
+int main(void)
+{
+\tint x = add(1, 2);
+\tprintf(\"%d\\n\", x);
+\treturn 0;
+}
+
+static void unused(void)
+{
+}
+";
+
+const NEW: &str = "\
+#include <stdio.h>
+
+int add(int a, int b)
+{
+\treturn a + b;
+}
+
+int sub(int a, int b)
+{
+\treturn a - b;
+}
+
+int main(void)
+{
+\tint x = add(1, 2);
+\tint y = sub(x, 1);
+\tprintf(\"%d %d\\n\", x, y);
+\treturn 0;
+}
+
+static void unused(void)
+{
+}";
+
+#[test]
+fn test_diff_no_index() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  fs::write(canonical_path.join("old.c"), OLD)?;
+  fs::write(canonical_path.join("new.c"), NEW)?;
+  for args in [
+    &[][..],
+    &["--patience"],
+    &["--histogram"],
+    &["--diff-algorithm=minimal"],
+    &["-U0"],
+    &["-U1"],
+    &["--unified=10"],
+  ] {
+    diff_template(&canonical_path, args, "old.c", "new.c")?;
+    diff_template(&canonical_path, args, "new.c", "old.c")?;
+  }
+
+  // repeated lines, where the algorithms pick different matches
+  let old: String = (0..200).map(|i| format!("{}\n}}\n\n", i % 7)).collect();
+  let new: String = (0..200).map(|i| format!("{}\n}}\n\n", i % 5)).collect();
+  fs::write(canonical_path.join("old.txt"), old)?;
+  fs::write(canonical_path.join("new.txt"), new)?;
+  for args in [&[][..], &["--patience"], &["--histogram"]] {
+    diff_template(&canonical_path, args, "old.txt", "new.txt")?;
+  }
+  Ok(())
+}
+
+#[test]
+fn test_diff_no_index_binary() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  fs::write(
+    canonical_path.join("a.png"),
+    b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR",
+  )?;
+  fs::write(
+    canonical_path.join("b.png"),
+    b"\x89PNG\r\n\x1a\n\0\0\0\rIHDX",
+  )?;
+  fs::write(canonical_path.join("text"), "text\n")?;
+  diff_template(&canonical_path, &[], "a.png", "b.png")?;
+  diff_template(&canonical_path, &[], "text", "a.png")?;
+  diff_template(&canonical_path, &[], "a.png", "a.png")?;
+  Ok(())
+}
+
+#[test]
+fn test_diff() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  git(&canonical_path, &["init", "-q"])?;
+  fs::create_dir(canonical_path.join("src"))?;
+  fs::write(canonical_path.join("src/main.c"), OLD)?;
+  fs::write(canonical_path.join("README"), "readme\n")?;
+  fs::write(canonical_path.join("gone"), "gone\n")?;
+  git(&canonical_path, &["add", "."])?;
+  git(&canonical_path, &["commit", "-q", "-m", "first"])?;
+
+  // a committed edit and move, then a staged edit and an unstaged one
+  fs::rename(
+    canonical_path.join("src/main.c"),
+    canonical_path.join("main.c"),
+  )?;
+  fs::write(canonical_path.join("main.c"), NEW)?;
+  fs::remove_file(canonical_path.join("gone"))?;
+  git(&canonical_path, &["add", "-A"])?;
+  git(&canonical_path, &["commit", "-q", "-m", "second"])?;
+  fs::write(canonical_path.join("README"), "readme\nstaged\n")?;
+  fs::write(canonical_path.join("new.png"), b"\x89PNG\r\n\x1a\n\0")?;
+  git(&canonical_path, &["add", "README", "new.png"])?;
+  fs::write(canonical_path.join("main.c"), OLD)?;
+
+  for args in [
+    &[][..],
+    &["--stat"],
+    &["--numstat"],
+    &["--name-status"],
+    &["--raw"],
+    &["-U1", "--histogram"],
+    &["--no-renames", "--name-status"],
+  ] {
+    for revs in [
+      &[][..],
+      &["--cached"],
+      &["HEAD"],
+      &["HEAD~"],
+      &["HEAD~", "HEAD"],
+      &["HEAD~..HEAD"],
+      &["HEAD", "--", "main.c"],
+    ] {
+      let all: Vec<&str> = args.iter().chain(revs).copied().collect();
+      diff_repo_template(&canonical_path, &all)?;
+    }
+  }
+  Ok(())
+}
+
+/// Checks that `git-rs diff` prints exactly what `git diff` prints in a
+/// repository.
+fn diff_repo_template(path: &Path, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+  let mut all = vec!["diff"];
+  all.extend(args);
+  let expected = git(path, &all)?;
+
+  // set the current directory and run `git-rs diff`
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(path).env("COLUMNS", "80");
+  cmd.arg("diff").args(args);
+
+  // verify the module works as expected
+  let output = cmd.assert().success().get_output().stdout.clone();
+  assert_eq!(String::from_utf8(output)?.trim(), expected, "{:?}", args);
+
+  Ok(())
+}
+
+/// Checks that `git-rs diff --no-index` prints exactly what
+/// `git diff --no-index` prints.
+fn diff_template(
+  path: &Path,
+  args: &[&str],
+  old: &str,
+  new: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+  // git exits with 1 when the files differ
+  let expected = Command::new("git")
+    .current_dir(path)
+    .args(["diff", "--no-index"])
+    .args(args)
+    .args([old, new])
+    .output()?;
+
+  // set the current directory and run `git-rs diff --no-index`
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(path);
+  cmd.args(["diff", "--no-index"]).args(args).args([old, new]);
+
+  // verify the module works as expected
+  cmd.assert().success().stdout(expected.stdout);
+
+  Ok(())
+}
+
+fn git(path: &Path, args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+  let output = Command::new("git")
+    .current_dir(path)
+    .env("GIT_AUTHOR_NAME", "Justin Shaw")
+    .env("GIT_AUTHOR_EMAIL", "realjustinshaw@gmail.com")
+    .env("GIT_COMMITTER_NAME", "Justin Shaw")
+    .env("GIT_COMMITTER_EMAIL", "realjustinshaw@gmail.com")
+    .env("COLUMNS", "80")
+    .args(args)
+    .output()?;
+  assert!(output.status.success(), "git {:?} failed", args);
+  Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
@@ -0,0 +1,104 @@
+use assert_cmd::prelude::*;
+use std::{fs, path::Path, process::Command};
+use tempdir::TempDir;
+
+mod common;
+use common::git;
+
+const SOURCE: &str = "\
+fn main() {
+    let greeting = \"hello\";
+    println!(\"{}, world\", greeting);
+    for i in 0..10 {
+        println!(\"{}\", i);
+    }
+}
+";
+
+#[test]
+fn test_diff_tree() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  git(&canonical_path, &["init", "-q"])?;
+  fs::create_dir_all(canonical_path.join("src/util"))?;
+  fs::write(canonical_path.join("src/main.rs"), SOURCE)?;
+  fs::write(canonical_path.join("src/util/mod.rs"), "pub mod io;\n")?;
+  fs::write(canonical_path.join("src/util/io.rs"), "// io\n")?;
+  fs::write(canonical_path.join("README"), "readme\n")?;
+  fs::write(canonical_path.join("gone"), "gone\n")?;
+  fs::write(canonical_path.join("swap"), "a file\n")?;
+  git(&canonical_path, &["add", "."])?;
+  git(&canonical_path, &["commit", "-q", "-m", "first"])?;
+
+  // an edited move, an exact move, a copy, a mode change, a deletion and a
+  // file that became a directory
+  fs::create_dir_all(canonical_path.join("lib"))?;
+  fs::rename(
+    canonical_path.join("src/main.rs"),
+    canonical_path.join("lib/app.rs"),
+  )?;
+  fs::write(
+    canonical_path.join("lib/app.rs"),
+    SOURCE.replace("hello", "goodbye"),
+  )?;
+  fs::rename(
+    canonical_path.join("src/util/io.rs"),
+    canonical_path.join("lib/io.rs"),
+  )?;
+  fs::write(canonical_path.join("README.md"), "readme\n")?;
+  fs::write(canonical_path.join("README"), "readme\nmore\n")?;
+  fs::remove_file(canonical_path.join("gone"))?;
+  fs::remove_file(canonical_path.join("swap"))?;
+  fs::create_dir(canonical_path.join("swap"))?;
+  fs::write(canonical_path.join("swap/file"), "a file\n")?;
+  git(&canonical_path, &["add", "-A"])?;
+  git(
+    &canonical_path,
+    &["update-index", "--chmod=+x", "src/util/mod.rs"],
+  )?;
+  git(&canonical_path, &["commit", "-q", "-m", "second"])?;
+
+  for args in [
+    &[][..],
+    &["-r"],
+    &["-r", "--name-only"],
+    &["-r", "-M"],
+    &["-r", "-M", "--name-status"],
+    &["-r", "-C"],
+    &["-r", "-C", "--name-status"],
+    &["-r", "--find-renames=95%"],
+    &["-r", "-M=5"],
+  ] {
+    diff_tree_template(&canonical_path, args, "HEAD~", "HEAD")?;
+    diff_tree_template(&canonical_path, args, "HEAD", "HEAD~")?;
+  }
+  Ok(())
+}
+
+/// Checks that `git-rs diff-tree` prints exactly what `git diff-tree` prints.
+fn diff_tree_template(
+  path: &Path,
+  args: &[&str],
+  old: &str,
+  new: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+  // git takes the threshold attached without an `=`
+  let git_args: Vec<String> = args.iter().map(|arg| arg.replace("M=", "M")).collect();
+  let git_args: Vec<&str> = git_args.iter().map(String::as_str).collect();
+  let mut all = vec!["diff-tree"];
+  all.extend(git_args);
+  all.extend([old, new]);
+  let expected = git(path, &all)?;
+
+  // set the current directory and run `git-rs diff-tree`
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(path);
+  cmd.arg("diff-tree").args(args).args([old, new]);
+
+  // verify the module works as expected
+  let output = cmd.assert().success().get_output().stdout.clone();
+  assert_eq!(String::from_utf8(output)?.trim(), expected, "{:?}", args);
+
+  Ok(())
+}
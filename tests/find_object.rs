@@ -0,0 +1,153 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use sha1::{Digest, Sha1};
+use std::{collections::HashMap, fs, path::Path, process::Command};
+use tempdir::TempDir;
+
+mod common;
+use common::git;
+
+#[test]
+fn test_find_object_by_name() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  fs::write(canonical_path.join("hello.txt"), "hello world\n")?;
+  git(&canonical_path, &["init", "-q", "-b", "main"])?;
+  git(&canonical_path, &["add", "hello.txt"])?;
+  git(&canonical_path, &["commit", "-q", "-m", "first commit"])?;
+  git(&canonical_path, &["tag", "v1.0"])?;
+  git(
+    &canonical_path,
+    &["update-ref", "refs/remotes/origin/main", "HEAD"],
+  )?;
+  let head = git(&canonical_path, &["rev-parse", "HEAD"])?;
+
+  for name in [
+    "HEAD",
+    "main",
+    "refs/heads/main",
+    "v1.0",
+    "origin/main",
+    &head[..7],
+  ] {
+    find_object_template(&canonical_path, "commit", name, "first commit")?;
+  }
+
+  // refs that only live in packed-refs resolve the same way
+  git(&canonical_path, &["pack-refs", "--all"])?;
+  for name in ["main", "v1.0", "origin/main"] {
+    find_object_template(&canonical_path, "commit", name, "first commit")?;
+  }
+  find_object_template(&canonical_path, "blob", "3b18e5", "hello world\n")?;
+  Ok(())
+}
+
+#[test]
+fn test_find_object_peels_tags() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  fs::write(canonical_path.join("hello.txt"), "hello world\n")?;
+  git(&canonical_path, &["init", "-q"])?;
+  git(&canonical_path, &["add", "hello.txt"])?;
+  git(&canonical_path, &["commit", "-q", "-m", "first commit"])?;
+  git(
+    &canonical_path,
+    &["tag", "-a", "v1.0", "-m", "release v1.0"],
+  )?;
+  let tree = git(&canonical_path, &["rev-parse", "HEAD^{tree}"])?;
+
+  find_object_template(&canonical_path, "tag", "v1.0", "release v1.0")?;
+  find_object_template(&canonical_path, "commit", "v1.0", "first commit")?;
+  find_object_template(&canonical_path, "tree", "v1.0", "hello.txt")?;
+
+  let mut ls_cmd = Command::cargo_bin("git-rs")?;
+  ls_cmd.current_dir(&canonical_path);
+  ls_cmd.arg("ls-tree").arg("v1.0");
+  ls_cmd.assert().success().stdout(predicate::str::contains(
+    "3b18e512dba79e4c8300dd08aeb37f8e728b8dad\thello.txt",
+  ));
+
+  let mut checkout_cmd = Command::cargo_bin("git-rs")?;
+  checkout_cmd
+    .current_dir(&canonical_path)
+    .env("GIT_COMMITTER_NAME", "Justin Shaw")
+    .env("GIT_COMMITTER_EMAIL", "realjustinshaw@gmail.com");
+  checkout_cmd.arg("checkout").arg("-q").arg("v1.0");
+  checkout_cmd.assert().success();
+  assert_eq!(
+    git(&canonical_path, &["rev-parse", "HEAD"])?,
+    git(&canonical_path, &["rev-parse", "v1.0^{commit}"])?
+  );
+
+  // a blob cannot be peeled into a commit
+  let mut cat_cmd = Command::cargo_bin("git-rs")?;
+  cat_cmd.current_dir(&canonical_path);
+  cat_cmd.arg("cat-file").arg("commit").arg(&tree);
+  cat_cmd
+    .assert()
+    .code(128)
+    .stderr(predicate::str::contains(format!(
+      "{} is a tree, not a commit",
+      tree
+    )));
+  Ok(())
+}
+
+#[test]
+fn test_find_object_ambiguous() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+  git(&canonical_path, &["init", "-q"])?;
+
+  // find two blobs whose hashes share the first four hex digits
+  let mut seen: HashMap<String, String> = HashMap::new();
+  let (prefix, first, second) = (0..)
+    .find_map(|i| {
+      let contents = format!("blob number {}\n", i);
+      let hash = hex::encode(Sha1::digest(format!(
+        "blob {}\0{}",
+        contents.len(),
+        contents
+      )));
+      let prefix = hash[..4].to_string();
+      seen
+        .insert(prefix.clone(), contents.clone())
+        .map(|other| (prefix, other, contents))
+    })
+    .unwrap();
+  for contents in [&first, &second] {
+    fs::write(canonical_path.join("blob.txt"), contents)?;
+    git(&canonical_path, &["hash-object", "-w", "blob.txt"])?;
+  }
+
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(&canonical_path);
+  cmd.arg("cat-file").arg("blob").arg(&prefix);
+  cmd.assert().code(128).stderr(
+    predicate::str::contains(format!("short object ID {} is ambiguous", prefix))
+      .and(predicate::str::contains(" blob").count(2)),
+  );
+  Ok(())
+}
+
+fn find_object_template(
+  path: &Path,
+  obj: &str,
+  name: &str,
+  plaintext_data: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+  // set the current directory and run `git-rs cat-file`
+  let mut cat_cmd = Command::cargo_bin("git-rs")?;
+  cat_cmd.current_dir(path);
+  cat_cmd.arg("cat-file").arg(obj).arg(name);
+
+  // verify the module works as expected
+  cat_cmd
+    .assert()
+    .success()
+    .stdout(predicate::str::contains(plaintext_data));
+
+  Ok(())
+}
@@ -60,7 +60,7 @@ fn hash_object_template(
   }
 
   // Add the file to be hash-object'ed
-  let mut f = File::create(&canonical_path.join(filename))?;
+  let mut f = File::create(canonical_path.join(filename))?;
   f.write_all(plaintext_data.as_bytes())?;
   f.flush()?;
 
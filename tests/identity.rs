@@ -0,0 +1,105 @@
+use assert_cmd::prelude::*;
+use std::{path::Path, process::Command};
+use tempdir::TempDir;
+
+#[test]
+fn test_identity_dates() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+  git(&canonical_path, &["init", "-q"], &[])?;
+
+  // stock git decides what every date means in every timezone
+  for tz in [
+    "UTC",
+    "America/New_York",
+    "Asia/Kolkata",
+    "CET-1CEST,M3.5.0,M10.5.0/3",
+  ] {
+    for date in [
+      "1112911993 +0200",
+      "@1112911993 -0130",
+      "Thu, 07 Apr 2005 22:13:13 +0200",
+      "2005-04-07T22:13:13Z",
+      "2005-04-07 22:13:13 -0700",
+      "2005-01-07T22:13:13",
+      "2005-07-07T22:13:13",
+      "2050-07-07T22:13:13",
+    ] {
+      let env = [("TZ", tz), ("GIT_AUTHOR_DATE", date)];
+      let expected = git(&canonical_path, &["var", "GIT_AUTHOR_IDENT"], &env)?;
+
+      let mut cmd = Command::cargo_bin("git-rs")?;
+      cmd.current_dir(&canonical_path).envs(env);
+      cmd
+        .env("GIT_AUTHOR_NAME", "Justin Shaw")
+        .env("GIT_AUTHOR_EMAIL", "realjustinshaw@gmail.com")
+        .env("GIT_COMMITTER_NAME", "Justin Shaw")
+        .env("GIT_COMMITTER_EMAIL", "realjustinshaw@gmail.com");
+      cmd.args(["commit", "-q", "--allow-empty", "-m", date]);
+      cmd.assert().success().stdout("");
+
+      let commit = git(&canonical_path, &["cat-file", "commit", "HEAD"], &[])?;
+      assert!(
+        commit.contains(&format!("\nauthor {}\n", expected)),
+        "{} in {}: expected {}\n{}",
+        date,
+        tz,
+        expected,
+        commit
+      );
+    }
+  }
+  Ok(())
+}
+
+#[test]
+fn test_annotated_tag() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+  git(&canonical_path, &["init", "-q"], &[])?;
+  git(
+    &canonical_path,
+    &["commit", "-q", "--allow-empty", "-m", "first commit"],
+    &[],
+  )?;
+
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(&canonical_path);
+  cmd
+    .env("GIT_COMMITTER_NAME", "Justin Shaw")
+    .env("GIT_COMMITTER_EMAIL", "realjustinshaw@gmail.com")
+    .env("GIT_COMMITTER_DATE", "1654899880 -0700");
+  cmd.args(["tag", "-m", "release v1.0", "v1.0"]);
+  cmd.assert().success();
+
+  let head = git(&canonical_path, &["rev-parse", "HEAD"], &[])?;
+  assert_eq!(
+    git(&canonical_path, &["cat-file", "tag", "v1.0"], &[])?,
+    format!(
+      "object {}\ntype commit\ntag v1.0\n\
+       tagger Justin Shaw <realjustinshaw@gmail.com> 1654899880 -0700\n\nrelease v1.0",
+      head
+    )
+  );
+  git(&canonical_path, &["fsck", "--strict"], &[])?;
+  Ok(())
+}
+
+/// Runs stock git with a fixed identity and returns its trimmed stdout.
+fn git(
+  path: &Path,
+  args: &[&str],
+  env: &[(&str, &str)],
+) -> Result<String, Box<dyn std::error::Error>> {
+  let output = Command::new("git")
+    .current_dir(path)
+    .env("GIT_AUTHOR_NAME", "Justin Shaw")
+    .env("GIT_AUTHOR_EMAIL", "realjustinshaw@gmail.com")
+    .env("GIT_COMMITTER_NAME", "Justin Shaw")
+    .env("GIT_COMMITTER_EMAIL", "realjustinshaw@gmail.com")
+    .envs(env.iter().copied())
+    .args(args)
+    .output()?;
+  assert!(output.status.success(), "git {:?} failed", args);
+  Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
@@ -0,0 +1,54 @@
+use std::{fs, os::unix::fs::PermissionsExt};
+use tempdir::TempDir;
+
+mod common;
+use common::{git, git_rs};
+
+#[test]
+fn test_index_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  // stage a few kinds of entries and let stock git add some extensions
+  git(&canonical_path, &["init", "-q"])?;
+  fs::create_dir_all(canonical_path.join("src/deep"))?;
+  fs::write(canonical_path.join("hello.txt"), "hello world\n")?;
+  fs::write(canonical_path.join("src/main.rs"), "fn main() {}\n")?;
+  fs::write(canonical_path.join("src/deep/lib.rs"), "// lib\n")?;
+  fs::write(canonical_path.join("run.sh"), "#!/bin/sh\n")?;
+  fs::set_permissions(
+    canonical_path.join("run.sh"),
+    fs::Permissions::from_mode(0o755),
+  )?;
+  git(&canonical_path, &["add", "."])?;
+  git(&canonical_path, &["commit", "-q", "-m", "first commit"])?;
+  git(&canonical_path, &["update-index", "--untracked-cache"])?;
+  git(&canonical_path, &["status"])?;
+  git(
+    &canonical_path,
+    &["update-index", "--skip-worktree", "src/main.rs"],
+  )?;
+  let expected = git(&canonical_path, &["ls-files", "-s", "--debug"])?;
+
+  // adding a file that didn't change rewrites the index, which must come out
+  // byte for byte the same
+  let original = fs::read(canonical_path.join(".git/index"))?;
+  git_rs(&canonical_path, &["add", "hello.txt"], 0)?;
+  assert_eq!(fs::read(canonical_path.join(".git/index"))?, original);
+
+  // and in whichever version stock git left it in
+  for version in ["4", "2", "3", "4"] {
+    git(
+      &canonical_path,
+      &["update-index", "--index-version", version],
+    )?;
+    let original = fs::read(canonical_path.join(".git/index"))?;
+    git_rs(&canonical_path, &["add", "hello.txt"], 0)?;
+    assert_eq!(fs::read(canonical_path.join(".git/index"))?, original);
+    assert_eq!(
+      git(&canonical_path, &["ls-files", "-s", "--debug"])?,
+      expected
+    );
+  }
+  Ok(())
+}
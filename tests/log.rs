@@ -0,0 +1,320 @@
+use assert_cmd::prelude::*;
+use std::{fs, path::Path, process::Command};
+use tempdir::TempDir;
+
+mod common;
+use common::{git, identity};
+
+#[test]
+fn test_log() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  // two lines of history whose commits alternate in time, with a few commits
+  // made at the same second
+  git(&canonical_path, &["init", "-q", "-b", "main"])?;
+  commit_at(&canonical_path, "root", 1_600_000_000)?;
+  git(&canonical_path, &["branch", "side"])?;
+  for (i, time) in [100, 300, 300, 500].iter().enumerate() {
+    commit_at(
+      &canonical_path,
+      &format!("main {}", i),
+      1_600_000_000 + time,
+    )?;
+  }
+  git(&canonical_path, &["checkout", "-q", "side"])?;
+  for (i, time) in [200, 300, 400, 600].iter().enumerate() {
+    commit_at(
+      &canonical_path,
+      &format!("side {}\n\nwith a body", i),
+      1_600_000_000 + time,
+    )?;
+  }
+  git(&canonical_path, &["checkout", "-q", "main"])?;
+  git(&canonical_path, &["branch", "unmerged"])?;
+  git(
+    &canonical_path,
+    &[
+      "merge",
+      "-q",
+      "--no-ff",
+      "-s",
+      "ours",
+      "-m",
+      "merge side",
+      "side",
+    ],
+  )?;
+
+  for args in [
+    &[][..],
+    &["side"],
+    &["main", "side"],
+    &["--topo-order", "main", "side"],
+    &["--date-order", "main", "side"],
+    &["--reverse", "side", "main"],
+    &["--topo-order", "--reverse", "side", "main"],
+    &["-n", "3", "main", "side"],
+    &["--skip", "2", "-n", "3", "main", "side"],
+    &["--first-parent"],
+    &["--topo-order", "--first-parent"],
+    &["--date-order", "--reverse"],
+    &["unmerged..side"],
+    &["side..main"],
+    &["side", "^main~2"],
+    &["unmerged...side"],
+    &["^main"],
+  ] {
+    log_template(&canonical_path, args)?;
+  }
+  Ok(())
+}
+
+#[test]
+fn test_log_format() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  // three branches merged at once into main, with tags and a message whose
+  // body has tabs in it
+  git(&canonical_path, &["init", "-q", "-b", "main"])?;
+  commit_at(&canonical_path, "root", 1_600_000_000)?;
+  for (i, branch) in ["one", "two", "three"].iter().enumerate() {
+    git(&canonical_path, &["checkout", "-q", "-b", branch, "main"])?;
+    commit_at(
+      &canonical_path,
+      &format!("{} 0\n\n\tindented\tbody", branch),
+      1_600_000_100 + 10 * i as i64,
+    )?;
+    commit_at(
+      &canonical_path,
+      &format!("{} 1", branch),
+      1_600_000_200 + 10 * i as i64,
+    )?;
+  }
+  git(&canonical_path, &["checkout", "-q", "main"])?;
+  commit_at(&canonical_path, "main 0", 1_600_000_300)?;
+  git(
+    &canonical_path,
+    &[
+      "merge", "-q", "-s", "ours", "-m", "octopus", "one", "two", "three",
+    ],
+  )?;
+  git(&canonical_path, &["tag", "v1.0", "main~1"])?;
+  git(
+    &canonical_path,
+    &["tag", "-a", "-m", "annotated", "v2.0", "two"],
+  )?;
+
+  for args in [
+    &["--oneline"][..],
+    &["--graph"],
+    &["--graph", "--oneline", "one", "main"],
+    &["--graph", "--oneline", "--decorate", "two", "main"],
+    &["--decorate=full", "--abbrev-commit"],
+    &["--pretty=short", "--graph"],
+    &["--pretty=full", "one"],
+    &["--pretty=fuller", "--date=iso", "two"],
+    &["--pretty=raw", "--graph", "three"],
+    &["--format=%h %p %d%n%s%+b%-b", "--graph"],
+    &[
+      "--pretty=format:%H %T %P %an <%ae> %al [%s] [%f]",
+      "--decorate",
+    ],
+    &["--format=%ad|%cd|%aD|%cs|%at|%ai|%cI%n%B", "--date=short"],
+    &["--date=unix", "--first-parent", "--graph"],
+  ] {
+    log_template(&canonical_path, args)?;
+  }
+  Ok(())
+}
+
+#[test]
+fn test_log_filter() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  // a topic branch that touches the sources and the docs, merged into main
+  // which moved on meanwhile, and a last commit by someone else
+  git(&canonical_path, &["init", "-q", "-b", "main"])?;
+  fs::create_dir_all(canonical_path.join("src"))?;
+  fs::create_dir_all(canonical_path.join("docs"))?;
+  commit_file(&canonical_path, "README", "hello\n", "root", 1_600_000_000)?;
+  commit_file(
+    &canonical_path,
+    "src/a.rs",
+    "fn foo() {}\n",
+    "add foo",
+    1_600_000_100,
+  )?;
+  git(&canonical_path, &["checkout", "-q", "-b", "topic"])?;
+  commit_file(
+    &canonical_path,
+    "docs/guide.md",
+    "how to use bar\n",
+    "document bar",
+    1_600_000_200,
+  )?;
+  commit_file(
+    &canonical_path,
+    "src/a.rs",
+    "fn foo() {}\nfn bar() {}\n",
+    "add bar\n\nFixes #12",
+    1_600_000_300,
+  )?;
+  git(&canonical_path, &["checkout", "-q", "main"])?;
+  commit_file(
+    &canonical_path,
+    "README",
+    "hello world\n",
+    "fix readme",
+    1_600_000_250,
+  )?;
+  commit_file(
+    &canonical_path,
+    "src/b.rs",
+    "fn foo2() {}\n",
+    "add another foo",
+    1_600_000_350,
+  )?;
+  git(
+    &canonical_path,
+    &["merge", "-q", "--no-ff", "-m", "merge topic", "topic"],
+  )?;
+  fs::write(canonical_path.join("README"), "hello world!\n")?;
+  git(
+    &canonical_path,
+    &[
+      "commit",
+      "-q",
+      "-a",
+      "-m",
+      "tweak readme",
+      "--author=Jane Doe <jane@example.com>",
+    ],
+  )?;
+
+  for args in [
+    &["--", "src"][..],
+    &["--oneline", "src/a.rs"],
+    &["--graph", "--oneline", "--", "src"],
+    &["--graph", "--oneline", "--", "docs", "README"],
+    &["--author=Jane"],
+    &["--author=jane", "-i", "--oneline"],
+    &["--committer=Shaw", "--author=Justin", "--oneline"],
+    &["--grep=foo", "--grep=bar", "--oneline"],
+    &["--grep=foo", "--grep=bar", "--all-match", "--oneline"],
+    &["--grep=bar", "--invert-grep", "--oneline"],
+    &["--grep=#12", "-F", "--oneline"],
+    &[
+      "--since=2020-09-13 12:30:00 -0700",
+      "--until=2020-09-13 12:32:00 -0700",
+      "--oneline",
+    ],
+    &["--merges", "--oneline"],
+    &["--no-merges", "--oneline", "--graph"],
+    &["-Sbar", "--oneline"],
+    &["-Gfoo", "--oneline", "--", "src"],
+    &["-Sfoo", "--graph", "--oneline"],
+    &["-S", "fo+", "--pickaxe-regex", "--oneline"],
+  ] {
+    log_template(&canonical_path, args)?;
+  }
+  Ok(())
+}
+
+#[test]
+fn test_log_changes() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  // a rename, a copy on a side branch that is merged back, a file deep
+  // enough to be cut short and a deletion
+  git(&canonical_path, &["init", "-q", "-b", "main"])?;
+  let lines: String = (1..=50).map(|i| format!("line {}\n", i)).collect();
+  commit_file(&canonical_path, "a", &lines, "root", 1_600_000_000)?;
+  commit_file(&canonical_path, "b", "hello\n", "add b", 1_600_000_100)?;
+  git(&canonical_path, &["mv", "a", "c"])?;
+  commit_file(
+    &canonical_path,
+    "c",
+    &format!("{}line 51\n", lines),
+    "rename a",
+    1_600_000_200,
+  )?;
+  git(&canonical_path, &["checkout", "-q", "-b", "side"])?;
+  commit_file(&canonical_path, "b2", "hello\n", "copy b", 1_600_000_300)?;
+  git(&canonical_path, &["checkout", "-q", "main"])?;
+  fs::create_dir_all(canonical_path.join("very/long/path/to/some"))?;
+  commit_file(
+    &canonical_path,
+    "very/long/path/to/some/file",
+    &lines.repeat(4),
+    "add a long path",
+    1_600_000_400,
+  )?;
+  git(
+    &canonical_path,
+    &["merge", "-q", "--no-ff", "-m", "merge side", "side"],
+  )?;
+  git(&canonical_path, &["rm", "-q", "b"])?;
+  git(&canonical_path, &["commit", "-q", "-m", "remove b"])?;
+
+  for args in [
+    &["--stat"][..],
+    &["--name-status"],
+    &["--oneline", "--stat"],
+    &["--format=%s", "--name-status", "-C"],
+    &["--format=", "--stat", "--no-renames"],
+    &["--graph", "--stat"],
+    &["--graph", "--oneline", "--name-status", "--first-parent"],
+    &["-M", "--stat", "--", "c"],
+    &["-Shello", "--name-status"],
+  ] {
+    log_template(&canonical_path, args)?;
+  }
+  Ok(())
+}
+
+/// Checks that `git-rs log` prints exactly what `git log` prints.
+fn log_template(path: &Path, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+  let mut all = vec!["log"];
+  all.extend(args);
+  let expected = git(path, &all)?;
+
+  // set the current directory and run `git-rs log`
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(path);
+  cmd.arg("log").args(args);
+
+  // verify the module works as expected
+  let output = cmd.assert().success().get_output().stdout.clone();
+  assert_eq!(String::from_utf8(output)?.trim(), expected, "{:?}", args);
+
+  Ok(())
+}
+
+/// Commits a change to `file` with the given committer time.
+fn commit_at(path: &Path, message: &str, time: i64) -> Result<(), Box<dyn std::error::Error>> {
+  commit_file(path, "file", message, message, time)
+}
+
+/// Writes a file and commits it with the given committer time.
+fn commit_file(
+  path: &Path,
+  file: &str,
+  contents: &str,
+  message: &str,
+  time: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+  fs::write(path.join(file), contents)?;
+  git(path, &["add", file])?;
+  let output = identity(&mut Command::new("git"))
+    .current_dir(path)
+    .env("GIT_AUTHOR_DATE", format!("{} -0700", time - 50))
+    .env("GIT_COMMITTER_DATE", format!("{} -0700", time))
+    .args(["commit", "-q", "-m", message])
+    .output()?;
+  assert!(output.status.success(), "git commit failed");
+  Ok(())
+}
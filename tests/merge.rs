@@ -0,0 +1,206 @@
+use assert_cmd::prelude::*;
+use std::{fs, path::Path, process::Command};
+use tempdir::TempDir;
+
+mod common;
+use common::{commit, git, git_rs, git_rs_err, identity};
+
+#[test]
+fn test_merge() -> Result<(), Box<dyn std::error::Error>> {
+  // fast-forwards, with a new executable file, a deletion and a rename
+  merge_template(
+    |path| {
+      fs::write(path.join("g"), "1\n2\n3\n4\n5\n6\n7\n8\n")?;
+      fs::write(path.join("x"), "x\n")?;
+      fs::write(path.join("mv"), "long enough to be renamed\n")?;
+      commit(path, "base")?;
+      git(path, &["checkout", "-q", "-b", "topic"])?;
+      fs::write(path.join("g"), "1\n2\n3\n4\n5\n6\n7\nT\n")?;
+      fs::write(path.join("new"), "new\n")?;
+      git(path, &["update-index", "--add", "--chmod=+x", "new"])?;
+      git(path, &["rm", "-q", "x"])?;
+      git(path, &["mv", "mv", "moved"])?;
+      commit(path, "topic")?;
+      git(path, &["checkout", "-q", "main"])
+    },
+    &[&["topic"], &["--no-ff", "topic"]],
+  )?;
+
+  // merges changes to both sides, following a rename to where it went
+  let diverged = |path: &Path| -> Result<String, Box<dyn std::error::Error>> {
+    fs::write(path.join("g"), "1\n2\n3\n4\n5\n6\n7\n8\n")?;
+    fs::write(path.join("r"), "some file\nwith lines\nthat is renamed\n")?;
+    commit(path, "base")?;
+    git(path, &["checkout", "-q", "-b", "topic"])?;
+    fs::write(path.join("g"), "1\n2\n3\n4\n5\n6\n7\nT\n")?;
+    git(path, &["mv", "r", "r2"])?;
+    commit(path, "topic")?;
+    git(path, &["checkout", "-q", "main"])?;
+    fs::write(path.join("g"), "O\n2\n3\n4\n5\n6\n7\n8\n")?;
+    fs::write(
+      path.join("r"),
+      "some file\nwith lines\nthat is renamed\nmore\n",
+    )?;
+    commit(path, "main")
+  };
+  merge_template(diverged, &[&["topic"], &["-m", "custom", "topic"]])?;
+
+  // leaves conflicts in the index and the working tree
+  let conflicted = |path: &Path| -> Result<String, Box<dyn std::error::Error>> {
+    fs::write(path.join("f"), "a\nb\nc\n")?;
+    fs::write(path.join("d"), "d\n")?;
+    fs::write(path.join("df"), "df\n")?;
+    fs::write(path.join("r"), "rename me please\nmore lines\nand more\n")?;
+    commit(path, "base")?;
+    git(path, &["checkout", "-q", "-b", "topic"])?;
+    fs::write(path.join("f"), "a\ntheirs\nc\n")?;
+    fs::write(path.join("added"), "theirs\n")?;
+    git(path, &["rm", "-q", "d", "df"])?;
+    fs::create_dir(path.join("df"))?;
+    fs::write(path.join("df/file"), "in\n")?;
+    git(path, &["mv", "r", "r-theirs"])?;
+    commit(path, "topic")?;
+    git(path, &["checkout", "-q", "main"])?;
+    fs::write(path.join("f"), "a\nours\nc\n")?;
+    fs::write(path.join("added"), "ours\n")?;
+    fs::write(path.join("d"), "modified\n")?;
+    fs::write(path.join("df"), "modified\n")?;
+    git(path, &["mv", "r", "r-ours"])?;
+    commit(path, "main")
+  };
+  merge_template(conflicted, &[&["topic"]])?;
+
+  // a directory that is deleted cleanly isn't in the way of a file
+  merge_template(
+    |path| {
+      fs::create_dir_all(path.join("d"))?;
+      fs::create_dir_all(path.join("e"))?;
+      fs::write(path.join("d/x"), "x\n")?;
+      fs::write(path.join("e/y"), "y\n")?;
+      fs::write(path.join("f"), "f\n")?;
+      commit(path, "base")?;
+      git(path, &["checkout", "-q", "-b", "topic"])?;
+      git(path, &["rm", "-q", "e/y"])?;
+      fs::write(path.join("e"), "file\n")?;
+      commit(path, "topic")?;
+      git(path, &["checkout", "-q", "main"])?;
+      git(path, &["rm", "-q", "d/x"])?;
+      fs::write(path.join("d"), "file\n")?;
+      fs::write(path.join("f"), "changed\n")?;
+      commit(path, "main")
+    },
+    &[&["topic"]],
+  )?;
+
+  // unless only a fast-forward is allowed
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+  diverged(&canonical_path)?;
+  assert_eq!(
+    git_rs_err(&canonical_path, &["merge", "--ff-only", "topic"], 128)?,
+    "fatal: Not possible to fast-forward, aborting."
+  );
+
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+  conflicted(&canonical_path)?;
+  let head = git(&canonical_path, &["rev-parse", "HEAD"])?;
+  git_rs(&canonical_path, &["merge", "topic"], 1)?;
+  assert_eq!(
+    fs::read_to_string(canonical_path.join("f"))?,
+    "a\n<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> topic\nc\n"
+  );
+  assert_eq!(
+    fs::read_to_string(canonical_path.join(".git/MERGE_MSG"))?,
+    "Merge branch 'topic'\n\n# Conflicts:\n#\tadded\n#\td\n#\tdf~HEAD\n#\tf\n\
+     #\tr\n#\tr-ours\n#\tr-theirs\n"
+  );
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(&canonical_path).args(["merge", "topic"]);
+  cmd
+    .assert()
+    .code(128)
+    .stdout(
+      "error: Merging is not possible because you have unmerged files.\n\
+       hint: Fix them up in the work tree, and then use 'git add/rm <file>'\n\
+       hint: as appropriate to mark resolution and make a commit.\n",
+    )
+    .stderr("fatal: Exiting because of an unresolved conflict.\n");
+
+  // --abort goes back to where the merge started
+  git_rs(&canonical_path, &["merge", "--abort"], 0)?;
+  assert_eq!(git(&canonical_path, &["status", "--porcelain"])?, "");
+  assert!(!canonical_path.join(".git/MERGE_HEAD").exists());
+  assert_eq!(
+    git_rs_err(&canonical_path, &["merge", "--abort"], 128)?,
+    "fatal: There is no merge to abort (MERGE_HEAD missing)."
+  );
+
+  // --continue records the resolved merge with both parents
+  git_rs(&canonical_path, &["merge", "topic"], 1)?;
+  fs::write(canonical_path.join("f"), "a\nresolved\nc\n")?;
+  git(&canonical_path, &["add", "-A"])?;
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  identity(&mut cmd)
+    .current_dir(&canonical_path)
+    .env("GIT_EDITOR", "true")
+    .args(["merge", "--continue"]);
+  cmd
+    .assert()
+    .success()
+    .stdout(predicates::str::starts_with("[main "));
+  assert_eq!(
+    git(&canonical_path, &["log", "-1", "--format=%P%n%s"])?,
+    format!(
+      "{} {}\nMerge branch 'topic'",
+      head,
+      git(&canonical_path, &["rev-parse", "topic"])?
+    )
+  );
+  assert_eq!(
+    git(&canonical_path, &["reflog", "-1", "--format=%gs"])?,
+    "commit (merge): Merge branch 'topic'"
+  );
+  assert!(!canonical_path.join(".git/MERGE_HEAD").exists());
+  git(&canonical_path, &["fsck", "--strict"])?;
+  Ok(())
+}
+
+/// Checks that `git-rs merge` prints what `git merge` prints, and leaves the
+/// same commits, index and working tree behind, for each set of arguments in
+/// a repository made by `setup`.
+fn merge_template(
+  setup: impl Fn(&Path) -> Result<String, Box<dyn std::error::Error>>,
+  all_args: &[&[&str]],
+) -> Result<(), Box<dyn std::error::Error>> {
+  for args in all_args {
+    let mut results = Vec::new();
+    for program in ["git", "git-rs"] {
+      let temp_dir = TempDir::new("gitrs")?;
+      let path = temp_dir.path().canonicalize().unwrap();
+      setup(&path)?;
+      let mut cmd = match program {
+        "git" => Command::new("git"),
+        _ => Command::cargo_bin("git-rs")?,
+      };
+      let output = identity(&mut cmd)
+        .current_dir(&path)
+        .arg("merge")
+        .args(*args)
+        .output()?;
+      let mut worktree = Vec::new();
+      for file in git(&path, &["ls-files"])?.lines() {
+        worktree.push(fs::read_to_string(path.join(file)).unwrap_or_default());
+      }
+      results.push((
+        String::from_utf8(output.stdout)?,
+        git(&path, &["log", "--format=%H %P %s", "-2"])?,
+        git(&path, &["reflog", "-1", "--format=%gs"])?,
+        git(&path, &["ls-files", "-s"])?,
+        worktree,
+      ));
+    }
+    assert_eq!(results[1], results[0], "{:?}", args);
+  }
+  Ok(())
+}
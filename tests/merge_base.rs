@@ -0,0 +1,82 @@
+use assert_cmd::prelude::*;
+use std::{path::Path, process::Command};
+use tempdir::TempDir;
+
+mod common;
+use common::{commit, git, git_rs};
+
+#[test]
+fn test_merge_base() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  // a criss-cross history: a and b are merged into each other, so main and
+  // side have two best common ancestors; other forks off main before that
+  git(&canonical_path, &["init", "-q", "-b", "main"])?;
+  commit(&canonical_path, "root")?;
+  git(&canonical_path, &["checkout", "-q", "-b", "side"])?;
+  commit(&canonical_path, "b")?;
+  git(&canonical_path, &["checkout", "-q", "main"])?;
+  commit(&canonical_path, "a")?;
+  git(&canonical_path, &["tag", "a"])?;
+  git(&canonical_path, &["tag", "b", "side"])?;
+  git(
+    &canonical_path,
+    &["merge", "-q", "--no-ff", "-m", "m1", "b"],
+  )?;
+  git(&canonical_path, &["checkout", "-q", "side"])?;
+  git(
+    &canonical_path,
+    &["merge", "-q", "--no-ff", "-m", "m2", "a"],
+  )?;
+  commit(&canonical_path, "side")?;
+  git(&canonical_path, &["checkout", "-q", "-b", "other", "a"])?;
+  commit(&canonical_path, "other")?;
+  git(&canonical_path, &["checkout", "-q", "main"])?;
+  commit(&canonical_path, "main")?;
+
+  for args in [
+    &["main", "side"][..],
+    &["--all", "main", "side"],
+    &["--all", "main", "side", "other"],
+    &["--octopus", "main", "side", "other"],
+    &["--all", "--octopus", "side", "other"],
+    &["main~1", "main"],
+  ] {
+    merge_base_template(&canonical_path, args)?;
+  }
+
+  let is_ancestor = |ancestor: &str, descendant: &str| -> Result<_, Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("git-rs")?;
+    cmd
+      .current_dir(&canonical_path)
+      .args(["merge-base", "--is-ancestor", ancestor, descendant]);
+    Ok(cmd.assert())
+  };
+  is_ancestor("a", "side")?.success();
+  is_ancestor("main", "main")?.success();
+  is_ancestor("side", "main")?.code(1);
+  is_ancestor("other", "main")?.code(1);
+
+  // unrelated histories have no merge base at all
+  git(
+    &canonical_path,
+    &["checkout", "-q", "--orphan", "unrelated"],
+  )?;
+  commit(&canonical_path, "unrelated")?;
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd
+    .current_dir(&canonical_path)
+    .args(["merge-base", "main", "unrelated"]);
+  cmd.assert().code(1).stdout("");
+  Ok(())
+}
+
+/// Checks that `git-rs merge-base` prints exactly what `git merge-base`
+/// prints.
+fn merge_base_template(path: &Path, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+  let mut all = vec!["merge-base"];
+  all.extend(args);
+  assert_eq!(git_rs(path, &all, 0)?, git(path, &all)?, "{:?}", args);
+  Ok(())
+}
@@ -0,0 +1,95 @@
+use assert_cmd::prelude::*;
+use std::{fs, path::Path, process::Command};
+use tempdir::TempDir;
+
+#[test]
+fn test_merge_file() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  // both sides change the greeting differently, but only ours changes the
+  // middle and only theirs the end; both make the same change to the start
+  fs::write(
+    canonical_path.join("base.txt"),
+    "start\n\nhello\nworld\n\nmiddle\n\nend\n",
+  )?;
+  fs::write(
+    canonical_path.join("ours.txt"),
+    "begin\n\nhello there\nworld\n\ncenter\n\nend\n",
+  )?;
+  fs::write(
+    canonical_path.join("theirs.txt"),
+    "begin\n\nhi\nworld!\n\nmiddle\n\nfinish",
+  )?;
+
+  for args in [
+    &[][..],
+    &["--diff3"],
+    &["--zdiff3"],
+    &["--ours"],
+    &["--theirs"],
+    &["--union"],
+    &[
+      "-L",
+      "mine",
+      "-L",
+      "old",
+      "-L",
+      "yours",
+      "--marker-size",
+      "3",
+    ],
+  ] {
+    merge_file_template(&canonical_path, args)?;
+  }
+
+  // without -p the result is written to the current file, and the exit status
+  // is the number of conflicts
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd
+    .current_dir(&canonical_path)
+    .args(["merge-file", "ours.txt", "base.txt", "theirs.txt"]);
+  cmd.assert().code(1).stdout("");
+  assert_eq!(
+    fs::read_to_string(canonical_path.join("ours.txt"))?,
+    "begin\n\n\
+     <<<<<<< ours.txt\nhello there\nworld\n=======\nhi\nworld!\n>>>>>>> theirs.txt\n\
+     \ncenter\n\nfinish"
+  );
+
+  // a side that didn't change anything takes the other side as is
+  fs::write(
+    canonical_path.join("same.txt"),
+    "start\n\nhello\nworld\n\nmiddle\n\nend\n",
+  )?;
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd
+    .current_dir(&canonical_path)
+    .args(["merge-file", "-p", "same.txt", "base.txt", "theirs.txt"]);
+  cmd
+    .assert()
+    .success()
+    .stdout("begin\n\nhi\nworld!\n\nmiddle\n\nfinish");
+  Ok(())
+}
+
+/// Checks that `git-rs merge-file -p` prints exactly what `git merge-file -p`
+/// prints, with the same exit status.
+fn merge_file_template(path: &Path, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+  let mut all = vec!["merge-file", "-p"];
+  all.extend(args);
+  all.extend(["ours.txt", "base.txt", "theirs.txt"]);
+  let expected = Command::new("git").current_dir(path).args(&all).output()?;
+  let actual = Command::cargo_bin("git-rs")?
+    .current_dir(path)
+    .args(&all)
+    .output()?;
+  assert_eq!(
+    String::from_utf8(actual.stdout)?,
+    String::from_utf8(expected.stdout)?,
+    "{:?}",
+    args
+  );
+  assert_eq!(actual.status.code(), expected.status.code(), "{:?}", args);
+  Ok(())
+}
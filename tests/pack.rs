@@ -0,0 +1,135 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::{fs, path::Path, process::Command};
+use tempdir::TempDir;
+
+mod common;
+use common::git;
+
+#[test]
+fn test_read_packed_objects() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  // build a history with stock git and pack every object without deltas
+  fs::write(canonical_path.join("hello.txt"), "hello world\n")?;
+  git(&canonical_path, &["init", "-q"])?;
+  git(&canonical_path, &["add", "hello.txt"])?;
+  git(&canonical_path, &["commit", "-q", "-m", "packed commit"])?;
+  git(
+    &canonical_path,
+    &["repack", "-q", "-a", "-d", "-f", "--window=0"],
+  )?;
+  assert!(!canonical_path.join(".git/objects/3b").exists());
+
+  let head = git(&canonical_path, &["rev-parse", "HEAD"])?;
+  pack_template(&canonical_path, "commit", &head, "packed commit")?;
+  pack_template(
+    &canonical_path,
+    "blob",
+    "3b18e512dba79e4c8300dd08aeb37f8e728b8dad",
+    "hello world\n",
+  )?;
+  Ok(())
+}
+
+#[test]
+fn test_read_deltified_objects() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  // grow a file one line per commit so that gc stores most versions as deltas
+  git(&canonical_path, &["init", "-q"])?;
+  let mut contents = String::new();
+  let mut versions = Vec::new();
+  for i in 0..20 {
+    contents.push_str(&format!("line number {} of a slowly growing file\n", i));
+    fs::write(canonical_path.join("grow.txt"), &contents)?;
+    git(&canonical_path, &["add", "grow.txt"])?;
+    git(
+      &canonical_path,
+      &["commit", "-q", "-m", &format!("version {}", i)],
+    )?;
+    versions.push((
+      git(&canonical_path, &["rev-parse", "HEAD:grow.txt"])?,
+      contents.clone(),
+    ));
+  }
+  git(&canonical_path, &["gc", "-q", "--aggressive"])?;
+
+  // make sure the pack actually contains delta chains
+  let packs = fs::read_dir(canonical_path.join(".git/objects/pack"))?;
+  for entry in packs.flatten() {
+    if entry.path().extension().unwrap() == "idx" {
+      let idx = entry.path().to_string_lossy().into_owned();
+      let stats = git(&canonical_path, &["verify-pack", "-s", &idx])?;
+      assert!(stats.contains("chain length"), "expected deltas: {}", stats);
+    }
+  }
+
+  for (hash, contents) in versions {
+    pack_template(&canonical_path, "blob", &hash, &contents)?;
+  }
+  Ok(())
+}
+
+#[test]
+fn test_read_truncated_pack() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  fs::write(canonical_path.join("hello.txt"), "hello world\n")?;
+  git(&canonical_path, &["init", "-q"])?;
+  git(&canonical_path, &["add", "hello.txt"])?;
+  git(&canonical_path, &["commit", "-q", "-m", "packed commit"])?;
+  git(&canonical_path, &["repack", "-q", "-a", "-d"])?;
+  let objects = [
+    ("commit", git(&canonical_path, &["rev-parse", "HEAD"])?),
+    ("tree", git(&canonical_path, &["rev-parse", "HEAD^{tree}"])?),
+    (
+      "blob",
+      git(&canonical_path, &["rev-parse", "HEAD:hello.txt"])?,
+    ),
+  ];
+
+  // keep only the header of the pack, so that no entry can be read whole
+  let packs = fs::read_dir(canonical_path.join(".git/objects/pack"))?;
+  for entry in packs.flatten() {
+    if entry.path().extension().unwrap() == "pack" {
+      let data = fs::read(entry.path())?;
+      fs::write(entry.path(), &data[..12])?;
+    }
+  }
+
+  // every read fails with an error instead of a panic
+  for (obj, hash) in objects {
+    let mut cat_cmd = Command::cargo_bin("git-rs")?;
+    cat_cmd.current_dir(&canonical_path);
+    cat_cmd.arg("cat-file").arg(obj).arg(hash);
+    cat_cmd
+      .assert()
+      .code(128)
+      .stderr(predicate::str::starts_with("fatal: "));
+  }
+  Ok(())
+}
+
+fn pack_template(
+  path: &Path,
+  obj: &str,
+  hash: &str,
+  plaintext_data: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+  // set the current directory and run `git-rs cat-file`
+  let mut cat_cmd = Command::cargo_bin("git-rs")?;
+  cat_cmd.current_dir(path);
+  cat_cmd.arg("cat-file").arg(obj).arg(hash);
+
+  // verify the module works as expected
+  cat_cmd
+    .assert()
+    .success()
+    .stdout(predicate::str::contains(plaintext_data));
+
+  Ok(())
+}
@@ -0,0 +1,236 @@
+use assert_cmd::prelude::*;
+use std::{fs, path::Path, process::Command};
+use tempdir::TempDir;
+
+mod common;
+use common::{commit, git, git_rs, git_rs_err, identity};
+
+/// Three commits on `topic`, and two on `main` since they diverged. The
+/// second commit of `topic` conflicts with `main` when `conflict` is set.
+fn diverged(path: &Path, conflict: bool) -> Result<(), Box<dyn std::error::Error>> {
+  common::diverged(path, conflict)?;
+  fs::write(path.join("g"), "g2\n")?;
+  commit(path, "m two")?;
+  git(path, &["checkout", "-q", "topic"])?;
+  Ok(())
+}
+
+#[test]
+fn test_rebase() -> Result<(), Box<dyn std::error::Error>> {
+  let clean = |path: &Path| diverged(path, false);
+  rebase_template(
+    clean,
+    &[&["main"], &["--onto", "main", "topic~2"], &["topic~1"]],
+  )?;
+
+  // a commit that main already has is skipped
+  let picked = |path: &Path| -> Result<(), Box<dyn std::error::Error>> {
+    diverged(path, false)?;
+    git(path, &["checkout", "-q", "main"])?;
+    git(path, &["cherry-pick", "topic~1"])?;
+    git(path, &["checkout", "-q", "topic"])?;
+    Ok(())
+  };
+  rebase_template(picked, &[&["main"]])?;
+
+  // stops when a commit conflicts
+  let conflicted = |path: &Path| diverged(path, true);
+  rebase_template(conflicted, &[&["main"]])?;
+
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+  conflicted(&canonical_path)?;
+  let topic = git(&canonical_path, &["rev-parse", "topic"])?;
+  git_rs(&canonical_path, &["rebase", "main"], 1)?;
+  assert_eq!(
+    fs::read_to_string(canonical_path.join("f"))?,
+    format!(
+      "a\n<<<<<<< HEAD\nB-main\n=======\nB-topic\n>>>>>>> {} (t two)\nc\n",
+      &git(&canonical_path, &["rev-parse", "topic~1"])?[..7]
+    )
+  );
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd
+    .current_dir(&canonical_path)
+    .args(["rebase", "--continue"]);
+  cmd.assert().code(128).stdout("f: needs merge\n").stderr(
+    "fatal: You must edit all merge conflicts and then\n\
+     mark them as resolved using git add\n",
+  );
+
+  // --continue commits the resolution and carries on
+  fs::write(canonical_path.join("f"), "a\nresolved\nc\n")?;
+  git(&canonical_path, &["add", "f"])?;
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  identity(&mut cmd)
+    .current_dir(&canonical_path)
+    .env("GIT_EDITOR", "true")
+    .args(["rebase", "--continue"]);
+  cmd.assert().success().stdout(predicates::str::ends_with(
+    "Successfully rebased and updated refs/heads/topic.\n",
+  ));
+  assert_eq!(
+    git(&canonical_path, &["log", "--format=%s", "main..topic"])?,
+    "t three\nt two\nt one"
+  );
+  assert_eq!(
+    git(&canonical_path, &["reflog", "-4", "--format=%gs"])?,
+    "rebase (finish): returning to refs/heads/topic\n\
+     rebase (pick): t three\n\
+     rebase (continue): t two\n\
+     rebase (pick): t one"
+  );
+  assert_eq!(git(&canonical_path, &["rev-parse", "ORIG_HEAD"])?, topic);
+  assert!(!canonical_path.join(".git/rebase-merge").exists());
+
+  // --skip leaves the commit out
+  git(&canonical_path, &["reset", "-q", "--hard", &topic])?;
+  git_rs(&canonical_path, &["rebase", "main"], 1)?;
+  git_rs(&canonical_path, &["rebase", "--skip"], 0)?;
+  assert_eq!(
+    git(&canonical_path, &["log", "--format=%s", "main..topic"])?,
+    "t three\nt one"
+  );
+
+  // --abort goes back to where the rebase started
+  git(&canonical_path, &["reset", "-q", "--hard", &topic])?;
+  git_rs(&canonical_path, &["rebase", "main"], 1)?;
+  git_rs(&canonical_path, &["rebase", "--abort"], 0)?;
+  assert_eq!(git(&canonical_path, &["rev-parse", "HEAD"])?, topic);
+  assert_eq!(
+    git(&canonical_path, &["symbolic-ref", "HEAD"])?,
+    "refs/heads/topic"
+  );
+  assert_eq!(git(&canonical_path, &["status", "--porcelain"])?, "");
+  assert_eq!(
+    git_rs_err(&canonical_path, &["rebase", "--abort"], 128)?,
+    "fatal: No rebase in progress?"
+  );
+  Ok(())
+}
+
+#[test]
+fn test_rebase_interactive() -> Result<(), Box<dyn std::error::Error>> {
+  let clean = |path: &Path| diverged(path, false);
+  rebase_template(
+    clean,
+    &[
+      &[
+        "-i",
+        "main",
+        "--",
+        "pick topic~2\nsquash topic~1\nfixup topic\n",
+      ],
+      &[
+        "-i",
+        "main",
+        "--",
+        "reword topic~2\nexec echo hi\ndrop topic~1\nfixup topic\n",
+      ],
+      &["-i", "main", "--", "p topic~1\nd topic~2\nf topic\n"],
+    ],
+  )?;
+
+  // stops to edit a commit
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+  clean(&canonical_path)?;
+  let short = |rev: &str| -> Result<String, Box<dyn std::error::Error>> {
+    git(&canonical_path, &["rev-parse", "--short", rev])
+  };
+  let stopped = format!("Stopped at {}...  t one", short("topic~2")?);
+  let todo = "edit topic~2\npick topic\n";
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  identity(&mut cmd)
+    .current_dir(&canonical_path)
+    .env("GIT_SEQUENCE_EDITOR", format!("printf '{}' >", todo))
+    .args(["rebase", "-i", "main"]);
+  cmd
+    .assert()
+    .success()
+    .stdout(predicates::str::starts_with(stopped));
+  fs::write(canonical_path.join("t1"), "amended\n")?;
+  git(&canonical_path, &["add", "t1"])?;
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  identity(&mut cmd)
+    .current_dir(&canonical_path)
+    .env("GIT_EDITOR", "true")
+    .args(["rebase", "--continue"]);
+  cmd.assert().success();
+  assert_eq!(
+    git(&canonical_path, &["log", "--format=%s", "main..topic"])?,
+    "t three\nt one"
+  );
+  assert_eq!(git(&canonical_path, &["show", "topic~1:t1"])?, "amended");
+
+  // an empty todo list gives up
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  identity(&mut cmd)
+    .current_dir(&canonical_path)
+    .env("GIT_SEQUENCE_EDITOR", "printf '' >")
+    .args(["rebase", "-i", "main~1"]);
+  cmd.assert().code(128).stderr("fatal: nothing to do\n");
+  assert!(!canonical_path.join(".git/rebase-merge").exists());
+  Ok(())
+}
+
+/// Checks that `git-rs rebase` prints what `git rebase` prints, and leaves the
+/// same commits, reflogs and working tree behind, for each set of arguments in
+/// a repository made by `setup`. Arguments after a `--` are the todo list the
+/// sequence editor writes.
+fn rebase_template(
+  setup: impl Fn(&Path) -> Result<(), Box<dyn std::error::Error>>,
+  all_args: &[&[&str]],
+) -> Result<(), Box<dyn std::error::Error>> {
+  for args in all_args {
+    let (args, todo) = match args.iter().position(|arg| *arg == "--") {
+      Some(n) => (&args[..n], args[n + 1]),
+      None => (*args, ""),
+    };
+    let mut results = Vec::new();
+    for program in ["git", "git-rs"] {
+      let temp_dir = TempDir::new("gitrs")?;
+      let path = temp_dir.path().canonicalize().unwrap();
+      setup(&path)?;
+      // git prints most of what it has to say to stderr, which git-rs doesn't
+      // have, so it is merged into stdout
+      let mut cmd = match program {
+        "git" => {
+          let mut cmd = Command::new("sh");
+          cmd.args(["-c", "git \"$@\" 2>&1", "git"]);
+          cmd
+        }
+        _ => Command::cargo_bin("git-rs")?,
+      };
+      let output = identity(&mut cmd)
+        .current_dir(&path)
+        .env("GIT_EDITOR", "true")
+        .env("GIT_SEQUENCE_EDITOR", format!("printf '{}' >", todo))
+        .arg("rebase")
+        .args(args)
+        .output()?;
+      let mut worktree = Vec::new();
+      for file in git(&path, &["ls-files"])?.lines() {
+        worktree.push(fs::read_to_string(path.join(file)).unwrap_or_default());
+      }
+      // the progress of git is overwritten (with `\r`), and its commit
+      // summaries list the changes, which git-rs leaves out
+      let stdout: Vec<String> = String::from_utf8(output.stdout)?
+        .lines()
+        .map(|line| line.rsplit('\r').next().unwrap().replace("\x1b[K", ""))
+        .filter(|line| !line.starts_with(' '))
+        .collect();
+      results.push((
+        stdout,
+        output.status.code(),
+        git(&path, &["log", "--format=%H %P %s", "-5"])?,
+        git(&path, &["reflog", "--format=%H %gs"])?,
+        git(&path, &["reflog", "topic", "--format=%H %gs"])?,
+        git(&path, &["ls-files", "-s"])?,
+        worktree,
+      ));
+    }
+    assert_eq!(results[1], results[0], "{:?}", args);
+  }
+  Ok(())
+}
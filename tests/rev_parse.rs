@@ -0,0 +1,109 @@
+use assert_cmd::prelude::*;
+use std::{fs, path::Path, process::Command};
+use tempdir::TempDir;
+
+mod common;
+use common::{git, git_rs_err};
+
+#[test]
+fn test_rev_parse() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  // build a short history with a side branch, tags and some reflog entries
+  git(&canonical_path, &["init", "-q", "-b", "main"])?;
+  fs::create_dir(canonical_path.join("src"))?;
+  for i in 0..6 {
+    fs::write(
+      canonical_path.join("src/main.rs"),
+      format!("// version {}\n", i),
+    )?;
+    git(&canonical_path, &["add", "src/main.rs"])?;
+    git(
+      &canonical_path,
+      &["commit", "-q", "-m", &format!("commit number {}", i)],
+    )?;
+  }
+  git(
+    &canonical_path,
+    &["tag", "-a", "v1.0", "-m", "release", "HEAD~2"],
+  )?;
+  git(&canonical_path, &["branch", "side", "HEAD~4"])?;
+  git(&canonical_path, &["config", "branch.main.remote", "."])?;
+  git(
+    &canonical_path,
+    &["config", "branch.main.merge", "refs/heads/side"],
+  )?;
+  git(&canonical_path, &["checkout", "-q", "side"])?;
+  git(&canonical_path, &["checkout", "-q", "main"])?;
+
+  for expr in [
+    "HEAD",
+    "@",
+    "HEAD~3",
+    "HEAD^",
+    "HEAD^0",
+    "main~2^{tree}",
+    "v1.0",
+    "v1.0^{}",
+    "v1.0~1",
+    "main@{1}",
+    "@{upstream}",
+    "@{-1}",
+    ":/number 3",
+    "HEAD^{/number 1}",
+    "HEAD:src/main.rs",
+    "HEAD~1:src",
+    "side..main",
+    "side...main",
+    "^side",
+  ] {
+    rev_parse_template(
+      &canonical_path,
+      &[expr],
+      &git(&canonical_path, &["rev-parse", expr])?,
+    )?;
+  }
+
+  for args in [
+    vec!["--verify", "main~1"],
+    vec!["--short", "HEAD"],
+    vec!["--short=10", "HEAD"],
+    vec!["--abbrev-ref", "HEAD"],
+    vec!["--abbrev-ref", "@{u}"],
+    vec!["--git-dir"],
+    vec!["--show-toplevel"],
+  ] {
+    let mut git_args = vec!["rev-parse"];
+    git_args.extend(&args);
+    rev_parse_template(&canonical_path, &args, &git(&canonical_path, &git_args)?)?;
+  }
+
+  // a name that cannot be resolved fails like it does in git
+  let expected = Command::new("git")
+    .current_dir(&canonical_path)
+    .args(["rev-parse", "--verify", "nonexist"])
+    .output()?;
+  assert_eq!(expected.status.code(), Some(128));
+  assert_eq!(
+    git_rs_err(&canonical_path, &["rev-parse", "--verify", "nonexist"], 128)?,
+    String::from_utf8(expected.stderr)?.trim()
+  );
+  Ok(())
+}
+
+fn rev_parse_template(
+  path: &Path,
+  args: &[&str],
+  expected: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+  // set the current directory and run `git-rs rev-parse`
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(path);
+  cmd.arg("rev-parse").args(args);
+
+  // verify the module works as expected
+  cmd.assert().success().stdout(format!("{}\n", expected));
+
+  Ok(())
+}
@@ -0,0 +1,129 @@
+use assert_cmd::prelude::*;
+use std::{fs, os::unix::fs::MetadataExt, path::Path, process::Command};
+use tempdir::TempDir;
+
+mod common;
+use common::git;
+
+#[test]
+fn test_status() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  git(&canonical_path, &["init", "-q", "-b", "main"])?;
+  status_template(&canonical_path, &[])?;
+  status_template(&canonical_path, &["--porcelain=v2", "--branch"])?;
+  assert!(!canonical_path.join(".git/index").exists());
+
+  // names that need quoting and a directory without tracked files
+  fs::create_dir_all(canonical_path.join("d/e"))?;
+  fs::write(canonical_path.join("a b"), "a b\n")?;
+  fs::write(canonical_path.join("q\"t"), "quote\n")?;
+  fs::write(canonical_path.join("é.txt"), "accent\n")?;
+  fs::write(canonical_path.join("d/e/x"), "x\n")?;
+  fs::write(canonical_path.join("top"), "top\n")?;
+  git(&canonical_path, &["add", "a b", "top"])?;
+  status_template(&canonical_path, &[])?;
+  status_template(&canonical_path, &["-s", "-b"])?;
+
+  // staged, unstaged and intent-to-add changes
+  git(&canonical_path, &["commit", "-q", "-m", "first commit"])?;
+  fs::write(canonical_path.join("a b"), "changed\n")?;
+  fs::remove_file(canonical_path.join("top"))?;
+  std::os::unix::fs::symlink("x", canonical_path.join("link"))?;
+  fs::write(canonical_path.join("d/tracked"), "tracked\n")?;
+  git(&canonical_path, &["add", "link", "d/tracked"])?;
+  git(&canonical_path, &["add", "-N", "é.txt"])?;
+  for args in [
+    &[][..],
+    &["--short"],
+    &["--porcelain"],
+    &["--porcelain=v2", "-b"],
+  ] {
+    status_template(&canonical_path, args)?;
+  }
+
+  // a status with nothing to refresh leaves the index file alone, once it is
+  // newer than the files it describes
+  std::thread::sleep(std::time::Duration::from_secs(1));
+  git(&canonical_path, &["status", "-s"])?;
+  let inode = fs::metadata(canonical_path.join(".git/index"))?.ino();
+  status_template(&canonical_path, &[])?;
+  assert_eq!(
+    fs::metadata(canonical_path.join(".git/index"))?.ino(),
+    inode
+  );
+  status_template(&canonical_path.join("d/e"), &[])?;
+  status_template(&canonical_path.join("d/e"), &["-s"])?;
+
+  // a detached HEAD is described by what was checked out
+  git(&canonical_path, &["add", "-A"])?;
+  git(&canonical_path, &["commit", "-q", "-m", "second commit"])?;
+  git(&canonical_path, &["tag", "v1"])?;
+  git(&canonical_path, &["checkout", "-q", "v1"])?;
+  status_template(&canonical_path, &[])?;
+  git(
+    &canonical_path,
+    &["commit", "-q", "--allow-empty", "-m", "third"],
+  )?;
+  status_template(&canonical_path, &[])?;
+  status_template(&canonical_path, &["-sb"])?;
+  Ok(())
+}
+
+#[test]
+fn test_status_conflicts() -> Result<(), Box<dyn std::error::Error>> {
+  let temp_dir = TempDir::new("gitrs")?;
+  let canonical_path = temp_dir.path().canonicalize().unwrap();
+
+  git(&canonical_path, &["init", "-q", "-b", "main"])?;
+  fs::write(canonical_path.join("f"), "a\n")?;
+  fs::write(canonical_path.join("g"), "x\n")?;
+  git(&canonical_path, &["add", "."])?;
+  git(&canonical_path, &["commit", "-q", "-m", "base"])?;
+  git(&canonical_path, &["checkout", "-q", "-b", "side"])?;
+  fs::write(canonical_path.join("f"), "b\n")?;
+  git(&canonical_path, &["rm", "-q", "g"])?;
+  git(&canonical_path, &["commit", "-q", "-a", "-m", "side"])?;
+  git(&canonical_path, &["checkout", "-q", "main"])?;
+  fs::write(canonical_path.join("f"), "c\n")?;
+  fs::write(canonical_path.join("g"), "y\n")?;
+  git(&canonical_path, &["commit", "-q", "-a", "-m", "main"])?;
+  // the merge stops with conflicts, so it "fails"
+  Command::new("git")
+    .current_dir(&canonical_path)
+    .env("GIT_COMMITTER_NAME", "Justin Shaw")
+    .env("GIT_COMMITTER_EMAIL", "realjustinshaw@gmail.com")
+    .args(["merge", "-q", "side"])
+    .output()?;
+  fs::write(canonical_path.join("n"), "new\n")?;
+  git(&canonical_path, &["add", "n"])?;
+
+  for args in [&[][..], &["-s"], &["--porcelain=v2"]] {
+    status_template(&canonical_path, args)?;
+  }
+  Ok(())
+}
+
+/// Checks that `git-rs status` prints exactly what `git status` prints.
+fn status_template(path: &Path, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+  let expected = Command::new("git")
+    .current_dir(path)
+    .arg("status")
+    .args(args)
+    .output()?;
+  assert!(expected.status.success(), "git status {:?} failed", args);
+
+  // set the current directory and run `git-rs status`
+  let mut cmd = Command::cargo_bin("git-rs")?;
+  cmd.current_dir(path);
+  cmd.arg("status").args(args);
+
+  // verify the module works as expected
+  cmd
+    .assert()
+    .success()
+    .stdout(String::from_utf8(expected.stdout)?);
+
+  Ok(())
+}